@@ -0,0 +1,195 @@
+use crate::input::InputReport;
+use crate::metrics::{self, AUDIT_KINDS};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 已发送报告的审计记录配置
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditConfig {
+    pub enabled: bool,
+    /// 以 JSON Lines 追加写入的文件；未设置时只在 `/metrics` 中计数
+    pub path: Option<PathBuf>,
+    /// 同时记录键码、按键与位移等内容，默认只记录报告类型、目标与时间
+    pub log_content: bool,
+}
+
+/// 报告类型在 [`AUDIT_KINDS`] 中的下标
+pub(crate) fn kind_index(report: &InputReport) -> usize {
+    match report {
+        InputReport::Keyboard { .. } => 0,
+        InputReport::Mouse { .. } => 1,
+        InputReport::Consumer { .. } => 2,
+        InputReport::System { .. } => 3,
+        InputReport::Vendor { .. } => 4,
+    }
+}
+
+/// 审计记录：在 `Core` 发送报告后逐条记录
+///
+/// 只追加不改写；写入失败只记录日志，不影响报告发送。
+pub struct AuditLog {
+    log_content: bool,
+    writer: Option<Mutex<Box<dyn Write + Send>>>,
+    counts: [metrics::Counter; AUDIT_KINDS.len()],
+}
+
+impl AuditLog {
+    /// 按配置打开审计记录，未启用时返回 `None`
+    pub fn open(config: &AuditConfig) -> Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let writer = match &config.path {
+            Some(path) => {
+                let file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("打开审计文件 {} 失败", path.display()))?;
+                Some(Box::new(file) as Box<dyn Write + Send>)
+            }
+            None => None,
+        };
+        Ok(Some(Self::with_writer(config.log_content, writer)))
+    }
+
+    pub fn with_writer(log_content: bool, writer: Option<Box<dyn Write + Send>>) -> Self {
+        Self {
+            log_content,
+            writer: writer.map(Mutex::new),
+            counts: Default::default(),
+        }
+    }
+
+    /// 记录一个已发往 `target` 的报告
+    pub fn record(&self, target: &str, report: &InputReport) {
+        let kind = kind_index(report);
+        self.counts[kind].inc();
+        metrics::global().audited_reports[kind].inc();
+
+        let Some(writer) = &self.writer else {
+            return;
+        };
+        let mut entry = json!({
+            "ts_ms": SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            "target": target,
+            "kind": AUDIT_KINDS[kind],
+        });
+        if self.log_content {
+            entry["report"] = content(report);
+        }
+        let mut writer = writer.lock().unwrap();
+        if let Err(e) = writeln!(writer, "{}", entry) {
+            log::warn!("写入审计记录失败: {:?}", e);
+        }
+    }
+
+    /// 各类型已记录的报告数
+    pub fn counts(&self) -> Vec<(&'static str, u64)> {
+        AUDIT_KINDS
+            .iter()
+            .zip(&self.counts)
+            .map(|(kind, count)| (*kind, count.get()))
+            .collect()
+    }
+}
+
+/// 报告内容，仅在 `log_content` 开启时写入
+pub(crate) fn content(report: &InputReport) -> Value {
+    match report {
+        InputReport::Keyboard { modifiers, keys } => {
+            json!({ "modifiers": modifiers, "keys": keys })
+        }
+        InputReport::Mouse {
+            buttons,
+            x,
+            y,
+            wheel,
+        } => json!({ "buttons": buttons, "x": x, "y": y, "wheel": wheel }),
+        InputReport::Consumer { usage } => json!({ "usage": usage }),
+        InputReport::System { usage } => json!({ "usage": usage }),
+        InputReport::Vendor { code, pressed } => json!({ "code": code, "pressed": pressed }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn record_keys(log_content: bool) -> (AuditLog, Vec<Value>) {
+        let buf = SharedBuf::default();
+        let audit = AuditLog::with_writer(log_content, Some(Box::new(buf.clone())));
+        for key in [0x04, 0x05] {
+            audit.record(
+                "usb",
+                &InputReport::Keyboard {
+                    modifiers: 0x02,
+                    keys: vec![key],
+                },
+            );
+        }
+        audit.record(
+            "ble",
+            &InputReport::Mouse {
+                buttons: 0,
+                x: 1,
+                y: 2,
+                wheel: 0,
+            },
+        );
+        let text = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        let lines = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        (audit, lines)
+    }
+
+    #[test]
+    fn test_privacy_mode_omits_content_but_counts() {
+        let (audit, lines) = record_keys(false);
+        assert_eq!(audit.counts()[0], ("keyboard", 2));
+        assert_eq!(audit.counts()[1], ("mouse", 1));
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["kind"], "keyboard");
+        assert_eq!(lines[2]["target"], "ble");
+        for line in &lines {
+            assert!(line.get("report").is_none(), "{line}");
+        }
+
+        let (audit, lines) = record_keys(true);
+        assert_eq!(audit.counts()[0], ("keyboard", 2));
+        assert_eq!(lines[0]["report"]["keys"], json!([0x04]));
+        assert_eq!(lines[1]["report"]["modifiers"], 0x02);
+        assert_eq!(lines[2]["report"]["y"], 2);
+    }
+
+    #[test]
+    fn test_disabled_by_default() {
+        assert!(AuditLog::open(&AuditConfig::default()).unwrap().is_none());
+    }
+}
@@ -0,0 +1,535 @@
+use crate::audit::AuditConfig;
+use crate::core::OutputPolicy;
+use crate::input::transform::TransformChain;
+use crate::input::{
+    AppleKeys, DEFAULT_CHANNEL_CAPACITY, DeviceFilter, DialTarget, InputConfig, KeyRemap,
+    KeyboardGrab, MergedKeyboards, MouseButtonMap, MouseSensitivity, ScanConfig,
+};
+use crate::output::bluetooth_ble::BleConfig;
+use crate::output::connection::HelloConfig;
+use crate::output::drag_heartbeat::DragHeartbeatConfig;
+use crate::output::keep_awake::KeepAwakeConfig;
+use crate::output::key_names::{Hotkey, KeyCombo};
+use crate::output::keyboard::TypingConfig;
+use crate::output::led_debounce::DEFAULT_LED_DEBOUNCE;
+use crate::output::mouse::{AxisTransform, DeadZone, ScrollAccel, WheelResolution};
+use crate::output::mouse_gesture::MouseGestureConfig;
+use crate::output::mouse_keys::MouseKeysConfig;
+use crate::output::usb::UsbConfig;
+use crate::web::ws::DEFAULT_SCROLL_THRESHOLD;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// 未定义同名方案时，由顶层设置构成的默认方案名
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// 系统级配置文件路径
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/bridge-hid/config.json";
+
+/// 全局配置
+///
+/// 所有字段都有默认值，配置文件中只需写出要修改的部分。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// USB 输出时的鼠标报告率（Hz）
+    pub usb_mouse_rate_hz: u32,
+    /// BLE 输出时的鼠标报告率（Hz）
+    pub ble_mouse_rate_hz: u32,
+    /// USB 后端单个报告的发送超时（毫秒），为 0 表示不限制
+    pub usb_send_timeout_ms: u64,
+    /// BLE 后端单个报告的发送超时（毫秒），为 0 表示不限制
+    pub ble_send_timeout_ms: u64,
+    /// 键盘报告最小间隔（毫秒），为 0 表示不限流
+    pub keyboard_interval_ms: u64,
+    /// LED 同步去抖窗口（毫秒）
+    pub led_debounce_ms: u64,
+    /// 根据 USB 线缆插拔自动切换输出
+    pub auto_switch: bool,
+    /// USB 与 BLE 主机同时在线时的发送策略：`single`、`mirror` 或 `prefer_usb`
+    pub output_policy: OutputPolicy,
+    /// 空闲时轻推鼠标防止主机休眠
+    pub keep_awake: KeepAwakeConfig,
+    /// 鼠标连击切换输出，供只有触控板或鼠标的场景使用
+    pub switch_gesture: MouseGestureConfig,
+    /// 用键盘控制指针，供无法使用鼠标的场景
+    pub mouse_keys: MouseKeysConfig,
+    /// 按住鼠标按键时定期重发按键状态，防止主机丢失拖拽
+    pub drag_heartbeat: DragHeartbeatConfig,
+    /// 主机新连接后先发送空报告，供会忽略第一个报告的主机使用
+    pub hello: HelloConfig,
+    /// 切换 USB/BLE 输出的组合键，前面的键可以是普通键，如 `caps_lock+q`
+    pub switch_combo: KeyCombo,
+    /// 紧急释放所有按键的热键，如 `ctrl+alt+backspace`，默认关闭
+    pub panic_hotkey: Option<Hotkey>,
+    pub input: InputSettings,
+    /// 可在运行时切换的配置方案
+    pub profiles: BTreeMap<String, Profile>,
+    /// 启动时使用的方案
+    pub active_profile: String,
+    pub usb: UsbConfig,
+    pub ble: BleConfig,
+    pub web: WebConfig,
+    /// 已发送报告的审计记录，默认关闭
+    pub audit: AuditConfig,
+}
+
+/// 输入处理相关配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputSettings {
+    pub dial_target: DialTarget,
+    pub channel_capacity: usize,
+    pub raw_passthrough: bool,
+    pub caps_to_ctrl: bool,
+    pub swap_alt_meta: bool,
+    pub disable_super: bool,
+    /// 鼠标灵敏度（百分比）
+    pub mouse_sensitivity: u32,
+    /// 不独占的设备与白名单
+    pub devices: DeviceFilter,
+    /// 鼠标按键重映射
+    pub mouse_buttons: MouseButtonMap,
+    /// 鼠标坐标轴反转与交换
+    pub mouse_axes: AxisTransform,
+    /// 滚轮每格输出的单位数（`multiplier / divisor`），默认 1:1
+    pub wheel_resolution: WheelResolution,
+    /// 鼠标位移死区，累积位移不超过该值时不发送，默认 0 关闭
+    pub mouse_dead_zone: DeadZone,
+    /// 滚轮加速曲线，与鼠标灵敏度无关，默认不加速
+    pub scroll_accel: ScrollAccel,
+    /// 设备扫描间隔
+    pub scan: ScanConfig,
+    /// 修饰键按住且没有其他输入超过该时长（毫秒）后核对键盘的实际按键状态，
+    /// 释放漏掉松开事件的键或重发当前状态，0 表示关闭
+    pub modifier_watchdog_ms: u64,
+    /// 同一个键松开后该时长（毫秒）内的再次按下视为抖动并丢弃，0 表示关闭
+    pub key_debounce_ms: u64,
+    /// Apple 键盘的 Fn/Globe 与顶排媒体键：`off`、`on` 或按设备名识别的 `auto`
+    pub apple_keys: AppleKeys,
+}
+
+/// 一组可整体切换的设置，例如游戏与打字使用不同的报告率和重映射
+///
+/// 方案中未写出的字段取内置默认值，而不是顶层设置。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub usb_mouse_rate_hz: u32,
+    pub ble_mouse_rate_hz: u32,
+    pub mouse_sensitivity: u32,
+    pub caps_to_ctrl: bool,
+    pub swap_alt_meta: bool,
+    pub disable_super: bool,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Config::default().base_profile()
+    }
+}
+
+/// 网页触控板配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebConfig {
+    /// 监听地址
+    pub bind: String,
+    /// 每格滚轮对应的滚动量，默认 0 不累加，每条滚动消息直接转发
+    pub scroll_threshold: i32,
+    /// WebSocket Ping 间隔（秒）
+    pub ping_interval_secs: u64,
+    /// 超过多久（秒）未收到 Pong 即断开连接
+    pub pong_timeout_secs: u64,
+    /// 触控板坐标轴反转与交换，例如手机横屏使用
+    pub mouse_axes: AxisTransform,
+    /// 触控板位移死区，累积位移不超过该值时不发送，默认 0 关闭
+    pub mouse_dead_zone: DeadZone,
+    /// 触控板滚动加速曲线，默认不加速
+    pub scroll_accel: ScrollAccel,
+    /// 组合键与文本输入的节奏
+    pub typing: TypingConfig,
+    /// 开放 `/api/preview` 实时预览，默认关闭，未开启时不注册该路由
+    pub preview: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            usb_mouse_rate_hz: 500,
+            ble_mouse_rate_hz: 125,
+            usb_send_timeout_ms: 100,
+            ble_send_timeout_ms: 500,
+            keyboard_interval_ms: 0,
+            led_debounce_ms: DEFAULT_LED_DEBOUNCE.as_millis() as u64,
+            auto_switch: false,
+            output_policy: OutputPolicy::default(),
+            keep_awake: KeepAwakeConfig::default(),
+            switch_gesture: MouseGestureConfig::default(),
+            mouse_keys: MouseKeysConfig::default(),
+            drag_heartbeat: DragHeartbeatConfig::default(),
+            hello: HelloConfig::default(),
+            switch_combo: KeyCombo::default(),
+            panic_hotkey: None,
+            input: InputSettings::default(),
+            profiles: BTreeMap::new(),
+            active_profile: DEFAULT_PROFILE.to_string(),
+            usb: UsbConfig::default(),
+            ble: BleConfig::default(),
+            web: WebConfig::default(),
+            audit: AuditConfig::default(),
+        }
+    }
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        Self {
+            dial_target: DialTarget::default(),
+            channel_capacity: DEFAULT_CHANNEL_CAPACITY,
+            raw_passthrough: false,
+            caps_to_ctrl: false,
+            swap_alt_meta: false,
+            disable_super: false,
+            mouse_sensitivity: 100,
+            devices: DeviceFilter::default(),
+            mouse_buttons: MouseButtonMap::default(),
+            mouse_axes: AxisTransform::default(),
+            wheel_resolution: WheelResolution::default(),
+            mouse_dead_zone: DeadZone::default(),
+            scroll_accel: ScrollAccel::default(),
+            scan: ScanConfig::default(),
+            modifier_watchdog_ms: 0,
+            key_debounce_ms: 0,
+            apple_keys: AppleKeys::default(),
+        }
+    }
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        Self {
+            bind: "0.0.0.0:3000".to_string(),
+            scroll_threshold: DEFAULT_SCROLL_THRESHOLD,
+            ping_interval_secs: 10,
+            pong_timeout_secs: 20,
+            mouse_axes: AxisTransform::default(),
+            mouse_dead_zone: DeadZone::default(),
+            scroll_accel: ScrollAccel::default(),
+            typing: TypingConfig::default(),
+            preview: false,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Config {
+    /// 不扫描 `/dev/input` 的默认配置，测试运行时不会独占本机的键盘和鼠标
+    pub(crate) fn without_devices() -> Self {
+        let mut config = Self::default();
+        config.input.scan.enabled = false;
+        config
+    }
+}
+
+impl WebConfig {
+    pub fn ping_interval(&self) -> Duration {
+        Duration::from_secs(self.ping_interval_secs.max(1))
+    }
+
+    pub fn pong_timeout(&self) -> Duration {
+        Duration::from_secs(self.pong_timeout_secs.max(1))
+    }
+}
+
+impl InputSettings {
+    /// 构造输入管线配置，重映射开关作为初始值
+    pub fn to_input_config(&self) -> InputConfig {
+        let key_remap = KeyRemap::new();
+        key_remap.set_caps_to_ctrl(self.caps_to_ctrl);
+        key_remap.set_swap_alt_meta(self.swap_alt_meta);
+        key_remap.set_disable_super(self.disable_super);
+        InputConfig {
+            dial_target: self.dial_target,
+            key_remap,
+            channel_capacity: self.channel_capacity,
+            raw_passthrough: self.raw_passthrough,
+            device_filter: self.devices.clone(),
+            button_map: self.mouse_buttons.clone(),
+            sensitivity: MouseSensitivity::new(self.mouse_sensitivity),
+            axes: self.mouse_axes,
+            wheel_resolution: self.wheel_resolution,
+            dead_zone: self.mouse_dead_zone,
+            scroll_accel: self.scroll_accel,
+            scan: self.scan,
+            modifier_watchdog: (self.modifier_watchdog_ms > 0)
+                .then(|| Duration::from_millis(self.modifier_watchdog_ms)),
+            key_debounce: (self.key_debounce_ms > 0)
+                .then(|| Duration::from_millis(self.key_debounce_ms)),
+            transforms: TransformChain::default(),
+            grab: KeyboardGrab::default(),
+            keyboards: MergedKeyboards::default(),
+            apple_keys: self.apple_keys,
+        }
+    }
+}
+
+impl Config {
+    /// 默认配置文件路径：优先 `$XDG_CONFIG_HOME/bridge-hid/config.json`（存在时），
+    /// 否则使用 `/etc/bridge-hid/config.json`
+    pub fn default_path() -> PathBuf {
+        if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME") {
+            let path = PathBuf::from(dir).join("bridge-hid").join("config.json");
+            if path.exists() {
+                return path;
+            }
+        }
+        PathBuf::from(SYSTEM_CONFIG_PATH)
+    }
+
+    /// 从文件加载配置，文件不存在时使用全部默认值
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                info!("配置文件 {} 不存在，使用默认配置", path.display());
+                return Ok(Self::default());
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("读取配置文件 {} 失败", path.display()));
+            }
+        };
+
+        let (config, unknown) =
+            Self::parse(&text).with_context(|| format!("解析配置文件 {} 失败", path.display()))?;
+        for field in unknown {
+            warn!("配置文件中存在未知字段，已忽略: {}", field);
+        }
+        info!("已加载配置文件 {}", path.display());
+        Ok(config)
+    }
+
+    /// 写入配置文件，先写临时文件再改名
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp = temp_path(path);
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("写入配置文件 {} 失败", tmp.display()))?;
+        std::fs::rename(&tmp, path).with_context(|| format!("写入配置文件 {} 失败", path.display()))
+    }
+
+    /// 解析配置文本，同时返回未识别的字段路径
+    pub fn parse(text: &str) -> Result<(Self, Vec<String>)> {
+        let raw: Value = serde_json::from_str(text)?;
+        let config: Self = serde_json::from_value(raw.clone())?;
+
+        // 与序列化后的完整配置对比，多出来的键即为未知字段
+        let known = serde_json::to_value(&config)?;
+        let mut unknown = Vec::new();
+        collect_unknown(&raw, &known, "", &mut unknown);
+        Ok((config, unknown))
+    }
+
+    /// 由顶层设置构成的方案
+    pub fn base_profile(&self) -> Profile {
+        Profile {
+            usb_mouse_rate_hz: self.usb_mouse_rate_hz,
+            ble_mouse_rate_hz: self.ble_mouse_rate_hz,
+            mouse_sensitivity: self.input.mouse_sensitivity,
+            caps_to_ctrl: self.input.caps_to_ctrl,
+            swap_alt_meta: self.input.swap_alt_meta,
+            disable_super: self.input.disable_super,
+        }
+    }
+
+    /// 所有方案，未显式定义时包含由顶层设置构成的 `default`
+    pub fn all_profiles(&self) -> BTreeMap<String, Profile> {
+        let mut profiles = self.profiles.clone();
+        profiles
+            .entry(DEFAULT_PROFILE.to_string())
+            .or_insert_with(|| self.base_profile());
+        profiles
+    }
+
+    pub fn usb_send_timeout(&self) -> Duration {
+        Duration::from_millis(self.usb_send_timeout_ms)
+    }
+
+    pub fn ble_send_timeout(&self) -> Duration {
+        Duration::from_millis(self.ble_send_timeout_ms)
+    }
+
+    pub fn keyboard_interval(&self) -> Duration {
+        Duration::from_millis(self.keyboard_interval_ms)
+    }
+
+    pub fn led_debounce(&self) -> Duration {
+        Duration::from_millis(self.led_debounce_ms)
+    }
+
+    /// USB gadget 配置，厂商透传功能跟随输入配置，旋钮映射为音量时启用消费类控制功能
+    pub fn usb_config(&self) -> UsbConfig {
+        UsbConfig {
+            raw_passthrough: self.input.raw_passthrough,
+            consumer_control: self.usb.consumer_control
+                || self.input.dial_target == DialTarget::Volume,
+            ..self.usb.clone()
+        }
+    }
+}
+
+/// 无需重启即可生效的设置（路径前缀）
+const HOT_RELOADABLE: &[&str] = &[
+    "usb_mouse_rate_hz",
+    "ble_mouse_rate_hz",
+    "panic_hotkey",
+    "switch_combo",
+    "profiles",
+    "active_profile",
+    "input.caps_to_ctrl",
+    "input.swap_alt_meta",
+    "input.disable_super",
+    "input.mouse_sensitivity",
+];
+
+/// 两份配置之间发生变化的设置路径，如 `input.caps_to_ctrl`、`ble.name`
+pub fn changed_settings(old: &Config, new: &Config) -> Vec<String> {
+    let (Ok(old), Ok(new)) = (serde_json::to_value(old), serde_json::to_value(new)) else {
+        return Vec::new();
+    };
+    let mut out = Vec::new();
+    collect_changed(&old, &new, "", &mut out);
+    out
+}
+
+/// 设置是否可以在运行时重新加载
+pub fn is_hot_reloadable(path: &str) -> bool {
+    HOT_RELOADABLE
+        .iter()
+        .any(|hot| path == *hot || path.starts_with(&format!("{}.", hot)))
+}
+
+fn collect_changed(old: &Value, new: &Value, prefix: &str, out: &mut Vec<String>) {
+    let (Value::Object(old), Value::Object(new)) = (old, new) else {
+        if old != new {
+            out.push(prefix.to_string());
+        }
+        return;
+    };
+    let keys: std::collections::BTreeSet<_> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match (old.get(key), new.get(key)) {
+            (Some(old), Some(new)) => collect_changed(old, new, &path, out),
+            _ => out.push(path),
+        }
+    }
+}
+
+fn collect_unknown(raw: &Value, known: &Value, prefix: &str, out: &mut Vec<String>) {
+    let (Value::Object(raw), Value::Object(known)) = (raw, known) else {
+        return;
+    };
+    for (key, value) in raw {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match known.get(key) {
+            Some(known_value) => collect_unknown(value, known_value, &path, out),
+            None => out.push(path),
+        }
+    }
+}
+
+/// `path` 旁的临时文件路径，形如 `<name>.<pid>.<n>.tmp`
+///
+/// 进程号与进程内序号保证同时写入的多个文件（包括同一文件的并发写入）不会共用临时文件。
+pub(crate) fn temp_path(path: &Path) -> PathBuf {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temp_paths_are_unique() {
+        let path = Path::new("/etc/bridge-hid/config.json");
+        let (a, b) = (temp_path(path), temp_path(path));
+        assert_ne!(a, b);
+        assert_eq!(a.parent(), path.parent());
+        let name = a.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with(&format!("config.json.{}.", std::process::id())));
+        assert!(name.ends_with(".tmp"));
+    }
+
+    #[test]
+    fn test_partial_config_fills_defaults() {
+        let text = r#"{
+            "usb_mouse_rate_hz": 1000,
+            "auto_switch": true,
+            "input": { "dial_target": "volume", "caps_to_ctrl": true },
+            "ble": { "name": "Desk Bridge", "adapter": "hci1" },
+            "web": { "bind": "127.0.0.1:8080" }
+        }"#;
+        let (config, unknown) = Config::parse(text).unwrap();
+        assert!(unknown.is_empty());
+
+        assert_eq!(config.usb_mouse_rate_hz, 1000);
+        assert_eq!(config.ble_mouse_rate_hz, 125);
+        assert!(config.auto_switch);
+        assert_eq!(config.keyboard_interval(), Duration::ZERO);
+        assert_eq!(config.led_debounce(), DEFAULT_LED_DEBOUNCE);
+
+        assert_eq!(config.input.dial_target, DialTarget::Volume);
+        assert!(config.usb_config().consumer_control);
+        assert!(!Config::default().usb_config().consumer_control);
+        assert!(!Config::default().usb_config().system_control);
+        assert_eq!(config.input.channel_capacity, DEFAULT_CHANNEL_CAPACITY);
+        let input = config.input.to_input_config();
+        assert!(input.key_remap.caps_to_ctrl());
+        assert!(!input.key_remap.swap_alt_meta());
+
+        assert_eq!(config.ble.name, "Desk Bridge");
+        assert_eq!(config.ble.adapter.as_deref(), Some("hci1"));
+        assert!(config.ble.mouse_enabled);
+
+        assert_eq!(config.web.bind, "127.0.0.1:8080");
+        assert_eq!(config.web.scroll_threshold, DEFAULT_SCROLL_THRESHOLD);
+    }
+
+    #[test]
+    fn test_unknown_fields_are_reported_not_fatal() {
+        let text = r#"{ "mouse_rate": 1, "ble": { "nmae": "typo" } }"#;
+        let (config, unknown) = Config::parse(text).unwrap();
+        assert_eq!(
+            unknown,
+            vec!["ble.nmae".to_string(), "mouse_rate".to_string()]
+        );
+        assert_eq!(config.ble.name, BleConfig::default().name);
+    }
+
+    #[test]
+    fn test_missing_file_uses_defaults() {
+        let config = Config::load(Path::new("/nonexistent/bridge-hid.json")).unwrap();
+        assert_eq!(config.usb_mouse_rate_hz, 500);
+    }
+}
@@ -1,134 +1,1173 @@
-use crate::input::{InputManager, InputReport, LedHandle};
+use crate::audit::AuditLog;
+use crate::config::{Config, DEFAULT_PROFILE, Profile, changed_settings, is_hot_reloadable};
+use crate::input::{
+    InputInjector, InputManager, InputReport, InputStatus, KeyRemap, KeyboardGrab, LedHandle,
+    MouseRateController, MouseSensitivity, ScanStatus,
+};
+use crate::metrics::{self, LatencyHistogram, ReportKind, Stage};
 use crate::output::bluetooth_ble::{
-    BluetoothBleMouseHidDevice, build_ble_hid_device, run_ble_server,
+    BleConfig, BleRegistration, BleUnavailable, build_ble_hid_device, run_ble_server,
 };
-use crate::output::usb::{UsbMouseHidDevice, build_usb_hid_device};
-use crate::output::{HidLedReader, HidReportSender, LedState, NoLedDevice};
-use log::{debug, info, warn};
+use crate::output::connection::{ConnectionState, HelloConfig, HelloSender, TimeoutSender};
+use crate::output::drag_heartbeat::{DragHeartbeat, DragHeartbeatConfig};
+use crate::output::host_prefs::{HostLedReader, HostPrefs};
+use crate::output::keep_awake::{KeepAwake, KeepAwakeConfig};
+use crate::output::key_names::{Hotkey, KeyCombo};
+use crate::output::led_debounce::LedDebouncer;
+use crate::output::mouse_gesture::{MouseGesture, MouseGestureConfig};
+use crate::output::mouse_keys::{MouseKeys, MouseKeysConfig};
+use crate::output::pairing::{AutoAccept, PairingProvider};
+use crate::output::throttle::ReportThrottle;
+use crate::output::usb::{UsbConfig, build_usb_hid_device_with_config, read_host_present};
+use crate::output::{HidLedReader, HidReportSender, LedState, NoOutput};
+use crate::preview::Preview;
+use crate::state::StateBundle;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
-use tokio::sync::{Mutex, RwLock, watch};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, Notify, RwLock, broadcast, watch};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum OutputMode {
+/// 报告输出目标
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputMode {
+    #[default]
     Usb,
     Ble,
+    /// 通过 [`OutputBackends::extra`] 注册的其他后端，按名称区分
+    Other(&'static str),
+}
+
+impl OutputMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Usb => "usb",
+            Self::Ble => "ble",
+            Self::Other(name) => name,
+        }
+    }
+}
+
+impl Serialize for OutputMode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+/// 状态文件中可以是其他后端的名称，只接受已注册的后端
+impl<'de> Deserialize<'de> for OutputMode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        match name.parse() {
+            Ok(mode) => Ok(mode),
+            Err(e) => registered_backend(&name)
+                .map(Self::Other)
+                .ok_or_else(|| serde::de::Error::custom(e)),
+        }
+    }
+}
+
+/// 已注册的其他后端名称，名称本身来自 [`NamedBackend`]，已是 `'static`
+static BACKEND_NAMES: std::sync::RwLock<BTreeSet<&'static str>> =
+    std::sync::RwLock::new(BTreeSet::new());
+
+fn register_backend(name: &'static str) {
+    BACKEND_NAMES.write().unwrap().insert(name);
+}
+
+fn registered_backend(name: &str) -> Option<&'static str> {
+    BACKEND_NAMES.read().unwrap().get(name).copied()
+}
+
+impl std::str::FromStr for OutputMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "usb" => Ok(Self::Usb),
+            "ble" => Ok(Self::Ble),
+            other => Err(format!("未知的输出: {}（可选 usb、ble）", other)),
+        }
+    }
+}
+
+/// 启动时的覆盖项，来自命令行，不写回配置
+#[derive(Debug, Clone, Default)]
+pub struct StartupOptions {
+    /// 初始输出，只能是 USB 或 BLE；其他后端在运行后才注册，之后再切换过去
+    pub mode: OutputMode,
+    /// 初始输出的鼠标报告率（Hz），未设置时使用当前方案的值
+    pub mouse_rate_hz: Option<u32>,
+}
+
+/// USB 与 BLE 主机同时在线时报告发往哪里
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputPolicy {
+    /// 只发往当前输出模式（默认）
+    #[default]
+    Single,
+    /// 同时发往所有已注册的输出
+    Mirror,
+    /// USB 主机在线时发往 USB，否则发往当前输出；当前输出为 USB 时发往下一个已注册的输出
+    PreferUsb,
+}
+
+impl OutputPolicy {
+    /// 报告的发送目标
+    /// - `registered`: 已注册的输出，按切换顺序排列
+    /// - `usb_connected`: USB 主机是否在线
+    fn targets(self, mode: OutputMode, registered: &[OutputMode], usb_connected: bool) -> Targets {
+        match self {
+            Self::Single => smallvec::smallvec![mode],
+            Self::Mirror => registered.iter().copied().collect(),
+            Self::PreferUsb if usb_connected => smallvec::smallvec![OutputMode::Usb],
+            Self::PreferUsb if mode != OutputMode::Usb => smallvec::smallvec![mode],
+            Self::PreferUsb => registered
+                .iter()
+                .copied()
+                .filter(|&output| output != OutputMode::Usb)
+                .take(1)
+                .collect(),
+        }
+    }
+}
+
+/// 一个报告的发送目标
+type Targets = SmallVec<[OutputMode; 2]>;
+
+/// 自动切换或向多个后端发送时轮询 UDC 状态的间隔
+const USB_PRESENCE_POLL: Duration = Duration::from_millis(500);
+
+/// 跟踪 USB 主机在线状态，只在线缆插拔的边沿给出目标输出模式
+#[derive(Default)]
+struct UsbPresenceTracker {
+    last: Option<bool>,
+}
+
+impl UsbPresenceTracker {
+    /// - `present`: 本次读取的在线状态，`None` 表示无法判断，不改变任何东西
+    fn update(&mut self, present: Option<bool>) -> Option<OutputMode> {
+        let present = present?;
+        if self.last == Some(present) {
+            return None;
+        }
+        self.last = Some(present);
+        Some(if present {
+            OutputMode::Usb
+        } else {
+            OutputMode::Ble
+        })
+    }
+}
+
+/// 管线使用的一组输出后端
+pub struct OutputBackends {
+    pub usb_keyboard: Box<dyn HidReportSender>,
+    pub usb_mouse: Box<dyn HidReportSender>,
+    pub usb_led: Box<dyn HidLedReader>,
+    pub ble: BleBackends,
+    /// USB 与 BLE 之外的后端，按顺序排在切换循环的末尾
+    ///
+    /// 只能由调用 [`Core::run_with`] 的代码注册，配置文件与命令行没有对应的入口。
+    pub extra: Vec<NamedBackend>,
+}
+
+/// 按名称注册的其他输出后端，例如虚拟输出
+pub struct NamedBackend {
+    /// 状态与日志中显示的名称，不能与 `usb`、`ble` 或其他后端重复
+    pub name: &'static str,
+    pub keyboard: Box<dyn HidReportSender>,
+    pub mouse: Box<dyn HidReportSender>,
+    /// 读取主机 LED 状态，没有时切换到该后端后物理键盘保持原来的 LED
+    pub led: Option<Box<dyn HidLedReader>>,
+    /// 主机连接状态，由后端自己维护
+    pub connection: ConnectionState,
+    /// 切换到该后端时使用的鼠标报告率（Hz）
+    pub mouse_rate_hz: u32,
+}
+
+/// 已注册输出的连接状态、鼠标报告率与延迟统计
+///
+/// 管线之外的状态查询、切换与报告率调整都从这里取，不再按模式逐个分支。
+#[derive(Clone)]
+struct OutputInfo {
+    mode: OutputMode,
+    connection: ConnectionState,
+    mouse_rate: Arc<AtomicU32>,
+    latency: &'static LatencyHistogram,
+}
+
+type SharedSender = Arc<Mutex<Box<dyn HidReportSender>>>;
+type SharedLedReader = Arc<Mutex<Box<dyn HidLedReader>>>;
+
+/// 运行中的一个输出后端
+struct Output {
+    mode: OutputMode,
+    keyboard: SharedSender,
+    mouse: SharedSender,
+    led: Option<SharedLedReader>,
+    connection: ConnectionState,
+    latency: &'static LatencyHistogram,
+}
+
+/// 运行中的全部输出后端，管线按模式从中选择发送端与 LED 来源
+struct Outputs {
+    entries: Vec<Output>,
+}
+
+impl Outputs {
+    fn get(&self, mode: OutputMode) -> Option<&Output> {
+        self.entries.iter().find(|output| output.mode == mode)
+    }
+
+    /// 模式对应的键盘或鼠标发送端
+    fn sender(&self, mode: OutputMode, is_mouse: bool) -> Option<&SharedSender> {
+        self.get(mode).map(|output| {
+            if is_mouse {
+                &output.mouse
+            } else {
+                &output.keyboard
+            }
+        })
+    }
+
+    fn led(&self, mode: OutputMode) -> Option<&SharedLedReader> {
+        self.get(mode).and_then(|output| output.led.as_ref())
+    }
+
+    /// 各鼠标后端中最早需要补发合并位移的时间
+    ///
+    /// 正在发送的后端被跳过，发送结束后主循环会重新计算。
+    fn flush_deadline(&self) -> Option<Instant> {
+        self.entries
+            .iter()
+            .filter_map(|output| output.mouse.try_lock().ok()?.flush_deadline())
+            .min()
+    }
+
+    /// 补发已到期的合并位移
+    async fn flush_due(&self, now: Instant) {
+        for output in &self.entries {
+            let mut mouse = output.mouse.lock().await;
+            if mouse.flush_deadline().is_some_and(|at| at <= now)
+                && let Err(e) = mouse.flush().await
+            {
+                debug!("{} 补发合并的鼠标报告失败: {:?}", output.mode.name(), e);
+            }
+        }
+    }
+}
+
+/// 已启动的 BLE 键盘与鼠标
+pub struct BleBackend {
+    pub keyboard: Box<dyn HidReportSender>,
+    pub mouse: Box<dyn HidReportSender>,
+    /// 停用或退出时注销的 GATT 服务与广播
+    pub registration: Option<Box<dyn BleRegistration>>,
+}
+
+impl BleBackend {
+    pub fn new(keyboard: Box<dyn HidReportSender>, mouse: Box<dyn HidReportSender>) -> Self {
+        Self {
+            keyboard,
+            mouse,
+            registration: None,
+        }
+    }
+}
+
+/// 创建并启动 BLE 后端
+#[async_trait]
+pub trait BleStarter: Send + Sync {
+    async fn start(&self) -> Result<BleBackend>;
+}
+
+/// BLE 后端从何而来
+pub enum BleBackends {
+    /// 已经启动，一直使用
+    Ready(BleBackend),
+    /// 第一次需要 BLE 输出时才启动
+    OnDemand(Box<dyn BleStarter>),
+}
+
+/// 通过 BlueZ 启动 BLE 服务
+struct BluezStarter {
+    config: BleConfig,
+    pairing: Arc<dyn PairingProvider>,
+    connection: ConnectionState,
+    host_prefs: HostPrefs,
+}
+
+#[async_trait]
+impl BleStarter for BluezStarter {
+    async fn start(&self) -> Result<BleBackend> {
+        let (mut ble_kb, ble_mouse, _session) =
+            build_ble_hid_device(&self.config, Arc::clone(&self.pairing)).await?;
+        ble_kb.set_connection(self.connection.clone());
+        ble_kb.set_host_prefs(self.host_prefs.clone());
+        let handles = run_ble_server(&ble_kb, &ble_mouse, &self.config).await?;
+        Ok(BleBackend {
+            keyboard: Box::new(ble_kb),
+            mouse: Box::new(ble_mouse),
+            registration: Some(Box::new(handles)),
+        })
+    }
 }
 
 pub struct Core {
     input_manager: Arc<Mutex<InputManager>>,
     led_handle: Arc<Mutex<LedHandle>>,
+    led_rx: watch::Receiver<LedState>,
     loop_cancellation_token: tokio_util::sync::CancellationToken,
     mode: Arc<RwLock<OutputMode>>,
     mode_tx: watch::Sender<OutputMode>,
     mode_rx: watch::Receiver<OutputMode>,
+    /// 已注册的输出，切换时按此顺序循环
+    outputs: std::sync::RwLock<Vec<OutputInfo>>,
+    /// 键盘报告最小间隔，为零表示不限流
+    keyboard_interval: Duration,
+    ble_config: BleConfig,
+    key_remap: KeyRemap,
+    /// LED 状态需保持不变多久才同步到物理键盘
+    led_debounce: Duration,
+    /// 根据 USB 线缆插拔自动切换输出，手动热键仍然可用
+    auto_switch: bool,
+    output_policy: OutputPolicy,
+    keep_awake: KeepAwakeConfig,
+    switch_gesture: MouseGestureConfig,
+    mouse_keys: MouseKeysConfig,
+    drag_heartbeat: DragHeartbeatConfig,
+    hello: HelloConfig,
+    /// 切换输出的组合键
+    switch_combo: std::sync::RwLock<KeyCombo>,
+    /// 紧急释放热键，与输出切换无关，任何模式下都可用
+    panic_hotkey: std::sync::RwLock<Option<Hotkey>>,
+    usb_send_timeout: Duration,
+    ble_send_timeout: Duration,
+    /// 各后端的连接状态，发送超时时标记为断开
+    usb_connection: ConnectionState,
+    ble_connection: ConnectionState,
+    /// 各 BLE 主机的 Protocol Mode 与 LED 状态，重连时恢复 LED
+    ble_host_prefs: HostPrefs,
+    /// 方案中的 USB 与 BLE 鼠标报告率，与 `outputs` 中的对应项共享
+    usb_mouse_rate: Arc<AtomicU32>,
+    ble_mouse_rate: Arc<AtomicU32>,
+    mouse_rate: MouseRateController,
+    sensitivity: MouseSensitivity,
+    profiles: std::sync::RwLock<BTreeMap<String, Profile>>,
+    /// 当前方案名，切换期间持有锁，保证各项设置一起生效
+    active_profile: Mutex<String>,
+    /// 请求主循环释放所有按键，例如切换方案之后
+    release_request: Notify,
+    /// 暂停期间丢弃所有输入，后端连接保持不变
+    paused: AtomicBool,
+    /// 蓝牙不可用时只使用 USB 输出，不能切换到 BLE
+    ble_available: AtomicBool,
+    keyboard_grab: KeyboardGrab,
+    /// 当前生效的配置，重新加载时用于比较变化
+    config: Mutex<Config>,
+    input_status: InputStatus,
+    /// 向输入管线注入报告，与 evdev 输入走同一路径
+    injector: InputInjector,
+    /// USB gadget 配置（序列号、厂商透传）
+    usb_config: UsbConfig,
+    /// 已发送报告的审计记录，未启用时为 `None`
+    audit: Option<AuditLog>,
+    preview: Preview,
+    /// 运行期间注册的 GATT 应用与广播，退出时显式注销
+    ble_registration: Mutex<Option<Box<dyn BleRegistration>>>,
+    /// 回答 BLE 配对的 passkey、确认与授权请求
+    pairing: Arc<dyn PairingProvider>,
+}
+
+impl Default for Core {
+    fn default() -> Self {
+        Self::new(&Config::default())
+    }
 }
 
 impl Core {
-    pub fn new() -> Self {
-        let mut manager = InputManager::new(500);
+    pub fn new(config: &Config) -> Self {
+        Self::with_startup(config, &StartupOptions::default())
+    }
+
+    /// 按命令行覆盖项设置初始输出与鼠标报告率
+    ///
+    /// 审计文件无法打开时只记录错误并关闭审计，启动时需要报错请用 [`Core::try_with_startup`]。
+    pub fn with_startup(config: &Config, startup: &StartupOptions) -> Self {
+        let audit = AuditLog::open(&config.audit).unwrap_or_else(|e| {
+            error!("审计记录不可用: {:?}", e);
+            None
+        });
+        Self::with_audit(config, startup, audit)
+    }
+
+    /// 与 [`Core::with_startup`] 相同，但审计文件无法打开时返回错误
+    pub fn try_with_startup(config: &Config, startup: &StartupOptions) -> Result<Self> {
+        let audit = AuditLog::open(&config.audit)?;
+        Ok(Self::with_audit(config, startup, audit))
+    }
+
+    fn with_audit(config: &Config, startup: &StartupOptions, audit: Option<AuditLog>) -> Self {
+        let profiles = config.all_profiles();
+        let (active_profile, profile) = match profiles.get(&config.active_profile) {
+            Some(profile) => (config.active_profile.clone(), profile.clone()),
+            None => {
+                warn!("配置方案 {} 不存在，使用默认方案", config.active_profile);
+                (
+                    DEFAULT_PROFILE.to_string(),
+                    profiles[DEFAULT_PROFILE].clone(),
+                )
+            }
+        };
+
+        let input_config = config.input.to_input_config();
+        let usb_connection = ConnectionState::new(true);
+        let ble_connection = ConnectionState::new(true);
+        let usb_mouse_rate = Arc::new(AtomicU32::new(profile.usb_mouse_rate_hz));
+        let ble_mouse_rate = Arc::new(AtomicU32::new(profile.ble_mouse_rate_hz));
+        let outputs = vec![
+            OutputInfo {
+                mode: OutputMode::Usb,
+                connection: usb_connection.clone(),
+                mouse_rate: Arc::clone(&usb_mouse_rate),
+                latency: &metrics::global().usb_latency,
+            },
+            OutputInfo {
+                mode: OutputMode::Ble,
+                connection: ble_connection.clone(),
+                mouse_rate: Arc::clone(&ble_mouse_rate),
+                latency: &metrics::global().ble_latency,
+            },
+        ];
+        let initial = match outputs.iter().find(|output| output.mode == startup.mode) {
+            Some(output) => output,
+            None => {
+                warn!("初始输出 {} 尚未注册，使用 USB", startup.mode.name());
+                &outputs[0]
+            }
+        };
+        if let Some(rate) = startup.mouse_rate_hz {
+            initial.mouse_rate.store(rate, Ordering::Relaxed);
+        }
+        let initial_mode = initial.mode;
+        let initial_rate = initial.mouse_rate.load(Ordering::Relaxed);
+        let mut manager = InputManager::with_config(initial_rate, input_config);
         let led_handle = manager.led_handle.take().unwrap();
-        let (mode_tx, mode_rx) = watch::channel(OutputMode::Usb);
+        let led_rx = led_handle.subscribe();
+        let key_remap = manager.key_remap.clone();
+        let sensitivity = manager.sensitivity.clone();
+        let mouse_rate = manager.mouse_rate_controller.clone();
+        let input_status = manager.status.clone();
+        let injector = manager.injector();
+        let keyboard_grab = manager.grab.clone();
+        let (mode_tx, mode_rx) = watch::channel(initial_mode);
+        apply_input_profile(&profile, &key_remap, &sensitivity);
 
         Self {
             input_manager: Arc::new(Mutex::new(manager)),
             led_handle: Arc::new(Mutex::new(led_handle)),
+            led_rx,
             loop_cancellation_token: tokio_util::sync::CancellationToken::new(),
-            mode: Arc::new(RwLock::new(OutputMode::Usb)),
+            mode: Arc::new(RwLock::new(initial_mode)),
             mode_tx,
             mode_rx,
+            outputs: std::sync::RwLock::new(outputs),
+            keyboard_interval: config.keyboard_interval(),
+            ble_config: config.ble.clone(),
+            key_remap,
+            led_debounce: config.led_debounce(),
+            auto_switch: config.auto_switch,
+            output_policy: config.output_policy,
+            keep_awake: config.keep_awake.clone(),
+            switch_gesture: config.switch_gesture.clone(),
+            mouse_keys: config.mouse_keys.clone(),
+            drag_heartbeat: config.drag_heartbeat.clone(),
+            hello: config.hello.clone(),
+            switch_combo: std::sync::RwLock::new(config.switch_combo.clone()),
+            panic_hotkey: std::sync::RwLock::new(config.panic_hotkey),
+            usb_mouse_rate,
+            ble_mouse_rate,
+            mouse_rate,
+            sensitivity,
+            profiles: std::sync::RwLock::new(profiles),
+            active_profile: Mutex::new(active_profile),
+            config: Mutex::new(config.clone()),
+            release_request: Notify::new(),
+            paused: AtomicBool::new(false),
+            ble_available: AtomicBool::new(true),
+            keyboard_grab,
+            usb_send_timeout: config.usb_send_timeout(),
+            ble_send_timeout: config.ble_send_timeout(),
+            usb_connection,
+            ble_connection,
+            ble_host_prefs: HostPrefs::load(config.ble.host_prefs_path.as_deref()),
+            input_status,
+            injector,
+            usb_config: config.usb_config(),
+            audit,
+            preview: Preview::default().with_content(config.audit.log_content),
+            ble_registration: Mutex::new(None),
+            pairing: Arc::new(AutoAccept::default()),
+        }
+    }
+
+    /// 使用自定义的配对处理，默认接受所有请求
+    pub fn with_pairing(mut self, pairing: Arc<dyn PairingProvider>) -> Self {
+        self.pairing = pairing;
+        self
+    }
+
+    /// 运行时按键重映射开关，修改后对新按下的键立即生效
+    pub fn key_remap(&self) -> &KeyRemap {
+        &self.key_remap
+    }
+
+    /// 各 BLE 主机的 Protocol Mode 与 LED 状态
+    pub fn ble_host_prefs(&self) -> &HostPrefs {
+        &self.ble_host_prefs
+    }
+
+    /// 导出当前配置、方案、输出与主机偏好
+    ///
+    /// 运行时修改过的重映射开关与鼠标灵敏度写入当前方案一并导出。
+    pub async fn export_state(&self) -> StateBundle {
+        let mut config = self.config.lock().await.clone();
+        config.active_profile = self.active_profile.lock().await.clone();
+        match config.profiles.get_mut(&config.active_profile) {
+            Some(profile) => capture_input_profile(profile, &self.key_remap, &self.sensitivity),
+            // 未显式定义的 `default` 方案由顶层设置构成
+            None => {
+                let input = &mut config.input;
+                input.caps_to_ctrl = self.key_remap.caps_to_ctrl();
+                input.swap_alt_meta = self.key_remap.swap_alt_meta();
+                input.disable_super = self.key_remap.disable_super();
+                input.mouse_sensitivity = self.sensitivity.get();
+            }
+        }
+        StateBundle::capture(&config, Some(*self.mode.read().await), &self.ble_host_prefs)
+    }
+
+    /// 导入状态：先整体检查，通过后再依次应用，检查失败时不做任何修改
+    ///
+    /// 需要重启才能生效的设置与 [`Core::reload`] 一样只记录警告。
+    pub async fn import_state(&self, bundle: StateBundle) -> Result<()> {
+        bundle.validate()?;
+        self.ble_host_prefs.replace_all(bundle.host_prefs)?;
+        self.reload(&bundle.config).await;
+        if let Some(output) = bundle.output
+            && self.set_output_mode(output).await
+        {
+            self.apply_mouse_rate(output).await;
+            self.release_request.notify_one();
+        }
+        info!("已导入运行状态");
+        Ok(())
+    }
+
+    /// 输入设备扫描状态，例如 `/dev/input` 不可读或为空
+    pub fn input_status(&self) -> ScanStatus {
+        self.input_status.get()
+    }
+
+    /// 运行状态摘要
+    pub async fn status(&self) -> CoreStatus {
+        let mode = *self.mode.read().await;
+        CoreStatus {
+            output: mode.name(),
+            targets: self.targets(mode).iter().map(|t| t.name()).collect(),
+            active_profile: self.active_profile.lock().await.clone(),
+            usb_connected: self.usb_connection.is_connected(),
+            ble_connected: self.ble_connection.is_connected(),
+            ble_peer: self.connected_peer(),
+            paused: self.is_paused(),
+            ble_available: self.ble_available.load(Ordering::Relaxed),
+            input: self.input_status(),
+        }
+    }
+
+    /// 暂停转发：释放所有按键并丢弃之后的输入，USB/BLE 连接保持不变
+    ///
+    /// 暂停期间键盘解除独占，可以在本机使用。
+    pub fn pause(&self) {
+        if !self.paused.swap(true, Ordering::Relaxed) {
+            info!("暂停转发输入");
+            self.keyboard_grab.set(false);
+            self.release_request.notify_one();
+        }
+    }
+
+    /// 恢复转发并重新独占键盘
+    pub fn resume(&self) {
+        if self.paused.swap(false, Ordering::Relaxed) {
+            info!("恢复转发输入");
+            self.keyboard_grab.set(true);
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// 通过蓝牙接收输入的主机地址
+    pub fn connected_peer(&self) -> Option<String> {
+        self.ble_connection.peer()
+    }
+
+    /// 已定义的方案名
+    pub fn profile_names(&self) -> Vec<String> {
+        self.profiles.read().unwrap().keys().cloned().collect()
+    }
+
+    /// 切换配置方案：报告率、重映射与灵敏度一起生效，随后释放所有按键
+    pub async fn switch_profile(&self, name: &str) -> Result<()> {
+        let profile = self
+            .profiles
+            .read()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("未知配置方案: {}", name))?;
+
+        let mut active = self.active_profile.lock().await;
+        self.apply_profile(&profile).await;
+        *active = name.to_string();
+        drop(active);
+
+        // 避免旧方案下按住的键在新方案中卡住
+        self.release_request.notify_one();
+        info!("已切换到配置方案: {}", name);
+        Ok(())
+    }
+
+    /// 重新加载配置，返回发生变化的设置
+    ///
+    /// 报告率、重映射、灵敏度、方案与紧急释放热键立即生效；其余变化（USB 序列号、
+    /// BLE 名称等）只记录警告，需要重启。当前方案在新配置中仍然存在时保持不变，
+    /// 除非配置文件中的 `active_profile` 本身被修改。
+    pub async fn reload(&self, new: &Config) -> Vec<String> {
+        let mut current = self.config.lock().await;
+        let changed = changed_settings(&current, new);
+        if changed.is_empty() {
+            info!("配置未变化");
+            return changed;
+        }
+        for path in &changed {
+            if is_hot_reloadable(path) {
+                info!("已重新加载设置: {}", path);
+            } else {
+                warn!("设置 {} 的修改需要重启后生效", path);
+            }
         }
+
+        let profiles = new.all_profiles();
+        let mut active = self.active_profile.lock().await;
+        let wanted = if new.active_profile != current.active_profile {
+            new.active_profile.clone()
+        } else {
+            active.clone()
+        };
+        let name = if profiles.contains_key(&wanted) {
+            wanted
+        } else {
+            warn!("配置方案 {} 不存在，使用默认方案", wanted);
+            DEFAULT_PROFILE.to_string()
+        };
+        self.apply_profile(&profiles[&name]).await;
+        *self.profiles.write().unwrap() = profiles;
+        *self.panic_hotkey.write().unwrap() = new.panic_hotkey;
+        *self.switch_combo.write().unwrap() = new.switch_combo.clone();
+        *active = name;
+        drop(active);
+        *current = new.clone();
+
+        self.release_request.notify_one();
+        changed
+    }
+
+    /// 写入方案的报告率、重映射与灵敏度
+    async fn apply_profile(&self, profile: &Profile) {
+        self.usb_mouse_rate
+            .store(profile.usb_mouse_rate_hz, Ordering::Relaxed);
+        self.ble_mouse_rate
+            .store(profile.ble_mouse_rate_hz, Ordering::Relaxed);
+        apply_input_profile(profile, &self.key_remap, &self.sensitivity);
+        let mode = *self.mode.read().await;
+        self.apply_mouse_rate(mode).await;
     }
 
     pub async fn run(&self) -> anyhow::Result<()> {
-        let (usb_kb, usb_kb_led, usb_mouse) = build_usb_hid_device().await?;
-        let (ble_kb, ble_mouse, _session) = build_ble_hid_device().await?;
-        let (_app_handle, _adv_handle) = run_ble_server(&ble_kb, &ble_mouse).await?;
-
-        let usb_kb_sender: Arc<Mutex<Box<dyn HidReportSender>>> =
-            Arc::new(Mutex::new(Box::new(usb_kb)));
-        let usb_mouse_sender: Arc<Mutex<Box<dyn HidReportSender>>> =
-            Arc::new(Mutex::new(Box::new(usb_mouse)));
-
-        let ble_kb_sender: Arc<Mutex<Box<dyn HidReportSender>>> =
-            Arc::new(Mutex::new(Box::new(ble_kb)));
-        let ble_mouse_sender: Arc<Mutex<Box<dyn HidReportSender>>> =
-            Arc::new(Mutex::new(Box::new(ble_mouse)));
-
-        let usb_led_reader: Arc<Mutex<Box<dyn HidLedReader>>> =
-            Arc::new(Mutex::new(Box::new(usb_kb_led)));
-        let ble_led_reader: Arc<Mutex<Box<dyn HidLedReader>>> =
-            Arc::new(Mutex::new(Box::new(NoLedDevice)));
-
-        let main = self.main_loop(
-            usb_kb_sender.clone(),
-            usb_mouse_sender.clone(),
-            ble_kb_sender.clone(),
-            ble_mouse_sender.clone(),
-        );
+        let (usb_kb, usb_kb_led, usb_mouse) =
+            build_usb_hid_device_with_config(&self.usb_config).await?;
+        let starter = BluezStarter {
+            config: self.ble_config.clone(),
+            pairing: Arc::clone(&self.pairing),
+            connection: self.ble_connection.clone(),
+            host_prefs: self.ble_host_prefs.clone(),
+        };
+        self.run_with_starter(
+            Box::new(usb_kb),
+            Box::new(usb_mouse),
+            Box::new(usb_kb_led),
+            Box::new(starter),
+        )
+        .await
+    }
+
+    /// 使用给定的 USB 后端运行管线，BLE 按配置立即或在第一次需要时由 `starter` 启动
+    ///
+    /// 蓝牙不可用时只使用 USB 输出，其他启动错误原样返回。
+    pub async fn run_with_starter(
+        &self,
+        usb_keyboard: Box<dyn HidReportSender>,
+        usb_mouse: Box<dyn HidReportSender>,
+        usb_led: Box<dyn HidLedReader>,
+        starter: Box<dyn BleStarter>,
+    ) -> anyhow::Result<()> {
+        let ble = if self.ble_config.on_demand {
+            BleBackends::OnDemand(starter)
+        } else {
+            let backend = self.ble_fallback(starter.start().await).await?;
+            BleBackends::Ready(
+                backend.unwrap_or_else(|| BleBackend::new(Box::new(NoOutput), Box::new(NoOutput))),
+            )
+        };
+
+        self.run_with(OutputBackends {
+            usb_keyboard,
+            usb_mouse,
+            usb_led,
+            ble,
+            extra: Vec::new(),
+        })
+        .await
+    }
+
+    /// 蓝牙不可用时切换到 USB 并继续运行，返回 `Ok(None)`；其他错误原样返回
+    async fn ble_fallback<T>(&self, result: Result<T>) -> Result<Option<T>> {
+        match result {
+            Ok(ble) => Ok(Some(ble)),
+            Err(e) if e.downcast_ref::<BleUnavailable>().is_some() => {
+                warn!("{}，仅使用 USB 输出", e);
+                self.set_output_mode(OutputMode::Usb).await;
+                self.ble_available.store(false, Ordering::Relaxed);
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// 使用给定的后端运行管线，直到收到退出信号或调用 [`Core::shutdown`]
+    pub async fn run_with(&self, backends: OutputBackends) -> anyhow::Result<()> {
+        let usb_kb_sender = self.usb_sender(backends.usb_keyboard);
+        let usb_mouse_sender = self.usb_sender(backends.usb_mouse);
+
+        let (ble_keyboard, ble_mouse, starter) = match backends.ble {
+            BleBackends::Ready(backend) => {
+                *self.ble_registration.lock().await = backend.registration;
+                (backend.keyboard, backend.mouse, None)
+            }
+            BleBackends::OnDemand(starter) => (
+                Box::new(NoOutput) as Box<dyn HidReportSender>,
+                Box::new(NoOutput) as Box<dyn HidReportSender>,
+                Some(starter),
+            ),
+        };
+        let ble_kb_sender = Arc::new(Mutex::new(self.ble_sender(ble_keyboard)));
+        let ble_mouse_sender = Arc::new(Mutex::new(self.ble_sender(ble_mouse)));
+
+        let mut outputs = Outputs {
+            entries: vec![
+                Output {
+                    mode: OutputMode::Usb,
+                    keyboard: usb_kb_sender,
+                    mouse: usb_mouse_sender,
+                    led: Some(Arc::new(Mutex::new(backends.usb_led))),
+                    connection: self.usb_connection.clone(),
+                    latency: &metrics::global().usb_latency,
+                },
+                Output {
+                    mode: OutputMode::Ble,
+                    keyboard: ble_kb_sender.clone(),
+                    mouse: ble_mouse_sender.clone(),
+                    led: Some(Arc::new(Mutex::new(Box::new(HostLedReader::new(
+                        self.ble_connection.subscribe_peer(),
+                        self.ble_host_prefs.clone(),
+                    ))))),
+                    connection: self.ble_connection.clone(),
+                    latency: &metrics::global().ble_latency,
+                },
+            ],
+        };
+        for backend in backends.extra {
+            let mode = OutputMode::Other(backend.name);
+            if outputs.get(mode).is_some() || backend.name.parse::<OutputMode>().is_ok() {
+                warn!("输出后端名称重复，忽略: {}", backend.name);
+                continue;
+            }
+            let info = OutputInfo {
+                mode,
+                connection: backend.connection,
+                mouse_rate: Arc::new(AtomicU32::new(backend.mouse_rate_hz)),
+                latency: &metrics::global().other_latency,
+            };
+            outputs.entries.push(Output {
+                mode,
+                keyboard: Arc::new(Mutex::new(backend.keyboard)),
+                mouse: Arc::new(Mutex::new(backend.mouse)),
+                led: backend.led.map(|led| Arc::new(Mutex::new(led))),
+                connection: info.connection.clone(),
+                latency: info.latency,
+            });
+            let mut registered = self.outputs.write().unwrap();
+            if !registered.iter().any(|output| output.mode == mode) {
+                registered.push(info);
+                register_backend(backend.name);
+            }
+        }
+
+        let main = self.main_loop(&outputs);
 
-        let led = self.led_loop(usb_led_reader, ble_led_reader, self.mode_rx.clone());
+        let led = self.led_loop(&outputs, self.mode_rx.clone());
+        let ble = self.ble_loop(starter, ble_kb_sender.clone(), ble_mouse_sender.clone());
 
         tokio::select! {
             _ = main => {},
             _ = led => {},
+            _ = ble => {},
+            _ = self.metrics_loop() => {},
+            _ = tokio::signal::ctrl_c() => {
+                info!("收到退出信号");
+            },
         }
 
+        self.shutdown().await;
         Ok(())
     }
 
-    async fn main_loop(
+    /// 向输入管线注入报告的句柄
+    pub fn injector(&self) -> InputInjector {
+        self.injector.clone()
+    }
+
+    /// 订阅主机下发的 LED 状态，供界面、灯带等外部程序使用
+    pub fn subscribe_leds(&self) -> watch::Receiver<LedState> {
+        self.led_rx.clone()
+    }
+
+    /// 订阅已转发报告的实时预览，每条消息为一个 JSON 对象
+    pub fn subscribe_preview(&self) -> broadcast::Receiver<String> {
+        self.preview.subscribe()
+    }
+
+    /// 当前输出名称（`usb`、`ble` 或其他后端的名称）
+    pub fn output_name(&self) -> &'static str {
+        self.mode_rx.borrow().name()
+    }
+
+    /// 当前输出后端的连接状态
+    pub fn output_connection(&self) -> ConnectionState {
+        let mode = *self.mode_rx.borrow();
+        self.registered(mode)
+            .map(|output| output.connection)
+            .unwrap_or_default()
+    }
+
+    /// 已注册输出的信息
+    fn registered(&self, mode: OutputMode) -> Option<OutputInfo> {
+        self.outputs
+            .read()
+            .unwrap()
+            .iter()
+            .find(|output| output.mode == mode)
+            .cloned()
+    }
+
+    /// 已注册的输出，按切换顺序排列
+    fn output_modes(&self) -> Targets {
+        self.outputs
+            .read()
+            .unwrap()
+            .iter()
+            .map(|output| output.mode)
+            .collect()
+    }
+
+    /// USB 后端的连接状态
+    pub fn usb_connection(&self) -> &ConnectionState {
+        &self.usb_connection
+    }
+
+    /// BLE 后端的连接状态
+    pub fn ble_connection(&self) -> &ConnectionState {
+        &self.ble_connection
+    }
+
+    /// 按配置在后端外包一层问候报告
+    fn with_hello(
+        &self,
+        inner: Box<dyn HidReportSender>,
+        connection: &ConnectionState,
+    ) -> Box<dyn HidReportSender> {
+        if self.hello.enabled {
+            Box::new(HelloSender::new(inner, connection, self.hello.delay()))
+        } else {
+            inner
+        }
+    }
+
+    fn usb_sender(&self, inner: Box<dyn HidReportSender>) -> Arc<Mutex<Box<dyn HidReportSender>>> {
+        Arc::new(Mutex::new(Box::new(TimeoutSender::new(
+            self.with_hello(inner, &self.usb_connection),
+            self.usb_send_timeout,
+            self.usb_connection.clone(),
+            "USB",
+        ))))
+    }
+
+    fn ble_sender(&self, inner: Box<dyn HidReportSender>) -> Box<dyn HidReportSender> {
+        Box::new(TimeoutSender::new(
+            self.with_hello(inner, &self.ble_connection),
+            self.ble_send_timeout,
+            self.ble_connection.clone(),
+            "BLE",
+        ))
+    }
+
+    /// 当前模式与策略下是否会向 BLE 发送报告
+    fn ble_needed(&self, mode: OutputMode) -> bool {
+        mode == OutputMode::Ble || self.output_policy != OutputPolicy::Single
+    }
+
+    /// 需要 BLE 输出时启动按需创建的后端，按配置在不再使用时停止
+    async fn ble_loop(
         &self,
-        usb_keyboard: Arc<Mutex<Box<dyn HidReportSender>>>,
-        usb_mouse: Arc<Mutex<Box<dyn HidReportSender>>>,
-        ble_keyboard: Arc<Mutex<Box<dyn HidReportSender>>>,
-        ble_mouse: Arc<Mutex<Box<dyn HidReportSender>>>,
+        starter: Option<Box<dyn BleStarter>>,
+        keyboard: Arc<Mutex<Box<dyn HidReportSender>>>,
+        mouse: Arc<Mutex<Box<dyn HidReportSender>>>,
     ) {
+        let Some(starter) = starter else {
+            return std::future::pending().await;
+        };
+        let mut mode_rx = self.mode_rx.clone();
+        let mut running = false;
+        loop {
+            let mode = *mode_rx.borrow_and_update();
+            if self.ble_needed(mode) && !running {
+                running = self.start_ble(starter.as_ref(), &keyboard, &mouse).await;
+            } else if !self.ble_needed(mode) && running && self.ble_config.stop_when_unused {
+                self.stop_ble(&keyboard, &mouse).await;
+                running = false;
+            }
+
+            tokio::select! {
+                _ = self.loop_cancellation_token.cancelled() => break,
+                changed = mode_rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// 启动 BLE 后端并替换占位的发送端，返回是否成功
+    async fn start_ble(
+        &self,
+        starter: &dyn BleStarter,
+        keyboard: &Arc<Mutex<Box<dyn HidReportSender>>>,
+        mouse: &Arc<Mutex<Box<dyn HidReportSender>>>,
+    ) -> bool {
+        info!("启动 BLE 服务");
+        match self.ble_fallback(starter.start().await).await {
+            Ok(Some(backend)) => {
+                *keyboard.lock().await = self.ble_sender(backend.keyboard);
+                *mouse.lock().await = self.ble_sender(backend.mouse);
+                *self.ble_registration.lock().await = backend.registration;
+                true
+            }
+            Ok(None) => false,
+            Err(e) => {
+                error!("启动 BLE 服务失败，切换回 USB: {:?}", e);
+                self.set_output_mode(OutputMode::Usb).await;
+                false
+            }
+        }
+    }
+
+    /// 注销 BLE 服务，发送端换回占位
+    async fn stop_ble(
+        &self,
+        keyboard: &Arc<Mutex<Box<dyn HidReportSender>>>,
+        mouse: &Arc<Mutex<Box<dyn HidReportSender>>>,
+    ) {
+        info!("BLE 输出不再使用，停止 BLE 服务");
+        *keyboard.lock().await = self.ble_sender(Box::new(NoOutput));
+        *mouse.lock().await = self.ble_sender(Box::new(NoOutput));
+        let registration = self.ble_registration.lock().await.take();
+        if let Some(registration) = registration
+            && let Err(e) = registration.unregister().await
+        {
+            warn!("注销 BLE 服务失败: {:?}", e);
+        }
+    }
+
+    /// 停止所有循环并注销 BLE 服务，可重复调用
+    pub async fn shutdown(&self) {
+        self.loop_cancellation_token.cancel();
+        let registration = self.ble_registration.lock().await.take();
+        if let Some(registration) = registration
+            && let Err(e) = registration.unregister().await
+        {
+            warn!("注销 BLE 服务失败: {:?}", e);
+        }
+        self.ble_host_prefs.flush().await;
+    }
+
+    async fn main_loop(&self, outputs: &Outputs) {
         let cancellation_token = self.loop_cancellation_token.clone();
         let input_manager = Arc::clone(&self.input_manager);
         let mut switch_latched = false;
+        let mut gesture = MouseGesture::new(&self.switch_gesture);
+        let mut panic_latched = false;
+        let mut keyboard_throttle = ReportThrottle::new(self.keyboard_interval);
+        let mut presence_poll = tokio::time::interval(USB_PRESENCE_POLL);
+        let mut presence = UsbPresenceTracker::default();
+        let mut keep_awake = KeepAwake::new(&self.keep_awake, Instant::now());
+        let mut mouse_keys = MouseKeys::new(&self.mouse_keys);
+        let mut drag_heartbeat = DragHeartbeat::new(&self.drag_heartbeat);
 
         loop {
+            let wiggle_at = keep_awake.deadline();
+            let mouse_keys_at = mouse_keys.deadline();
+            let heartbeat_at = drag_heartbeat.deadline();
+            let flush_at = outputs.flush_deadline();
+            let keyboard_flush_at = keyboard_throttle.flush_deadline();
             tokio::select! {
                 _ = cancellation_token.cancelled() => {
                     info!("主循环退出");
                     break;
                 }
-                event = async {
+                _ = self.release_request.notified() => {
+                    self.release_all(outputs).await;
+                    keyboard_throttle.reset();
+                    mouse_keys.reset();
+                    drag_heartbeat.reset();
+                }
+                _ = presence_poll.tick(), if self.auto_switch || self.output_policy != OutputPolicy::Single => {
+                    let present = read_host_present().await;
+                    if let Some(present) = present {
+                        self.usb_connection.set_connected(present);
+                    }
+                    if self.auto_switch
+                        && let Some(target) = presence.update(present)
+                        && self.set_output_mode(target).await
+                    {
+                        self.release_all(outputs).await;
+                        keyboard_throttle.reset();
+                        mouse_keys.reset();
+                        drag_heartbeat.reset();
+                        self.apply_mouse_rate(target).await;
+                    }
+                }
+                _ = tokio::time::sleep_until(mouse_keys_at.unwrap_or(wiggle_at).into()), if mouse_keys_at.is_some() && !self.is_paused() => {
+                    if let Some(movement) = mouse_keys.poll(Instant::now()) {
+                        drag_heartbeat.observe(&movement, Instant::now());
+                        if let Err(e) = self
+                            .dispatch(movement, outputs)
+                            .await
+                        {
+                            debug!("发送键盘指针移动失败: {:?}", e);
+                        }
+                    }
+                }
+                _ = tokio::time::sleep_until(heartbeat_at.unwrap_or(wiggle_at).into()), if heartbeat_at.is_some() && !self.is_paused() => {
+                    if let Some(beat) = drag_heartbeat.poll(Instant::now())
+                        && let Err(e) = self
+                            .dispatch(beat, outputs)
+                            .await
+                    {
+                        debug!("发送拖拽心跳失败: {:?}", e);
+                    }
+                }
+                _ = tokio::time::sleep_until(flush_at.unwrap_or(wiggle_at).into()), if flush_at.is_some() => {
+                    outputs.flush_due(Instant::now()).await;
+                }
+                _ = tokio::time::sleep_until(keyboard_flush_at.unwrap_or(wiggle_at).into()), if keyboard_flush_at.is_some() => {
+                    if let Some(report) = keyboard_throttle.flush()
+                        && let Err(e) = self.dispatch(report, outputs).await
+                    {
+                        debug!("发送排队的键盘报告失败: {:?}", e);
+                    }
+                }
+                _ = tokio::time::sleep_until(wiggle_at.into()), if self.keep_awake.enabled => {
+                    if let Some(wiggle) = keep_awake.poll(Instant::now())
+                        && let Err(e) = self
+                            .dispatch(wiggle, outputs)
+                            .await
+                    {
+                        debug!("发送防休眠鼠标报告失败: {:?}", e);
+                    }
+                }
+                timed = async {
                     let mut mgr = input_manager.lock().await;
-                    mgr.next_event().await
+                    mgr.next_timed_event().await
                 } => {
-                    if let Some(event) = event {
-                        if self.should_toggle(&event, &mut switch_latched) {
+                    if let Some(timed) = timed {
+                        if self.is_paused() {
+                            continue;
+                        }
+                        keep_awake.activity(Instant::now());
+                        let event = timed.report;
+                        if self.should_panic_release(&event, &mut panic_latched) {
+                            info!("紧急释放所有按键");
+                            self.release_all(outputs).await;
+                            keyboard_throttle.reset();
+                            mouse_keys.reset();
+                            drag_heartbeat.reset();
+                            continue;
+                        }
+                        if self.should_toggle(&event, &mut switch_latched)
+                            || gesture.observe(&event, Instant::now())
+                        {
                             self.toggle_output().await;
-                            self.release_all(&usb_keyboard, &usb_mouse, &ble_keyboard, &ble_mouse).await;
+                            self.release_all(outputs).await;
+                            keyboard_throttle.reset();
+                            mouse_keys.reset();
+                            drag_heartbeat.reset();
                             let mode = *self.mode.read().await;
-                            {
-                                let mgr = input_manager.lock().await;
-                                match mode {
-                                    OutputMode::Usb => mgr.set_mouse_rate(500),
-                                    OutputMode::Ble => mgr.set_mouse_rate(125),
-                                }
-                            }
+                            self.apply_mouse_rate(mode).await;
                             continue;
                         }
-                        let mode = *self.mode.read().await;
-                        let result = match (&event, mode) {
-                            (InputReport::Keyboard { .. }, OutputMode::Usb) => {
-                                usb_keyboard.lock().await.send_report(event).await
-                            }
-                            (InputReport::Mouse { .. }, OutputMode::Usb) => {
-                                usb_mouse.lock().await.send_report(event).await
-                            }
-                            (InputReport::Keyboard { .. }, OutputMode::Ble) => {
-                                ble_keyboard.lock().await.send_report(event).await
-                            }
-                            (InputReport::Mouse { .. }, OutputMode::Ble) => {
-                                ble_mouse.lock().await.send_report(event).await
-                            }
-                        };
+                        let mut failed = false;
+                        for event in mouse_keys.filter(event, Instant::now()) {
+                            let Some(event) = keyboard_throttle.admit(event) else {
+                                continue;
+                            };
+                            drag_heartbeat.observe(&event, Instant::now());
+                            let Ok(targets) = self
+                                .dispatch(event, outputs)
+                                .await
+                            else {
+                                failed = true;
+                                break;
+                            };
 
-                        if result.is_err() {
+                            let latency = timed.created_at.elapsed();
+                            for target in targets {
+                                if let Some(output) = outputs.get(target) {
+                                    output.latency.record(latency);
+                                }
+                            }
+                        }
+                        if failed {
                             info!("发送 HID 报告出错，退出主循环");
                             break;
                         }
@@ -138,22 +1177,60 @@ impl Core {
         }
     }
 
-    async fn led_loop(
-        &self,
-        usb_led_reader: Arc<Mutex<Box<dyn HidLedReader>>>,
-        ble_led_reader: Arc<Mutex<Box<dyn HidLedReader>>>,
-        mut mode_rx: watch::Receiver<OutputMode>,
-    ) {
+    async fn led_loop(&self, outputs: &Outputs, mut mode_rx: watch::Receiver<OutputMode>) {
         let cancellation_token = self.loop_cancellation_token.clone();
         let led_handle = Arc::clone(&self.led_handle);
-        let mut current_led_state: LedState = LedState::default();
+        let mut debouncer = LedDebouncer::new(self.led_debounce);
+        let mut connections: Vec<_> = outputs
+            .entries
+            .iter()
+            .map(|output| (output.mode, output.connection.subscribe()))
+            .collect();
+        let mut warmup = false;
 
         loop {
             let mode = *mode_rx.borrow();
+            let reader = outputs.led(mode);
+            if std::mem::take(&mut warmup)
+                && let Some(reader) = reader
+            {
+                let state = reader.lock().await.host_led_state();
+                if let Some(state) = state {
+                    debug!("主机已连接，同步 LED 状态: {:?}", state);
+                    debouncer.mark_pushed(state);
+                    led_handle.lock().await.set_leds(&state).await;
+                }
+            }
+            let connected = connections
+                .iter_mut()
+                .find(|(output, _)| *output == mode)
+                .map(|(_, rx)| rx);
+            let connection_changed = async {
+                let Some(rx) = connected else {
+                    return std::future::pending().await;
+                };
+                rx.changed().await.ok()?;
+                Some(*rx.borrow_and_update())
+            };
             let read_future = async {
-                match mode {
-                    OutputMode::Usb => usb_led_reader.lock().await.get_led_state().await,
-                    OutputMode::Ble => ble_led_reader.lock().await.get_led_state().await,
+                // 没有 LED 来源的后端不读取，物理键盘保持原来的 LED
+                let Some(reader) = reader else {
+                    return std::future::pending().await;
+                };
+                let mut reader = reader.lock().await;
+                let result = reader.get_led_state().await;
+                // 非阻塞读取没有数据时等待一个轮询间隔，期间仍能及时响应退出
+                if let (Ok(None), Some(interval)) = (&result, reader.poll_interval()) {
+                    tokio::time::sleep(interval).await;
+                }
+                result
+            };
+
+            let deadline = debouncer.deadline();
+            let settle = async {
+                match deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                    None => std::future::pending::<()>().await,
                 }
             };
 
@@ -163,17 +1240,27 @@ impl Core {
                     break;
                 }
                 _ = mode_rx.changed() => {
-                    current_led_state = LedState::default();
+                    debouncer.discard_pending();
                     continue;
                 }
-                result = read_future => {
-                    match result {
-                        Ok(Some(state)) => {
-                            if current_led_state != state {
-                                let handle = led_handle.lock().await;
-                                handle.set_leds(&state).await;
-                                current_led_state = state;
-                            }
+                Some(now_connected) = connection_changed => {
+                    // 新主机连接时立即同步它的 LED 状态，不等待下一次输出报告
+                    warmup = now_connected;
+                    if !now_connected && let Some(reader) = reader {
+                        reader.lock().await.clear_host_led_state();
+                    }
+                    continue;
+                }
+                _ = settle => {
+                    if let Some(state) = debouncer.poll(Instant::now()) {
+                        let handle = led_handle.lock().await;
+                        handle.set_leds(&state).await;
+                    }
+                }
+                result = read_future => {
+                    match result {
+                        Ok(Some(state)) => {
+                            debouncer.observe(state, Instant::now());
                         }
                         Ok(None) => {}
                         Err(e) => {
@@ -186,40 +1273,162 @@ impl Core {
         }
     }
 
+    /// 定期输出端到端延迟摘要，有报告被丢弃或合并时一并输出
+    async fn metrics_loop(&self) {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        interval.tick().await;
+        let mut last_counts = (0, 0, 0);
+        let mut last_loss = 0;
+
+        loop {
+            tokio::select! {
+                _ = self.loop_cancellation_token.cancelled() => break,
+                _ = interval.tick() => {
+                    let m = metrics::global();
+                    let counts = (
+                        m.usb_latency.count(),
+                        m.ble_latency.count(),
+                        m.other_latency.count(),
+                    );
+                    if counts != last_counts {
+                        info!(
+                            "输入延迟统计 USB: {} | BLE: {} | 其他: {}",
+                            m.usb_latency.summary(),
+                            m.ble_latency.summary(),
+                            m.other_latency.summary()
+                        );
+                        last_counts = counts;
+                    }
+                    let loss = m.report_loss.total();
+                    if loss != last_loss {
+                        info!("报告丢弃/合并统计: {}", m.report_loss.summary());
+                        last_loss = loss;
+                    }
+                }
+            }
+        }
+    }
+
+    /// 按注册顺序切换到下一个可用的输出
     async fn toggle_output(&self) {
+        let current = *self.mode.read().await;
+        let outputs = self.output_modes();
+        let start = outputs
+            .iter()
+            .position(|&mode| mode == current)
+            .unwrap_or(0);
+        for step in 1..outputs.len() {
+            if self
+                .set_output_mode(outputs[(start + step) % outputs.len()])
+                .await
+            {
+                return;
+            }
+        }
+    }
+
+    /// 切换到指定输出，返回模式是否发生变化
+    async fn set_output_mode(&self, target: OutputMode) -> bool {
+        if target == OutputMode::Ble && !self.ble_available.load(Ordering::Relaxed) {
+            warn!("蓝牙不可用，保持 USB 输出");
+            return false;
+        }
+        if self.registered(target).is_none() {
+            warn!("未注册的输出: {}", target.name());
+            return false;
+        }
         let mut mode = self.mode.write().await;
-        *mode = match *mode {
-            OutputMode::Usb => OutputMode::Ble,
-            OutputMode::Ble => OutputMode::Usb,
-        };
+        if *mode == target {
+            return false;
+        }
+        *mode = target;
         let _ = self.mode_tx.send(*mode);
         info!("当前输出切换为: {:?}", *mode);
+        true
+    }
+
+    /// 按输出模式设置鼠标报告率
+    async fn apply_mouse_rate(&self, mode: OutputMode) {
+        if let Some(output) = self.registered(mode) {
+            self.mouse_rate
+                .set_rate(output.mouse_rate.load(Ordering::Relaxed));
+        }
+    }
+
+    /// 按输出策略得到的发送目标
+    fn targets(&self, mode: OutputMode) -> Targets {
+        self.output_policy.targets(
+            mode,
+            &self.output_modes(),
+            self.usb_connection.is_connected(),
+        )
+    }
+
+    /// 把报告发往输出策略选定的后端，返回实际发送成功的目标
+    ///
+    /// 只有一个目标时发送错误原样返回。镜像到多个目标时跳过已断开的后端，
+    /// 某个后端出错只记录并计入丢弃，其他后端照常发送。
+    async fn dispatch(&self, event: InputReport, outputs: &Outputs) -> Result<Targets> {
+        let targets = self.targets(*self.mode.read().await);
+        let mirrored = targets.len() > 1;
+        let is_mouse = matches!(event, InputReport::Mouse { .. });
+        let mut sent = Targets::new();
+        for target in targets {
+            let Some(sender) = outputs.sender(target, is_mouse) else {
+                continue;
+            };
+            if mirrored
+                && outputs
+                    .get(target)
+                    .is_some_and(|output| !output.connection.is_connected())
+            {
+                continue;
+            }
+            match send_if_supported(sender.lock().await.as_mut(), event.clone()).await {
+                Ok(true) => {}
+                // 审计与预览只记录真正发出的报告
+                Ok(false) => continue,
+                Err(e) => {
+                    metrics::global()
+                        .report_loss
+                        .record_dropped(Stage::BackendSend, ReportKind::of(&event));
+                    if !mirrored {
+                        return Err(e);
+                    }
+                    warn!("{} 发送报告失败，继续发往其他后端: {:?}", target.name(), e);
+                    continue;
+                }
+            }
+            if let Some(audit) = &self.audit {
+                audit.record(target.name(), &event);
+            }
+            self.preview.publish(target.name(), &event);
+            sent.push(target);
+        }
+        Ok(sent)
     }
 
     fn should_toggle(&self, event: &InputReport, switch_latched: &mut bool) -> bool {
         match event {
             InputReport::Keyboard { modifiers, keys } => {
-                let hit = is_switch_combo(*modifiers, keys);
-                if hit && !*switch_latched {
-                    *switch_latched = true;
-                    return true;
-                }
-                if !hit && *switch_latched {
-                    *switch_latched = false;
-                }
-                false
+                let hit = self.switch_combo.read().unwrap().matches(*modifiers, keys);
+                latch_combo(hit, switch_latched)
             }
             _ => false,
         }
     }
 
-    async fn release_all(
-        &self,
-        usb_keyboard: &Arc<Mutex<Box<dyn HidReportSender>>>,
-        usb_mouse: &Arc<Mutex<Box<dyn HidReportSender>>>,
-        ble_keyboard: &Arc<Mutex<Box<dyn HidReportSender>>>,
-        ble_mouse: &Arc<Mutex<Box<dyn HidReportSender>>>,
-    ) {
+    /// 是否按下了紧急释放热键，按住期间只触发一次
+    fn should_panic_release(&self, event: &InputReport, panic_latched: &mut bool) -> bool {
+        match (event, *self.panic_hotkey.read().unwrap()) {
+            (InputReport::Keyboard { modifiers, keys }, Some(hotkey)) => {
+                latch_combo(hotkey.matches(*modifiers, keys), panic_latched)
+            }
+            _ => false,
+        }
+    }
+
+    async fn release_all(&self, outputs: &Outputs) {
         let empty_kb = InputReport::Keyboard {
             modifiers: 0,
             keys: vec![],
@@ -231,25 +1440,1021 @@ impl Core {
             wheel: 0,
         };
 
-        let _ = usb_keyboard
-            .lock()
+        for output in &outputs.entries {
+            let _ = output
+                .keyboard
+                .lock()
+                .await
+                .send_report(empty_kb.clone())
+                .await;
+            let _ = output
+                .mouse
+                .lock()
+                .await
+                .send_report(empty_mouse.clone())
+                .await;
+        }
+    }
+}
+
+/// 运行状态摘要，可直接序列化为状态接口的响应
+#[derive(Debug, Clone, Serialize)]
+pub struct CoreStatus {
+    pub output: &'static str,
+    /// 按输出策略实际接收报告的后端
+    pub targets: Vec<&'static str>,
+    pub active_profile: String,
+    pub usb_connected: bool,
+    pub ble_connected: bool,
+    pub ble_peer: Option<String>,
+    /// 是否暂停转发
+    pub paused: bool,
+    /// 蓝牙是否可用，不可用时只使用 USB 输出
+    pub ble_available: bool,
+    /// 输入设备扫描状态
+    pub input: ScanStatus,
+}
+
+/// 把方案中的重映射与灵敏度写入共享的运行时开关
+fn apply_input_profile(profile: &Profile, key_remap: &KeyRemap, sensitivity: &MouseSensitivity) {
+    key_remap.set_caps_to_ctrl(profile.caps_to_ctrl);
+    key_remap.set_swap_alt_meta(profile.swap_alt_meta);
+    key_remap.set_disable_super(profile.disable_super);
+    sensitivity.set(profile.mouse_sensitivity);
+}
+
+/// 与 [`apply_input_profile`] 相反：把运行时开关的当前值写回方案
+fn capture_input_profile(
+    profile: &mut Profile,
+    key_remap: &KeyRemap,
+    sensitivity: &MouseSensitivity,
+) {
+    profile.caps_to_ctrl = key_remap.caps_to_ctrl();
+    profile.swap_alt_meta = key_remap.swap_alt_meta();
+    profile.disable_super = key_remap.disable_super();
+    profile.mouse_sensitivity = sensitivity.get();
+}
+
+/// 组合键边沿检测：按下时触发一次，松开后才能再次触发
+fn latch_combo(hit: bool, latched: &mut bool) -> bool {
+    let fire = hit && !*latched;
+    *latched = hit;
+    fire
+}
+
+/// 仅在后端支持时发送报告，返回是否已发送；不支持的报告直接跳过而不是报错
+///
+/// 没有任何能力的后端是尚未就绪的占位（例如启动中的 BLE），发往它的报告计入丢弃。
+async fn send_if_supported(sender: &mut dyn HidReportSender, report: InputReport) -> Result<bool> {
+    let capabilities = sender.capabilities();
+    if capabilities.is_empty() {
+        debug!("后端尚未就绪，丢弃报告: {:?}", report);
+        metrics::global()
+            .report_loss
+            .record_dropped(Stage::BackendSend, ReportKind::of(&report));
+        return Ok(false);
+    }
+    if !capabilities.supports(&report) {
+        debug!("后端不支持该报告，已跳过: {:?}", report);
+        return Ok(false);
+    }
+    sender.send_report(report).await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::TimedReport;
+    use crate::output::BackendCapabilities;
+    use crate::output::virtual_hid::VirtualHidDevice;
+
+    /// 依次为 USB 键盘、USB 鼠标、BLE 键盘、BLE 鼠标的输出
+    fn virtual_outputs(devices: &[VirtualHidDevice]) -> Outputs {
+        let shared = |device: &VirtualHidDevice| -> SharedSender {
+            Arc::new(Mutex::new(Box::new(device.clone())))
+        };
+        Outputs {
+            entries: vec![
+                Output {
+                    mode: OutputMode::Usb,
+                    keyboard: shared(&devices[0]),
+                    mouse: shared(&devices[1]),
+                    led: None,
+                    connection: ConnectionState::new(true),
+                    latency: &metrics::global().usb_latency,
+                },
+                Output {
+                    mode: OutputMode::Ble,
+                    keyboard: shared(&devices[2]),
+                    mouse: shared(&devices[3]),
+                    led: None,
+                    connection: ConnectionState::new(true),
+                    latency: &metrics::global().ble_latency,
+                },
+            ],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_report_is_skipped() {
+        let mut keyboard_only = VirtualHidDevice::with_capabilities(BackendCapabilities::KEYBOARD);
+        let mouse = InputReport::Mouse {
+            buttons: 0,
+            x: 1,
+            y: 1,
+            wheel: 0,
+        };
+
+        // 直接发送会出错，按能力路由则跳过
+        assert!(keyboard_only.send_report(mouse.clone()).await.is_err());
+        assert!(!send_if_supported(&mut keyboard_only, mouse).await.unwrap());
+        assert!(
+            send_if_supported(
+                &mut keyboard_only,
+                InputReport::Keyboard {
+                    modifiers: 0,
+                    keys: vec![0x04],
+                },
+            )
             .await
-            .send_report(empty_kb.clone())
-            .await;
-        let _ = usb_mouse
-            .lock()
+            .unwrap()
+        );
+
+        let reports = keyboard_only.reports();
+        assert_eq!(reports.len(), 1);
+        assert!(matches!(reports[0], InputReport::Keyboard { .. }));
+    }
+
+    struct MockRegistration {
+        unregistered: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl BleRegistration for MockRegistration {
+        async fn unregister(self: Box<Self>) -> Result<()> {
+            self.unregistered
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_unregisters_ble_once() {
+        let core = Core::new(&Config::without_devices());
+        let unregistered = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        *core.ble_registration.lock().await = Some(Box::new(MockRegistration {
+            unregistered: unregistered.clone(),
+        }));
+
+        core.shutdown().await;
+        assert!(core.loop_cancellation_token.is_cancelled());
+        assert_eq!(unregistered.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        // 再次调用不会重复注销
+        core.shutdown().await;
+        assert_eq!(unregistered.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    /// 记录启动次数，每次启动返回同一个虚拟键盘
+    struct CountingStarter {
+        starts: Arc<std::sync::atomic::AtomicUsize>,
+        unregistered: Arc<std::sync::atomic::AtomicUsize>,
+        keyboard: VirtualHidDevice,
+    }
+
+    #[async_trait::async_trait]
+    impl BleStarter for CountingStarter {
+        async fn start(&self) -> Result<BleBackend> {
+            self.starts
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(BleBackend {
+                keyboard: Box::new(self.keyboard.clone()),
+                mouse: Box::new(VirtualHidDevice::new()),
+                registration: Some(Box::new(MockRegistration {
+                    unregistered: self.unregistered.clone(),
+                })),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ble_starts_on_first_switch() {
+        use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+
+        let core = Arc::new(Core::new(&Config {
+            ble: BleConfig {
+                stop_when_unused: true,
+                ..BleConfig::default()
+            },
+            ..Config::without_devices()
+        }));
+        let starts = Arc::new(AtomicUsize::new(0));
+        let unregistered = Arc::new(AtomicUsize::new(0));
+        let usb_keyboard = VirtualHidDevice::new();
+        let ble_keyboard = VirtualHidDevice::new();
+        let backends = OutputBackends {
+            usb_keyboard: Box::new(usb_keyboard.clone()),
+            usb_mouse: Box::new(VirtualHidDevice::new()),
+            usb_led: Box::new(crate::output::NoLedDevice),
+            ble: BleBackends::OnDemand(Box::new(CountingStarter {
+                starts: starts.clone(),
+                unregistered: unregistered.clone(),
+                keyboard: ble_keyboard.clone(),
+            })),
+            extra: Vec::new(),
+        };
+        let runner = Arc::clone(&core);
+        let pipeline = tokio::spawn(async move { runner.run_with(backends).await });
+        async fn wait_until(done: impl Fn() -> bool) {
+            tokio::time::timeout(Duration::from_secs(2), async {
+                while !done() {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .unwrap()
+        }
+        let key = InputReport::Keyboard {
+            modifiers: 0,
+            keys: vec![0x04],
+        };
+
+        // USB 输出时不启动 BLE
+        assert_eq!(core.output_name(), "usb");
+        core.injector().inject(key.clone()).await.unwrap();
+        wait_until(|| !usb_keyboard.reports().is_empty()).await;
+        assert_eq!(starts.load(SeqCst), 0);
+
+        // 第一次切换到 BLE 时启动，报告发往 BLE 键盘
+        assert!(core.set_output_mode(OutputMode::Ble).await);
+        wait_until(|| starts.load(SeqCst) == 1).await;
+        core.injector().inject(key.clone()).await.unwrap();
+        wait_until(|| !ble_keyboard.reports().is_empty()).await;
+
+        // 切换离开后注销，再切回来时重新启动
+        core.set_output_mode(OutputMode::Usb).await;
+        wait_until(|| unregistered.load(SeqCst) == 1).await;
+        core.set_output_mode(OutputMode::Ble).await;
+        wait_until(|| starts.load(SeqCst) == 2).await;
+
+        core.shutdown().await;
+        pipeline.await.unwrap().unwrap();
+        assert_eq!(unregistered.load(SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unopenable_audit_file_fails_startup() {
+        let mut config = Config::without_devices();
+        config.audit.enabled = true;
+        config.audit.path = Some(std::env::temp_dir().join("bridge-hid-missing/audit.log"));
+
+        let startup = StartupOptions::default();
+        assert!(Core::try_with_startup(&config, &startup).is_err());
+        assert!(Core::with_startup(&config, &startup).audit.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_pending_ble_is_not_audited_or_previewed() {
+        let core = Core::with_startup(
+            &Config::without_devices(),
+            &StartupOptions {
+                mode: OutputMode::Ble,
+                mouse_rate_hz: None,
+            },
+        );
+        let mut preview = core.subscribe_preview();
+        let devices: Vec<VirtualHidDevice> = (0..4).map(|_| VirtualHidDevice::new()).collect();
+        let mut outputs = virtual_outputs(&devices);
+        // 按需启动的 BLE 尚未就绪时发送端是占位
+        outputs.entries[1].keyboard = Arc::new(Mutex::new(Box::new(NoOutput)));
+
+        let sent = core
+            .dispatch(
+                InputReport::Keyboard {
+                    modifiers: 0,
+                    keys: vec![0x04],
+                },
+                &outputs,
+            )
             .await
-            .send_report(empty_mouse.clone())
+            .unwrap();
+        assert!(sent.is_empty());
+        assert!(preview.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_panic_hotkey_releases_without_switching() {
+        use crate::output::keycodes::KEY_BACKSPACE;
+
+        let core = Core::new(&Config {
+            panic_hotkey: Some(Hotkey::try_from("ctrl+alt+backspace".to_string()).unwrap()),
+            ..Config::without_devices()
+        });
+        let devices: Vec<VirtualHidDevice> = (0..4).map(|_| VirtualHidDevice::new()).collect();
+        let outputs = virtual_outputs(&devices);
+
+        let combo = InputReport::Keyboard {
+            modifiers: 0x10 | 0x04,
+            keys: vec![KEY_BACKSPACE],
+        };
+        let (mut panic_latched, mut switch_latched) = (false, false);
+        assert!(!core.should_toggle(&combo, &mut switch_latched));
+        assert!(core.should_panic_release(&combo, &mut panic_latched));
+        // 按住不重复触发
+        assert!(!core.should_panic_release(&combo, &mut panic_latched));
+
+        core.release_all(&outputs).await;
+        for device in &devices {
+            match device.reports().as_slice() {
+                [InputReport::Keyboard { modifiers: 0, keys }] => assert!(keys.is_empty()),
+                [InputReport::Mouse { buttons: 0, .. }] => {}
+                other => panic!("unexpected reports: {:?}", other),
+            }
+        }
+        assert_eq!(*core.mode.read().await, OutputMode::Usb);
+    }
+
+    #[tokio::test]
+    async fn test_startup_options_seed_mode_and_rate() {
+        let core = Core::with_startup(
+            &Config::without_devices(),
+            &StartupOptions {
+                mode: OutputMode::Ble,
+                mouse_rate_hz: Some(125),
+            },
+        );
+        assert_eq!(*core.mode.read().await, OutputMode::Ble);
+        assert_eq!(core.output_name(), "ble");
+        assert_eq!(core.mouse_rate.get_rate(), 125);
+        // 覆盖只作用于初始输出
+        core.apply_mouse_rate(OutputMode::Usb).await;
+        assert_eq!(
+            core.mouse_rate.get_rate(),
+            Profile::default().usb_mouse_rate_hz
+        );
+    }
+
+    #[tokio::test]
+    async fn test_switch_profile_applies_rate_and_remap() {
+        let mut config = Config::without_devices();
+        config.profiles.insert(
+            "gaming".to_string(),
+            Profile {
+                usb_mouse_rate_hz: 1000,
+                mouse_sensitivity: 150,
+                ..Default::default()
+            },
+        );
+        config.profiles.insert(
+            "typing".to_string(),
+            Profile {
+                usb_mouse_rate_hz: 125,
+                caps_to_ctrl: true,
+                ..Default::default()
+            },
+        );
+        config.active_profile = "gaming".to_string();
+        let core = Core::new(&config);
+        assert_eq!(core.mouse_rate.get_rate(), 1000);
+        assert_eq!(core.sensitivity.get(), 150);
+        assert!(!core.key_remap().caps_to_ctrl());
+
+        core.switch_profile("typing").await.unwrap();
+        assert_eq!(core.mouse_rate.get_rate(), 125);
+        assert_eq!(core.sensitivity.get(), 100);
+        assert!(core.key_remap().caps_to_ctrl());
+        assert_eq!(core.status().await.active_profile, "typing");
+        // 切换后请求释放所有按键
+        tokio::time::timeout(Duration::from_secs(1), core.release_request.notified())
+            .await
+            .unwrap();
+
+        assert!(core.switch_profile("missing").await.is_err());
+        assert_eq!(core.status().await.active_profile, "typing");
+        assert!(
+            core.profile_names()
+                .iter()
+                .any(|name| name == DEFAULT_PROFILE)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_includes_runtime_remap_and_sensitivity() {
+        let mut config = Config::without_devices();
+        config
+            .profiles
+            .insert("gaming".to_string(), Profile::default());
+        let core = Core::new(&config);
+
+        // 隐式的 default 方案写回顶层设置
+        core.key_remap().set_caps_to_ctrl(true);
+        core.sensitivity.set(150);
+        let exported = core.export_state().await.config;
+        assert!(exported.input.caps_to_ctrl);
+        assert_eq!(exported.input.mouse_sensitivity, 150);
+
+        core.switch_profile("gaming").await.unwrap();
+        core.key_remap().set_swap_alt_meta(true);
+        core.sensitivity.set(80);
+        let exported = core.export_state().await.config;
+        let gaming = &exported.profiles["gaming"];
+        assert!(gaming.swap_alt_meta && !gaming.caps_to_ctrl);
+        assert_eq!(gaming.mouse_sensitivity, 80);
+        assert!(!exported.input.swap_alt_meta);
+    }
+
+    #[tokio::test]
+    async fn test_connected_peer_follows_ble_connection() {
+        let core = Core::new(&Config::without_devices());
+        assert_eq!(core.connected_peer(), None);
+
+        assert!(
+            core.ble_connection()
+                .set_peer(Some("AA:BB:CC:DD:EE:FF".to_string()))
+        );
+        assert_eq!(core.connected_peer().as_deref(), Some("AA:BB:CC:DD:EE:FF"));
+        let status = serde_json::to_value(core.status().await).unwrap();
+        assert_eq!(status["ble_peer"], "AA:BB:CC:DD:EE:FF");
+
+        assert!(core.ble_connection().set_peer(None));
+        assert_eq!(core.connected_peer(), None);
+        assert!(core.status().await.ble_peer.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_runs_usb_only_when_bluez_unavailable() {
+        // 启动时立即启动 BLE，与按需启动走同一个回退
+        let config = Config {
+            ble: BleConfig {
+                on_demand: false,
+                ..BleConfig::default()
+            },
+            ..Config::without_devices()
+        };
+        let core = Arc::new(Core::with_startup(
+            &config,
+            &StartupOptions {
+                mode: OutputMode::Ble,
+                mouse_rate_hz: None,
+            },
+        ));
+        let keyboard = VirtualHidDevice::new();
+        let runner = Arc::clone(&core);
+        let usb_keyboard = keyboard.clone();
+        let pipeline = tokio::spawn(async move {
+            runner
+                .run_with_starter(
+                    Box::new(usb_keyboard),
+                    Box::new(VirtualHidDevice::new()),
+                    Box::new(crate::output::NoLedDevice),
+                    Box::new(FailingStarter(|| {
+                        BleUnavailable::new("无法连接 BlueZ").into()
+                    })),
+                )
+                .await
+        });
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while core.status().await.ble_available {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let status = core.status().await;
+        assert_eq!(status.output, "usb");
+        core.toggle_output().await;
+        assert_eq!(core.output_name(), "usb");
+
+        core.injector()
+            .inject(InputReport::Keyboard {
+                modifiers: 0,
+                keys: vec![0x04],
+            })
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while keyboard.reports().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        core.shutdown().await;
+        pipeline.await.unwrap().unwrap();
+
+        // 其他启动错误仍然中止运行
+        let core = Core::new(&config);
+        let result = core
+            .run_with_starter(
+                Box::new(VirtualHidDevice::new()),
+                Box::new(VirtualHidDevice::new()),
+                Box::new(crate::output::NoLedDevice),
+                Box::new(FailingStarter(|| anyhow!("注册 GATT 服务失败"))),
+            )
             .await;
-        let _ = ble_keyboard.lock().await.send_report(empty_kb).await;
-        let _ = ble_mouse.lock().await.send_report(empty_mouse).await;
+        assert!(result.is_err());
     }
-}
 
-// 默认切换组合键：Ctrl + Alt + F12
-fn is_switch_combo(modifiers: u8, keys: &Vec<u8>) -> bool {
-    let ctrl = modifiers & 0x01 != 0 || modifiers & 0x10 != 0;
-    let alt = modifiers & 0x04 != 0 || modifiers & 0x40 != 0;
-    let f12 = keys.contains(&0x45);
-    ctrl && alt && f12
+    /// 启动时总是返回给定错误的 BLE 启动器
+    struct FailingStarter(fn() -> anyhow::Error);
+
+    #[async_trait::async_trait]
+    impl BleStarter for FailingStarter {
+        async fn start(&self) -> Result<BleBackend> {
+            Err((self.0)())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_toggle_cycles_registered_backends() {
+        let core = Arc::new(Core::new(&Config::without_devices()));
+        let virtual_keyboard = VirtualHidDevice::new();
+        let backends = OutputBackends {
+            usb_keyboard: Box::new(VirtualHidDevice::new()),
+            usb_mouse: Box::new(VirtualHidDevice::new()),
+            usb_led: Box::new(crate::output::NoLedDevice),
+            ble: BleBackends::Ready(BleBackend::new(
+                Box::new(VirtualHidDevice::new()),
+                Box::new(VirtualHidDevice::new()),
+            )),
+            extra: vec![NamedBackend {
+                name: "virtual",
+                keyboard: Box::new(virtual_keyboard.clone()),
+                mouse: Box::new(VirtualHidDevice::new()),
+                led: None,
+                connection: ConnectionState::new(true),
+                mouse_rate_hz: 250,
+            }],
+        };
+        let runner = Arc::clone(&core);
+        let pipeline = tokio::spawn(async move { runner.run_with(backends).await });
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while core.outputs.read().unwrap().len() < 3 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let mut seen = vec![core.output_name()];
+        for _ in 0..3 {
+            core.toggle_output().await;
+            seen.push(core.output_name());
+        }
+        assert_eq!(seen, ["usb", "ble", "virtual", "usb"]);
+
+        // 报告发往当前选中的后端，使用该后端自己的报告率与连接状态
+        core.toggle_output().await;
+        core.toggle_output().await;
+        assert_eq!(core.output_name(), "virtual");
+        core.apply_mouse_rate(OutputMode::Other("virtual")).await;
+        assert_eq!(core.mouse_rate.get_rate(), 250);
+        assert!(core.output_connection().is_connected());
+        let exported = serde_json::to_value(core.export_state().await).unwrap();
+        assert_eq!(exported["output"], "virtual");
+        let mode: OutputMode = serde_json::from_value(exported["output"].clone()).unwrap();
+        assert_eq!(mode, OutputMode::Other("virtual"));
+        core.injector()
+            .inject(InputReport::Keyboard {
+                modifiers: 0,
+                keys: vec![0x04],
+            })
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while virtual_keyboard.reports().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        core.shutdown().await;
+        pipeline.await.unwrap().unwrap();
+    }
+
+    /// 主机不再下发输出报告，只记得最近一次的 LED 状态
+    struct SilentHostLeds(Option<LedState>);
+
+    #[async_trait::async_trait]
+    impl HidLedReader for SilentHostLeds {
+        async fn get_led_state(&mut self) -> Result<Option<LedState>> {
+            std::future::pending().await
+        }
+
+        fn host_led_state(&self) -> Option<LedState> {
+            self.0
+        }
+
+        fn clear_host_led_state(&mut self) {
+            self.0 = None;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_host_leds_synced_on_connect() {
+        let core = Arc::new(Core::new(&Config::without_devices()));
+        let caps = LedState {
+            caps_lock: true,
+            ..Default::default()
+        };
+        core.usb_connection().set_connected(false);
+        let mut leds = core.subscribe_leds();
+        let runner = Arc::clone(&core);
+        let backends = OutputBackends {
+            usb_keyboard: Box::new(VirtualHidDevice::new()),
+            usb_mouse: Box::new(VirtualHidDevice::new()),
+            usb_led: Box::new(SilentHostLeds(Some(caps))),
+            ble: BleBackends::Ready(BleBackend::new(Box::new(NoOutput), Box::new(NoOutput))),
+            extra: Vec::new(),
+        };
+        let pipeline = tokio::spawn(async move { runner.run_with(backends).await });
+
+        // 未连接时不同步
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!leds.has_changed().unwrap());
+
+        // 主机连接后立即读取并下发它的 LED 状态
+        core.usb_connection().set_connected(true);
+        tokio::time::timeout(Duration::from_secs(2), leds.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(*leds.borrow_and_update(), caps);
+
+        // 断开后忘记旧主机的状态，重新连接时不再下发
+        core.usb_connection().set_connected(false);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        core.usb_connection().set_connected(true);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!leds.has_changed().unwrap());
+
+        core.shutdown().await;
+        pipeline.await.unwrap().unwrap();
+    }
+
+    /// 下发一次 LED 状态后不再有输出报告
+    struct OneShotLeds(Option<LedState>);
+
+    #[async_trait::async_trait]
+    impl HidLedReader for OneShotLeds {
+        async fn get_led_state(&mut self) -> Result<Option<LedState>> {
+            match self.0.take() {
+                Some(state) => Ok(Some(state)),
+                None => std::future::pending().await,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_leds_kept_across_switch_until_new_state() {
+        let core = Arc::new(Core::new(&Config::without_devices()));
+        let caps = LedState {
+            caps_lock: true,
+            num_lock: true,
+            ..Default::default()
+        };
+        let mut leds = core.subscribe_leds();
+        let runner = Arc::clone(&core);
+        let backends = OutputBackends {
+            usb_keyboard: Box::new(VirtualHidDevice::new()),
+            usb_mouse: Box::new(VirtualHidDevice::new()),
+            usb_led: Box::new(OneShotLeds(Some(caps))),
+            ble: BleBackends::Ready(BleBackend::new(Box::new(NoOutput), Box::new(NoOutput))),
+            extra: Vec::new(),
+        };
+        let pipeline = tokio::spawn(async move { runner.run_with(backends).await });
+        let next_leds = async |leds: &mut watch::Receiver<LedState>| {
+            tokio::time::timeout(Duration::from_secs(2), leds.changed())
+                .await
+                .unwrap()
+                .unwrap();
+            *leds.borrow_and_update()
+        };
+        assert_eq!(next_leds(&mut leds).await, caps);
+
+        // 切换到 BLE，尚未连接主机，物理键盘保持原来的 LED
+        assert!(core.set_output_mode(OutputMode::Ble).await);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!leds.has_changed().unwrap());
+        assert_eq!(*leds.borrow(), caps);
+
+        // BLE 主机给出新状态后才更新
+        let host = "AA:BB:CC:DD:EE:02";
+        core.ble_host_prefs.set_leds(host, LedState::default());
+        core.ble_connection().set_peer(Some(host.to_string()));
+        assert_eq!(next_leds(&mut leds).await, LedState::default());
+
+        core.shutdown().await;
+        pipeline.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_report_is_previewed() {
+        let mut config = Config::without_devices();
+        config.audit.log_content = true;
+        let core = Arc::new(Core::new(&config));
+        let mut preview = core.subscribe_preview();
+        let runner = Arc::clone(&core);
+        let backends = OutputBackends {
+            usb_keyboard: Box::new(VirtualHidDevice::new()),
+            usb_mouse: Box::new(VirtualHidDevice::new()),
+            usb_led: Box::new(crate::output::NoLedDevice),
+            ble: BleBackends::Ready(BleBackend::new(Box::new(NoOutput), Box::new(NoOutput))),
+            extra: Vec::new(),
+        };
+        let pipeline = tokio::spawn(async move { runner.run_with(backends).await });
+
+        core.injector()
+            .inject(InputReport::Keyboard {
+                modifiers: 0,
+                keys: vec![0x04],
+            })
+            .await
+            .unwrap();
+        let message = tokio::time::timeout(Duration::from_secs(2), preview.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let message: serde_json::Value = serde_json::from_str(&message).unwrap();
+        assert_eq!(message["target"], "usb");
+        assert_eq!(message["kind"], "keyboard");
+        assert_eq!(message["report"]["keys"], serde_json::json!([4]));
+
+        core.shutdown().await;
+        pipeline.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_records_latency_from_creation() {
+        let core = Arc::new(Core::new(&Config::without_devices()));
+        let keyboard = VirtualHidDevice::new();
+        let runner = Arc::clone(&core);
+        let backends = OutputBackends {
+            usb_keyboard: Box::new(keyboard.clone()),
+            usb_mouse: Box::new(VirtualHidDevice::new()),
+            usb_led: Box::new(crate::output::NoLedDevice),
+            ble: BleBackends::Ready(BleBackend::new(Box::new(NoOutput), Box::new(NoOutput))),
+            extra: Vec::new(),
+        };
+        let pipeline = tokio::spawn(async move { runner.run_with(backends).await });
+
+        // 全局直方图由所有测试共享，只比较前后差值
+        let histogram = &metrics::global().usb_latency;
+        let overflow = histogram.bucket_counts().len() - 1;
+        let before = histogram.bucket_counts()[overflow];
+        core.injector()
+            .inject_timed(TimedReport {
+                report: InputReport::Keyboard {
+                    modifiers: 0,
+                    keys: vec![0x04],
+                },
+                created_at: Instant::now() - Duration::from_millis(100),
+            })
+            .await
+            .unwrap();
+
+        // 100ms 前创建的报告经管线发出后，应落在超出最大桶的那一档
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while histogram.bucket_counts()[overflow] == before {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(keyboard.reports().len(), 1);
+
+        core.shutdown().await;
+        pipeline.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pause_drops_reports_until_resume() {
+        let core = Arc::new(Core::new(&Config::without_devices()));
+        let keyboard = VirtualHidDevice::new();
+        let runner = Arc::clone(&core);
+        let backends = OutputBackends {
+            usb_keyboard: Box::new(keyboard.clone()),
+            usb_mouse: Box::new(VirtualHidDevice::new()),
+            usb_led: Box::new(crate::output::NoLedDevice),
+            ble: BleBackends::Ready(BleBackend::new(
+                Box::new(VirtualHidDevice::new()),
+                Box::new(VirtualHidDevice::new()),
+            )),
+            extra: Vec::new(),
+        };
+        let pipeline = tokio::spawn(async move { runner.run_with(backends).await });
+
+        let key = |usage| InputReport::Keyboard {
+            modifiers: 0,
+            keys: vec![usage],
+        };
+        let sent_keys = || -> Vec<Vec<u8>> {
+            keyboard
+                .reports()
+                .into_iter()
+                .filter_map(|report| match report {
+                    InputReport::Keyboard { keys, .. } => Some(keys),
+                    _ => None,
+                })
+                .collect()
+        };
+        let wait_for = |count: usize| {
+            tokio::time::timeout(Duration::from_secs(2), async move {
+                while sent_keys().len() < count {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+        };
+
+        core.injector().inject(key(0x04)).await.unwrap();
+        wait_for(1).await.unwrap();
+
+        core.pause();
+        assert!(core.status().await.paused);
+        assert!(!core.keyboard_grab.is_grabbed());
+        core.injector().inject(key(0x05)).await.unwrap();
+        // 暂停时先释放按键，之后的输入被丢弃
+        wait_for(2).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(sent_keys(), vec![vec![0x04], vec![]]);
+
+        core.resume();
+        assert!(core.keyboard_grab.is_grabbed());
+        core.injector().inject(key(0x06)).await.unwrap();
+        wait_for(3).await.unwrap();
+        assert_eq!(sent_keys(), vec![vec![0x04], vec![], vec![0x06]]);
+
+        core.shutdown().await;
+        pipeline.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_output_policy_routes_reports() {
+        let key = || InputReport::Keyboard {
+            modifiers: 0,
+            keys: vec![0x04],
+        };
+        // 返回 [USB 键盘, BLE 键盘] 各收到的报告数
+        async fn route(
+            policy: OutputPolicy,
+            usb_connected: bool,
+            report: InputReport,
+        ) -> [usize; 2] {
+            let core = Core::new(&Config {
+                output_policy: policy,
+                ..Config::without_devices()
+            });
+            core.usb_connection().set_connected(usb_connected);
+            let devices: Vec<VirtualHidDevice> = (0..4).map(|_| VirtualHidDevice::new()).collect();
+            core.dispatch(report, &virtual_outputs(&devices))
+                .await
+                .unwrap();
+            assert!(devices[1].reports().is_empty() && devices[3].reports().is_empty());
+            [devices[0].reports().len(), devices[2].reports().len()]
+        }
+
+        assert_eq!(route(OutputPolicy::Single, true, key()).await, [1, 0]);
+        assert_eq!(route(OutputPolicy::Mirror, true, key()).await, [1, 1]);
+        assert_eq!(route(OutputPolicy::PreferUsb, true, key()).await, [1, 0]);
+        assert_eq!(route(OutputPolicy::PreferUsb, false, key()).await, [0, 1]);
+
+        let core = Core::new(&Config {
+            output_policy: OutputPolicy::Mirror,
+            ..Config::without_devices()
+        });
+        assert_eq!(core.status().await.targets, vec!["usb", "ble"]);
+
+        // 目标来自已注册的输出，其他后端同样参与镜像与回退
+        let virtual_output = OutputMode::Other("virtual");
+        let registered = [OutputMode::Usb, OutputMode::Ble, virtual_output];
+        assert_eq!(
+            OutputPolicy::Mirror.targets(OutputMode::Usb, &registered, true)[..],
+            registered
+        );
+        assert_eq!(
+            OutputPolicy::PreferUsb.targets(virtual_output, &registered, false)[..],
+            [virtual_output]
+        );
+        assert_eq!(
+            OutputPolicy::PreferUsb.targets(OutputMode::Usb, &registered, false)[..],
+            [OutputMode::Ble]
+        );
+    }
+
+    /// 没有主机订阅，每次发送都失败
+    struct Unsubscribed;
+
+    #[async_trait::async_trait]
+    impl HidReportSender for Unsubscribed {
+        async fn send_report(&mut self, _report: InputReport) -> Result<()> {
+            Err(anyhow!("通知器未就绪"))
+        }
+
+        fn capabilities(&self) -> BackendCapabilities {
+            BackendCapabilities::all()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mirror_survives_failed_and_disconnected_targets() {
+        let core = Core::new(&Config {
+            output_policy: OutputPolicy::Mirror,
+            ..Config::without_devices()
+        });
+        let key = || InputReport::Keyboard {
+            modifiers: 0,
+            keys: vec![0x04],
+        };
+        let devices: Vec<VirtualHidDevice> = (0..4).map(|_| VirtualHidDevice::new()).collect();
+        let mut outputs = virtual_outputs(&devices);
+
+        // BLE 出错不影响 USB，返回实际送达的目标
+        outputs.entries[1].keyboard = Arc::new(Mutex::new(Box::new(Unsubscribed)));
+        let sent = core.dispatch(key(), &outputs).await.unwrap();
+        assert_eq!(sent[..], [OutputMode::Usb]);
+        assert_eq!(devices[0].reports().len(), 1);
+
+        // 已断开的后端直接跳过
+        outputs.entries[1].keyboard = Arc::new(Mutex::new(Box::new(devices[2].clone())));
+        outputs.entries[0].connection.set_connected(false);
+        let sent = core.dispatch(key(), &outputs).await.unwrap();
+        assert_eq!(sent[..], [OutputMode::Ble]);
+        assert_eq!(devices[0].reports().len(), 1);
+        assert_eq!(devices[2].reports().len(), 1);
+
+        // 单一输出时错误原样返回
+        let single = Core::new(&Config::without_devices());
+        outputs.entries[0].connection.set_connected(true);
+        outputs.entries[0].keyboard = Arc::new(Mutex::new(Box::new(Unsubscribed)));
+        assert!(single.dispatch(key(), &outputs).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reload_applies_rate_and_remap() {
+        let core = Core::new(&Config::without_devices());
+        assert_eq!(core.mouse_rate.get_rate(), 500);
+        assert!(!core.key_remap().caps_to_ctrl());
+
+        let mut config = Config {
+            usb_mouse_rate_hz: 250,
+            ..Config::without_devices()
+        };
+        config.input.caps_to_ctrl = true;
+        config.ble.name = "Desk Bridge".to_string();
+        let changed = core.reload(&config).await;
+
+        assert_eq!(core.mouse_rate.get_rate(), 250);
+        assert!(core.key_remap().caps_to_ctrl());
+        assert!(changed.contains(&"usb_mouse_rate_hz".to_string()));
+        assert!(changed.contains(&"input.caps_to_ctrl".to_string()));
+        // BLE 名称需要重启，但仍被报告为已变化
+        assert!(changed.contains(&"ble.name".to_string()));
+        assert!(!crate::config::is_hot_reloadable("ble.name"));
+
+        assert!(core.reload(&config).await.is_empty());
+    }
+
+    #[test]
+    fn test_unregistered_output_name_is_rejected() {
+        let mode: OutputMode = serde_json::from_str("\"ble\"").unwrap();
+        assert_eq!(mode, OutputMode::Ble);
+        assert!(serde_json::from_str::<OutputMode>("\"not-registered\"").is_err());
+        assert!(serde_json::from_str::<OutputMode>("\"\"").is_err());
+    }
+
+    #[test]
+    fn test_mode_follows_usb_cable() {
+        use crate::output::usb::host_present_from_state;
+
+        let mut tracker = UsbPresenceTracker::default();
+        let mut mode = OutputMode::Usb;
+        let mut feed = |state: &str| {
+            if let Some(target) = tracker.update(host_present_from_state(state)) {
+                mode = target;
+            }
+            mode
+        };
+
+        assert_eq!(feed("configured"), OutputMode::Usb);
+        // 拔出线缆
+        assert_eq!(feed("not attached"), OutputMode::Ble);
+        // 主机休眠不影响当前模式
+        assert_eq!(feed("suspended"), OutputMode::Ble);
+        // 重新插入并完成枚举前保持 BLE
+        assert_eq!(feed("powered"), OutputMode::Ble);
+        assert_eq!(feed("configured"), OutputMode::Usb);
+    }
+
+    #[test]
+    fn test_presence_only_reports_edges() {
+        let mut tracker = UsbPresenceTracker::default();
+        assert_eq!(tracker.update(Some(true)), Some(OutputMode::Usb));
+        assert_eq!(tracker.update(Some(true)), None);
+        assert_eq!(tracker.update(None), None);
+        assert_eq!(tracker.update(Some(false)), Some(OutputMode::Ble));
+    }
 }
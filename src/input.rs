@@ -1,14 +1,25 @@
-use crate::output::LedState;
+use crate::metrics::{self, ReportKind, ReportLoss, Stage};
+use crate::output::mouse::{
+    AxisTransform, DeadZone, ScrollAccel, ScrollAccelerator, WheelResolution,
+};
+use crate::output::{LedState, consumer, system};
 use anyhow::Context;
 use evdev::{Device, EventType, InputEvent, KeyCode};
 use log::{debug, error, info, trace, warn};
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
+use std::collections::{BTreeMap, HashMap, HashSet};
 #[cfg(unix)]
-use std::os::fd::AsRawFd;
-use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::atomic::{AtomicU8, AtomicU32, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex, OnceLock};
-use std::time::{Duration, Instant};
-use tokio::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{mpsc, watch};
+
+pub mod recording;
+pub mod transform;
+
+use transform::{RemapTransform, TransformChain};
 
 /// 鼠标报告率控制器，可在运行时动态调整
 #[derive(Clone)]
@@ -17,6 +28,155 @@ pub struct MouseRateController {
     interval_micros: Arc<AtomicU32>,
 }
 
+/// 按键重映射开关，可在运行时切换
+///
+/// 只影响新按下的键：已按住的键保持按下时的映射直到释放，避免卡键。
+#[derive(Debug, Clone, Default)]
+pub struct KeyRemap {
+    flags: Arc<AtomicU8>,
+}
+
+/// 鼠标灵敏度（百分比），可在运行时调整，所有鼠标共享
+///
+/// 缩放后不足一个单位的余量留到下一次报告，慢速移动不会被吞掉。
+#[derive(Debug, Clone)]
+pub struct MouseSensitivity {
+    percent: Arc<AtomicU32>,
+}
+
+impl Default for MouseSensitivity {
+    fn default() -> Self {
+        Self::new(100)
+    }
+}
+
+impl MouseSensitivity {
+    pub fn new(percent: u32) -> Self {
+        Self {
+            percent: Arc::new(AtomicU32::new(percent)),
+        }
+    }
+
+    pub fn set(&self, percent: u32) {
+        self.percent.store(percent, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u32 {
+        self.percent.load(Ordering::Relaxed)
+    }
+
+    /// 按灵敏度缩放位移，`remainder` 保存以 1/100 为单位的余量
+    fn scale(&self, delta: i32, remainder: &mut i32) -> i32 {
+        let total = delta as i64 * self.get() as i64 + *remainder as i64;
+        *remainder = (total % 100) as i32;
+        (total / 100).clamp(i32::MIN as i64, i32::MAX as i64) as i32
+    }
+}
+
+/// 键盘独占开关，所有键盘共享
+///
+/// 关闭时解除独占，本机控制台可以正常使用键盘，事件仍会被读取。
+/// 关闭期间新接入的键盘也不会被独占。
+#[derive(Debug, Clone)]
+pub struct KeyboardGrab {
+    grabbed: Arc<watch::Sender<bool>>,
+}
+
+impl Default for KeyboardGrab {
+    fn default() -> Self {
+        Self {
+            grabbed: Arc::new(watch::channel(true).0),
+        }
+    }
+}
+
+impl KeyboardGrab {
+    pub fn set(&self, grabbed: bool) {
+        self.grabbed.send_if_modified(|current| {
+            let changed = *current != grabbed;
+            *current = grabbed;
+            changed
+        });
+    }
+
+    pub fn is_grabbed(&self) -> bool {
+        *self.grabbed.borrow()
+    }
+
+    fn subscribe(&self) -> watch::Receiver<bool> {
+        self.grabbed.subscribe()
+    }
+}
+
+/// 所有键盘合并后的按键状态
+///
+/// 每个键盘只知道自己按住的键，各自发送时，一个键盘的报告会清掉另一个键盘按住的修饰键。
+/// 各键盘把自己的状态登记在这里，发出的报告是所有键盘的并集。
+#[derive(Debug, Clone, Default)]
+pub struct MergedKeyboards {
+    inner: Arc<Mutex<MergedState>>,
+}
+
+#[derive(Debug, Default)]
+struct MergedState {
+    next_source: u64,
+    /// 各键盘当前的修饰键与按住的键，没有按住任何键的键盘不在其中
+    sources: BTreeMap<u64, (u8, Vec<u8>)>,
+}
+
+impl MergedKeyboards {
+    /// 为一个键盘分配标识
+    fn register(&self) -> u64 {
+        let mut state = self.inner.lock().unwrap();
+        state.next_source += 1;
+        state.next_source
+    }
+
+    /// 移除一个键盘登记的状态
+    fn unregister(&self, source: u64) {
+        self.inner.lock().unwrap().sources.remove(&source);
+    }
+
+    /// 更新一个键盘的状态，返回合并后的报告
+    fn update(&self, source: u64, modifiers: u8, keys: &[u8]) -> InputReport {
+        let mut state = self.inner.lock().unwrap();
+        if modifiers == 0 && keys.is_empty() {
+            state.sources.remove(&source);
+        } else {
+            state.sources.insert(source, (modifiers, keys.to_vec()));
+        }
+        let mut merged_modifiers = 0;
+        let mut merged_keys = Vec::new();
+        for (modifiers, keys) in state.sources.values() {
+            merged_modifiers |= modifiers;
+            for key in keys {
+                if !merged_keys.contains(key) {
+                    merged_keys.push(*key);
+                }
+            }
+        }
+        InputReport::Keyboard {
+            modifiers: merged_modifiers,
+            keys: merged_keys,
+        }
+    }
+}
+
+/// 对读取事件的设备切换独占
+///
+/// 独占属于打开设备的文件描述，`fd` 必须是读取设备 FD 的 `dup`，
+/// 通过 `/proc/self/fd` 重新打开的设备是另一个 evdev 客户端，对它切换独占不影响读取端。
+/// evdev 的 `Device` 在另一个线程里阻塞读取，因此直接发出 `EVIOCGRAB`。
+#[cfg(unix)]
+fn set_fd_grab(fd: std::os::fd::RawFd, grab: bool) -> std::io::Result<()> {
+    // _IOW('E', 0x90, int)
+    const EVIOCGRAB: libc::c_ulong = 0x4004_4590;
+    if unsafe { libc::ioctl(fd, EVIOCGRAB as _, grab as libc::c_int) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
 pub enum InputReport {
     Keyboard {
@@ -29,18 +189,584 @@ pub enum InputReport {
         y: i16,
         wheel: i8,
     },
+    /// 消费类控制（媒体键），`usage` 为 0 表示释放
+    Consumer {
+        usage: u16,
+    },
+    /// 系统控制（关机、睡眠、唤醒），`usage` 为 0 表示释放
+    System {
+        usage: u8,
+    },
+    /// 厂商自定义报告：透传没有标准 HID 映射的原始 evdev 键码
+    Vendor {
+        code: u16,
+        pressed: bool,
+    },
+}
+
+/// 携带创建时间的报告，仅在管线内部使用，用于统计端到端延迟
+#[derive(Debug, Clone)]
+pub(crate) struct TimedReport {
+    pub report: InputReport,
+    pub created_at: Instant,
 }
 
+impl TimedReport {
+    pub fn new(report: InputReport) -> Self {
+        Self {
+            report,
+            created_at: Instant::now(),
+        }
+    }
+}
+
+/// 单个输入事件可能产生的报告（通常 0~1 个，按下+释放时为 2 个）
+pub type Reports = SmallVec<[InputReport; 2]>;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum DeviceType {
     Keyboard,
     Mouse,
 }
 
+/// 单个 `REL_DIAL` 事件最多处理的格数
+const MAX_DIAL_STEPS: i32 = 16;
+
+/// `REL_DIAL` 旋钮的映射目标
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DialTarget {
+    /// 忽略旋钮（默认）
+    #[default]
+    Ignore,
+    /// 作为滚轮
+    Wheel,
+    /// 作为音量加减
+    Volume,
+}
+
+/// Apple 键盘专用键（Fn/Globe 与顶排媒体键）的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AppleKeys {
+    /// 不做特殊处理，顶排键按 F1~F12 发送，Fn 被忽略（默认）
+    #[default]
+    Off,
+    /// 所有键盘都按 Apple 键盘处理
+    On,
+    /// 只对设备名像 Apple 键盘的设备启用
+    Auto,
+}
+
+impl AppleKeys {
+    /// 按设备名判断是否启用
+    pub fn enabled_for(self, device_name: &str) -> bool {
+        match self {
+            Self::Off => false,
+            Self::On => true,
+            Self::Auto => is_apple_keyboard(device_name),
+        }
+    }
+}
+
+/// 设备名是否像 Apple 键盘，例如 "Apple Inc. Magic Keyboard" 或蓝牙连接时的 "Magic Keyboard"
+fn is_apple_keyboard(device_name: &str) -> bool {
+    let name = device_name.to_ascii_lowercase();
+    name.contains("magic keyboard") || (name.contains("apple") && name.contains("keyboard"))
+}
+
+/// 输入设备目录
+const INPUT_DIR: &str = "/dev/input";
+
+/// 同一问题重复告警的最小间隔
+const SCAN_WARN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 输入设备扫描状态
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanStatus {
+    /// 尚未完成第一次扫描
+    Pending,
+    /// 找到事件节点
+    Ok { event_nodes: usize },
+    /// 目录不存在
+    NotFound,
+    /// 没有权限读取目录或打开事件节点（通常需要 root 或 input 组）
+    PermissionDenied,
+    /// 目录中没有事件节点
+    Empty,
+    /// 其他读取错误
+    Error(String),
+}
+
+impl ScanStatus {
+    pub fn is_ok(&self) -> bool {
+        matches!(self, Self::Ok { .. })
+    }
+
+    /// 问题描述，正常或尚未扫描时为 `None`
+    pub fn problem(&self) -> Option<String> {
+        match self {
+            Self::NotFound => Some(format!("{} 不存在，无法捕获任何输入设备", INPUT_DIR)),
+            Self::PermissionDenied => Some(format!(
+                "没有权限读取 {} 中的设备，请以 root 运行或加入 input 组",
+                INPUT_DIR
+            )),
+            Self::Empty => Some(format!("{} 中没有事件节点，未连接任何输入设备", INPUT_DIR)),
+            Self::Error(e) => Some(format!("读取 {} 失败: {}", INPUT_DIR, e)),
+            Self::Pending | Self::Ok { .. } => None,
+        }
+    }
+
+    fn from_io_error(e: &std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => Self::NotFound,
+            std::io::ErrorKind::PermissionDenied => Self::PermissionDenied,
+            _ => Self::Error(e.to_string()),
+        }
+    }
+}
+
+/// 共享的扫描状态，供状态查询使用
+#[derive(Debug, Clone)]
+pub struct InputStatus(Arc<Mutex<ScanStatus>>);
+
+impl Default for InputStatus {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(ScanStatus::Pending)))
+    }
+}
+
+impl InputStatus {
+    pub fn get(&self) -> ScanStatus {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn set(&self, status: ScanStatus) {
+        *self.0.lock().unwrap() = status;
+    }
+}
+
+/// 扫描问题告警限流：状态变化时立即告警，同一问题每分钟最多一次
+#[derive(Default)]
+struct ScanWarner {
+    last: Option<(ScanStatus, Instant)>,
+}
+
+impl ScanWarner {
+    fn should_warn(&mut self, status: &ScanStatus, now: Instant) -> bool {
+        if status.is_ok() || *status == ScanStatus::Pending {
+            self.last = None;
+            return false;
+        }
+        if let Some((last, at)) = &self.last
+            && last == status
+            && now.duration_since(*at) < SCAN_WARN_INTERVAL
+        {
+            return false;
+        }
+        self.last = Some((status.clone(), now));
+        true
+    }
+}
+
+/// 设备扫描间隔配置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanConfig {
+    /// 是否扫描并独占 `/dev/input` 中的设备；关闭后只处理注入的报告，例如只使用网页触控板
+    pub enabled: bool,
+    /// 扫描间隔（毫秒），自适应时为稳定后的最长间隔
+    pub interval_ms: u64,
+    /// 设备变化后缩短扫描间隔，稳定后逐步退回 `interval_ms`
+    pub adaptive: bool,
+    /// 自适应时设备变化后的最短间隔（毫秒）
+    pub min_interval_ms: u64,
+    /// 启动后第一次扫描前的等待（毫秒），开机时给驱动留出初始化的时间
+    pub startup_delay_ms: u64,
+    /// 新设备节点出现后等待多久（毫秒）再打开并独占
+    pub settle_ms: u64,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_ms: 1000,
+            adaptive: false,
+            min_interval_ms: 100,
+            startup_delay_ms: 0,
+            settle_ms: 0,
+        }
+    }
+}
+
+impl ScanConfig {
+    pub fn startup_delay(&self) -> Duration {
+        Duration::from_millis(self.startup_delay_ms)
+    }
+
+    pub fn settle(&self) -> Duration {
+        Duration::from_millis(self.settle_ms)
+    }
+}
+
+/// 新设备节点的稳定等待
+///
+/// 节点出现时驱动可能还没初始化完，过早独占会失败或拿到不完整的设备。
+/// 节点第一次被扫描到之后等待 `settle` 才开始监听，等待期间节点消失则重新计时。
+struct DeviceSettle {
+    settle: Duration,
+    first_seen: HashMap<std::path::PathBuf, Instant>,
+}
+
+impl DeviceSettle {
+    fn new(settle: Duration) -> Self {
+        Self {
+            settle,
+            first_seen: HashMap::new(),
+        }
+    }
+
+    /// 记录本次扫描到的节点，忘记已经消失的节点
+    fn observe(&mut self, nodes: &[std::path::PathBuf], now: Instant) {
+        self.first_seen.retain(|node, _| nodes.contains(node));
+        for node in nodes {
+            self.first_seen.entry(node.clone()).or_insert(now);
+        }
+    }
+
+    /// 节点是否已经稳定，可以打开
+    fn is_settled(&self, node: &std::path::Path, now: Instant) -> bool {
+        self.first_seen
+            .get(node)
+            .is_none_or(|seen| now.duration_since(*seen) >= self.settle)
+    }
+
+    /// 最早一个仍在等待的节点还需等待的时间
+    fn next_due(&self, now: Instant) -> Option<Duration> {
+        self.first_seen
+            .values()
+            .map(|seen| self.settle.saturating_sub(now.duration_since(*seen)))
+            .filter(|remaining| !remaining.is_zero())
+            .min()
+    }
+}
+
+/// 自适应扫描间隔
+///
+/// 设备增删往往成批出现（如插入复合设备、USB 集线器），变化后先快速重扫，
+/// 之后每次无变化的扫描把间隔翻倍，直到回到配置的最长间隔。
+struct ScanInterval {
+    min: Duration,
+    max: Duration,
+    adaptive: bool,
+    current: Duration,
+}
+
+impl ScanInterval {
+    fn new(config: &ScanConfig) -> Self {
+        let max = Duration::from_millis(config.interval_ms.max(1));
+        let min = Duration::from_millis(config.min_interval_ms.max(1)).min(max);
+        Self {
+            min,
+            max,
+            adaptive: config.adaptive,
+            current: max,
+        }
+    }
+
+    /// 根据本次扫描是否发现设备变化，返回到下次扫描的等待时间
+    fn next(&mut self, changed: bool) -> Duration {
+        if !self.adaptive {
+            return self.max;
+        }
+        self.current = if changed {
+            self.min
+        } else {
+            (self.current * 2).min(self.max)
+        };
+        self.current
+    }
+}
+
+/// 列出目录中的事件节点
+fn scan_event_nodes(dir: &std::path::Path) -> Result<Vec<std::path::PathBuf>, ScanStatus> {
+    let entries = std::fs::read_dir(dir).map_err(|e| ScanStatus::from_io_error(&e))?;
+    let nodes: Vec<_> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.to_string_lossy().contains("event"))
+        .collect();
+    if nodes.is_empty() {
+        return Err(ScanStatus::Empty);
+    }
+    Ok(nodes)
+}
+
+/// 按设备名称或路径选择要独占和监听的输入设备
+///
+/// 模式使用 glob 语法（如 `*Logitech*`、`/dev/input/event3`），同时匹配设备名称和路径。
+/// 被排除的设备不会被 `grab()`，本机控制台可以继续使用它们。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceFilter {
+    /// 不独占、不监听的设备
+    pub exclude: Vec<String>,
+    /// 非空时只监听匹配的设备（白名单模式），排除列表仍然优先
+    pub allow: Vec<String>,
+}
+
+impl DeviceFilter {
+    /// 设备是否应被独占并监听
+    pub fn is_selected(&self, name: &str, path: &str) -> bool {
+        let matches = |patterns: &[String]| {
+            patterns
+                .iter()
+                .any(|pattern| match glob::Pattern::new(pattern) {
+                    Ok(p) => p.matches(name) || p.matches(path),
+                    Err(_) => pattern == name || pattern == path,
+                })
+        };
+        if matches(&self.exclude) {
+            return false;
+        }
+        self.allow.is_empty() || matches(&self.allow)
+    }
+}
+
+/// 鼠标按键的重映射目标
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ButtonAction {
+    /// 映射为另一个鼠标按键位（0x01 左键、0x02 右键、0x04 中键、0x08/0x10 侧键）
+    Button(u8),
+    /// 映射为键盘组合键：按下鼠标键时按下，松开时释放
+    Chord { modifiers: u8, keys: Vec<u8> },
+}
+
+/// 鼠标按键重映射表，未配置的按键保持原样
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MouseButtonMap {
+    pub left: Option<ButtonAction>,
+    pub right: Option<ButtonAction>,
+    pub middle: Option<ButtonAction>,
+    pub side: Option<ButtonAction>,
+    pub extra: Option<ButtonAction>,
+}
+
+impl MouseButtonMap {
+    /// 物理按键位对应的重映射
+    pub fn action(&self, button_bit: u8) -> Option<&ButtonAction> {
+        match button_bit {
+            0x01 => self.left.as_ref(),
+            0x02 => self.right.as_ref(),
+            0x04 => self.middle.as_ref(),
+            0x08 => self.side.as_ref(),
+            0x10 => self.extra.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// 输入事件通道的默认容量
+pub const DEFAULT_CHANNEL_CAPACITY: usize = 256;
+
+/// 输入处理配置
+#[derive(Debug, Clone)]
+pub struct InputConfig {
+    pub dial_target: DialTarget,
+    /// 与所有设备共享的按键重映射
+    pub key_remap: KeyRemap,
+    /// 输入事件通道容量，满时的处理策略见 [`EventSender`]
+    pub channel_capacity: usize,
+    /// 没有标准 HID 映射的键以厂商报告透传原始键码，关闭时这些键被忽略
+    pub raw_passthrough: bool,
+    /// 设备排除与白名单
+    pub device_filter: DeviceFilter,
+    /// 鼠标按键重映射
+    pub button_map: MouseButtonMap,
+    /// 与所有鼠标共享的灵敏度
+    pub sensitivity: MouseSensitivity,
+    /// 鼠标坐标轴变换
+    pub axes: AxisTransform,
+    /// 滚轮分辨率
+    pub wheel_resolution: WheelResolution,
+    /// 鼠标位移死区
+    pub dead_zone: DeadZone,
+    /// 滚轮加速曲线
+    pub scroll_accel: ScrollAccel,
+    /// 设备扫描间隔
+    pub scan: ScanConfig,
+    /// 修饰键按住且空闲超过该时长后核对设备按键状态，`None` 表示关闭
+    pub modifier_watchdog: Option<Duration>,
+    /// 同一个键松开后该时长内的再次按下视为抖动，`None` 表示关闭
+    pub key_debounce: Option<Duration>,
+    /// 内置重映射与坐标轴变换之后执行的变换，所有设备共享
+    pub transforms: TransformChain,
+    /// 键盘独占开关，所有键盘共享
+    pub grab: KeyboardGrab,
+    /// 所有键盘合并后的按键状态
+    pub keyboards: MergedKeyboards,
+    /// Apple 键盘专用键的处理方式
+    pub apple_keys: AppleKeys,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            dial_target: DialTarget::default(),
+            key_remap: KeyRemap::default(),
+            channel_capacity: DEFAULT_CHANNEL_CAPACITY,
+            raw_passthrough: false,
+            device_filter: DeviceFilter::default(),
+            button_map: MouseButtonMap::default(),
+            sensitivity: MouseSensitivity::default(),
+            axes: AxisTransform::default(),
+            wheel_resolution: WheelResolution::default(),
+            dead_zone: DeadZone::default(),
+            scroll_accel: ScrollAccel::default(),
+            scan: ScanConfig::default(),
+            modifier_watchdog: None,
+            key_debounce: None,
+            transforms: TransformChain::default(),
+            grab: KeyboardGrab::default(),
+            keyboards: MergedKeyboards::default(),
+            apple_keys: AppleKeys::default(),
+        }
+    }
+}
+
+/// 有界输入通道的发送端（每个设备一个）
+///
+/// 通道满时的策略：
+/// - 键盘与媒体键报告阻塞等待，保证按键边沿不丢失；
+/// - 鼠标报告不等待，合并到一个待发报告中（位移与滚轮累加），
+///   按键状态变化或下一个键盘报告到来前会先阻塞发出待发报告，保证顺序与点击不丢失。
+///
+/// 因此后端卡顿时队列长度受容量限制，鼠标只会丢失中间的位移分段而不会丢失总位移。
+/// 之后没有新事件时，读取循环每隔 [`PENDING_MOUSE_RETRY`] 重试发出待发报告。
+pub(crate) struct EventSender {
+    tx: mpsc::Sender<TimedReport>,
+    pending_mouse: Option<TimedReport>,
+    loss: &'static ReportLoss,
+}
+
+impl EventSender {
+    pub fn new(tx: mpsc::Sender<TimedReport>) -> Self {
+        Self::with_loss(tx, &metrics::global().report_loss)
+    }
+
+    /// 合并与丢弃计入 `loss`
+    pub fn with_loss(tx: mpsc::Sender<TimedReport>, loss: &'static ReportLoss) -> Self {
+        Self {
+            tx,
+            pending_mouse: None,
+            loss,
+        }
+    }
+
+    /// 发送一个报告（阻塞），接收端已关闭时返回 `Err`
+    pub fn send(&mut self, report: InputReport) -> Result<(), ()> {
+        match report {
+            InputReport::Mouse { buttons, .. } => {
+                if let Some(pending) = self.pending_mouse.as_mut() {
+                    if Self::merge_mouse(pending, &report, buttons) {
+                        self.loss
+                            .record_coalesced(Stage::InputChannel, ReportKind::Mouse);
+                        return self.try_flush();
+                    }
+                    self.flush()?;
+                }
+                self.try_send(TimedReport::new(report))
+            }
+            _ => {
+                self.flush()?;
+                self.blocking_send(TimedReport::new(report))
+            }
+        }
+    }
+
+    /// 阻塞发送，接收端已关闭时计为丢弃
+    fn blocking_send(&mut self, timed: TimedReport) -> Result<(), ()> {
+        let kind = ReportKind::of(&timed.report);
+        self.tx.blocking_send(timed).map_err(|_| {
+            self.loss.record_dropped(Stage::InputChannel, kind);
+        })
+    }
+
+    /// 按键状态相同时把位移累加进待发报告，返回是否合并成功
+    fn merge_mouse(pending: &mut TimedReport, report: &InputReport, buttons: u8) -> bool {
+        let (
+            InputReport::Mouse {
+                buttons: pending_buttons,
+                x: px,
+                y: py,
+                wheel: pw,
+            },
+            InputReport::Mouse { x, y, wheel, .. },
+        ) = (&mut pending.report, report)
+        else {
+            return false;
+        };
+        if *pending_buttons != buttons {
+            return false;
+        }
+        *px = px.saturating_add(*x);
+        *py = py.saturating_add(*y);
+        *pw = pw.saturating_add(*wheel);
+        true
+    }
+
+    /// 非阻塞发送鼠标报告，通道满时转为待发
+    fn try_send(&mut self, timed: TimedReport) -> Result<(), ()> {
+        match self.tx.try_send(timed) {
+            Ok(()) => Ok(()),
+            Err(mpsc::error::TrySendError::Full(timed)) => {
+                trace!("输入通道已满，合并鼠标报告");
+                self.pending_mouse = Some(timed);
+                Ok(())
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                self.loss
+                    .record_dropped(Stage::InputChannel, ReportKind::Mouse);
+                Err(())
+            }
+        }
+    }
+
+    /// 是否有待发的鼠标报告
+    fn has_pending(&self) -> bool {
+        self.pending_mouse.is_some()
+    }
+
+    /// 尝试非阻塞地发出待发鼠标报告
+    fn try_flush(&mut self) -> Result<(), ()> {
+        match self.pending_mouse.take() {
+            Some(timed) => self.try_send(timed),
+            None => Ok(()),
+        }
+    }
+
+    /// 阻塞发出待发鼠标报告
+    fn flush(&mut self) -> Result<(), ()> {
+        match self.pending_mouse.take() {
+            Some(timed) => self.blocking_send(timed),
+            None => Ok(()),
+        }
+    }
+}
+
+// 调试用：统计 SYN_REPORT 频率与间隔
+#[allow(dead_code)]
 static SYN_COUNT: AtomicU64 = AtomicU64::new(0);
+#[allow(dead_code)]
 static SYN_LAST: OnceLock<Mutex<Instant>> = OnceLock::new();
+#[allow(dead_code)]
 static LAST_CALL: OnceLock<Mutex<Instant>> = OnceLock::new();
 
+#[allow(dead_code)]
 fn record_syn_rate() {
     SYN_COUNT.fetch_add(1, Ordering::Relaxed);
 
@@ -54,6 +780,7 @@ fn record_syn_rate() {
     }
 }
 
+#[allow(dead_code)]
 fn elapsed_since_last_call_ms() {
     // 第一次调用时初始化
     let lock = LAST_CALL.get_or_init(|| Mutex::new(Instant::now()));
@@ -75,16 +802,124 @@ fn elapsed_since_last_call_ms() {
     }
 }
 
+/// 暂时性读取错误后的重试间隔
+const TRANSIENT_RETRY_DELAY: Duration = Duration::from_millis(10);
+/// 没有新事件时重试发出待发鼠标报告的间隔
+const PENDING_MOUSE_RETRY: Duration = Duration::from_millis(4);
+
+/// 输入事件来源，测试中可替换为模拟实现
+trait EventSource {
+    fn fetch(&mut self) -> std::io::Result<Vec<InputEvent>>;
+
+    /// 等待事件到来，超时返回 `false`
+    fn wait(&mut self, _timeout: Duration) -> std::io::Result<bool> {
+        Ok(true)
+    }
+
+    /// 设备上实际按住的键码，无法查询时返回 `None`
+    fn held_keys(&mut self) -> std::io::Result<Option<Vec<u16>>> {
+        Ok(None)
+    }
+}
+
+impl EventSource for Device {
+    fn fetch(&mut self) -> std::io::Result<Vec<InputEvent>> {
+        Ok(self.fetch_events()?.collect())
+    }
+
+    fn wait(&mut self, timeout: Duration) -> std::io::Result<bool> {
+        let mut fd = libc::pollfd {
+            fd: self.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let millis = timeout.as_millis().min(i32::MAX as u128) as i32;
+        match unsafe { libc::poll(&mut fd, 1, millis) } {
+            n if n < 0 => Err(std::io::Error::last_os_error()),
+            n => Ok(n > 0),
+        }
+    }
+
+    fn held_keys(&mut self) -> std::io::Result<Option<Vec<u16>>> {
+        Ok(Some(
+            self.get_key_state()?.iter().map(|key| key.code()).collect(),
+        ))
+    }
+}
+
+/// 是否为可以重试的暂时性错误
+fn is_transient(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::Interrupted | std::io::ErrorKind::WouldBlock
+    ) || matches!(e.raw_os_error(), Some(libc::EINTR) | Some(libc::EAGAIN))
+}
+
 struct DeviceMonitor {
     device_type: DeviceType,
     keyboard_state: KeyboardState,
+    /// 在合并状态中的标识
+    keyboard_source: u64,
     mouse_state: MouseState,
+    /// 按 Apple 键盘处理专用键
+    apple_keys: bool,
+    debounce: KeyDebounce,
+    /// 报告合并、入队前执行的变换
+    transforms: TransformChain,
+    config: InputConfig,
 }
 
 #[derive(Default)]
 struct KeyboardState {
     modifiers: u8,
     pressed_keys: Vec<u8>,
+    /// 按住中的键
+    held: HashSet<u16>,
+}
+
+/// 按键抖动过滤
+///
+/// 廉价键盘松开后几毫秒内可能再次报告按下，造成重复字符。同一个键松开后 `window`
+/// 内的再次按下连同其自动重复与松开一起丢弃；只比较同一个键，快速交替输入不同的键不受影响。
+/// 时间取自事件自带的内核时间戳。
+#[derive(Debug, Default)]
+struct KeyDebounce {
+    window: Option<Duration>,
+    last_release: HashMap<u16, SystemTime>,
+    suppressed: HashSet<u16>,
+}
+
+impl KeyDebounce {
+    fn new(window: Option<Duration>) -> Self {
+        Self {
+            window,
+            ..Default::default()
+        }
+    }
+
+    /// 事件是否为抖动，应当丢弃
+    fn is_chatter(&mut self, code: u16, value: i32, time: SystemTime) -> bool {
+        let Some(window) = self.window else {
+            return false;
+        };
+        match value {
+            1 => {
+                let bounced = self.last_release.get(&code).is_some_and(|last| {
+                    time.duration_since(*last)
+                        .is_ok_and(|elapsed| elapsed < window)
+                });
+                if bounced {
+                    self.suppressed.insert(code);
+                }
+                bounced
+            }
+            0 => {
+                self.last_release.insert(code, time);
+                self.suppressed.remove(&code)
+            }
+            _ => self.suppressed.contains(&code),
+        }
+    }
 }
 
 #[derive(Default)]
@@ -93,30 +928,51 @@ struct MouseState {
     x_delta: i32,
     y_delta: i32,
     wheel_delta: i32,
-    dirty: bool,
-    button_changed: bool,
+    /// 上一个报告中的按键状态
+    reported_buttons: u8,
     last_report_time: Option<Instant>,
     rate_controller: MouseRateController,
+    sensitivity: MouseSensitivity,
+    wheel_resolution: WheelResolution,
+    dead_zone: DeadZone,
+    scroll_accel: ScrollAccelerator,
+    /// 灵敏度缩放后的余量
+    x_remainder: i32,
+    y_remainder: i32,
+    /// 滚轮分辨率缩放后的余量
+    wheel_remainder: i32,
 }
 
 impl MouseState {
-    fn new(rate_controller: MouseRateController) -> Self {
+    fn new(
+        rate_controller: MouseRateController,
+        sensitivity: MouseSensitivity,
+        wheel_resolution: WheelResolution,
+        dead_zone: DeadZone,
+        scroll_accel: ScrollAccel,
+    ) -> Self {
         Self {
             buttons: 0,
             x_delta: 0,
             y_delta: 0,
             wheel_delta: 0,
-            dirty: false,
-            button_changed: false,
+            reported_buttons: 0,
             last_report_time: None,
             rate_controller,
+            sensitivity,
+            wheel_resolution,
+            dead_zone,
+            scroll_accel: ScrollAccelerator::new(scroll_accel),
+            x_remainder: 0,
+            y_remainder: 0,
+            wheel_remainder: 0,
         }
     }
 
     /// 检查是否应该发送报告
     fn should_send_report(&self) -> bool {
         // 按钮变化必须立即发送
-        if self.button_changed {
+        if self.buttons != self.reported_buttons {
             return true;
         }
 
@@ -135,46 +991,65 @@ impl MouseState {
     /// 累积 X 移动量
     fn accumulate_x(&mut self, delta: i32) {
         self.x_delta = self.x_delta.saturating_add(delta);
-        self.dirty = true;
     }
 
     /// 累积 Y 移动量
     fn accumulate_y(&mut self, delta: i32) {
         self.y_delta = self.y_delta.saturating_add(delta);
-        self.dirty = true;
     }
 
-    /// 累积滚轮量
+    /// 按加速曲线累积滚轮量
     fn accumulate_wheel(&mut self, delta: i32) {
+        let delta = self.scroll_accel.apply(delta, Instant::now());
         self.wheel_delta = self.wheel_delta.saturating_add(delta);
-        self.dirty = true;
+    }
+
+    /// 自上一个报告以来是否有新的位移、滚轮或按键变化
+    ///
+    /// 只有 SYN 到来、却没有实际变化时（例如一帧内只有 `EV_MSC` 事件，或按下又松开）
+    /// 不产生报告。位移未超出死区时留到后续报告中。
+    fn has_pending(&self) -> bool {
+        self.buttons != self.reported_buttons
+            || self.dead_zone.exceeded(self.x_delta, self.y_delta)
+            || self.wheel_delta != 0
     }
 
     /// 构建报告并重置状态
-    fn build_report(&mut self) -> InputReport {
-        let report = InputReport::Mouse {
-            buttons: self.buttons,
-            // 裁剪到 i16 范围
-            x: self.x_delta.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
-            y: self.y_delta.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
-            wheel: self.wheel_delta.clamp(i8::MIN as i32, i8::MAX as i32) as i8,
-        };
+    ///
+    /// 缩放后全部落入余量、按键也没有变化时（如两格滚轮才输出一个单位）不产生报告。
+    fn build_report(&mut self) -> Option<InputReport> {
+        let x = self.sensitivity.scale(self.x_delta, &mut self.x_remainder);
+        let y = self.sensitivity.scale(self.y_delta, &mut self.y_remainder);
+        let wheel = self
+            .wheel_resolution
+            .scale(self.wheel_delta, &mut self.wheel_remainder);
 
         // 重置累积值
         self.x_delta = 0;
         self.y_delta = 0;
         self.wheel_delta = 0;
-        self.dirty = false;
-        self.button_changed = false;
+        if self.buttons == self.reported_buttons && x == 0 && y == 0 && wheel == 0 {
+            return None;
+        }
+
+        let report = InputReport::Mouse {
+            buttons: self.buttons,
+            // 裁剪到 i16 范围
+            x: x.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+            y: y.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+            wheel: wheel.clamp(i8::MIN as i32, i8::MAX as i32) as i8,
+        };
+        self.reported_buttons = self.buttons;
         self.last_report_time = Some(Instant::now());
 
-        report
+        Some(report)
     }
 }
 
 pub struct LedHandle {
     keyboard_controls: Arc<Mutex<Vec<mpsc::UnboundedSender<LedState>>>>,
     current_led_state: Arc<Mutex<LedState>>,
+    led_tx: watch::Sender<LedState>,
 }
 
 impl MouseRateController {
@@ -205,7 +1080,7 @@ impl MouseRateController {
     /// 获取当前报告率（Hz）
     pub fn get_rate(&self) -> u32 {
         let micros = self.interval_micros.load(Ordering::Relaxed);
-        if micros == 0 { 0 } else { 1_000_000 / micros }
+        1_000_000u32.checked_div(micros).unwrap_or(0)
     }
 
     /// 获取当前间隔
@@ -220,7 +1095,93 @@ impl MouseRateController {
     }
 
     fn hz_to_micros(rate_hz: u32) -> u32 {
-        if rate_hz == 0 { 0 } else { 1_000_000 / rate_hz }
+        1_000_000u32.checked_div(rate_hz).unwrap_or(0)
+    }
+}
+
+impl KeyRemap {
+    const CAPS_TO_CTRL: u8 = 0x01;
+    const SWAP_ALT_META: u8 = 0x02;
+    const DISABLE_SUPER: u8 = 0x04;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Caps Lock 作为左 Ctrl
+    pub fn set_caps_to_ctrl(&self, enabled: bool) {
+        self.set_flag(Self::CAPS_TO_CTRL, enabled);
+    }
+
+    pub fn caps_to_ctrl(&self) -> bool {
+        self.flag(Self::CAPS_TO_CTRL)
+    }
+
+    /// 交换 Alt 与 Meta（Super）
+    pub fn set_swap_alt_meta(&self, enabled: bool) {
+        self.set_flag(Self::SWAP_ALT_META, enabled);
+    }
+
+    pub fn swap_alt_meta(&self) -> bool {
+        self.flag(Self::SWAP_ALT_META)
+    }
+
+    /// 禁用 Super 键
+    pub fn set_disable_super(&self, enabled: bool) {
+        self.set_flag(Self::DISABLE_SUPER, enabled);
+    }
+
+    pub fn disable_super(&self) -> bool {
+        self.flag(Self::DISABLE_SUPER)
+    }
+
+    /// 按当前开关映射按键，返回 `None` 表示该键被禁用
+    pub fn map(&self, key: KeyCode) -> Option<KeyCode> {
+        let flags = self.flags.load(Ordering::Relaxed);
+
+        let key = match key {
+            KeyCode::KEY_CAPSLOCK if flags & Self::CAPS_TO_CTRL != 0 => KeyCode::KEY_LEFTCTRL,
+            other => other,
+        };
+
+        let Some(modifier) = Modifier::from_key(key) else {
+            return Some(key);
+        };
+        let modifier = if flags & Self::SWAP_ALT_META != 0 {
+            modifier.swap_alt_meta()
+        } else {
+            modifier
+        };
+        if modifier.is_meta() && flags & Self::DISABLE_SUPER != 0 {
+            return None;
+        }
+        Some(modifier.key())
+    }
+
+    /// 按当前开关映射 HID 键码，修饰键为 0xE0..=0xE7，返回 `None` 表示该键被禁用
+    pub fn map_usage(&self, usage: u8) -> Option<u8> {
+        let key = match usage {
+            crate::output::keycodes::KEY_CAPS_LOCK => KeyCode::KEY_CAPSLOCK,
+            0xE0..=0xE7 => Modifier::ALL[(usage - 0xE0) as usize].key(),
+            other => return Some(other),
+        };
+        let mapped = self.map(key)?;
+        Some(match modifier_bit(mapped) {
+            Some(bit) => 0xE0 + bit.trailing_zeros() as u8,
+            None => usage,
+        })
+    }
+
+    fn set_flag(&self, flag: u8, enabled: bool) {
+        if enabled {
+            self.flags.fetch_or(flag, Ordering::Relaxed);
+        } else {
+            self.flags.fetch_and(!flag, Ordering::Relaxed);
+        }
+    }
+
+    fn flag(&self, flag: u8) -> bool {
+        self.flags.load(Ordering::Relaxed) & flag != 0
     }
 }
 
@@ -230,31 +1191,80 @@ impl Default for MouseRateController {
     }
 }
 
+impl Default for LedHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LedHandle {
     pub fn new() -> Self {
         Self {
             keyboard_controls: Arc::new(Mutex::new(Vec::new())),
             current_led_state: Arc::new(Mutex::new(LedState::default())),
+            led_tx: watch::channel(LedState::default()).0,
         }
     }
 
+    /// 订阅主机 LED 状态，初始值为当前状态，之后每次 [`LedHandle::set_leds`] 都会更新
+    pub fn subscribe(&self) -> watch::Receiver<LedState> {
+        self.led_tx.subscribe()
+    }
+
     pub async fn set_leds(&self, ctrl: &LedState) {
         let mut controls = self.keyboard_controls.lock().unwrap();
-        self.current_led_state.lock().unwrap().clone_from(&ctrl);
+        self.current_led_state.lock().unwrap().clone_from(ctrl);
+        self.led_tx.send_replace(*ctrl);
         // 发送指令并移除已失效的设备连接
-        controls.retain(|tx| tx.send(ctrl.clone()).is_ok());
+        controls.retain(|tx| tx.send(*ctrl).is_ok());
+    }
+}
+
+/// 向输入管线注入报告的句柄
+///
+/// 注入的报告与 evdev 设备产生的报告进入同一通道，之后的限速、切换与发送完全一致，
+/// 可用于测试以及网络、脚本等其他输入来源。
+#[derive(Clone)]
+pub struct InputInjector {
+    tx: mpsc::Sender<TimedReport>,
+}
+
+impl InputInjector {
+    /// 注入一个报告，通道满时等待；管线已关闭时返回错误
+    pub async fn inject(&self, report: InputReport) -> anyhow::Result<()> {
+        self.inject_timed(TimedReport::new(report)).await
+    }
+
+    /// 注入一个已带创建时间的报告，延迟从 `created_at` 起算
+    pub(crate) async fn inject_timed(&self, timed: TimedReport) -> anyhow::Result<()> {
+        self.tx
+            .send(timed)
+            .await
+            .map_err(|_| anyhow::anyhow!("输入管线已关闭"))
     }
 }
 
 pub struct InputManager {
-    event_rx: mpsc::UnboundedReceiver<InputReport>,
+    event_rx: mpsc::Receiver<TimedReport>,
     pub led_handle: Option<LedHandle>,
     pub mouse_rate_controller: MouseRateController,
+    pub key_remap: KeyRemap,
+    pub sensitivity: MouseSensitivity,
+    /// 报告变换链，注册的变换对之后所有设备产生的报告生效
+    pub transforms: TransformChain,
+    /// 键盘独占开关
+    pub grab: KeyboardGrab,
+    pub status: InputStatus,
+    injector: InputInjector,
 }
 
 impl InputManager {
     pub fn new(rate_hz: u32) -> Self {
-        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        Self::with_config(rate_hz, InputConfig::default())
+    }
+
+    pub fn with_config(rate_hz: u32, config: InputConfig) -> Self {
+        let (event_tx, event_rx) = mpsc::channel(config.channel_capacity.max(1));
 
         let led_handle = LedHandle::new();
         let keyboard_controls = Arc::clone(&led_handle.keyboard_controls);
@@ -262,27 +1272,53 @@ impl InputManager {
 
         let mouse_rate_controller = MouseRateController::new(rate_hz);
         let rate_controller_clone = mouse_rate_controller.clone();
+        let key_remap = config.key_remap.clone();
+        let sensitivity = config.sensitivity.clone();
+        let transforms = config.transforms.clone();
+        let grab = config.grab.clone();
+        let status = InputStatus::default();
+        let status_clone = status.clone();
+        let injector = InputInjector {
+            tx: event_tx.clone(),
+        };
 
-        tokio::spawn(async move {
-            if let Err(e) = Self::monitor_devices(
-                event_tx,
-                keyboard_controls,
-                current_led_state,
-                rate_controller_clone, // 传递控制器
-            )
-            .await
-            {
-                error!("Monitor Devices task failed: {}", e);
-            }
-        });
+        if !config.scan.enabled {
+            info!("设备扫描已关闭，只处理注入的报告");
+        } else {
+            tokio::spawn(async move {
+                if let Err(e) = Self::monitor_devices(
+                    event_tx,
+                    keyboard_controls,
+                    current_led_state,
+                    rate_controller_clone, // 传递控制器
+                    config,
+                    status_clone,
+                )
+                .await
+                {
+                    error!("Monitor Devices task failed: {}", e);
+                }
+            });
+        }
 
         Self {
             event_rx,
             led_handle: Some(led_handle),
             mouse_rate_controller,
+            key_remap,
+            sensitivity,
+            transforms,
+            grab,
+            status,
+            injector,
         }
     }
 
+    /// 获取向本管线注入报告的句柄
+    pub fn injector(&self) -> InputInjector {
+        self.injector.clone()
+    }
+
     /// 动态设置鼠标报告率
     pub fn set_mouse_rate(&self, rate_hz: u32) {
         self.mouse_rate_controller.set_rate(rate_hz);
@@ -294,94 +1330,155 @@ impl InputManager {
     }
 
     async fn monitor_devices(
-        tx: mpsc::UnboundedSender<InputReport>,
+        tx: mpsc::Sender<TimedReport>,
         keyboard_controls: Arc<Mutex<Vec<mpsc::UnboundedSender<LedState>>>>,
         current_led_state: Arc<Mutex<LedState>>,
         mouse_rate_controller: MouseRateController,
+        config: InputConfig,
+        status: InputStatus,
     ) -> anyhow::Result<()> {
-        use tokio::time::{Duration, sleep};
+        use tokio::time::sleep;
         let active_monitors = Arc::new(Mutex::new(HashSet::<String>::new()));
+        // 已按配置跳过的设备，只记录一次日志
+        let mut skipped = HashSet::<String>::new();
+        let mut warner = ScanWarner::default();
+        let mut interval = ScanInterval::new(&config.scan);
+        let mut settle = DeviceSettle::new(config.scan.settle());
+        let mut last_nodes: Option<Vec<std::path::PathBuf>> = None;
+
+        let startup_delay = config.scan.startup_delay();
+        if !startup_delay.is_zero() {
+            info!("等待 {:?} 后开始扫描输入设备", startup_delay);
+            sleep(startup_delay).await;
+        }
 
         loop {
-            // 用 try_read_dir 防止 IO 异常导致整个 loop 退出
-            if let Ok(paths) = std::fs::read_dir("/dev/input") {
-                for path in paths.flatten() {
-                    let path_buf = path.path();
+            // 读取失败不退出循环，记录状态并限流告警
+            let scan = scan_event_nodes(std::path::Path::new(INPUT_DIR));
+            let mut denied = 0;
+            let now = Instant::now();
+            if let Ok(paths) = &scan {
+                settle.observe(paths, now);
+                forget_removed(&mut skipped, paths);
+                for path_buf in paths {
+                    if !settle.is_settled(path_buf, now) {
+                        continue;
+                    }
                     let path_str = path_buf.to_string_lossy().to_string();
 
-                    if path_str.contains("event") {
-                        let already_monitored = active_monitors.lock().unwrap().contains(&path_str);
-
-                        if !already_monitored {
-                            // 尝试打开设备
-                            if let Ok(mut device) = Device::open(&path_buf) {
-                                if let Some(device_type) = Self::detect_device_type(&device) {
-                                    active_monitors.lock().unwrap().insert(path_str.clone());
-
-                                    let tx_clone = tx.clone();
-                                    let mut led_rx_to_pass = None;
-                                    let mut current_led_state_clone = None;
-
-                                    let rate_controller_for_device =
-                                        if device_type == DeviceType::Mouse {
-                                            Some(mouse_rate_controller.clone())
-                                        } else {
-                                            None
-                                        };
-
-                                    // 如果是键盘，创建 LED 控制通道
-                                    if device_type == DeviceType::Keyboard {
-                                        device.grab().context("独占键盘设备失败")?;
-                                        let (led_tx, led_rx) =
-                                            mpsc::unbounded_channel::<LedState>();
-                                        // 将 tx 存入全局列表，以便 InputManager::set_all_leds 广播
-                                        keyboard_controls.lock().unwrap().push(led_tx);
-                                        // 将 rx 准备好传给 monitor.run
-                                        led_rx_to_pass = Some(led_rx);
-                                        current_led_state_clone = Some(
-                                            current_led_state
-                                                .lock()
-                                                .map(|guard| guard.clone())
-                                                .unwrap_or_default(),
-                                        );
-
-                                        debug!(
-                                            "current_led_state_clone: {:?}",
-                                            current_led_state_clone
-                                        );
-                                    }
-                                    let path_id = path_str.clone();
-                                    let active_monitors_clone = Arc::clone(&active_monitors);
-
-                                    tokio::spawn(async move {
-                                        let monitor = DeviceMonitor::new(
-                                            device_type,
-                                            rate_controller_for_device,
-                                        );
-
-                                        info!("Started monitoring: {}", path_id);
-                                        monitor.run(tx_clone, led_rx_to_pass, device).await;
-
-                                        active_monitors_clone.lock().unwrap().remove(&path_id);
-                                        info!("Stopped monitoring: {}", path_id);
-                                    });
-
-                                    // 发送当前 LED 状态以同步新连接的键盘
-                                    if let Some(ctrl) = current_led_state_clone {
-                                        if let Some(last_tx) =
-                                            keyboard_controls.lock().unwrap().last()
-                                        {
-                                            let _ = last_tx.send(ctrl);
-                                        }
-                                    }
+                    let already_monitored = active_monitors.lock().unwrap().contains(&path_str);
+
+                    if !already_monitored && !skipped.contains(&path_str) {
+                        // 尝试打开设备
+                        let opened = Device::open(path_buf);
+                        if let Err(e) = &opened
+                            && e.kind() == std::io::ErrorKind::PermissionDenied
+                        {
+                            denied += 1;
+                        }
+                        if let Ok(device) = &opened
+                            && !config
+                                .device_filter
+                                .is_selected(device.name().unwrap_or_default(), &path_str)
+                        {
+                            info!(
+                                "按配置跳过设备: {} ({})",
+                                device.name().unwrap_or_default(),
+                                path_str
+                            );
+                            skipped.insert(path_str);
+                            continue;
+                        }
+                        if let Ok(mut device) = opened
+                            && let Some(device_type) = Self::detect_device_type(&device)
+                        {
+                            active_monitors.lock().unwrap().insert(path_str.clone());
+
+                            let tx_clone = tx.clone();
+                            let mut led_rx_to_pass = None;
+                            let mut current_led_state_clone = None;
+
+                            let rate_controller_for_device = if device_type == DeviceType::Mouse {
+                                Some(mouse_rate_controller.clone())
+                            } else {
+                                None
+                            };
+
+                            // 如果是键盘，创建 LED 控制通道
+                            if device_type == DeviceType::Keyboard {
+                                if config.grab.is_grabbed() {
+                                    device.grab().context("独占键盘设备失败")?;
                                 }
+                                let (led_tx, led_rx) = mpsc::unbounded_channel::<LedState>();
+                                // 将 tx 存入全局列表，以便 InputManager::set_all_leds 广播
+                                keyboard_controls.lock().unwrap().push(led_tx);
+                                // 将 rx 准备好传给 monitor.run
+                                led_rx_to_pass = Some(led_rx);
+                                current_led_state_clone = Some(
+                                    current_led_state
+                                        .lock()
+                                        .map(|guard| *guard)
+                                        .unwrap_or_default(),
+                                );
+
+                                debug!("current_led_state_clone: {:?}", current_led_state_clone);
+                            }
+                            let path_id = path_str.clone();
+                            let active_monitors_clone = Arc::clone(&active_monitors);
+                            let monitor_config = config.clone();
+
+                            tokio::spawn(async move {
+                                let monitor = DeviceMonitor::new(
+                                    device_type,
+                                    rate_controller_for_device,
+                                    monitor_config,
+                                );
+
+                                info!("Started monitoring: {}", path_id);
+                                monitor.run(tx_clone, led_rx_to_pass, device).await;
+
+                                active_monitors_clone.lock().unwrap().remove(&path_id);
+                                info!("Stopped monitoring: {}", path_id);
+                            });
+
+                            // 发送当前 LED 状态以同步新连接的键盘
+                            if let Some(ctrl) = current_led_state_clone
+                                && let Some(last_tx) = keyboard_controls.lock().unwrap().last()
+                            {
+                                let _ = last_tx.send(ctrl);
                             }
                         }
                     }
                 }
             }
-            // 扫描间隔
-            sleep(Duration::from_secs(1)).await;
+
+            let mut nodes = scan.as_ref().cloned().unwrap_or_default();
+            nodes.sort();
+            let current = match scan {
+                // 所有节点都无法打开且没有任何设备在监听时，按权限不足处理
+                Ok(paths)
+                    if denied == paths.len() && active_monitors.lock().unwrap().is_empty() =>
+                {
+                    ScanStatus::PermissionDenied
+                }
+                Ok(paths) => ScanStatus::Ok {
+                    event_nodes: paths.len(),
+                },
+                Err(problem) => problem,
+            };
+            if warner.should_warn(&current, Instant::now()) {
+                warn_scan_problem(&current);
+            }
+            status.set(current);
+
+            // 事件节点集合变化（首次扫描除外）时缩短扫描间隔
+            let changed = last_nodes.as_ref().is_some_and(|last| *last != nodes);
+            last_nodes = Some(nodes);
+            let mut wait = interval.next(changed);
+            if let Some(due) = settle.next_due(Instant::now()) {
+                wait = wait.min(due);
+            }
+            sleep(wait).await;
         }
     }
 
@@ -404,28 +1501,57 @@ impl InputManager {
     }
 
     pub async fn next_event(&mut self) -> Option<InputReport> {
+        self.event_rx.recv().await.map(|timed| timed.report)
+    }
+
+    /// 获取下一个报告及其创建时间
+    pub(crate) async fn next_timed_event(&mut self) -> Option<TimedReport> {
         self.event_rx.recv().await
     }
 
     pub async fn clear_events(&mut self) {
-        while let Ok(report) = self.event_rx.try_recv() {
-            debug!("Cleared event: {:?}", report);
+        while let Ok(timed) = self.event_rx.try_recv() {
+            debug!("Cleared event: {:?}", timed.report);
         }
     }
 }
 
 impl DeviceMonitor {
-    fn new(device_type: DeviceType, rate_controller: Option<MouseRateController>) -> Self {
+    fn new(
+        device_type: DeviceType,
+        rate_controller: Option<MouseRateController>,
+        config: InputConfig,
+    ) -> Self {
         Self {
             device_type,
             keyboard_state: KeyboardState::default(),
-            mouse_state: MouseState::new(rate_controller.unwrap_or_default()),
+            keyboard_source: config.keyboards.register(),
+            mouse_state: MouseState::new(
+                rate_controller.unwrap_or_default(),
+                config.sensitivity.clone(),
+                config.wheel_resolution,
+                config.dead_zone,
+                config.scroll_accel,
+            ),
+            apple_keys: config.apple_keys == AppleKeys::On,
+            debounce: KeyDebounce::new(config.key_debounce),
+            transforms: Self::transforms(&config),
+            config,
         }
     }
 
+    /// 本设备的变换链：按键重映射、坐标轴变换，然后是共享的变换
+    fn transforms(config: &InputConfig) -> TransformChain {
+        let chain = TransformChain::default();
+        chain.push(RemapTransform::new(config.key_remap.clone()));
+        chain.push(config.axes);
+        chain.push(config.transforms.clone());
+        chain
+    }
+
     async fn run(
         mut self,
-        tx: mpsc::UnboundedSender<InputReport>,
+        tx: mpsc::Sender<TimedReport>,
         led_rx: Option<mpsc::UnboundedReceiver<LedState>>,
         mut device: Device,
     ) {
@@ -435,6 +1561,12 @@ impl DeviceMonitor {
             .map(|n| n.to_string())
             .unwrap_or_else(|| "Unknown".to_string());
         debug!("Device name: {}", device_name);
+        if self.device_type == DeviceType::Keyboard
+            && self.config.apple_keys.enabled_for(&device_name)
+        {
+            self.apple_keys = true;
+            info!("按 Apple 键盘处理专用键: {}", device_name);
+        }
 
         if self.device_type == DeviceType::Keyboard {
             let raw_fd = device.as_raw_fd();
@@ -446,14 +1578,31 @@ impl DeviceMonitor {
                 return;
             }
 
+            // 与读取端共享同一个文件描述，用于切换独占
+            let grab_fd = unsafe { OwnedFd::from_raw_fd(cloned_fd) };
             let fd_path = format!("/proc/self/fd/{}", cloned_fd);
             match Device::open(&fd_path)
                 .with_context(|| format!("打开克隆 FD 设备失败: {}", fd_path))
             {
                 Ok(mut write_device) => {
+                    let mut grab_rx = self.config.grab.subscribe();
                     led_handle = Some(tokio::spawn(async move {
                         if let Some(mut rx) = led_rx {
-                            while let Some(ctrl) = rx.recv().await {
+                            loop {
+                                let ctrl = tokio::select! {
+                                    ctrl = rx.recv() => match ctrl {
+                                        Some(ctrl) => ctrl,
+                                        None => break,
+                                    },
+                                    Ok(()) = grab_rx.changed() => {
+                                        let grab = *grab_rx.borrow_and_update();
+                                        match set_fd_grab(grab_fd.as_raw_fd(), grab) {
+                                            Ok(()) => info!("键盘独占: {}", grab),
+                                            Err(e) => warn!("切换键盘独占失败: {}", e),
+                                        }
+                                        continue;
+                                    }
+                                };
                                 let events = [
                                     InputEvent::new(
                                         evdev::EventType::LED.0,
@@ -492,29 +1641,13 @@ impl DeviceMonitor {
                 }
                 Err(e) => {
                     error!("通过克隆的 FD 创建新 Device 失败: {}", e);
-                    unsafe { libc::close(cloned_fd) };
                 }
             }
         }
 
         let fetch_handle = tokio::task::spawn_blocking(move || {
-            loop {
-                match device.fetch_events() {
-                    Ok(events) => {
-                        for event in events {
-                            if let Some(report) = self.process_event(event) {
-                                if tx.send(report).is_err() {
-                                    return;
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        error!("读取事件失败: {}", e);
-                        return;
-                    }
-                }
-            }
+            let mut sender = EventSender::new(tx);
+            self.fetch_loop(&mut device, &mut sender);
         });
 
         // 等待任务结束
@@ -535,11 +1668,184 @@ impl DeviceMonitor {
         };
     }
 
-    fn process_event(&mut self, event: evdev::InputEvent) -> Option<InputReport> {
+    /// 阻塞读取事件直到设备被移除或接收端关闭
+    ///
+    /// `EINTR`/`EAGAIN` 等暂时性错误短暂等待后重试，其他错误（如设备被拔出）退出循环，
+    /// 由 `monitor_devices` 清理并在设备重新出现时重新监听。
+    /// 设备被移除时如果还有按住的键，先发送一个释放报告，避免主机上卡键。
+    fn fetch_loop(&mut self, source: &mut impl EventSource, sender: &mut EventSender) {
+        loop {
+            // 通道满时合并的鼠标报告不能等到下一个事件才发出
+            if sender.has_pending() {
+                match source.wait(PENDING_MOUSE_RETRY) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        if sender.try_flush().is_err() {
+                            return;
+                        }
+                        continue;
+                    }
+                    Err(e) => debug!("等待输入事件失败: {}", e),
+                }
+            }
+            if let Some(idle) = self.modifier_watchdog() {
+                match source.wait(idle) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        let physical = source.held_keys().unwrap_or_else(|e| {
+                            debug!("读取设备按键状态失败: {}", e);
+                            None
+                        });
+                        let reports = self.reconcile_held(physical.as_deref());
+                        if !self.send_reports(reports, sender) {
+                            return;
+                        }
+                        continue;
+                    }
+                    Err(e) => debug!("等待输入事件失败: {}", e),
+                }
+            }
+            match source.fetch() {
+                Ok(events) => {
+                    for event in events {
+                        let reports = self.process_event(event);
+                        if !self.send_reports(reports, sender) {
+                            return;
+                        }
+                    }
+                }
+                Err(e) if is_transient(&e) => {
+                    debug!("读取事件暂时失败，稍后重试: {}", e);
+                    std::thread::sleep(TRANSIENT_RETRY_DELAY);
+                }
+                Err(e) => {
+                    error!("读取事件失败: {}", e);
+                    let release = self.release_held();
+                    if !release.is_empty() {
+                        info!("设备已移除，释放其按住的键");
+                        self.send_reports(release, sender);
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    /// 发送报告，通道已关闭时返回 `false`
+    fn send_reports(&self, reports: Reports, sender: &mut EventSender) -> bool {
+        for report in reports {
+            if sender.send(report).is_err() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// 键盘按住修饰键时的空闲检查时长，未启用或没有按住修饰键时返回 `None`
+    fn modifier_watchdog(&self) -> Option<Duration> {
         match self.device_type {
-            DeviceType::Keyboard => self.process_keyboard_event(event),
+            DeviceType::Keyboard if self.keyboard_state.modifiers != 0 => {
+                self.config.modifier_watchdog
+            }
+            _ => None,
+        }
+    }
+
+    /// 按住修饰键空闲超时后核对设备的实际按键状态
+    ///
+    /// 设备已不再报告按下的键视为漏掉了松开事件，补发释放；否则重发当前状态，
+    /// 纠正主机因丢失报告而与本机不一致的修饰键。
+    fn reconcile_held(&mut self, physical: Option<&[u16]>) -> Reports {
+        let mut reports = Reports::new();
+        if let Some(physical) = physical {
+            let mut orphaned: Vec<u16> = self
+                .keyboard_state
+                .held
+                .iter()
+                .filter(|code| !physical.contains(code))
+                .copied()
+                .collect();
+            orphaned.sort_unstable();
+            for code in orphaned {
+                warn!(
+                    "{:?} 已松开但没有收到松开事件，补发释放",
+                    KeyCode::new(code)
+                );
+                reports.extend(self.process_event(InputEvent::new(EventType::KEY.0, code, 0)));
+            }
+        }
+        if reports.is_empty() {
+            debug!("修饰键按住超时，重发当前按键状态");
+            let state = &self.keyboard_state;
+            let report = InputReport::Keyboard {
+                modifiers: state.modifiers,
+                keys: state.pressed_keys.clone(),
+            };
+            reports = self.finish(smallvec::smallvec![report]);
+        }
+        reports
+    }
+
+    /// 清空按住的键或鼠标按键并返回释放报告，没有按住任何键时返回空
+    fn release_held(&mut self) -> Reports {
+        let release = match self.device_type {
+            DeviceType::Keyboard => {
+                let state = &mut self.keyboard_state;
+                if state.modifiers == 0 && state.pressed_keys.is_empty() {
+                    return Reports::new();
+                }
+                *state = KeyboardState::default();
+                InputReport::Keyboard {
+                    modifiers: 0,
+                    keys: vec![],
+                }
+            }
+            DeviceType::Mouse => {
+                let state = &mut self.mouse_state;
+                if state.buttons == 0 && state.reported_buttons == 0 {
+                    return Reports::new();
+                }
+                state.buttons = 0;
+                state.reported_buttons = 0;
+                InputReport::Mouse {
+                    buttons: 0,
+                    x: 0,
+                    y: 0,
+                    wheel: 0,
+                }
+            }
+        };
+        self.finish(smallvec::smallvec![release])
+    }
+
+    fn process_event(&mut self, event: evdev::InputEvent) -> Reports {
+        // EV_MSC（如 MSC_SCAN 扫描码）伴随按键事件出现，不携带需要转发的信息
+        if event.event_type() == EventType::MISC {
+            return Reports::new();
+        }
+        let reports = match self.device_type {
+            DeviceType::Keyboard => self.process_keyboard_event(event).into_iter().collect(),
             DeviceType::Mouse => self.process_mouse_event(event),
+        };
+        self.finish(reports)
+    }
+
+    /// 本设备的报告依次经过变换链，键盘报告再与其他键盘的状态合并
+    fn finish(&mut self, reports: Reports) -> Reports {
+        let mut finished = Reports::new();
+        for report in reports {
+            for report in self.transforms.apply(report) {
+                finished.push(match report {
+                    InputReport::Keyboard { modifiers, keys } => {
+                        self.config
+                            .keyboards
+                            .update(self.keyboard_source, modifiers, &keys)
+                    }
+                    other => other,
+                });
+            }
         }
+        finished
     }
 
     fn process_keyboard_event(&mut self, event: evdev::InputEvent) -> Option<InputReport> {
@@ -548,86 +1854,66 @@ impl DeviceMonitor {
             debug!("raw key: {:?}, code={}", key, event.code()); // 将原始 code 转换为 Key 枚举
             let value = event.value();
 
+            if self
+                .debounce
+                .is_chatter(event.code(), value, event.timestamp())
+            {
+                debug!("丢弃按键抖动: {:?} = {}", key, value);
+                return None;
+            }
+
             if value == 2 {
                 return None;
             } // 忽略自动重复
 
             let is_pressed = value == 1;
-            let scancode = evdev_to_hid(key);
-
-            match key {
-                KeyCode::KEY_LEFTCTRL => {
-                    self.keyboard_state.modifiers = if is_pressed {
-                        self.keyboard_state.modifiers | 0x01
-                    } else {
-                        self.keyboard_state.modifiers & !0x01
-                    }
-                }
-                KeyCode::KEY_LEFTSHIFT => {
-                    self.keyboard_state.modifiers = if is_pressed {
-                        self.keyboard_state.modifiers | 0x02
-                    } else {
-                        self.keyboard_state.modifiers & !0x02
-                    }
-                }
-                KeyCode::KEY_LEFTALT => {
-                    self.keyboard_state.modifiers = if is_pressed {
-                        self.keyboard_state.modifiers | 0x04
-                    } else {
-                        self.keyboard_state.modifiers & !0x04
-                    }
-                }
-                KeyCode::KEY_LEFTMETA => {
-                    self.keyboard_state.modifiers = if is_pressed {
-                        self.keyboard_state.modifiers | 0x08
-                    } else {
-                        self.keyboard_state.modifiers & !0x08
-                    }
-                }
-                KeyCode::KEY_RIGHTCTRL => {
-                    self.keyboard_state.modifiers = if is_pressed {
-                        self.keyboard_state.modifiers | 0x10
-                    } else {
-                        self.keyboard_state.modifiers & !0x10
-                    }
-                }
-                KeyCode::KEY_RIGHTSHIFT => {
-                    self.keyboard_state.modifiers = if is_pressed {
-                        self.keyboard_state.modifiers | 0x20
-                    } else {
-                        self.keyboard_state.modifiers & !0x20
-                    }
-                }
-                KeyCode::KEY_RIGHTALT => {
-                    self.keyboard_state.modifiers = if is_pressed {
-                        self.keyboard_state.modifiers | 0x40
-                    } else {
-                        self.keyboard_state.modifiers & !0x40
-                    }
+            if is_pressed {
+                self.keyboard_state.held.insert(event.code());
+            } else {
+                self.keyboard_state.held.remove(&event.code());
+            }
+
+            if self.apple_keys
+                && let Some(usage) = apple_usage(key)
+            {
+                return Some(InputReport::Consumer {
+                    usage: if is_pressed { usage } else { 0 },
+                });
+            }
+            if let Some(usage) = system_usage(key) {
+                return Some(InputReport::System {
+                    usage: if is_pressed { usage } else { 0 },
+                });
+            }
+            if let Some(usage) = consumer_usage(key) {
+                return Some(InputReport::Consumer {
+                    usage: if is_pressed { usage } else { 0 },
+                });
+            }
+
+            if let Some(bit) = modifier_bit(key) {
+                if is_pressed {
+                    self.keyboard_state.modifiers |= bit;
+                } else {
+                    self.keyboard_state.modifiers &= !bit;
                 }
-                KeyCode::KEY_RIGHTMETA => {
-                    self.keyboard_state.modifiers = if is_pressed {
-                        self.keyboard_state.modifiers | 0x80
-                    } else {
-                        self.keyboard_state.modifiers & !0x80
+            } else {
+                let Some(scancode) = evdev_to_hid(key) else {
+                    if self.config.raw_passthrough {
+                        return Some(InputReport::Vendor {
+                            code: key.code(),
+                            pressed: is_pressed,
+                        });
                     }
-                }
-                _ => {
-                    if is_pressed {
-                        if !self
-                            .keyboard_state
-                            .pressed_keys
-                            .contains(&(scancode.expect("键码错误")))
-                        {
-                            self.keyboard_state
-                                .pressed_keys
-                                .push(scancode.expect("键码错误"));
-                        }
-                    } else {
-                        self.keyboard_state
-                            .pressed_keys
-                            .retain(|&k| k != scancode.expect("键码错误"));
+                    debug!("按键没有对应的 HID 键码，已忽略: {:?}", key);
+                    return None;
+                };
+                if is_pressed {
+                    if !self.keyboard_state.pressed_keys.contains(&scancode) {
+                        self.keyboard_state.pressed_keys.push(scancode);
                     }
+                } else {
+                    self.keyboard_state.pressed_keys.retain(|&k| k != scancode);
                 }
             }
 
@@ -639,28 +1925,51 @@ impl DeviceMonitor {
         None
     }
 
-    fn process_mouse_event(&mut self, event: evdev::InputEvent) -> Option<InputReport> {
+    fn process_mouse_event(&mut self, event: evdev::InputEvent) -> Reports {
         match event.event_type() {
             EventType::KEY => {
                 let key = KeyCode::new(event.code());
                 let is_pressed = event.value() == 1;
 
-                let button_bit = match key {
+                let mut button_bit = match key {
                     KeyCode::BTN_LEFT => 0x01,
                     KeyCode::BTN_RIGHT => 0x02,
                     KeyCode::BTN_MIDDLE => 0x04,
-                    KeyCode::BTN_SIDE => 0x08,  // 侧键1
-                    KeyCode::BTN_EXTRA => 0x10, // 侧键2
-                    _ => return None,
+                    // 部分鼠标用 BTN_BACK/BTN_FORWARD 代替 BTN_SIDE/BTN_EXTRA 上报侧键
+                    KeyCode::BTN_SIDE | KeyCode::BTN_BACK => 0x08, // 侧键1
+                    KeyCode::BTN_EXTRA | KeyCode::BTN_FORWARD => 0x10, // 侧键2
+                    _ => {
+                        trace!("忽略未知鼠标按键: {:?} = {}", key, event.value());
+                        return Reports::new();
+                    }
                 };
 
+                match self.config.button_map.action(button_bit) {
+                    Some(ButtonAction::Button(target)) => button_bit = *target,
+                    Some(ButtonAction::Chord { modifiers, keys }) => {
+                        // 按下与松开分别对应组合键的按下与释放，忽略自动重复；
+                        // 与键盘一样登记到合并状态，释放时不会清掉键盘上按住的键
+                        let report = match event.value() {
+                            1 => InputReport::Keyboard {
+                                modifiers: *modifiers,
+                                keys: keys.clone(),
+                            },
+                            0 => InputReport::Keyboard {
+                                modifiers: 0,
+                                keys: vec![],
+                            },
+                            _ => return Reports::new(),
+                        };
+                        return smallvec::smallvec![report];
+                    }
+                    None => {}
+                }
+
                 if is_pressed {
                     self.mouse_state.buttons |= button_bit;
                 } else {
                     self.mouse_state.buttons &= !button_bit;
                 }
-                self.mouse_state.dirty = true;
-                self.mouse_state.button_changed = true;
             }
 
             EventType::RELATIVE => {
@@ -678,21 +1987,187 @@ impl DeviceMonitor {
                     evdev::RelativeAxisCode::REL_HWHEEL => {
                         // 水平滚轮，如需支持可扩展
                     }
-                    _ => return None,
+                    evdev::RelativeAxisCode::REL_DIAL => {
+                        return self.process_dial(event.value());
+                    }
+                    _ => return Reports::new(),
+                }
+            }
+
+            EventType::SYNCHRONIZATION if self.mouse_state.has_pending() => {
+                if self.mouse_state.should_send_report() {
+                    return self.mouse_state.build_report().into_iter().collect();
+                }
+                metrics::global()
+                    .report_loss
+                    .record_coalesced(Stage::RateController, ReportKind::Mouse);
+            }
+
+            _ => {}
+        }
+
+        Reports::new()
+    }
+
+    /// 按配置处理旋钮：作为滚轮累积，或每格产生一次音量键的按下+释放
+    ///
+    /// 单个事件最多处理 [`MAX_DIAL_STEPS`] 格，异常的大数值被截断。
+    fn process_dial(&mut self, value: i32) -> Reports {
+        let value = value.clamp(-MAX_DIAL_STEPS, MAX_DIAL_STEPS);
+        match self.config.dial_target {
+            DialTarget::Ignore => Reports::new(),
+            DialTarget::Wheel => {
+                self.mouse_state.accumulate_wheel(value);
+                Reports::new()
+            }
+            DialTarget::Volume => {
+                let usage = match value.signum() {
+                    1 => consumer::VOLUME_UP,
+                    -1 => consumer::VOLUME_DOWN,
+                    _ => return Reports::new(),
+                };
+                let mut reports = Reports::new();
+                for _ in 0..value.unsigned_abs() {
+                    reports.push(InputReport::Consumer { usage });
+                    reports.push(InputReport::Consumer { usage: 0 });
                 }
+                reports
             }
+        }
+    }
+}
+
+/// 监听任务无论因设备移除还是通道关闭而结束，都不能在合并状态里留下按住的键
+impl Drop for DeviceMonitor {
+    fn drop(&mut self) {
+        self.config.keyboards.unregister(self.keyboard_source);
+    }
+}
 
-            EventType::SYNCHRONIZATION => {
-                if self.mouse_state.dirty && self.mouse_state.should_send_report() {
-                    return Some(self.mouse_state.build_report());
-                }
-            }
+/// 忘记已拔出设备的跳过记录，同一路径上重新插入的设备会重新按配置判断
+fn forget_removed(skipped: &mut HashSet<String>, paths: &[std::path::PathBuf]) {
+    skipped.retain(|skipped| paths.iter().any(|path| path.to_string_lossy() == *skipped));
+}
 
-            _ => {}
+fn warn_scan_problem(status: &ScanStatus) {
+    if let Some(problem) = status.problem() {
+        warn!("{}", problem);
+    }
+}
+
+/// 立即扫描一次输入设备目录
+pub fn probe_input_devices() -> ScanStatus {
+    match scan_event_nodes(std::path::Path::new(INPUT_DIR)) {
+        Ok(nodes) => ScanStatus::Ok {
+            event_nodes: nodes.len(),
+        },
+        Err(status) => status,
+    }
+}
+
+/// 键盘报告修饰键字节中的八个修饰键，取值即对应的位
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Modifier {
+    LeftCtrl = 0x01,
+    LeftShift = 0x02,
+    LeftAlt = 0x04,
+    LeftMeta = 0x08,
+    RightCtrl = 0x10,
+    RightShift = 0x20,
+    RightAlt = 0x40,
+    RightMeta = 0x80,
+}
+
+impl Modifier {
+    pub const ALL: [Self; 8] = [
+        Self::LeftCtrl,
+        Self::LeftShift,
+        Self::LeftAlt,
+        Self::LeftMeta,
+        Self::RightCtrl,
+        Self::RightShift,
+        Self::RightAlt,
+        Self::RightMeta,
+    ];
+
+    pub fn from_key(key: KeyCode) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.key() == key)
+    }
+
+    pub fn key(self) -> KeyCode {
+        match self {
+            Self::LeftCtrl => KeyCode::KEY_LEFTCTRL,
+            Self::LeftShift => KeyCode::KEY_LEFTSHIFT,
+            Self::LeftAlt => KeyCode::KEY_LEFTALT,
+            Self::LeftMeta => KeyCode::KEY_LEFTMETA,
+            Self::RightCtrl => KeyCode::KEY_RIGHTCTRL,
+            Self::RightShift => KeyCode::KEY_RIGHTSHIFT,
+            Self::RightAlt => KeyCode::KEY_RIGHTALT,
+            Self::RightMeta => KeyCode::KEY_RIGHTMETA,
         }
+    }
 
-        None
+    pub const fn bit(self) -> u8 {
+        self as u8
     }
+
+    fn is_meta(self) -> bool {
+        matches!(self, Self::LeftMeta | Self::RightMeta)
+    }
+
+    /// 同侧的 Alt 与 Meta 互换，其余修饰键不变
+    fn swap_alt_meta(self) -> Self {
+        match self {
+            Self::LeftAlt => Self::LeftMeta,
+            Self::LeftMeta => Self::LeftAlt,
+            Self::RightAlt => Self::RightMeta,
+            Self::RightMeta => Self::RightAlt,
+            other => other,
+        }
+    }
+}
+
+/// 电源相关按键对应的系统控制用法
+fn system_usage(key: KeyCode) -> Option<u8> {
+    match key {
+        KeyCode::KEY_POWER => Some(system::POWER_DOWN),
+        KeyCode::KEY_SLEEP => Some(system::SLEEP),
+        KeyCode::KEY_WAKEUP => Some(system::WAKE_UP),
+        _ => None,
+    }
+}
+
+/// 浏览器导航键对应的消费类控制用法
+fn consumer_usage(key: KeyCode) -> Option<u16> {
+    match key {
+        KeyCode::KEY_HOMEPAGE => Some(consumer::AC_HOME),
+        KeyCode::KEY_BACK => Some(consumer::AC_BACK),
+        KeyCode::KEY_SEARCH => Some(consumer::AC_SEARCH),
+        _ => None,
+    }
+}
+
+/// Apple 键盘专用键对应的消费类控制用法
+///
+/// hid-apple 把 Fn/Globe 报告为 `KEY_FN`，顶排默认（未按 Fn）报告为亮度、调度中心等键，
+/// 这里发送 macOS/iPadOS 能识别的用法，而不是兼容层的 F1~F6。
+fn apple_usage(key: KeyCode) -> Option<u16> {
+    match key {
+        KeyCode::KEY_FN => Some(consumer::AC_NEXT_KEYBOARD_LAYOUT),
+        KeyCode::KEY_BRIGHTNESSDOWN => Some(consumer::DISPLAY_BRIGHTNESS_DOWN),
+        KeyCode::KEY_BRIGHTNESSUP => Some(consumer::DISPLAY_BRIGHTNESS_UP),
+        KeyCode::KEY_SCALE => Some(consumer::AC_SHOW_ALL_WINDOWS),
+        KeyCode::KEY_DASHBOARD => Some(consumer::AC_SHOW_ALL_APPLICATIONS),
+        KeyCode::KEY_KBDILLUMDOWN => Some(consumer::KEYBOARD_BRIGHTNESS_DOWN),
+        KeyCode::KEY_KBDILLUMUP => Some(consumer::KEYBOARD_BRIGHTNESS_UP),
+        _ => None,
+    }
+}
+
+/// 修饰键在修饰键字节中的位，普通键返回 `None`
+pub fn modifier_bit(key: KeyCode) -> Option<u8> {
+    Modifier::from_key(key).map(Modifier::bit)
 }
 
 fn evdev_to_hid(code: KeyCode) -> Option<u8> {
@@ -823,6 +2298,15 @@ fn evdev_to_hid(code: KeyCode) -> Option<u8> {
         KeyCode::KEY_KPDOT => 0x63,
         KeyCode::KEY_102ND => 0x64, // 非美式键盘的 \| 键
 
+        // ----- 日文 / 韩文键盘 -----
+        KeyCode::KEY_RO => 0x87,               // International1
+        KeyCode::KEY_KATAKANAHIRAGANA => 0x88, // International2
+        KeyCode::KEY_YEN => 0x89,              // International3
+        KeyCode::KEY_HENKAN => 0x8A,           // International4
+        KeyCode::KEY_MUHENKAN => 0x8B,         // International5
+        KeyCode::KEY_HANGEUL => 0x90,          // Lang1
+        KeyCode::KEY_HANJA => 0x91,            // Lang2
+
         _ => return None,
     })
 }
@@ -831,6 +2315,1123 @@ fn evdev_to_hid(code: KeyCode) -> Option<u8> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_device_filter_skips_excluded() {
+        let devices = [
+            ("AT Translated Set 2 keyboard", "/dev/input/event0"),
+            ("Logitech USB Receiver", "/dev/input/event3"),
+            ("Logitech USB Receiver Mouse", "/dev/input/event4"),
+            ("Keychron K2", "/dev/input/event7"),
+        ];
+        let monitored = |filter: &DeviceFilter| -> Vec<&str> {
+            devices
+                .iter()
+                .filter(|(name, path)| filter.is_selected(name, path))
+                .map(|(name, _)| *name)
+                .collect()
+        };
+
+        assert_eq!(monitored(&DeviceFilter::default()).len(), devices.len());
+
+        let exclude = DeviceFilter {
+            exclude: vec!["AT Translated*".into(), "/dev/input/event4".into()],
+            allow: vec![],
+        };
+        assert_eq!(
+            monitored(&exclude),
+            vec!["Logitech USB Receiver", "Keychron K2"]
+        );
+
+        // 白名单模式，排除优先
+        let allow_only = DeviceFilter {
+            exclude: vec!["*Mouse".into()],
+            allow: vec!["Logitech*".into()],
+        };
+        assert_eq!(monitored(&allow_only), vec!["Logitech USB Receiver"]);
+    }
+
+    fn dial(value: i32) -> InputEvent {
+        InputEvent::new(
+            EventType::RELATIVE.0,
+            evdev::RelativeAxisCode::REL_DIAL.0,
+            value,
+        )
+    }
+
+    fn syn() -> InputEvent {
+        InputEvent::new(EventType::SYNCHRONIZATION.0, 0, 0)
+    }
+
+    fn key(code: KeyCode, value: i32) -> InputEvent {
+        InputEvent::new(EventType::KEY.0, code.0, value)
+    }
+
+    fn mouse_monitor(config: InputConfig) -> DeviceMonitor {
+        DeviceMonitor::new(DeviceType::Mouse, None, config)
+    }
+
+    fn keyboard_monitor(config: InputConfig) -> DeviceMonitor {
+        DeviceMonitor::new(DeviceType::Keyboard, None, config)
+    }
+
+    fn keyboard_report(reports: Reports) -> (u8, Vec<u8>) {
+        match reports.as_slice() {
+            [InputReport::Keyboard { modifiers, keys }] => (*modifiers, keys.clone()),
+            other => panic!("unexpected reports: {:?}", other),
+        }
+    }
+
+    fn mouse_move(x: i16) -> InputReport {
+        InputReport::Mouse {
+            buttons: 0,
+            x,
+            y: 0,
+            wheel: 0,
+        }
+    }
+
+    #[test]
+    fn test_full_channel_coalesces_mouse_keeps_keyboard() {
+        let (tx, mut rx) = mpsc::channel(2);
+        let mut sender = EventSender::new(tx);
+
+        // 容量为 2，后 8 个位移被合并为一个待发报告
+        for _ in 0..10 {
+            sender.send(mouse_move(1)).unwrap();
+        }
+
+        let receiver = std::thread::spawn(move || {
+            let mut received = Vec::new();
+            while let Some(timed) = rx.blocking_recv() {
+                received.push(timed.report);
+            }
+            received
+        });
+
+        sender
+            .send(InputReport::Keyboard {
+                modifiers: 0,
+                keys: vec![0x04],
+            })
+            .unwrap();
+        sender
+            .send(InputReport::Keyboard {
+                modifiers: 0,
+                keys: vec![],
+            })
+            .unwrap();
+        drop(sender);
+
+        let received = receiver.join().unwrap();
+        let moves: Vec<i16> = received
+            .iter()
+            .filter_map(|r| match r {
+                InputReport::Mouse { x, .. } => Some(*x),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(moves, vec![1, 1, 8]);
+        assert_eq!(moves.iter().sum::<i16>(), 10);
+
+        // 键盘按下与释放都送达，且排在合并后的鼠标报告之后
+        assert_eq!(received.len(), 5);
+        assert!(matches!(&received[3], InputReport::Keyboard { keys, .. } if keys == &vec![0x04]));
+        assert!(matches!(&received[4], InputReport::Keyboard { keys, .. } if keys.is_empty()));
+    }
+
+    #[test]
+    fn test_full_channel_counts_coalesced_and_dropped() {
+        static LOSS: ReportLoss = ReportLoss::new();
+        let (tx, rx) = mpsc::channel(2);
+        let mut sender = EventSender::with_loss(tx, &LOSS);
+
+        // 容量为 2，后 8 个位移合并为一个待发报告，其中 7 个计为合并
+        for _ in 0..10 {
+            sender.send(mouse_move(1)).unwrap();
+        }
+        assert_eq!(LOSS.coalesced(Stage::InputChannel, ReportKind::Mouse), 7);
+        assert_eq!(LOSS.dropped(Stage::InputChannel, ReportKind::Mouse), 0);
+
+        // 接收端关闭后待发的鼠标报告与之后的键盘报告都被丢弃
+        drop(rx);
+        let key = InputReport::Keyboard {
+            modifiers: 0,
+            keys: vec![0x04],
+        };
+        assert!(sender.send(key.clone()).is_err());
+        assert_eq!(LOSS.dropped(Stage::InputChannel, ReportKind::Mouse), 1);
+        assert!(sender.send(key).is_err());
+        assert_eq!(LOSS.dropped(Stage::InputChannel, ReportKind::Keyboard), 1);
+        assert!(LOSS.summary().contains("input_channel/keyboard dropped=1"));
+    }
+
+    #[test]
+    fn test_toggle_caps_to_ctrl_at_runtime() {
+        let config = InputConfig::default();
+        let remap = config.key_remap.clone();
+        let mut monitor = keyboard_monitor(config);
+
+        // 默认 Caps Lock 为普通键
+        let (modifiers, keys) =
+            keyboard_report(monitor.process_event(key(KeyCode::KEY_CAPSLOCK, 1)));
+        assert_eq!(modifiers, 0);
+        assert_eq!(keys, vec![0x39]);
+
+        // 按住期间切换，释放仍按原映射处理
+        remap.set_caps_to_ctrl(true);
+        let (modifiers, keys) =
+            keyboard_report(monitor.process_event(key(KeyCode::KEY_CAPSLOCK, 0)));
+        assert_eq!(modifiers, 0);
+        assert!(keys.is_empty());
+
+        // 之后的按下设置 Ctrl 位
+        let (modifiers, keys) =
+            keyboard_report(monitor.process_event(key(KeyCode::KEY_CAPSLOCK, 1)));
+        assert_eq!(modifiers, 0x01);
+        assert!(keys.is_empty());
+        let (modifiers, _) = keyboard_report(monitor.process_event(key(KeyCode::KEY_CAPSLOCK, 0)));
+        assert_eq!(modifiers, 0);
+    }
+
+    #[test]
+    fn test_scan_missing_dir_is_reported() {
+        let status = scan_event_nodes(std::path::Path::new("/nonexistent/input")).unwrap_err();
+        assert_eq!(status, ScanStatus::NotFound);
+
+        // 首次告警，短时间内重复的同一问题被限流，状态变化后再次告警
+        let mut warner = ScanWarner::default();
+        let now = Instant::now();
+        assert!(warner.should_warn(&status, now));
+        assert!(!warner.should_warn(&status, now + Duration::from_secs(1)));
+        assert!(warner.should_warn(&ScanStatus::Empty, now + Duration::from_secs(2)));
+        assert!(warner.should_warn(&ScanStatus::Empty, now + SCAN_WARN_INTERVAL * 2));
+        assert!(!warner.should_warn(&ScanStatus::Ok { event_nodes: 1 }, now));
+    }
+
+    #[test]
+    fn test_device_monitored_only_after_settle() {
+        let settle_time = Duration::from_millis(500);
+        let mut settle = DeviceSettle::new(settle_time);
+        let node = std::path::PathBuf::from("/dev/input/event3");
+        let t0 = Instant::now();
+
+        settle.observe(std::slice::from_ref(&node), t0);
+        assert!(!settle.is_settled(&node, t0));
+        assert_eq!(settle.next_due(t0), Some(settle_time));
+
+        let early = t0 + Duration::from_millis(300);
+        settle.observe(std::slice::from_ref(&node), early);
+        assert!(!settle.is_settled(&node, early));
+        assert_eq!(settle.next_due(early), Some(Duration::from_millis(200)));
+
+        let due = t0 + settle_time;
+        settle.observe(std::slice::from_ref(&node), due);
+        assert!(settle.is_settled(&node, due));
+        assert_eq!(settle.next_due(due), None);
+
+        // 节点消失后重新出现，重新计时
+        settle.observe(&[], due);
+        settle.observe(std::slice::from_ref(&node), due);
+        assert!(!settle.is_settled(&node, due));
+
+        // 不等待时立即可用
+        let mut immediate = DeviceSettle::new(Duration::ZERO);
+        immediate.observe(std::slice::from_ref(&node), t0);
+        assert!(immediate.is_settled(&node, t0));
+        assert_eq!(immediate.next_due(t0), None);
+    }
+
+    #[test]
+    fn test_scan_interval_adapts_to_device_changes() {
+        let config = ScanConfig {
+            interval_ms: 1000,
+            adaptive: true,
+            min_interval_ms: 100,
+            ..Default::default()
+        };
+        let ms = Duration::from_millis;
+        let mut interval = ScanInterval::new(&config);
+
+        assert_eq!(interval.next(false), ms(1000));
+        // 插入设备后缩短，之后空闲逐步退回最长间隔
+        assert_eq!(interval.next(true), ms(100));
+        assert_eq!(interval.next(false), ms(200));
+        assert_eq!(interval.next(false), ms(400));
+        assert_eq!(interval.next(false), ms(800));
+        assert_eq!(interval.next(false), ms(1000));
+        assert_eq!(interval.next(false), ms(1000));
+
+        let mut fixed = ScanInterval::new(&ScanConfig::default());
+        assert_eq!(fixed.next(true), ms(1000));
+    }
+
+    #[test]
+    fn test_unmapped_key_passthrough() {
+        // 默认忽略未映射的键，不会 panic
+        let mut monitor = keyboard_monitor(InputConfig::default());
+        assert!(monitor.process_event(key(KeyCode::KEY_PROG1, 1)).is_empty());
+
+        let mut monitor = keyboard_monitor(InputConfig {
+            raw_passthrough: true,
+            ..Default::default()
+        });
+        let reports = monitor.process_event(key(KeyCode::KEY_PROG1, 1));
+        assert_eq!(reports.len(), 1);
+        assert!(matches!(
+            reports[0],
+            InputReport::Vendor { code, pressed: true } if code == KeyCode::KEY_PROG1.code()
+        ));
+        let reports = monitor.process_event(key(KeyCode::KEY_PROG1, 0));
+        assert!(matches!(
+            reports[0],
+            InputReport::Vendor { pressed: false, .. }
+        ));
+
+        // 有映射的键不受影响
+        let (_, keys) = keyboard_report(monitor.process_event(key(KeyCode::KEY_A, 1)));
+        assert_eq!(keys, vec![0x04]);
+    }
+
+    #[test]
+    fn test_japanese_keys_map_to_international_usages() {
+        use crate::output::keycodes::{KEY_INTERNATIONAL1, KEY_INTERNATIONAL3};
+
+        let mut monitor = keyboard_monitor(InputConfig::default());
+        let (_, keys) = keyboard_report(monitor.process_event(key(KeyCode::KEY_YEN, 1)));
+        assert_eq!(keys, vec![KEY_INTERNATIONAL3]);
+        let (_, keys) = keyboard_report(monitor.process_event(key(KeyCode::KEY_RO, 1)));
+        assert_eq!(keys, vec![KEY_INTERNATIONAL3, KEY_INTERNATIONAL1]);
+
+        assert_eq!(evdev_to_hid(KeyCode::KEY_HENKAN), Some(0x8A));
+        assert_eq!(evdev_to_hid(KeyCode::KEY_MUHENKAN), Some(0x8B));
+        assert_eq!(evdev_to_hid(KeyCode::KEY_KATAKANAHIRAGANA), Some(0x88));
+        assert_eq!(evdev_to_hid(KeyCode::KEY_HANGEUL), Some(0x90));
+        assert_eq!(evdev_to_hid(KeyCode::KEY_HANJA), Some(0x91));
+    }
+
+    #[test]
+    fn test_swap_alt_meta_and_disable_super() {
+        let remap = KeyRemap::new();
+        remap.set_swap_alt_meta(true);
+        assert_eq!(remap.map(KeyCode::KEY_LEFTALT), Some(KeyCode::KEY_LEFTMETA));
+        assert_eq!(
+            remap.map(KeyCode::KEY_RIGHTMETA),
+            Some(KeyCode::KEY_RIGHTALT)
+        );
+
+        // 交换后再禁用 Super：物理 Alt 被禁用
+        remap.set_disable_super(true);
+        assert_eq!(remap.map(KeyCode::KEY_LEFTALT), None);
+        assert_eq!(remap.map(KeyCode::KEY_LEFTMETA), Some(KeyCode::KEY_LEFTALT));
+
+        remap.set_swap_alt_meta(false);
+        assert_eq!(remap.map(KeyCode::KEY_LEFTMETA), None);
+        assert_eq!(remap.map(KeyCode::KEY_A), Some(KeyCode::KEY_A));
+    }
+
+    #[test]
+    fn test_each_modifier_sets_and_clears_its_bit() {
+        let mut monitor = keyboard_monitor(InputConfig::default());
+        for (i, modifier) in Modifier::ALL.into_iter().enumerate() {
+            let bit = 1u8 << i;
+            assert_eq!(modifier_bit(modifier.key()), Some(bit));
+
+            let (modifiers, keys) = keyboard_report(monitor.process_event(key(modifier.key(), 1)));
+            assert_eq!((modifiers, keys), (bit, vec![]));
+            let (modifiers, _) = keyboard_report(monitor.process_event(key(modifier.key(), 0)));
+            assert_eq!(modifiers, 0);
+        }
+        assert_eq!(modifier_bit(KeyCode::KEY_A), None);
+    }
+
+    #[test]
+    fn test_sleep_key_sends_system_report() {
+        let mut monitor = keyboard_monitor(InputConfig::default());
+        let press = monitor.process_event(key(KeyCode::KEY_SLEEP, 1));
+        assert!(matches!(
+            press.as_slice(),
+            [InputReport::System {
+                usage: system::SLEEP
+            }]
+        ));
+        let release = monitor.process_event(key(KeyCode::KEY_SLEEP, 0));
+        assert!(matches!(
+            release.as_slice(),
+            [InputReport::System { usage: 0 }]
+        ));
+    }
+
+    #[test]
+    fn test_browser_keys_send_consumer_reports() {
+        let mut monitor = keyboard_monitor(InputConfig::default());
+        for (code, expected) in [
+            (KeyCode::KEY_BACK, consumer::AC_BACK),
+            (KeyCode::KEY_SEARCH, consumer::AC_SEARCH),
+        ] {
+            let press = monitor.process_event(key(code, 1));
+            assert!(
+                matches!(press.as_slice(), [InputReport::Consumer { usage }] if *usage == expected)
+            );
+            let release = monitor.process_event(key(code, 0));
+            assert!(matches!(
+                release.as_slice(),
+                [InputReport::Consumer { usage: 0 }]
+            ));
+        }
+        assert_eq!(consumer::AC_BACK, 0x0224);
+        assert_eq!(consumer::AC_SEARCH, 0x0221);
+    }
+
+    #[test]
+    fn test_keyboards_merge_into_one_state() {
+        let config = InputConfig::default();
+        let mut a = keyboard_monitor(config.clone());
+        let mut b = keyboard_monitor(config);
+
+        // A 按住 Shift，B 按下 A 键：B 的报告不会清掉 A 的 Shift
+        assert_eq!(
+            keyboard_report(a.process_event(key(KeyCode::KEY_LEFTSHIFT, 1))),
+            (0x02, vec![])
+        );
+        assert_eq!(
+            keyboard_report(b.process_event(key(KeyCode::KEY_A, 1))),
+            (0x02, vec![0x04])
+        );
+        assert_eq!(
+            keyboard_report(a.process_event(key(KeyCode::KEY_B, 1))),
+            (0x02, vec![0x05, 0x04])
+        );
+        assert_eq!(
+            keyboard_report(a.process_event(key(KeyCode::KEY_LEFTSHIFT, 0))),
+            (0, vec![0x05, 0x04])
+        );
+        // 拔掉 A 只释放 A 的键
+        assert!(matches!(
+            a.release_held().as_slice(),
+            [InputReport::Keyboard { modifiers: 0, keys }] if keys == &vec![0x04]
+        ));
+        assert_eq!(
+            keyboard_report(b.process_event(key(KeyCode::KEY_A, 0))),
+            (0, vec![])
+        );
+    }
+
+    #[test]
+    fn test_closed_channel_drops_merged_keys() {
+        let config = InputConfig::default();
+        let mut a = keyboard_monitor(config.clone());
+        let mut b = keyboard_monitor(config);
+        a.process_event(key(KeyCode::KEY_LEFTCTRL, 1));
+
+        // 通道关闭时 A 的监听任务直接退出，不会发送释放报告
+        let (tx, rx) = mpsc::channel(1);
+        drop(rx);
+        let mut source = MockSource(vec![Ok(vec![key(KeyCode::KEY_C, 1), syn()])].into());
+        a.fetch_loop(&mut source, &mut EventSender::new(tx));
+        assert!(source.0.is_empty());
+        drop(a);
+
+        assert_eq!(
+            keyboard_report(b.process_event(key(KeyCode::KEY_A, 1))),
+            (0, vec![0x04])
+        );
+    }
+
+    #[test]
+    fn test_apple_globe_key_sends_layout_select() {
+        // 默认关闭：Fn 没有 HID 键码，被忽略
+        let mut monitor = keyboard_monitor(InputConfig::default());
+        assert!(monitor.process_event(key(KeyCode::KEY_FN, 1)).is_empty());
+
+        let mut monitor = keyboard_monitor(InputConfig {
+            apple_keys: AppleKeys::On,
+            ..Default::default()
+        });
+        let press = monitor.process_event(key(KeyCode::KEY_FN, 1));
+        assert!(matches!(
+            press.as_slice(),
+            [InputReport::Consumer {
+                usage: consumer::AC_NEXT_KEYBOARD_LAYOUT
+            }]
+        ));
+        let release = monitor.process_event(key(KeyCode::KEY_FN, 0));
+        assert!(matches!(
+            release.as_slice(),
+            [InputReport::Consumer { usage: 0 }]
+        ));
+        // 顶排键发送亮度用法而不是 F1
+        let press = monitor.process_event(key(KeyCode::KEY_BRIGHTNESSDOWN, 1));
+        assert!(matches!(
+            press.as_slice(),
+            [InputReport::Consumer {
+                usage: consumer::DISPLAY_BRIGHTNESS_DOWN
+            }]
+        ));
+        assert_eq!(consumer::AC_NEXT_KEYBOARD_LAYOUT, 0x029D);
+
+        assert!(AppleKeys::Auto.enabled_for("Apple Inc. Magic Keyboard with Numeric Keypad"));
+        assert!(AppleKeys::Auto.enabled_for("Magic Keyboard"));
+        assert!(!AppleKeys::Auto.enabled_for("Logitech USB Keyboard"));
+    }
+
+    #[test]
+    fn test_removed_device_is_no_longer_skipped() {
+        let mut skipped: HashSet<String> = ["/dev/input/event3", "/dev/input/event5"]
+            .map(String::from)
+            .into();
+        forget_removed(
+            &mut skipped,
+            &["/dev/input/event5".into(), "/dev/input/event7".into()],
+        );
+        assert_eq!(skipped, ["/dev/input/event5".to_string()].into());
+    }
+
+    #[test]
+    fn test_dial_ignored_by_default() {
+        let mut monitor = mouse_monitor(InputConfig::default());
+        assert!(monitor.process_event(dial(1)).is_empty());
+        assert!(monitor.process_event(syn()).is_empty());
+    }
+
+    #[test]
+    fn test_dial_as_volume() {
+        let mut monitor = mouse_monitor(InputConfig {
+            dial_target: DialTarget::Volume,
+            ..Default::default()
+        });
+
+        let reports = monitor.process_event(dial(-1));
+        assert_eq!(reports.len(), 2);
+        assert!(matches!(
+            reports[0],
+            InputReport::Consumer {
+                usage: consumer::VOLUME_DOWN
+            }
+        ));
+        assert!(matches!(reports[1], InputReport::Consumer { usage: 0 }));
+        assert!(monitor.process_event(syn()).is_empty());
+
+        // 异常的大数值被截断
+        let reports = monitor.process_event(dial(i32::MAX));
+        assert_eq!(reports.len(), 2 * MAX_DIAL_STEPS as usize);
+    }
+
+    #[test]
+    fn test_dial_as_wheel() {
+        let mut monitor = mouse_monitor(InputConfig {
+            dial_target: DialTarget::Wheel,
+            ..Default::default()
+        });
+
+        assert!(monitor.process_event(dial(2)).is_empty());
+        let reports = monitor.process_event(syn());
+        assert_eq!(reports.len(), 1);
+        assert!(matches!(reports[0], InputReport::Mouse { wheel: 2, .. }));
+    }
+
+    #[test]
+    fn test_dead_zone_holds_jitter_until_exceeded() {
+        let mut monitor = mouse_monitor(InputConfig {
+            dead_zone: DeadZone(2),
+            ..Default::default()
+        });
+        let rel_x = |v| InputEvent::new(EventType::RELATIVE.0, evdev::RelativeAxisCode::REL_X.0, v);
+
+        // 抖动不产生报告
+        for v in [1, -1, 1, 1] {
+            monitor.process_event(rel_x(v));
+            assert!(monitor.process_event(syn()).is_empty());
+        }
+        // 累积 3 超出阈值，一次发出全部位移
+        monitor.process_event(rel_x(1));
+        assert!(matches!(
+            monitor.process_event(syn()).as_slice(),
+            [InputReport::Mouse { x: 3, y: 0, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_wheel_resolution_scales_detents() {
+        let wheel = |value| {
+            InputEvent::new(
+                EventType::RELATIVE.0,
+                evdev::RelativeAxisCode::REL_WHEEL.0,
+                value,
+            )
+        };
+        let wheel_units = |config: InputConfig, detents: &[i32]| -> Vec<i8> {
+            let mut monitor = mouse_monitor(config);
+            detents
+                .iter()
+                .flat_map(|&detent| {
+                    monitor.process_event(wheel(detent));
+                    monitor.process_event(syn())
+                })
+                .map(|report| match report {
+                    InputReport::Mouse { wheel, .. } => wheel,
+                    other => panic!("unexpected report: {:?}", other),
+                })
+                .collect()
+        };
+
+        let triple = InputConfig {
+            wheel_resolution: WheelResolution {
+                multiplier: 3,
+                divisor: 1,
+            },
+            ..Default::default()
+        };
+        assert_eq!(wheel_units(triple, &[1, 1, -1]), vec![3, 3, -3]);
+
+        // 两格输出一个单位，余量跨报告保留
+        let half = InputConfig {
+            wheel_resolution: WheelResolution {
+                multiplier: 1,
+                divisor: 2,
+            },
+            ..Default::default()
+        };
+        // 不足一个单位的那一格不产生空报告
+        assert_eq!(wheel_units(half, &[1, 1, 1, 1]), vec![1, 1]);
+        assert_eq!(wheel_units(InputConfig::default(), &[2]), vec![2]);
+    }
+
+    struct MockSource(std::collections::VecDeque<std::io::Result<Vec<InputEvent>>>);
+
+    impl EventSource for MockSource {
+        fn fetch(&mut self) -> std::io::Result<Vec<InputEvent>> {
+            self.0
+                .pop_front()
+                .unwrap_or_else(|| Err(std::io::Error::from_raw_os_error(libc::ENODEV)))
+        }
+    }
+
+    #[test]
+    fn test_fetch_loop_retries_transient_errors() {
+        let (tx, mut rx) = mpsc::channel(16);
+        let mut sender = EventSender::new(tx);
+        let mut source = MockSource(
+            vec![
+                Err(std::io::Error::from_raw_os_error(libc::EINTR)),
+                Err(std::io::ErrorKind::WouldBlock.into()),
+                Ok(vec![key(KeyCode::KEY_A, 1), syn()]),
+            ]
+            .into(),
+        );
+
+        // 暂时性错误后继续读取，设备移除（ENODEV）后退出
+        keyboard_monitor(InputConfig::default()).fetch_loop(&mut source, &mut sender);
+        assert!(source.0.is_empty());
+        let report = rx.try_recv().unwrap().report;
+        assert!(matches!(report, InputReport::Keyboard { ref keys, .. } if keys == &[0x04]));
+        // 设备移除时 A 仍按住，随后是释放报告
+        let report = rx.try_recv().unwrap().report;
+        assert!(matches!(report, InputReport::Keyboard { ref keys, .. } if keys.is_empty()));
+        assert!(rx.try_recv().is_err());
+    }
+
+    /// 带内核时间戳（毫秒）的按键事件
+    fn key_at(code: KeyCode, value: i32, ms: i64) -> InputEvent {
+        InputEvent::from(libc::input_event {
+            time: libc::timeval {
+                tv_sec: ms / 1000,
+                tv_usec: (ms % 1000) * 1000,
+            },
+            type_: EventType::KEY.0,
+            code: code.0,
+            value,
+        })
+    }
+
+    #[test]
+    fn test_key_debounce_drops_chatter_only() {
+        use crate::output::keycodes::{KEY_A as A, KEY_B as B, KEY_C as C};
+
+        let mut monitor = keyboard_monitor(InputConfig {
+            key_debounce: Some(Duration::from_millis(30)),
+            ..Default::default()
+        });
+        let mut feed = |events: &[(KeyCode, i32, i64)]| -> Vec<(u8, Vec<u8>)> {
+            events
+                .iter()
+                .flat_map(|&(code, value, ms)| monitor.process_event(key_at(code, value, ms)))
+                .map(|report| match report {
+                    InputReport::Keyboard { modifiers, keys } => (modifiers, keys),
+                    other => panic!("unexpected report: {:?}", other),
+                })
+                .collect()
+        };
+        // A 按下后抖动两次：只输出一次按下与松开
+        let reports = feed(&[
+            (KeyCode::KEY_A, 1, 1000),
+            (KeyCode::KEY_A, 0, 1040),
+            (KeyCode::KEY_A, 1, 1045),
+            (KeyCode::KEY_A, 0, 1050),
+            (KeyCode::KEY_A, 1, 1060),
+            (KeyCode::KEY_A, 2, 1065),
+            (KeyCode::KEY_A, 0, 1070),
+        ]);
+        assert_eq!(reports, [(0, vec![A]), (0, vec![])]);
+
+        // 超出窗口后再次按下照常输出
+        let reports = feed(&[(KeyCode::KEY_A, 1, 1200), (KeyCode::KEY_A, 0, 1210)]);
+        assert_eq!(reports, [(0, vec![A]), (0, vec![])]);
+
+        // 快速交替输入不同的键不受影响
+        let reports = feed(&[
+            (KeyCode::KEY_B, 1, 1300),
+            (KeyCode::KEY_B, 0, 1302),
+            (KeyCode::KEY_C, 1, 1304),
+            (KeyCode::KEY_C, 0, 1306),
+            (KeyCode::KEY_B, 1, 1350),
+            (KeyCode::KEY_C, 1, 1352),
+            (KeyCode::KEY_B, 0, 1354),
+            (KeyCode::KEY_C, 0, 1356),
+        ]);
+        assert_eq!(
+            reports,
+            [
+                (0, vec![B]),
+                (0, vec![]),
+                (0, vec![C]),
+                (0, vec![]),
+                (0, vec![B]),
+                (0, vec![B, C]),
+                (0, vec![C]),
+                (0, vec![]),
+            ]
+        );
+
+        // 默认关闭：同样的抖动全部输出
+        let mut monitor = keyboard_monitor(InputConfig::default());
+        let presses = [(1, 1000), (0, 1040), (1, 1045), (0, 1050)]
+            .iter()
+            .filter_map(|&(value, ms)| {
+                monitor.process_keyboard_event(key_at(KeyCode::KEY_A, value, ms))
+            })
+            .count();
+        assert_eq!(presses, 4);
+    }
+
+    /// 等待时取走通道中的报告，模拟主循环在设备空闲期间消费
+    struct DrainingSource {
+        script: std::collections::VecDeque<Vec<InputEvent>>,
+        rx: mpsc::Receiver<TimedReport>,
+        received: Vec<InputReport>,
+    }
+
+    impl DrainingSource {
+        fn drain(&mut self) {
+            while let Ok(timed) = self.rx.try_recv() {
+                self.received.push(timed.report);
+            }
+        }
+    }
+
+    impl EventSource for DrainingSource {
+        fn fetch(&mut self) -> std::io::Result<Vec<InputEvent>> {
+            self.script
+                .pop_front()
+                .ok_or_else(|| std::io::Error::from_raw_os_error(libc::ENODEV))
+        }
+
+        fn wait(&mut self, _timeout: Duration) -> std::io::Result<bool> {
+            self.drain();
+            Ok(!self.script.is_empty())
+        }
+    }
+
+    #[test]
+    fn test_pending_mouse_flushed_when_device_idle() {
+        let (tx, rx) = mpsc::channel(1);
+        let rel_x = |v| InputEvent::new(EventType::RELATIVE.0, evdev::RelativeAxisCode::REL_X.0, v);
+        let mut source = DrainingSource {
+            script: [vec![rel_x(1), syn(), rel_x(2), syn(), rel_x(3), syn()]].into(),
+            rx,
+            received: Vec::new(),
+        };
+
+        mouse_monitor(InputConfig::default()).fetch_loop(&mut source, &mut EventSender::new(tx));
+        source.drain();
+        let moved: i32 = source
+            .received
+            .iter()
+            .map(|r| match r {
+                InputReport::Mouse { x, .. } => *x as i32,
+                _ => 0,
+            })
+            .sum();
+        assert_eq!(moved, 6);
+    }
+
+    /// `None` 表示一次空闲超时，`physical` 为设备报告的实际按键状态
+    struct IdleSource {
+        script: std::collections::VecDeque<Option<Vec<InputEvent>>>,
+        physical: Vec<u16>,
+        waits: Vec<Duration>,
+    }
+
+    impl EventSource for IdleSource {
+        fn fetch(&mut self) -> std::io::Result<Vec<InputEvent>> {
+            match self.script.pop_front() {
+                Some(Some(events)) => Ok(events),
+                _ => Err(std::io::Error::from_raw_os_error(libc::ENODEV)),
+            }
+        }
+
+        fn wait(&mut self, timeout: Duration) -> std::io::Result<bool> {
+            self.waits.push(timeout);
+            if matches!(self.script.front(), Some(None)) {
+                self.script.pop_front();
+                return Ok(false);
+            }
+            Ok(true)
+        }
+
+        fn held_keys(&mut self) -> std::io::Result<Option<Vec<u16>>> {
+            Ok(Some(self.physical.clone()))
+        }
+    }
+
+    #[test]
+    fn test_stuck_modifier_released_after_idle() {
+        let idle = Duration::from_millis(500);
+        let run = |physical: Vec<u16>| {
+            let (tx, mut rx) = mpsc::channel(16);
+            let mut sender = EventSender::new(tx);
+            let mut source = IdleSource {
+                script: vec![Some(vec![key(KeyCode::KEY_LEFTCTRL, 1), syn()]), None].into(),
+                physical,
+                waits: vec![],
+            };
+            keyboard_monitor(InputConfig {
+                modifier_watchdog: Some(idle),
+                ..Default::default()
+            })
+            .fetch_loop(&mut source, &mut sender);
+            let mut modifiers = vec![];
+            while let Ok(timed) = rx.try_recv() {
+                match timed.report {
+                    InputReport::Keyboard { modifiers: m, .. } => modifiers.push(m),
+                    other => panic!("unexpected report: {:?}", other),
+                }
+            }
+            (modifiers, source.waits)
+        };
+
+        // 设备已松开 Ctrl 但松开事件丢失：空闲超时后补发释放
+        let (modifiers, waits) = run(vec![]);
+        assert_eq!(modifiers, [0x01, 0]);
+        assert!(!waits.is_empty() && waits.iter().all(|w| *w == idle));
+
+        // Ctrl 确实仍按住：重发当前状态，设备移除时再释放
+        let (modifiers, _) = run(vec![KeyCode::KEY_LEFTCTRL.code()]);
+        assert_eq!(modifiers, [0x01, 0x01, 0]);
+
+        // 未按住修饰键时不等待
+        let (tx, _rx) = mpsc::channel(16);
+        let mut source = IdleSource {
+            script: vec![Some(vec![key(KeyCode::KEY_A, 1), syn()])].into(),
+            physical: vec![],
+            waits: vec![],
+        };
+        keyboard_monitor(InputConfig {
+            modifier_watchdog: Some(idle),
+            ..Default::default()
+        })
+        .fetch_loop(&mut source, &mut EventSender::new(tx));
+        assert!(source.waits.is_empty());
+    }
+
+    #[test]
+    fn test_device_removal_releases_held_modifier() {
+        let (tx, mut rx) = mpsc::channel(16);
+        let mut sender = EventSender::new(tx);
+        let mut source = MockSource(vec![Ok(vec![key(KeyCode::KEY_LEFTCTRL, 1), syn()])].into());
+
+        keyboard_monitor(InputConfig::default()).fetch_loop(&mut source, &mut sender);
+        let held = rx.try_recv().unwrap().report;
+        assert!(matches!(
+            held,
+            InputReport::Keyboard {
+                modifiers: 0x01,
+                ..
+            }
+        ));
+        let released = rx.try_recv().unwrap().report;
+        assert!(matches!(
+            released,
+            InputReport::Keyboard { modifiers: 0, ref keys } if keys.is_empty()
+        ));
+        assert!(rx.try_recv().is_err());
+
+        // 没有按住任何键时不额外发送
+        let (tx, mut rx) = mpsc::channel(16);
+        let mut sender = EventSender::new(tx);
+        let mut source = MockSource(
+            vec![Ok(vec![
+                key(KeyCode::KEY_LEFTCTRL, 1),
+                key(KeyCode::KEY_LEFTCTRL, 0),
+            ])]
+            .into(),
+        );
+        keyboard_monitor(InputConfig::default()).fetch_loop(&mut source, &mut sender);
+        assert_eq!(std::iter::from_fn(|| rx.try_recv().ok()).count(), 2);
+    }
+
+    #[test]
+    fn test_transform_chain_runs_before_enqueue() {
+        use transform::ReportTransform;
+
+        /// 把 A 键换成 Esc 的共享变换
+        struct AToEsc;
+        impl ReportTransform for AToEsc {
+            fn transform(&mut self, report: InputReport) -> Reports {
+                match report {
+                    InputReport::Keyboard { modifiers, keys } => {
+                        smallvec::smallvec![InputReport::Keyboard {
+                            modifiers,
+                            keys: keys
+                                .into_iter()
+                                .map(|key| if key == 0x04 { 0x29 } else { key })
+                                .collect(),
+                        }]
+                    }
+                    other => smallvec::smallvec![other],
+                }
+            }
+        }
+
+        let (tx, mut rx) = mpsc::channel(16);
+        let mut sender = EventSender::new(tx);
+        let mut source = MockSource(
+            vec![Ok(vec![
+                key(KeyCode::KEY_CAPSLOCK, 1),
+                key(KeyCode::KEY_A, 1),
+                syn(),
+            ])]
+            .into(),
+        );
+        let config = InputConfig::default();
+        config.key_remap.set_caps_to_ctrl(true);
+        config.transforms.push(AToEsc);
+
+        // 内置的 Caps Lock -> Ctrl 之后再执行共享变换
+        keyboard_monitor(config).fetch_loop(&mut source, &mut sender);
+        let report = rx.try_recv().unwrap().report;
+        assert!(
+            matches!(report, InputReport::Keyboard { modifiers: 0x01, ref keys } if keys.is_empty())
+        );
+        let report = rx.try_recv().unwrap().report;
+        assert!(
+            matches!(report, InputReport::Keyboard { modifiers: 0x01, ref keys } if keys == &[0x29])
+        );
+    }
+
+    #[test]
+    fn test_mouse_sensitivity_keeps_remainder() {
+        let sensitivity = MouseSensitivity::new(50);
+        let mut monitor = mouse_monitor(InputConfig {
+            sensitivity: sensitivity.clone(),
+            ..Default::default()
+        });
+        let rel_x = |v| InputEvent::new(EventType::RELATIVE.0, evdev::RelativeAxisCode::REL_X.0, v);
+
+        // 每次移动 1，减半后两次合计 1
+        let mut total = 0;
+        for _ in 0..4 {
+            monitor.process_event(rel_x(1));
+            if let [InputReport::Mouse { x, .. }] = monitor.process_event(syn()).as_slice() {
+                total += *x;
+            }
+        }
+        assert_eq!(total, 2);
+
+        // 运行时调整立即生效
+        sensitivity.set(200);
+        monitor.process_event(rel_x(3));
+        assert!(matches!(
+            monitor.process_event(syn()).as_slice(),
+            [InputReport::Mouse { x: 6, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_mouse_axes_applied_to_report() {
+        let mut monitor = mouse_monitor(InputConfig {
+            axes: AxisTransform {
+                invert_x: true,
+                swap_xy: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let rel =
+            |code: evdev::RelativeAxisCode, v| InputEvent::new(EventType::RELATIVE.0, code.0, v);
+
+        monitor.process_event(rel(evdev::RelativeAxisCode::REL_X, 3));
+        monitor.process_event(rel(evdev::RelativeAxisCode::REL_Y, -4));
+        assert!(matches!(
+            monitor.process_event(syn()).as_slice(),
+            [InputReport::Mouse { x: 4, y: 3, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_msc_noise_does_not_emit_report() {
+        let msc_scan = |v| InputEvent::new(EventType::MISC.0, evdev::MiscCode::MSC_SCAN.0, v);
+        let rel_x = |v| InputEvent::new(EventType::RELATIVE.0, evdev::RelativeAxisCode::REL_X.0, v);
+        let mut monitor = mouse_monitor(InputConfig::default());
+
+        assert!(monitor.process_event(msc_scan(0x90001)).is_empty());
+        assert!(monitor.process_event(syn()).is_empty());
+
+        // 真实的点击与移动之后，只有 MSC 的帧不会重复上一个报告
+        monitor.process_event(msc_scan(0x90001));
+        monitor.process_event(key(KeyCode::BTN_LEFT, 1));
+        assert_eq!(monitor.process_event(syn()).len(), 1);
+        monitor.process_event(rel_x(2));
+        assert_eq!(monitor.process_event(syn()).len(), 1);
+        monitor.process_event(msc_scan(0x90001));
+        assert!(monitor.process_event(syn()).is_empty());
+
+        // 键盘同样忽略 MSC
+        let mut keyboard = keyboard_monitor(InputConfig::default());
+        assert!(keyboard.process_event(msc_scan(0x70004)).is_empty());
+        assert!(keyboard.process_event(syn()).is_empty());
+    }
+
+    #[test]
+    fn test_mouse_button_swap() {
+        let mut monitor = mouse_monitor(InputConfig {
+            button_map: MouseButtonMap {
+                left: Some(ButtonAction::Button(0x02)),
+                right: Some(ButtonAction::Button(0x01)),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        assert!(monitor.process_event(key(KeyCode::BTN_LEFT, 1)).is_empty());
+        let reports = monitor.process_event(syn());
+        assert!(matches!(
+            reports[0],
+            InputReport::Mouse { buttons: 0x02, .. }
+        ));
+
+        monitor.process_event(key(KeyCode::BTN_LEFT, 0));
+        let reports = monitor.process_event(syn());
+        assert!(matches!(reports[0], InputReport::Mouse { buttons: 0, .. }));
+    }
+
+    #[test]
+    fn test_back_forward_alias_side_buttons() {
+        let mut monitor = mouse_monitor(InputConfig::default());
+
+        monitor.process_event(key(KeyCode::BTN_BACK, 1));
+        let reports = monitor.process_event(syn());
+        assert!(matches!(
+            reports[0],
+            InputReport::Mouse { buttons: 0x08, .. }
+        ));
+
+        monitor.process_event(key(KeyCode::BTN_FORWARD, 1));
+        let reports = monitor.process_event(syn());
+        assert!(matches!(
+            reports[0],
+            InputReport::Mouse { buttons: 0x18, .. }
+        ));
+
+        monitor.process_event(key(KeyCode::BTN_BACK, 0));
+        monitor.process_event(key(KeyCode::BTN_FORWARD, 0));
+        let reports = monitor.process_event(syn());
+        assert!(matches!(reports[0], InputReport::Mouse { buttons: 0, .. }));
+
+        // 未知按键被忽略，不产生报告
+        assert!(monitor.process_event(key(KeyCode::BTN_TASK, 1)).is_empty());
+        assert!(monitor.process_event(syn()).is_empty());
+    }
+
+    #[test]
+    fn test_mouse_button_to_chord() {
+        use crate::output::keycodes::KEY_C;
+
+        let mut monitor = mouse_monitor(InputConfig {
+            button_map: MouseButtonMap {
+                side: Some(ButtonAction::Chord {
+                    modifiers: 0x01,
+                    keys: vec![KEY_C],
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        let down = monitor.process_event(key(KeyCode::BTN_SIDE, 1));
+        assert_eq!(keyboard_report(down), (0x01, vec![KEY_C]));
+        // 鼠标按键状态不受影响
+        assert!(monitor.process_event(syn()).is_empty());
+
+        assert!(monitor.process_event(key(KeyCode::BTN_SIDE, 2)).is_empty());
+        let up = monitor.process_event(key(KeyCode::BTN_SIDE, 0));
+        assert_eq!(keyboard_report(up), (0, vec![]));
+    }
+
+    #[test]
+    fn test_mouse_chord_merges_with_held_keyboard() {
+        use crate::output::keycodes::KEY_C;
+
+        let config = InputConfig {
+            button_map: MouseButtonMap {
+                side: Some(ButtonAction::Chord {
+                    modifiers: 0x08,
+                    keys: vec![KEY_C],
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut keyboard = keyboard_monitor(config.clone());
+        let mut mouse = mouse_monitor(config);
+
+        // 键盘按住 Ctrl 时，鼠标组合键的按下与释放都保留 Ctrl
+        keyboard.process_event(key(KeyCode::KEY_LEFTCTRL, 1));
+        let down = mouse.process_event(key(KeyCode::BTN_SIDE, 1));
+        assert_eq!(keyboard_report(down), (0x09, vec![KEY_C]));
+        let up = mouse.process_event(key(KeyCode::BTN_SIDE, 0));
+        assert_eq!(keyboard_report(up), (0x01, vec![]));
+
+        let release = keyboard.process_event(key(KeyCode::KEY_LEFTCTRL, 0));
+        assert_eq!(keyboard_report(release), (0, vec![]));
+    }
+
+    #[tokio::test]
+    async fn test_injected_report_reaches_next_event() {
+        let mut manager = InputManager::with_config(
+            0,
+            InputConfig {
+                scan: ScanConfig {
+                    enabled: false,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        let report = InputReport::Keyboard {
+            modifiers: 0x02,
+            keys: vec![0x04],
+        };
+        manager.injector().inject(report).await.unwrap();
+
+        match manager.next_event().await {
+            Some(InputReport::Keyboard { modifiers, keys }) => {
+                assert_eq!(modifiers, 0x02);
+                assert_eq!(keys, vec![0x04]);
+            }
+            other => panic!("unexpected report: {:?}", other),
+        }
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_input_manager() {
@@ -842,6 +3443,24 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_led_subscriber_sees_updates() {
+        let handle = LedHandle::new();
+        let mut leds = handle.subscribe();
+        assert_eq!(*leds.borrow(), LedState::default());
+
+        let caps = LedState {
+            caps_lock: true,
+            ..Default::default()
+        };
+        handle.set_leds(&caps).await;
+        leds.changed().await.unwrap();
+        assert_eq!(*leds.borrow_and_update(), caps);
+
+        // 之后订阅的也能看到当前状态
+        assert_eq!(*handle.subscribe().borrow(), caps);
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_set_all_leds() {
@@ -0,0 +1,219 @@
+//! 原始 evdev 事件的录制与回放
+//!
+//! 录制文件为 JSON Lines：第一行是文件头（设备名与类型），其后每行一个事件。
+//! 回放时把事件重新构造成 `InputEvent`，送入与实时处理相同的 `DeviceMonitor`。
+
+use super::{DeviceMonitor, DeviceType, InputConfig, InputManager, InputReport};
+use anyhow::{Context, Result, bail};
+use evdev::{Device, InputEvent};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// 录制文件头
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    name: String,
+    device_type: String,
+}
+
+/// 单个录制事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    pub sec: i64,
+    pub usec: i64,
+    #[serde(rename = "type")]
+    pub type_: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+impl From<&InputEvent> for RecordedEvent {
+    fn from(event: &InputEvent) -> Self {
+        let since_epoch = event
+            .timestamp()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Self {
+            sec: since_epoch.as_secs() as i64,
+            usec: since_epoch.subsec_micros() as i64,
+            type_: event.event_type().0,
+            code: event.code(),
+            value: event.value(),
+        }
+    }
+}
+
+impl From<RecordedEvent> for InputEvent {
+    fn from(event: RecordedEvent) -> Self {
+        InputEvent::from(libc::input_event {
+            time: libc::timeval {
+                tv_sec: event.sec as libc::time_t,
+                tv_usec: event.usec as libc::suseconds_t,
+            },
+            type_: event.type_,
+            code: event.code,
+            value: event.value,
+        })
+    }
+}
+
+fn device_type_name(device_type: &DeviceType) -> &'static str {
+    match device_type {
+        DeviceType::Keyboard => "keyboard",
+        DeviceType::Mouse => "mouse",
+    }
+}
+
+/// 录制写入器
+pub struct Recorder<W: Write> {
+    out: W,
+}
+
+impl<W: Write> Recorder<W> {
+    /// 写入文件头并创建录制器
+    pub fn new(mut out: W, name: &str, device_type: &DeviceType) -> Result<Self> {
+        let header = Header {
+            name: name.to_string(),
+            device_type: device_type_name(device_type).to_string(),
+        };
+        serde_json::to_writer(&mut out, &header)?;
+        out.write_all(b"\n")?;
+        Ok(Self { out })
+    }
+
+    pub fn record(&mut self, event: &InputEvent) -> Result<()> {
+        serde_json::to_writer(&mut self.out, &RecordedEvent::from(event))?;
+        self.out.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// 读取录制内容，返回设备类型与事件序列
+pub fn read_recording<R: BufRead>(reader: R) -> Result<(DeviceType, Vec<InputEvent>)> {
+    let mut lines = reader.lines();
+    let header_line = lines.next().context("录制文件为空")??;
+    let header: Header = serde_json::from_str(&header_line).context("解析录制文件头失败")?;
+    let device_type = match header.device_type.as_str() {
+        "keyboard" => DeviceType::Keyboard,
+        "mouse" => DeviceType::Mouse,
+        other => bail!("未知的设备类型: {}", other),
+    };
+
+    let mut events = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event: RecordedEvent =
+            serde_json::from_str(&line).with_context(|| format!("解析第 {} 个事件失败", i + 1))?;
+        events.push(event.into());
+    }
+    Ok((device_type, events))
+}
+
+/// 将事件序列送入 `DeviceMonitor`，返回产生的报告
+pub fn replay(
+    device_type: DeviceType,
+    events: &[InputEvent],
+    config: InputConfig,
+) -> Vec<InputReport> {
+    let mut monitor = DeviceMonitor::new(device_type, None, config);
+    events
+        .iter()
+        .flat_map(|event| monitor.process_event(*event))
+        .collect()
+}
+
+/// 录制指定设备的原始事件到文件，直到读取出错（如拔出设备）
+pub fn record_device(device_path: &Path, out_path: &Path) -> Result<()> {
+    let mut device = Device::open(device_path)
+        .with_context(|| format!("打开设备失败: {}", device_path.display()))?;
+    let device_type = InputManager::detect_device_type(&device)
+        .with_context(|| format!("无法识别设备类型: {}", device_path.display()))?;
+    let name = device.name().unwrap_or("Unknown").to_string();
+
+    let file = File::create(out_path)
+        .with_context(|| format!("创建录制文件失败: {}", out_path.display()))?;
+    let mut recorder = Recorder::new(BufWriter::new(file), &name, &device_type)?;
+    info!(
+        "开始录制 {} ({}) -> {}",
+        name,
+        device_type_name(&device_type),
+        out_path.display()
+    );
+
+    loop {
+        let events = match device.fetch_events() {
+            Ok(events) => events,
+            Err(e) => {
+                warn!("读取事件结束: {}", e);
+                break;
+            }
+        };
+        for event in events {
+            recorder.record(&event)?;
+        }
+        recorder.flush()?;
+    }
+    recorder.flush()
+}
+
+/// 回放录制文件并打印产生的报告
+pub fn replay_file(in_path: &Path) -> Result<Vec<InputReport>> {
+    let file =
+        File::open(in_path).with_context(|| format!("打开录制文件失败: {}", in_path.display()))?;
+    let (device_type, events) = read_recording(BufReader::new(file))?;
+    info!(
+        "回放 {} 个事件 ({})",
+        events.len(),
+        device_type_name(&device_type)
+    );
+    Ok(replay(device_type, &events, InputConfig::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evdev::{EventType, KeyCode};
+
+    #[test]
+    fn test_round_trip_matches_live_processing() {
+        let events = [
+            InputEvent::new_now(EventType::KEY.0, KeyCode::KEY_LEFTSHIFT.0, 1),
+            InputEvent::new_now(EventType::KEY.0, KeyCode::KEY_A.0, 1),
+            InputEvent::new_now(EventType::KEY.0, KeyCode::KEY_A.0, 2),
+            InputEvent::new_now(EventType::KEY.0, KeyCode::KEY_A.0, 0),
+            InputEvent::new_now(EventType::KEY.0, KeyCode::KEY_LEFTSHIFT.0, 0),
+        ];
+
+        let mut buf = Vec::new();
+        {
+            let mut recorder = Recorder::new(&mut buf, "test", &DeviceType::Keyboard).unwrap();
+            for event in &events {
+                recorder.record(event).unwrap();
+            }
+        }
+
+        let (device_type, replayed) = read_recording(buf.as_slice()).unwrap();
+        assert_eq!(device_type, DeviceType::Keyboard);
+        assert_eq!(replayed.len(), events.len());
+        for (original, restored) in events.iter().zip(&replayed) {
+            assert_eq!(RecordedEvent::from(original), RecordedEvent::from(restored));
+        }
+
+        let live = replay(DeviceType::Keyboard, &events, InputConfig::default());
+        let from_file = replay(device_type, &replayed, InputConfig::default());
+        assert_eq!(live.len(), 4);
+        assert_eq!(format!("{:?}", live), format!("{:?}", from_file));
+    }
+}
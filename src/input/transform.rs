@@ -0,0 +1,217 @@
+//! 报告变换链
+//!
+//! 重映射、坐标轴变换等功能都是对报告流的变换。每个功能实现 [`ReportTransform`]，
+//! 由设备监听任务在键盘报告合并、报告入队之前依次执行，不必再把新功能写进 `process_event`。
+
+use super::{InputReport, KeyRemap, Reports};
+use crate::output::mouse::AxisTransform;
+use smallvec::smallvec;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// 对单个报告的变换，可以输出零个、一个或多个报告
+pub trait ReportTransform: Send {
+    fn transform(&mut self, report: InputReport) -> Reports;
+}
+
+impl ReportTransform for AxisTransform {
+    fn transform(&mut self, report: InputReport) -> Reports {
+        smallvec![self.apply_report(report)]
+    }
+}
+
+/// 以键盘报告为单位执行 [`KeyRemap`]
+///
+/// 每个修饰键与按键在报告中出现时按当时的开关映射并记住结果，消失前沿用，
+/// 运行时切换开关不会造成卡键。
+pub struct RemapTransform {
+    remap: KeyRemap,
+    /// 按住中的键码及其出现时的映射结果（`None` 表示被禁用）
+    held: HashMap<u8, Option<u8>>,
+}
+
+impl RemapTransform {
+    pub fn new(remap: KeyRemap) -> Self {
+        Self {
+            remap,
+            held: HashMap::new(),
+        }
+    }
+}
+
+impl ReportTransform for RemapTransform {
+    fn transform(&mut self, report: InputReport) -> Reports {
+        let InputReport::Keyboard { modifiers, keys } = report else {
+            return smallvec![report];
+        };
+        // 修饰键位按键码 0xE0..=0xE7 与普通键一起处理
+        let usages: Vec<u8> = (0..8)
+            .filter(|bit| modifiers & (1 << bit) != 0)
+            .map(|bit| 0xE0 + bit)
+            .chain(keys)
+            .collect();
+        self.held.retain(|usage, _| usages.contains(usage));
+
+        let mut modifiers = 0;
+        let mut keys = Vec::new();
+        for usage in usages {
+            let remap = &self.remap;
+            let mapped = *self
+                .held
+                .entry(usage)
+                .or_insert_with(|| remap.map_usage(usage));
+            match mapped {
+                Some(mapped @ 0xE0..=0xE7) => modifiers |= 1 << (mapped - 0xE0),
+                Some(mapped) if !keys.contains(&mapped) => keys.push(mapped),
+                _ => {}
+            }
+        }
+        smallvec![InputReport::Keyboard { modifiers, keys }]
+    }
+}
+
+/// 按注册顺序执行的变换链
+///
+/// 前一个变换的每个输出依次交给下一个变换。链为空时报告原样通过。
+/// 克隆的链共享同一组变换，链本身也可以作为另一条链中的一个变换。
+#[derive(Clone, Default)]
+pub struct TransformChain {
+    transforms: Arc<Mutex<Vec<Box<dyn ReportTransform>>>>,
+}
+
+impl std::fmt::Debug for TransformChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransformChain")
+            .field("len", &self.transforms.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl TransformChain {
+    /// 在链尾追加一个变换
+    pub fn push(&self, transform: impl ReportTransform + 'static) {
+        self.transforms.lock().unwrap().push(Box::new(transform));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.lock().unwrap().is_empty()
+    }
+
+    /// 依次执行所有变换
+    pub fn apply(&self, report: InputReport) -> Reports {
+        let mut transforms = self.transforms.lock().unwrap();
+        let mut reports: Reports = smallvec![report];
+        for transform in transforms.iter_mut() {
+            reports = reports
+                .into_iter()
+                .flat_map(|report| transform.transform(report))
+                .collect();
+        }
+        reports
+    }
+}
+
+impl ReportTransform for TransformChain {
+    fn transform(&mut self, report: InputReport) -> Reports {
+        self.apply(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyboard(modifiers: u8, keys: &[u8]) -> InputReport {
+        InputReport::Keyboard {
+            modifiers,
+            keys: keys.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_chain_applies_remap_then_inversion() {
+        let remap = KeyRemap::new();
+        remap.set_caps_to_ctrl(true);
+        let chain = TransformChain::default();
+        chain.push(RemapTransform::new(remap));
+        chain.push(AxisTransform {
+            invert_x: true,
+            ..Default::default()
+        });
+
+        // Caps Lock 变为左 Ctrl，其他键不变
+        let reports = chain.apply(keyboard(0x02, &[0x39, 0x06]));
+        assert!(matches!(
+            reports.as_slice(),
+            [InputReport::Keyboard { modifiers: 0x03, keys }] if keys == &vec![0x06]
+        ));
+
+        let mouse = chain.apply(InputReport::Mouse {
+            buttons: 0x01,
+            x: 7,
+            y: -3,
+            wheel: 0,
+        });
+        assert!(matches!(
+            mouse.as_slice(),
+            [InputReport::Mouse {
+                buttons: 0x01,
+                x: -7,
+                y: -3,
+                wheel: 0
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_remap_keeps_mapping_until_release() {
+        let remap = KeyRemap::new();
+        let mut transform = RemapTransform::new(remap.clone());
+        let apply =
+            |transform: &mut RemapTransform, report| match transform.transform(report).as_slice() {
+                [InputReport::Keyboard { modifiers, keys }] => (*modifiers, keys.clone()),
+                other => panic!("unexpected reports: {:?}", other),
+            };
+
+        // 按住左 Alt 期间开启交换，仍按 Alt 输出；新按下的右 Alt 按 Meta 输出
+        assert_eq!(apply(&mut transform, keyboard(0x04, &[])), (0x04, vec![]));
+        remap.set_swap_alt_meta(true);
+        assert_eq!(apply(&mut transform, keyboard(0x44, &[])), (0x84, vec![]));
+        assert_eq!(apply(&mut transform, keyboard(0x00, &[])), (0x00, vec![]));
+        assert_eq!(apply(&mut transform, keyboard(0x04, &[])), (0x08, vec![]));
+
+        // 禁用 Super 后，交换为 Meta 的左 Alt 不再输出，左 Meta 按 Alt 输出
+        remap.set_disable_super(true);
+        assert_eq!(apply(&mut transform, keyboard(0x00, &[])), (0x00, vec![]));
+        assert_eq!(
+            apply(&mut transform, keyboard(0x0C, &[0x04])),
+            (0x04, vec![0x04])
+        );
+    }
+
+    #[test]
+    fn test_transform_may_drop_or_split() {
+        struct DropMouse;
+        impl ReportTransform for DropMouse {
+            fn transform(&mut self, report: InputReport) -> Reports {
+                match report {
+                    InputReport::Mouse { .. } => Reports::new(),
+                    other => smallvec![other.clone(), other],
+                }
+            }
+        }
+
+        let chain = TransformChain::default();
+        assert!(chain.is_empty());
+        chain.push(DropMouse);
+        chain.push(RemapTransform::new(KeyRemap::new()));
+        let mouse = InputReport::Mouse {
+            buttons: 0,
+            x: 1,
+            y: 0,
+            wheel: 0,
+        };
+        assert!(chain.apply(mouse).is_empty());
+        assert_eq!(chain.apply(keyboard(0, &[0x04])).len(), 2);
+    }
+}
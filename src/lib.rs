@@ -1,5 +1,11 @@
+pub mod audit;
+pub mod config;
 pub mod core;
 pub mod input;
 pub mod logging;
+pub mod metrics;
 pub mod output;
+pub mod preview;
+pub mod selftest;
+pub mod state;
 pub mod web;
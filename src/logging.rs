@@ -1,5 +1,6 @@
 pub fn init() {
     // 默认 info，可用 RUST_LOG 覆盖（例如 debug/trace）
+    // 查看每个发出的报告字节：RUST_LOG=info,bridge_hid::report=trace
     let mut builder =
         env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"));
 
@@ -1,21 +1,69 @@
+use bridge_hid::config::Config;
 use bridge_hid::core;
+use bridge_hid::input::recording;
 use bridge_hid::logging::init;
+use bridge_hid::output::host_prefs::HostPrefs;
+use bridge_hid::selftest;
+use bridge_hid::state::StateBundle;
 use bridge_hid::web;
 use clap::{Parser, ValueEnum};
-use log::{debug, info};
+use log::{debug, info, warn};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Args {
-    /// 运行模式: switcher | web-touchpad
+    /// 运行模式: switcher | web-touchpad | switcher-web | record | replay-evdev
     #[arg(long, value_enum, default_value = "switcher")]
     mode: Mode,
+
+    /// 配置文件路径，默认 $XDG_CONFIG_HOME/bridge-hid/config.json 或 /etc/bridge-hid/config.json
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// record 模式：要录制的设备，如 /dev/input/event3
+    #[arg(long, required_if_eq("mode", "record"))]
+    device: Option<PathBuf>,
+
+    /// record 模式：录制输出文件
+    #[arg(long, required_if_eq("mode", "record"))]
+    out: Option<PathBuf>,
+
+    /// replay-evdev 模式：录制文件
+    #[arg(long = "in", required_if_eq("mode", "replay-evdev"))]
+    input: Option<PathBuf>,
+
+    /// switcher 模式：启动时的输出（usb | ble）
+    #[arg(long, default_value = "usb")]
+    initial_mode: core::OutputMode,
+
+    /// switcher 模式：初始输出的鼠标报告率（Hz），默认使用配置方案中的值
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=1000))]
+    mouse_rate: Option<u32>,
+
+    /// 导出配置、方案与 BLE 主机偏好到文件后退出，用于迁移。
+    /// 不包含输出，运行中的输出通过 `GET /api/state` 导出
+    #[arg(long, conflicts_with = "import_state")]
+    export_state: Option<PathBuf>,
+
+    /// 从导出的文件恢复状态：写入配置文件与主机偏好，文件中有输出时以该输出启动
+    #[arg(long)]
+    import_state: Option<PathBuf>,
+
+    /// 检查 UDC、蓝牙适配器与输入设备后退出，失败时返回非零状态
+    #[arg(long)]
+    selftest: bool,
 }
 
 #[derive(Copy, Clone, Debug, ValueEnum)]
 enum Mode {
     Switcher,
     WebTouchpad,
+    /// 切换器与网页触控板同时运行，共用一组输出后端
+    SwitcherWeb,
+    Record,
+    ReplayEvdev,
 }
 
 #[tokio::main(flavor = "multi_thread", worker_threads = 2)]
@@ -24,25 +72,124 @@ async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
     debug!("启动模式: {:?}", args.mode);
+    let config_path = args.config.clone().unwrap_or_else(Config::default_path);
+    let mut config = Config::load(&config_path)?;
+    let mut startup = core::StartupOptions {
+        mode: args.initial_mode,
+        mouse_rate_hz: args.mouse_rate,
+    };
+    if let Some(path) = &args.export_state {
+        let host_prefs = HostPrefs::load(config.ble.host_prefs_path.as_deref());
+        // 离线导出不知道运行中的输出，运行中的输出通过 GET /api/state 导出
+        StateBundle::capture(&config, None, &host_prefs).write(path)?;
+        info!("已导出运行状态到 {}", path.display());
+        return Ok(());
+    }
+    if let Some(path) = &args.import_state {
+        let bundle = StateBundle::read(path)?;
+        bundle.persist(&config_path)?;
+        info!("已从 {} 导入运行状态", path.display());
+        if let Some(output) = bundle.output {
+            startup.mode = output;
+        }
+        config = bundle.config;
+    }
+    if args.selftest {
+        let report = selftest::run(&selftest::SystemProbe::new(&config)).await;
+        println!("{}", report);
+        if !report.passed() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
     match args.mode {
-        Mode::Switcher => run_switcher().await?,
-        Mode::WebTouchpad => run_web_touchpad().await?,
+        Mode::Switcher => {
+            run_switcher(
+                &core::Core::try_with_startup(&config, &startup)?,
+                &config_path,
+            )
+            .await?
+        }
+        Mode::WebTouchpad => run_web_touchpad(&config).await?,
+        Mode::SwitcherWeb => run_switcher_web(&config, &startup, &config_path).await?,
+        Mode::Record => {
+            let (device, out) = (args.device.unwrap(), args.out.unwrap());
+            tokio::task::spawn_blocking(move || recording::record_device(&device, &out)).await??
+        }
+        Mode::ReplayEvdev => {
+            for report in recording::replay_file(&args.input.unwrap())? {
+                info!("{:?}", report);
+            }
+        }
     }
     Ok(())
 }
 
-async fn run_switcher() -> anyhow::Result<()> {
-    let core = core::Core::new();
-    core.run().await?;
+async fn run_switcher(core: &core::Core, config_path: &Path) -> anyhow::Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    // 收到 SIGHUP 时重新读取配置文件
+    let reload = async {
+        while hangup.recv().await.is_some() {
+            info!("收到 SIGHUP，重新加载配置");
+            match Config::load(config_path) {
+                Ok(new) => {
+                    core.reload(&new).await;
+                }
+                Err(e) => warn!("重新加载配置失败，保持当前配置: {:?}", e),
+            }
+        }
+    };
+
+    // SIGUSR1 暂停转发，SIGUSR2 恢复
+    let mut usr1 = signal(SignalKind::user_defined1())?;
+    let mut usr2 = signal(SignalKind::user_defined2())?;
+    let pause = async {
+        loop {
+            tokio::select! {
+                Some(()) = usr1.recv() => core.pause(),
+                Some(()) = usr2.recv() => core.resume(),
+                else => break,
+            }
+        }
+    };
+
+    tokio::select! {
+        result = core.run() => result?,
+        _ = reload => {}
+        _ = pause => {}
+    }
 
     Ok(())
 }
 
-async fn run_web_touchpad() -> anyhow::Result<()> {
-    let app = web::router::build_router().await;
+async fn run_web_touchpad(config: &Config) -> anyhow::Result<()> {
+    let app = web::router::build_router(&config.web).await;
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    println!("listening on http://0.0.0.0:3000");
+    let listener = tokio::net::TcpListener::bind(&config.web.bind)
+        .await
+        .unwrap();
+    println!("listening on http://{}", config.web.bind);
     axum::serve(listener, app).await.unwrap();
     Ok(())
 }
+
+async fn run_switcher_web(
+    config: &Config,
+    startup: &core::StartupOptions,
+    config_path: &Path,
+) -> anyhow::Result<()> {
+    let core = Arc::new(core::Core::try_with_startup(config, startup)?);
+    let ws_state = web::ws::WsState::with_core(&config.web, Arc::clone(&core));
+    let app = web::router::router_with_state(Arc::new(ws_state));
+
+    let listener = tokio::net::TcpListener::bind(&config.web.bind).await?;
+    println!("listening on http://{}", config.web.bind);
+
+    tokio::select! {
+        result = run_switcher(&core, config_path) => result?,
+        result = axum::serve(listener, app) => result?,
+    }
+    Ok(())
+}
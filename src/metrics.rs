@@ -0,0 +1,372 @@
+use crate::input::InputReport;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// 延迟直方图的桶上界（微秒），最后一个桶收纳所有更大的值
+const LATENCY_BUCKETS_MICROS: [u64; 8] = [250, 500, 1_000, 2_000, 4_000, 8_000, 16_000, 32_000];
+
+/// 无锁延迟直方图
+pub struct LatencyHistogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS_MICROS.len() + 1],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+    max_micros: AtomicU64,
+}
+
+impl LatencyHistogram {
+    pub const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicU64::new(0) }; LATENCY_BUCKETS_MICROS.len() + 1],
+            count: AtomicU64::new(0),
+            sum_micros: AtomicU64::new(0),
+            max_micros: AtomicU64::new(0),
+        }
+    }
+
+    /// 记录一次延迟
+    pub fn record(&self, latency: Duration) {
+        let micros = latency.as_micros().min(u64::MAX as u128) as u64;
+        self.buckets[Self::bucket_index(latency)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+        self.max_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    /// 延迟所属桶的下标
+    pub fn bucket_index(latency: Duration) -> usize {
+        let micros = latency.as_micros();
+        LATENCY_BUCKETS_MICROS
+            .iter()
+            .position(|&bound| micros <= bound as u128)
+            .unwrap_or(LATENCY_BUCKETS_MICROS.len())
+    }
+
+    /// 各桶计数（非累计）
+    pub fn bucket_counts(&self) -> Vec<u64> {
+        self.buckets
+            .iter()
+            .map(|b| b.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// 摘要：次数、平均值与最大值
+    pub fn summary(&self) -> String {
+        let count = self.count();
+        let avg = self
+            .sum_micros
+            .load(Ordering::Relaxed)
+            .checked_div(count)
+            .unwrap_or(0);
+        format!(
+            "count={} avg={}μs max={}μs",
+            count,
+            avg,
+            self.max_micros.load(Ordering::Relaxed)
+        )
+    }
+
+    /// 以 Prometheus 文本格式输出
+    fn render(&self, name: &str, out: &mut String) {
+        let counts = self.bucket_counts();
+        let mut cumulative = 0;
+        for (i, count) in counts.iter().enumerate() {
+            cumulative += count;
+            let le = LATENCY_BUCKETS_MICROS
+                .get(i)
+                .map(|b| (*b as f64 / 1_000_000.0).to_string())
+                .unwrap_or_else(|| "+Inf".to_string());
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, le, cumulative);
+        }
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{}_sum {}", name, sum);
+        let _ = writeln!(out, "{}_count {}", name, self.count());
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 单调递增计数器
+#[derive(Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub const fn new() -> Self {
+        Self(AtomicU64::new(0))
+    }
+
+    pub fn inc(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// 审计计数的报告类型标签，顺序与 [`Metrics::audited_reports`] 一致
+pub const AUDIT_KINDS: [&str; 5] = ["keyboard", "mouse", "consumer", "system", "vendor"];
+
+/// 报告可能被丢弃或合并的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// 输入设备到管线的有界通道
+    InputChannel,
+    /// 鼠标报告率与键盘节流
+    RateController,
+    /// 后端发送（超时或出错）
+    BackendSend,
+}
+
+impl Stage {
+    const ALL: [Stage; 3] = [
+        Stage::InputChannel,
+        Stage::RateController,
+        Stage::BackendSend,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Stage::InputChannel => "input_channel",
+            Stage::RateController => "rate_controller",
+            Stage::BackendSend => "backend_send",
+        }
+    }
+}
+
+/// 丢弃统计区分的报告类型，鼠标以外的报告都计为键盘
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportKind {
+    Keyboard,
+    Mouse,
+}
+
+impl ReportKind {
+    const ALL: [ReportKind; 2] = [ReportKind::Keyboard, ReportKind::Mouse];
+
+    pub fn of(report: &InputReport) -> Self {
+        match report {
+            InputReport::Mouse { .. } => ReportKind::Mouse,
+            _ => ReportKind::Keyboard,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ReportKind::Keyboard => "keyboard",
+            ReportKind::Mouse => "mouse",
+        }
+    }
+}
+
+/// 各阶段丢弃与合并的报告数
+///
+/// 合并的报告内容并入了相邻报告（位移累加、重复的按键状态），不会改变主机看到的最终状态；
+/// 丢弃的报告则没有送达主机。
+pub struct ReportLoss {
+    dropped: [[Counter; ReportKind::ALL.len()]; Stage::ALL.len()],
+    coalesced: [[Counter; ReportKind::ALL.len()]; Stage::ALL.len()],
+}
+
+impl ReportLoss {
+    pub const fn new() -> Self {
+        Self {
+            dropped: [const { [const { Counter::new() }; ReportKind::ALL.len()] };
+                Stage::ALL.len()],
+            coalesced: [const { [const { Counter::new() }; ReportKind::ALL.len()] };
+                Stage::ALL.len()],
+        }
+    }
+
+    pub fn record_dropped(&self, stage: Stage, kind: ReportKind) {
+        self.dropped[stage as usize][kind as usize].inc();
+    }
+
+    pub fn record_coalesced(&self, stage: Stage, kind: ReportKind) {
+        self.coalesced[stage as usize][kind as usize].inc();
+    }
+
+    pub fn dropped(&self, stage: Stage, kind: ReportKind) -> u64 {
+        self.dropped[stage as usize][kind as usize].get()
+    }
+
+    pub fn coalesced(&self, stage: Stage, kind: ReportKind) -> u64 {
+        self.coalesced[stage as usize][kind as usize].get()
+    }
+
+    /// 所有阶段丢弃与合并的总数
+    pub fn total(&self) -> u64 {
+        self.dropped
+            .iter()
+            .chain(&self.coalesced)
+            .flatten()
+            .map(Counter::get)
+            .sum()
+    }
+
+    /// 非零计数的摘要，例如 `input_channel/mouse coalesced=7 backend_send/keyboard dropped=1`
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        for stage in Stage::ALL {
+            for kind in ReportKind::ALL {
+                for (what, count) in [
+                    ("dropped", self.dropped(stage, kind)),
+                    ("coalesced", self.coalesced(stage, kind)),
+                ] {
+                    if count > 0 {
+                        parts.push(format!(
+                            "{}/{} {}={}",
+                            stage.name(),
+                            kind.name(),
+                            what,
+                            count
+                        ));
+                    }
+                }
+            }
+        }
+        parts.join(" ")
+    }
+
+    /// 以 Prometheus 文本格式输出
+    fn render(&self, out: &mut String) {
+        for (name, counters) in [
+            ("bridge_hid_dropped_reports_total", &self.dropped),
+            ("bridge_hid_coalesced_reports_total", &self.coalesced),
+        ] {
+            for stage in Stage::ALL {
+                for kind in ReportKind::ALL {
+                    let _ = writeln!(
+                        out,
+                        "{}{{stage=\"{}\",kind=\"{}\"}} {}",
+                        name,
+                        stage.name(),
+                        kind.name(),
+                        counters[stage as usize][kind as usize].get()
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl Default for ReportLoss {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 全局运行指标
+pub struct Metrics {
+    /// 从 evdev 事件到 USB 报告发送完成的延迟
+    pub usb_latency: LatencyHistogram,
+    /// 从 evdev 事件到 BLE 报告发送完成的延迟
+    pub ble_latency: LatencyHistogram,
+    /// 其他注册后端合并统计的延迟
+    pub other_latency: LatencyHistogram,
+    /// 审计记录的报告数，按类型区分
+    pub audited_reports: [Counter; AUDIT_KINDS.len()],
+    /// 各阶段丢弃与合并的报告数
+    pub report_loss: ReportLoss,
+}
+
+static METRICS: Metrics = Metrics {
+    usb_latency: LatencyHistogram::new(),
+    ble_latency: LatencyHistogram::new(),
+    other_latency: LatencyHistogram::new(),
+    audited_reports: [const { Counter::new() }; AUDIT_KINDS.len()],
+    report_loss: ReportLoss::new(),
+};
+
+/// 获取全局指标
+pub fn global() -> &'static Metrics {
+    &METRICS
+}
+
+impl Metrics {
+    /// 以 Prometheus 文本格式输出全部指标
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.usb_latency
+            .render("bridge_hid_usb_latency_seconds", &mut out);
+        self.ble_latency
+            .render("bridge_hid_ble_latency_seconds", &mut out);
+        self.other_latency
+            .render("bridge_hid_other_latency_seconds", &mut out);
+        for (kind, counter) in AUDIT_KINDS.iter().zip(&self.audited_reports) {
+            let _ = writeln!(
+                out,
+                "bridge_hid_audited_reports_total{{kind=\"{}\"}} {}",
+                kind,
+                counter.get()
+            );
+        }
+        self.report_loss.render(&mut out);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_boundaries() {
+        assert_eq!(LatencyHistogram::bucket_index(Duration::from_micros(0)), 0);
+        assert_eq!(
+            LatencyHistogram::bucket_index(Duration::from_micros(250)),
+            0
+        );
+        assert_eq!(
+            LatencyHistogram::bucket_index(Duration::from_micros(251)),
+            1
+        );
+        assert_eq!(LatencyHistogram::bucket_index(Duration::from_secs(1)), 8);
+    }
+
+    #[test]
+    fn test_render_is_cumulative() {
+        let histogram = LatencyHistogram::new();
+        histogram.record(Duration::from_micros(100));
+        histogram.record(Duration::from_micros(600));
+        let mut out = String::new();
+        histogram.render("lat", &mut out);
+        assert!(out.contains("lat_bucket{le=\"0.00025\"} 1"));
+        assert!(out.contains("lat_bucket{le=\"0.001\"} 2"));
+        assert!(out.contains("lat_bucket{le=\"+Inf\"} 2"));
+        assert!(out.contains("lat_count 2"));
+    }
+
+    #[test]
+    fn test_report_loss_renders_every_stage() {
+        let loss = ReportLoss::new();
+        assert_eq!(loss.total(), 0);
+        assert!(loss.summary().is_empty());
+
+        loss.record_dropped(Stage::BackendSend, ReportKind::Keyboard);
+        loss.record_coalesced(Stage::RateController, ReportKind::Mouse);
+        loss.record_coalesced(Stage::RateController, ReportKind::Mouse);
+        assert_eq!(loss.total(), 3);
+        assert_eq!(
+            loss.summary(),
+            "rate_controller/mouse coalesced=2 backend_send/keyboard dropped=1"
+        );
+
+        let mut out = String::new();
+        loss.render(&mut out);
+        assert!(out.contains(
+            "bridge_hid_dropped_reports_total{stage=\"backend_send\",kind=\"keyboard\"} 1"
+        ));
+        assert!(out.contains(
+            "bridge_hid_coalesced_reports_total{stage=\"input_channel\",kind=\"mouse\"} 0"
+        ));
+    }
+}
@@ -1,9 +1,25 @@
 pub mod bluetooth_ble;
+pub mod conn_params;
+pub mod connection;
+pub mod drag_heartbeat;
+pub mod host_prefs;
+pub mod keep_awake;
+pub mod key_names;
+pub mod keyboard;
+pub mod led_debounce;
+pub mod mouse;
+pub mod mouse_gesture;
+pub mod mouse_keys;
+pub mod pairing;
+pub mod report;
+pub mod throttle;
 pub mod usb;
+pub mod virtual_hid;
 
 use crate::input::InputReport;
 use anyhow::Result;
 use async_trait::async_trait;
+use std::time::{Duration, Instant};
 
 /// 键盘修饰键
 #[derive(Debug, Clone, Copy, Default)]
@@ -62,7 +78,8 @@ impl KeyboardModifiers {
     }
 }
 
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct LedState {
     pub num_lock: bool,
     pub caps_lock: bool,
@@ -81,6 +98,14 @@ impl LedState {
             kana: (byte & 0x10) != 0,
         }
     }
+
+    fn to_byte(self) -> u8 {
+        self.num_lock as u8
+            | (self.caps_lock as u8) << 1
+            | (self.scroll_lock as u8) << 2
+            | (self.compose as u8) << 3
+            | (self.kana as u8) << 4
+    }
 }
 
 /// 鼠标按钮
@@ -115,21 +140,104 @@ impl MouseButtons {
     }
 }
 
+bitflags::bitflags! {
+    /// 输出后端支持的能力
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct BackendCapabilities: u8 {
+        const KEYBOARD = 0x01;
+        const MOUSE = 0x02;
+        const CONSUMER = 0x04;
+        const LED_READ = 0x08;
+        /// 厂商自定义的原始键码透传报告
+        const VENDOR = 0x10;
+        /// 系统控制（关机、睡眠、唤醒）
+        const SYSTEM = 0x20;
+    }
+}
+
+impl BackendCapabilities {
+    /// 发送该报告所需的能力
+    pub fn required_for(report: &InputReport) -> Self {
+        match report {
+            InputReport::Keyboard { .. } => Self::KEYBOARD,
+            InputReport::Mouse { .. } => Self::MOUSE,
+            InputReport::Consumer { .. } => Self::CONSUMER,
+            InputReport::System { .. } => Self::SYSTEM,
+            InputReport::Vendor { .. } => Self::VENDOR,
+        }
+    }
+
+    /// 是否支持发送该报告
+    pub fn supports(&self, report: &InputReport) -> bool {
+        self.contains(Self::required_for(report))
+    }
+}
+
 /// HID 设备通用接口
 #[async_trait]
 pub trait HidReportSender: Send + Sync {
     /// 核心方法：直接发送解析好的报告枚举
     async fn send_report(&mut self, report: InputReport) -> Result<()>;
+
+    /// 按顺序发送一组报告，用于宏回放等连续发送的场景
+    ///
+    /// 调用方只需获取一次后端锁。默认逐个调用 `send_report`，遇到错误即停止。
+    async fn send_reports(&mut self, reports: &[InputReport]) -> Result<()> {
+        for report in reports {
+            self.send_report(report.clone()).await?;
+        }
+        Ok(())
+    }
+
+    /// 后端支持的能力，调用方据此跳过不支持的报告
+    fn capabilities(&self) -> BackendCapabilities;
+
+    /// 报告率上限合并了尚未发出的位移时，应当调用 [`HidReportSender::flush`] 的时间
+    fn flush_deadline(&self) -> Option<Instant> {
+        None
+    }
+
+    /// 补发已到期的合并位移
+    async fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[async_trait]
 pub trait HidLedReader: Send + Sync {
     /// 核心方法：读取 LED 状态字节
     async fn get_led_state(&mut self) -> Result<Option<LedState>>;
+
+    /// 主机最近一次下发的 LED 状态，不等待新的输出报告；未知时为 `None`
+    fn host_led_state(&self) -> Option<LedState> {
+        None
+    }
+
+    /// 主机断开后忘记它下发的 LED 状态，下次连接的可能是另一台主机
+    fn clear_host_led_state(&mut self) {}
+
+    /// 读取不等待数据时两次读取之间的间隔，`None` 表示读取会挂起到有新状态为止
+    fn poll_interval(&self) -> Option<Duration> {
+        None
+    }
 }
 
 pub struct NoLedDevice;
 
+/// 不可用的输出后端，不支持任何报告
+pub struct NoOutput;
+
+#[async_trait]
+impl HidReportSender for NoOutput {
+    async fn send_report(&mut self, _report: InputReport) -> Result<()> {
+        Ok(())
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities::empty()
+    }
+}
+
 #[async_trait]
 impl HidLedReader for NoLedDevice {
     async fn get_led_state(&mut self) -> Result<Option<LedState>> {
@@ -217,6 +325,74 @@ pub mod keycodes {
     pub const KEY_LEFT_ARROW: u8 = 0x50;
     pub const KEY_DOWN_ARROW: u8 = 0x51;
     pub const KEY_UP_ARROW: u8 = 0x52;
+    pub const KEY_NUM_LOCK: u8 = 0x53;
+    pub const KEY_KP_SLASH: u8 = 0x54;
+    pub const KEY_KP_ASTERISK: u8 = 0x55;
+    pub const KEY_KP_MINUS: u8 = 0x56;
+    pub const KEY_KP_PLUS: u8 = 0x57;
+    pub const KEY_KP_ENTER: u8 = 0x58;
+    pub const KEY_KP_1: u8 = 0x59;
+    pub const KEY_KP_2: u8 = 0x5A;
+    pub const KEY_KP_3: u8 = 0x5B;
+    pub const KEY_KP_4: u8 = 0x5C;
+    pub const KEY_KP_5: u8 = 0x5D;
+    pub const KEY_KP_6: u8 = 0x5E;
+    pub const KEY_KP_7: u8 = 0x5F;
+    pub const KEY_KP_8: u8 = 0x60;
+    pub const KEY_KP_9: u8 = 0x61;
+    pub const KEY_KP_0: u8 = 0x62;
+    pub const KEY_KP_DOT: u8 = 0x63;
+    pub const KEY_NON_US_BACKSLASH: u8 = 0x64;
+    /// 日文键盘：ろ
+    pub const KEY_INTERNATIONAL1: u8 = 0x87;
+    /// 日文键盘：カタカナ/ひらがな
+    pub const KEY_INTERNATIONAL2: u8 = 0x88;
+    /// 日文键盘：¥
+    pub const KEY_INTERNATIONAL3: u8 = 0x89;
+    /// 日文键盘：変換
+    pub const KEY_INTERNATIONAL4: u8 = 0x8A;
+    /// 日文键盘：無変換
+    pub const KEY_INTERNATIONAL5: u8 = 0x8B;
+    /// 韩文键盘：한/영
+    pub const KEY_LANG1: u8 = 0x90;
+    /// 韩文键盘：한자
+    pub const KEY_LANG2: u8 = 0x91;
+    pub const KEY_LEFT_CTRL: u8 = 0xE0;
+    pub const KEY_LEFT_SHIFT: u8 = 0xE1;
+    pub const KEY_LEFT_ALT: u8 = 0xE2;
+    pub const KEY_LEFT_GUI: u8 = 0xE3;
+    pub const KEY_RIGHT_CTRL: u8 = 0xE4;
+    pub const KEY_RIGHT_SHIFT: u8 = 0xE5;
+    pub const KEY_RIGHT_ALT: u8 = 0xE6;
+    pub const KEY_RIGHT_GUI: u8 = 0xE7;
+}
+
+/// 常用消费类控制用法（HID Usage Tables, Consumer Page 0x0C）
+pub mod consumer {
+    pub const DISPLAY_BRIGHTNESS_UP: u16 = 0x006F;
+    pub const DISPLAY_BRIGHTNESS_DOWN: u16 = 0x0070;
+    pub const KEYBOARD_BRIGHTNESS_UP: u16 = 0x0079;
+    pub const KEYBOARD_BRIGHTNESS_DOWN: u16 = 0x007A;
+    pub const PLAY_PAUSE: u16 = 0x00CD;
+    pub const MUTE: u16 = 0x00E2;
+    pub const VOLUME_UP: u16 = 0x00E9;
+    pub const VOLUME_DOWN: u16 = 0x00EA;
+    pub const AC_SEARCH: u16 = 0x0221;
+    pub const AC_HOME: u16 = 0x0223;
+    pub const AC_BACK: u16 = 0x0224;
+    /// 下一个键盘布局，iPadOS/macOS 识别为 Globe 键
+    pub const AC_NEXT_KEYBOARD_LAYOUT: u16 = 0x029D;
+    /// 显示所有窗口（调度中心）
+    pub const AC_SHOW_ALL_WINDOWS: u16 = 0x029F;
+    /// 显示所有应用（启动台）
+    pub const AC_SHOW_ALL_APPLICATIONS: u16 = 0x02A2;
+}
+
+/// 系统控制用法（HID Usage Tables, Generic Desktop Page 0x01）
+pub mod system {
+    pub const POWER_DOWN: u8 = 0x81;
+    pub const SLEEP: u8 = 0x82;
+    pub const WAKE_UP: u8 = 0x83;
 }
 
 // 重新导出常用类型
@@ -1,17 +1,21 @@
-use anyhow::{Result, anyhow};
+use anyhow::Result;
 use async_trait::async_trait;
 use bluer::adv::{Advertisement, AdvertisementHandle};
-use bluer::agent::Agent;
 use bluer::gatt::local::{
-    Application, Characteristic, CharacteristicNotify, CharacteristicNotifyMethod,
-    CharacteristicRead, CharacteristicWrite, CharacteristicWriteMethod, Descriptor, DescriptorRead,
-    Service,
+    Application, ApplicationHandle, Characteristic, CharacteristicNotify,
+    CharacteristicNotifyMethod, CharacteristicRead, CharacteristicWrite, CharacteristicWriteMethod,
+    Descriptor, DescriptorRead, Service,
 };
-use bluer::{Adapter, Uuid};
-use futures::FutureExt;
+use bluer::{Adapter, AdapterEvent, Address, DeviceEvent, DeviceProperty, Uuid};
+use futures::stream::SelectAll;
+use futures::{FutureExt, StreamExt};
+use serde::{Deserialize, Serialize};
 use std::error::Error as StdError;
 use std::fmt;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::time::Duration;
 use tokio::sync::{Mutex, mpsc};
 
 #[derive(Debug, Clone)]
@@ -25,7 +29,31 @@ impl fmt::Display for BleError {
 
 impl StdError for BleError {}
 
-use super::{HidReportSender, InputReport, LedState};
+/// 蓝牙不可用：D-Bus 或 bluetoothd 未运行，或者没有蓝牙适配器
+#[derive(Debug, Clone)]
+pub struct BleUnavailable(String);
+
+impl fmt::Display for BleUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "蓝牙不可用: {}", self.0)
+    }
+}
+
+impl StdError for BleUnavailable {}
+
+impl BleUnavailable {
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self(reason.into())
+    }
+}
+
+use super::conn_params::{self, ConnParams, DebugfsConnParams};
+use super::connection::ConnectionState;
+use super::host_prefs::HostPrefs;
+use super::pairing::{PairingProvider, pairing_agent};
+use super::report::{self, Framing};
+use super::throttle::MouseRateCap;
+use super::{BackendCapabilities, HidReportSender, InputReport, LedState};
 
 macro_rules! ble_uuid {
     ($short:expr) => {
@@ -116,16 +144,248 @@ const HID_REPORT_MAP: &[u8] = &[
     0x81, 0x06, //     Input (Data, Variable, Relative)
     0xC0, //   End Collection
     0xC0, // End Collection
+    // ----- Consumer Control (Report ID 3) -----
+    0x05, 0x0C, // Usage Page (Consumer)
+    0x09, 0x01, // Usage (Consumer Control)
+    0xA1, 0x01, // Collection (Application)
+    0x85, 0x03, //   Report ID (3)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x26, 0xFF, 0x03, //   Logical Maximum (0x3FF)
+    0x19, 0x00, //   Usage Minimum (0)
+    0x2A, 0xFF, 0x03, //   Usage Maximum (0x3FF)
+    0x75, 0x10, //   Report Size (16)
+    0x95, 0x01, //   Report Count (1)
+    0x81, 0x00, //   Input (Data, Array, Absolute)
+    0xC0, // End Collection
+    // ----- System Control (Report ID 4) -----
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x80, // Usage (System Control)
+    0xA1, 0x01, // Collection (Application)
+    0x85, 0x04, //   Report ID (4)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x26, 0x83, 0x00, //   Logical Maximum (0x83)
+    0x19, 0x00, //   Usage Minimum (0)
+    0x29, 0x83, //   Usage Maximum (System Wake Up)
+    0x75, 0x08, //   Report Size (8)
+    0x95, 0x01, //   Report Count (1)
+    0x81, 0x00, //   Input (Data, Array, Absolute)
+    0xC0, // End Collection
 ];
 
+// GAP Appearance 取值（Bluetooth Assigned Numbers, HID 类别 0x03C0）
+const APPEARANCE_KEYBOARD: u16 = 0x03C1;
+// 键鼠组合没有独立的取值，沿用历史上广播的 0x03C2，主机会同时识别指针能力
+const APPEARANCE_COMBO: u16 = 0x03C2;
+
 // HID Information: bcdHID=1.11, bCountryCode=0, Flags=0x02 (normally connectable)
 const HID_INFORMATION: &[u8] = &[0x01, 0x11, 0x00, 0x02];
 
 type ReportNotifier = mpsc::Sender<Vec<u8>>;
 
+/// 每个订阅者最多积压的报告数
+const SUBSCRIBER_CAPACITY: usize = 16;
+
+/// 订阅了某个输入报告的所有主机
+///
+/// 每次启用通知都会加入一个新通道，报告分发给全部订阅者，后连接的主机不会覆盖
+/// 先前主机的通道；通知停止后通道关闭，在下次发送时移除。
+/// 发送不等待：积压已满的主机丢弃这一个报告，不拖慢其他主机，也不在持锁时等待。
+#[derive(Clone, Default)]
+struct ReportSubscribers(Arc<Mutex<Vec<ReportNotifier>>>);
+
+impl ReportSubscribers {
+    /// 新增一个订阅，返回通知循环使用的接收端
+    async fn subscribe(&self) -> mpsc::Receiver<Vec<u8>> {
+        let (tx, rx) = mpsc::channel(SUBSCRIBER_CAPACITY);
+        self.0.lock().await.push(tx);
+        rx
+    }
+
+    async fn is_empty(&self) -> bool {
+        let mut subscribers = self.0.lock().await;
+        subscribers.retain(|tx| !tx.is_closed());
+        subscribers.is_empty()
+    }
+
+    /// 发送给所有订阅者，一个都没有送达时返回错误
+    async fn send(&self, report: Vec<u8>) -> Result<()> {
+        let mut subscribers = self.0.lock().await;
+        subscribers.retain(|tx| !tx.is_closed());
+        if subscribers.is_empty() {
+            return Err(BleError("通知器未就绪".to_string()).into());
+        }
+        let mut delivered = false;
+        subscribers.retain(|tx| match tx.try_send(report.clone()) {
+            Ok(()) => {
+                delivered = true;
+                true
+            }
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                log::debug!(
+                    "订阅者积压 {} 个报告未发出，丢弃本次报告",
+                    SUBSCRIBER_CAPACITY
+                );
+                true
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                log::debug!("订阅者已断开");
+                false
+            }
+        });
+        if !delivered {
+            return Err(BleError("发送报告失败: 所有订阅者均已断开".to_string()).into());
+        }
+        Ok(())
+    }
+}
+
+/// 各主机写入的 Protocol Mode（0 = Boot，1 = Report），未写入时为 Report
+///
+/// 记录在主机偏好中，重连后沿用上次的值
+#[derive(Clone, Default)]
+struct ProtocolModes(HostPrefs);
+
+impl ProtocolModes {
+    const REPORT: u8 = 0x01;
+
+    fn get(&self, host: Address) -> u8 {
+        self.0
+            .get(&host.to_string())
+            .map(|pref| pref.protocol_mode)
+            .unwrap_or(Self::REPORT)
+    }
+
+    fn set(&self, host: Address, mode: u8) {
+        self.0.set_protocol_mode(&host.to_string(), mode);
+    }
+}
+
+/// BLE 外设配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BleConfig {
+    /// 广播名称
+    pub name: String,
+    /// 是否同时提供鼠标功能，关闭时按纯键盘广播
+    pub mouse_enabled: bool,
+    /// 自定义广播外观值，未设置时根据功能推导
+    pub appearance: Option<u16>,
+    /// 指定使用的蓝牙适配器（如 `hci1`），未设置时使用默认适配器
+    pub adapter: Option<String>,
+    /// 主机尚未告知协商结果时假定的 ATT MTU
+    pub default_mtu: u16,
+    /// 鼠标报告率硬上限（Hz），超出的报告被合并，为 0 表示不限制
+    pub max_mouse_rate_hz: u32,
+    /// 按主机地址保存 Protocol Mode 与 LED 状态的文件，未设置时只在内存中记录
+    pub host_prefs_path: Option<PathBuf>,
+    /// 期望的连接参数，未设置时使用主机的默认值
+    pub conn_params: Option<ConnParams>,
+    /// 第一次切换到 BLE 时才启动 BLE 服务，关闭时在启动时就开始广播
+    pub on_demand: bool,
+    /// 切换离开 BLE 后注销服务并停止广播，下次切换回来时重新启动
+    pub stop_when_unused: bool,
+}
+
+impl Default for BleConfig {
+    fn default() -> Self {
+        Self {
+            name: "BLE Keyboard".to_string(),
+            mouse_enabled: true,
+            appearance: None,
+            adapter: None,
+            default_mtu: DEFAULT_ATT_MTU,
+            max_mouse_rate_hz: DEFAULT_BLE_MAX_MOUSE_RATE_HZ,
+            host_prefs_path: None,
+            conn_params: None,
+            on_demand: true,
+            stop_when_unused: false,
+        }
+    }
+}
+
+/// BLE 鼠标报告率上限：最短连接间隔为 7.5ms，更快的通知只会在主机端堆积
+pub const DEFAULT_BLE_MAX_MOUSE_RATE_HZ: u32 = 133;
+
+/// ATT 协议规定的最小 MTU
+pub const DEFAULT_ATT_MTU: u16 = 23;
+/// 通知 PDU 中 ATT 头（操作码 + 句柄）占用的字节数
+const ATT_NOTIFY_HEADER: usize = 3;
+/// 本设备最长的输入报告（键盘报告）
+const LARGEST_REPORT_LEN: usize = 8;
+
+/// 与主机协商得到的 ATT MTU
+///
+/// bluer 的通知接口不提供 MTU，只能从主机的读请求中得到；主机连接后会读取
+/// HID Information 与 Report Map，因此一般在第一个报告之前就已更新。
+#[derive(Debug, Clone)]
+pub struct AttMtu(Arc<AtomicU16>);
+
+impl AttMtu {
+    pub fn new(mtu: u16) -> Self {
+        Self(Arc::new(AtomicU16::new(mtu)))
+    }
+
+    pub fn get(&self) -> u16 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// 记录读请求中携带的 MTU
+    pub fn update(&self, mtu: u16) {
+        if self.0.swap(mtu, Ordering::Relaxed) == mtu {
+            return;
+        }
+        log::info!("ATT MTU: {}", mtu);
+        if self.max_payload() < LARGEST_REPORT_LEN {
+            log::error!(
+                "ATT MTU {} 只能通知 {} 字节，小于最长的 {} 字节报告，这些报告将无法发送",
+                mtu,
+                self.max_payload(),
+                LARGEST_REPORT_LEN
+            );
+        }
+    }
+
+    /// 单个通知 PDU 可携带的最大报告长度
+    pub fn max_payload(&self) -> usize {
+        (self.get() as usize).saturating_sub(ATT_NOTIFY_HEADER)
+    }
+
+    /// 检查报告能否放进一个通知，超出时返回错误而不是被截断
+    pub fn check(&self, report: &[u8]) -> Result<()> {
+        if report.len() > self.max_payload() {
+            return Err(BleError(format!(
+                "报告长度 {} 字节超过 ATT MTU {} 允许的 {} 字节通知负载",
+                report.len(),
+                self.get(),
+                self.max_payload()
+            ))
+            .into());
+        }
+        Ok(())
+    }
+}
+
+impl BleConfig {
+    /// 实际广播的外观值
+    pub fn effective_appearance(&self) -> u16 {
+        self.appearance.unwrap_or(if self.mouse_enabled {
+            APPEARANCE_COMBO
+        } else {
+            APPEARANCE_KEYBOARD
+        })
+    }
+}
+
 pub struct BluetoothBleKeyboardHidDevice {
     adapter: Arc<Adapter>,
-    keyboard_notifier: Arc<Mutex<Option<ReportNotifier>>>,
+    keyboard_notifier: ReportSubscribers,
+    consumer_notifier: ReportSubscribers,
+    system_notifier: ReportSubscribers,
+    /// 记录已连接主机的地址
+    connection: ConnectionState,
+    /// 各主机的 Protocol Mode 与 LED 状态
+    host_prefs: HostPrefs,
+    mtu: AttMtu,
     #[allow(dead_code)]
     session: bluer::Session,
     #[allow(dead_code)]
@@ -136,25 +396,85 @@ pub struct BluetoothBleMouseHidDevice {
     #[allow(dead_code)]
     adapter: Arc<Adapter>,
     #[allow(dead_code)]
-    mouse_notifier: Arc<Mutex<Option<ReportNotifier>>>,
+    mouse_notifier: ReportSubscribers,
+    mtu: AttMtu,
+    rate_cap: MouseRateCap,
     #[allow(dead_code)]
     session: bluer::Session,
     #[allow(dead_code)]
     _agent_handle: Arc<bluer::agent::AgentHandle>,
 }
 
+impl BluetoothBleKeyboardHidDevice {
+    /// 使用外部的连接状态记录已连接主机，需在 [`run_ble_server`] 之前调用
+    pub fn set_connection(&mut self, connection: ConnectionState) {
+        self.connection = connection;
+    }
+
+    /// 使用外部的主机偏好记录，需在 [`run_ble_server`] 之前调用
+    pub fn set_host_prefs(&mut self, host_prefs: HostPrefs) {
+        self.host_prefs = host_prefs;
+    }
+}
+
 struct BleHidState {
-    keyboard_notifier: Arc<Mutex<Option<ReportNotifier>>>,
-    mouse_notifier: Arc<Mutex<Option<ReportNotifier>>>,
+    keyboard_notifier: ReportSubscribers,
+    mouse_notifier: ReportSubscribers,
+    consumer_notifier: ReportSubscribers,
+    system_notifier: ReportSubscribers,
+    protocol_modes: ProtocolModes,
+    host_prefs: HostPrefs,
+    mtu: AttMtu,
+}
+
+/// 连接 BlueZ 并列出适配器，无法连接或没有适配器时返回 [`BleUnavailable`]
+async fn open_session() -> Result<(bluer::Session, Vec<String>)> {
+    let session = bluer::Session::new().await.map_err(|e| {
+        BleUnavailable(format!(
+            "无法连接 BlueZ（D-Bus 或 bluetoothd 未运行？）: {}",
+            e
+        ))
+    })?;
+    let available = session
+        .adapter_names()
+        .await
+        .map_err(|e| BleUnavailable(format!("读取蓝牙适配器失败: {}", e)))?;
+    if available.is_empty() {
+        return Err(BleUnavailable("没有蓝牙适配器".to_string()).into());
+    }
+    Ok((session, available))
 }
 
-pub async fn build_ble_hid_device() -> Result<(
+/// 校验配置中的适配器名称
+///
+/// 未指定时返回 `None` 表示使用默认适配器；指定的适配器不存在时返回错误并列出可用适配器。
+fn select_adapter_name(requested: Option<&str>, available: &[String]) -> Result<Option<String>> {
+    match requested {
+        None => Ok(None),
+        Some(name) if available.iter().any(|a| a == name) => Ok(Some(name.to_string())),
+        Some(name) => Err(BleError(format!(
+            "蓝牙适配器 {} 不存在，可用适配器: [{}]",
+            name,
+            available.join(", ")
+        ))
+        .into()),
+    }
+}
+
+/// 按配置创建 BLE HID 设备，配对请求交给 `pairing` 处理
+pub async fn build_ble_hid_device(
+    config: &BleConfig,
+    pairing: Arc<dyn PairingProvider>,
+) -> Result<(
     BluetoothBleKeyboardHidDevice,
     BluetoothBleMouseHidDevice,
     bluer::Session,
 )> {
-    let session = bluer::Session::new().await?;
-    let adapter = session.default_adapter().await?;
+    let (session, available) = open_session().await?;
+    let adapter = match select_adapter_name(config.adapter.as_deref(), &available)? {
+        Some(name) => session.adapter(&name)?,
+        None => session.default_adapter().await?,
+    };
 
     // 配置适配器
     adapter.set_powered(true).await?;
@@ -166,61 +486,47 @@ pub async fn build_ble_hid_device() -> Result<(
     log::info!("BLE 适配器已配置: {}", adapter.name());
     log::info!("适配器地址: {}", adapter.address().await?);
 
-    // Agent 配置 - 使用 KeyboardOnly capability（和 Python 版本一致）
-    let agent = Agent {
-        request_default: true,
-        request_passkey: Some(Box::new(|req| {
-            Box::pin(async move {
-                log::info!("请求 Passkey，设备: {}", req.device);
-                // 可以在这里实现真正的 passkey 输入
-                Ok(123456)
-            })
-        })),
-        display_passkey: Some(Box::new(|req| {
-            Box::pin(async move {
-                log::info!("显示 Passkey: {} (已输入: {})", req.passkey, req.entered);
-                Ok(())
-            })
-        })),
-        request_confirmation: Some(Box::new(|req| {
-            Box::pin(async move {
-                log::info!("确认配对请求，passkey: {}", req.passkey);
-                Ok(())
-            })
-        })),
-        authorize_service: Some(Box::new(|req| {
-            Box::pin(async move {
-                log::info!("授权服务: 设备 {} 访问 {}", req.device, req.service);
-                Ok(())
-            })
-        })),
-        request_authorization: Some(Box::new(|req| {
-            Box::pin(async move {
-                log::info!("授权请求: {}", req.device);
-                Ok(())
-            })
-        })),
-        ..Default::default()
-    };
+    if let Some(params) = config.conn_params {
+        match conn_params::apply(&DebugfsConnParams::for_adapter(adapter.name()), params) {
+            Ok(applied) => log::debug!(
+                "已请求 BLE 连接参数: 间隔 {}~{} ms，延迟 {}，超时 {} ms",
+                applied.min_interval as f32 * 1.25,
+                applied.max_interval as f32 * 1.25,
+                applied.latency,
+                applied.supervision_timeout as u32 * 10
+            ),
+            Err(e) => log::warn!("设置 BLE 连接参数失败，使用主机默认值: {:?}", e),
+        }
+    }
 
-    let agent_handle = session.register_agent(agent).await?;
+    let agent_handle = session.register_agent(pairing_agent(pairing)).await?;
     log::info!("Agent 已注册");
 
     let adapter = Arc::new(adapter);
-    let keyboard_notifier = Arc::new(Mutex::new(None));
-    let mouse_notifier = Arc::new(Mutex::new(None));
+    let keyboard_notifier = ReportSubscribers::default();
+    let mouse_notifier = ReportSubscribers::default();
+    let consumer_notifier = ReportSubscribers::default();
+    let system_notifier = ReportSubscribers::default();
+    let mtu = AttMtu::new(config.default_mtu);
     let shared_handle = Arc::new(agent_handle);
 
     let keyboard = BluetoothBleKeyboardHidDevice {
         adapter: Arc::clone(&adapter),
-        keyboard_notifier: Arc::clone(&keyboard_notifier),
+        keyboard_notifier: keyboard_notifier.clone(),
+        consumer_notifier: consumer_notifier.clone(),
+        system_notifier: system_notifier.clone(),
+        connection: ConnectionState::default(),
+        host_prefs: HostPrefs::default(),
+        mtu: mtu.clone(),
         session: session.clone(),
         _agent_handle: Arc::clone(&shared_handle),
     };
 
     let mouse = BluetoothBleMouseHidDevice {
         adapter: Arc::clone(&adapter),
-        mouse_notifier: Arc::clone(&mouse_notifier),
+        mouse_notifier: mouse_notifier.clone(),
+        mtu,
+        rate_cap: MouseRateCap::new(config.max_mouse_rate_hz),
         session: session.clone(),
         _agent_handle: Arc::clone(&shared_handle),
     };
@@ -228,46 +534,268 @@ pub async fn build_ble_hid_device() -> Result<(
     Ok((keyboard, mouse, session))
 }
 
+/// 等待 BlueZ 确认广播注销的最长时间
+const UNREGISTER_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 已注册到 BlueZ 的 BLE 服务，退出时需要显式注销
+#[async_trait]
+pub trait BleRegistration: Send + Sync {
+    /// 注销服务并等待确认
+    async fn unregister(self: Box<Self>) -> Result<()>;
+}
+
+/// GATT 应用与广播的句柄
+///
+/// bluer 只在句柄被丢弃后于后台任务中注销，进程紧接着退出时注销可能来不及完成，
+/// 导致主机上残留一个过期的 "BLE Keyboard"。
+pub struct BleServerHandles {
+    adapter: Arc<Adapter>,
+    app: ApplicationHandle,
+    adv: AdvertisementHandle,
+    peer_watch: tokio::task::JoinHandle<()>,
+    /// 记录协商后的连接参数，只在配置了连接参数时启动
+    conn_watch: Option<tokio::task::JoinHandle<()>>,
+}
+
+#[async_trait]
+impl BleRegistration for BleServerHandles {
+    async fn unregister(self: Box<Self>) -> Result<()> {
+        let Self {
+            adapter,
+            app,
+            adv,
+            peer_watch,
+            conn_watch,
+        } = *self;
+        peer_watch.abort();
+        if let Some(conn_watch) = conn_watch {
+            conn_watch.abort();
+        }
+        let before = adapter.active_advertising_instances().await?;
+
+        // 先停止广播，再注销 GATT 应用
+        drop(adv);
+        let confirmed = tokio::time::timeout(UNREGISTER_TIMEOUT, async {
+            loop {
+                match adapter.active_advertising_instances().await {
+                    Ok(count) if count < before => break,
+                    Ok(_) => tokio::time::sleep(Duration::from_millis(50)).await,
+                    Err(e) => {
+                        log::warn!("查询广播状态失败: {:?}", e);
+                        break;
+                    }
+                }
+            }
+        })
+        .await;
+        if confirmed.is_err() {
+            log::warn!("等待 BLE 广播注销超时");
+        } else {
+            log::info!("BLE 广播已停止");
+        }
+
+        drop(app);
+        // 注销完成后适配器不再列出 HID 服务
+        let confirmed = tokio::time::timeout(UNREGISTER_TIMEOUT, async {
+            loop {
+                match adapter.uuids().await {
+                    Ok(Some(uuids)) if uuids.contains(&HID_SERVICE_UUID) => {
+                        tokio::time::sleep(Duration::from_millis(50)).await
+                    }
+                    Ok(_) => break,
+                    Err(e) => {
+                        log::warn!("查询适配器服务失败: {:?}", e);
+                        break;
+                    }
+                }
+            }
+        })
+        .await;
+        if confirmed.is_err() {
+            log::warn!("等待 GATT 应用注销超时");
+        } else {
+            log::info!("GATT 应用已注销");
+        }
+        Ok(())
+    }
+}
+
 pub async fn run_ble_server(
     keyboard: &BluetoothBleKeyboardHidDevice,
     mouse: &BluetoothBleMouseHidDevice,
-) -> Result<(bluer::gatt::local::ApplicationHandle, AdvertisementHandle)> {
+    config: &BleConfig,
+) -> Result<BleServerHandles> {
     let adapter = &keyboard.adapter;
 
     let state = Arc::new(BleHidState {
-        keyboard_notifier: Arc::clone(&keyboard.keyboard_notifier),
-        mouse_notifier: Arc::clone(&mouse.mouse_notifier),
+        keyboard_notifier: keyboard.keyboard_notifier.clone(),
+        mouse_notifier: mouse.mouse_notifier.clone(),
+        consumer_notifier: keyboard.consumer_notifier.clone(),
+        system_notifier: keyboard.system_notifier.clone(),
+        protocol_modes: ProtocolModes(keyboard.host_prefs.clone()),
+        host_prefs: keyboard.host_prefs.clone(),
+        mtu: keyboard.mtu.clone(),
     });
 
     let app = build_gatt_application(state).await?;
     let app_handle = adapter.serve_gatt_application(app).await?;
     log::info!("GATT 应用已注册");
 
-    // 广播配置
-    let adv = Advertisement {
+    let adv = build_advertisement(config);
+    let adv_handle = adapter.advertise(adv).await?;
+    log::info!("BLE 广播已启动");
+
+    if !mouse.mouse_notifier.is_empty().await {
+        log::info!("连接成功！");
+    }
+
+    let peer_watch = tokio::spawn(watch_peer(Arc::clone(adapter), keyboard.connection.clone()));
+    let conn_watch = config
+        .conn_params
+        .map(|_| tokio::spawn(conn_params::log_negotiated(adapter.name().to_string())));
+
+    Ok(BleServerHandles {
+        adapter: Arc::clone(adapter),
+        app: app_handle,
+        adv: adv_handle,
+        peer_watch,
+        conn_watch,
+    })
+}
+
+/// 自检：确认蓝牙适配器存在且能开始广播，随后停止广播，返回适配器名称
+pub async fn probe_advertising(config: &BleConfig) -> Result<String> {
+    let (session, available) = open_session().await?;
+    let adapter = match select_adapter_name(config.adapter.as_deref(), &available)? {
+        Some(name) => session.adapter(&name)?,
+        None => session.default_adapter().await?,
+    };
+    adapter.set_powered(true).await?;
+    let handle = adapter.advertise(build_advertisement(config)).await?;
+    drop(handle);
+    Ok(adapter.name().to_string())
+}
+
+/// 监听适配器与设备的属性变化，把已配对主机的地址与连接状态写入 `connection`
+///
+/// 只在设备增减或 `Connected`/`Paired` 属性变化时重新读取，不轮询 D-Bus。
+async fn watch_peer(adapter: Arc<Adapter>, connection: ConnectionState) {
+    let mut adapter_events = match adapter.events().await {
+        Ok(events) => events.boxed(),
+        Err(e) => {
+            log::warn!("无法监听蓝牙设备变化，不再跟踪 BLE 主机: {}", e);
+            return;
+        }
+    };
+    let mut device_events = SelectAll::new();
+    for address in adapter.device_addresses().await.unwrap_or_default() {
+        watch_device(&adapter, address, &mut device_events).await;
+    }
+
+    loop {
+        let peer = paired_peer(&adapter).await;
+        connection.set_connected(peer.is_some());
+        if connection.set_peer(peer.clone()) {
+            match peer {
+                Some(address) => log::info!("BLE 主机已连接: {}", address),
+                None => log::info!("BLE 主机已断开"),
+            }
+        }
+
+        tokio::select! {
+            event = adapter_events.next() => match event {
+                Some(AdapterEvent::DeviceAdded(address)) => {
+                    watch_device(&adapter, address, &mut device_events).await;
+                }
+                Some(_) => {}
+                None => break,
+            },
+            Some(()) = device_events.next() => {}
+        }
+    }
+}
+
+/// 订阅设备的连接与配对状态变化
+async fn watch_device(
+    adapter: &Adapter,
+    address: Address,
+    events: &mut SelectAll<futures::stream::BoxStream<'static, ()>>,
+) {
+    let Ok(device) = adapter.device(address) else {
+        return;
+    };
+    match device.events().await {
+        Ok(stream) => events.push(
+            stream
+                .filter_map(|DeviceEvent::PropertyChanged(property)| async move {
+                    matches!(
+                        property,
+                        DeviceProperty::Connected(_) | DeviceProperty::Paired(_)
+                    )
+                    .then_some(())
+                })
+                .boxed(),
+        ),
+        Err(e) => log::debug!("无法监听设备 {} 的属性变化: {}", address, e),
+    }
+}
+
+/// 适配器上已连接且已配对的主机地址
+async fn paired_peer(adapter: &Adapter) -> Option<String> {
+    let mut devices = Vec::new();
+    for address in adapter.device_addresses().await.ok()? {
+        if let Ok(device) = adapter.device(address) {
+            devices.push(PeerCandidate {
+                address: address.to_string(),
+                connected: device.is_connected().await.unwrap_or(false),
+                paired: device.is_paired().await.unwrap_or(false),
+            });
+        }
+    }
+    select_peer(&devices)
+}
+
+/// 适配器上的设备及其连接、配对状态
+#[derive(Clone)]
+struct PeerCandidate {
+    address: String,
+    connected: bool,
+    paired: bool,
+}
+
+/// 选出已连接且已配对的设备，附近只是连接过来的未配对设备不算主机
+fn select_peer(devices: &[PeerCandidate]) -> Option<String> {
+    devices
+        .iter()
+        .find(|device| device.connected && device.paired)
+        .map(|device| device.address.clone())
+}
+
+/// 根据配置生成广播内容
+fn build_advertisement(config: &BleConfig) -> Advertisement {
+    Advertisement {
         advertisement_type: bluer::adv::Type::Peripheral,
         service_uuids: vec![HID_SERVICE_UUID, BATTERY_SERVICE_UUID]
             .into_iter()
             .collect(),
-        local_name: Some("BLE Keyboard".to_string()),
-        appearance: Some(0x03C2), // Keyboard+Mouse
+        local_name: Some(config.name.clone()),
+        appearance: Some(config.effective_appearance()),
         discoverable: Some(true),
         ..Default::default()
-    };
-
-    let adv_handle = adapter.advertise(adv).await?;
-    log::info!("BLE 广播已启动");
-
-    if mouse.mouse_notifier.lock().await.is_some() {
-        log::info!("连接成功！");
     }
-
-    Ok((app_handle, adv_handle))
 }
 
 async fn build_gatt_application(state: Arc<BleHidState>) -> Result<Application> {
-    let keyboard_notifier = Arc::clone(&state.keyboard_notifier);
-    let mouse_notifier = Arc::clone(&state.mouse_notifier);
+    let keyboard_notifier = state.keyboard_notifier.clone();
+    let mouse_notifier = state.mouse_notifier.clone();
+    let consumer_notifier = state.consumer_notifier.clone();
+    let system_notifier = state.system_notifier.clone();
+    let read_modes = state.protocol_modes.clone();
+    let write_modes = state.protocol_modes.clone();
+    let read_leds = state.host_prefs.clone();
+    let write_leds = state.host_prefs.clone();
+    let info_mtu = state.mtu.clone();
+    let map_mtu = state.mtu.clone();
 
     // HID Service
     let hid_service = Service {
@@ -279,10 +807,11 @@ async fn build_gatt_application(state: Arc<BleHidState>) -> Result<Application>
                 uuid: PROTOCOL_MODE_UUID,
                 read: Some(CharacteristicRead {
                     read: true,
-                    fun: Box::new(|_req| {
+                    fun: Box::new(move |req| {
+                        let mode = read_modes.get(req.device_address);
                         async move {
-                            log::debug!("读取 Protocol Mode");
-                            Ok(vec![0x01]) // Report Protocol
+                            log::debug!("读取 Protocol Mode: {}", mode);
+                            Ok(vec![mode])
                         }
                         .boxed()
                     }),
@@ -290,9 +819,16 @@ async fn build_gatt_application(state: Arc<BleHidState>) -> Result<Application>
                 }),
                 write: Some(CharacteristicWrite {
                     write_without_response: true,
-                    method: CharacteristicWriteMethod::Fun(Box::new(|new_value, _req| {
+                    method: CharacteristicWriteMethod::Fun(Box::new(move |new_value, req| {
+                        if let Some(&mode) = new_value.first() {
+                            write_modes.set(req.device_address, mode);
+                        }
                         async move {
-                            log::info!("Protocol Mode 写入: {:?}", new_value);
+                            log::info!(
+                                "Protocol Mode 写入: {:?} (主机 {})",
+                                new_value,
+                                req.device_address
+                            );
                             Ok(())
                         }
                         .boxed()
@@ -307,7 +843,8 @@ async fn build_gatt_application(state: Arc<BleHidState>) -> Result<Application>
                 read: Some(CharacteristicRead {
                     read: true,
                     encrypt_read: true, // 加密读取
-                    fun: Box::new(|_req| {
+                    fun: Box::new(move |req| {
+                        info_mtu.update(req.mtu);
                         async move {
                             log::debug!("读取 HID Information");
                             Ok(HID_INFORMATION.to_vec())
@@ -323,7 +860,8 @@ async fn build_gatt_application(state: Arc<BleHidState>) -> Result<Application>
                 uuid: HID_REPORT_MAP_UUID,
                 read: Some(CharacteristicRead {
                     read: true,
-                    fun: Box::new(|_req| {
+                    fun: Box::new(move |req| {
+                        map_mtu.update(req.mtu);
                         async move {
                             log::info!("读取 Report Map ({} bytes)", HID_REPORT_MAP.len());
                             Ok(HID_REPORT_MAP.to_vec())
@@ -370,17 +908,13 @@ async fn build_gatt_application(state: Arc<BleHidState>) -> Result<Application>
                 notify: Some(CharacteristicNotify {
                     notify: true,
                     method: CharacteristicNotifyMethod::Fun(Box::new(move |mut notifier| {
-                        let keyboard_notifier = Arc::clone(&keyboard_notifier);
+                        let keyboard_notifier = keyboard_notifier.clone();
                         async move {
-                            let (tx, mut rx) = mpsc::channel::<Vec<u8>>(16);
-                            {
-                                let mut guard = keyboard_notifier.lock().await;
-                                *guard = Some(tx);
-                            }
+                            let mut rx = keyboard_notifier.subscribe().await;
                             log::info!("键盘 Report 通知已启用");
 
                             while let Some(report) = rx.recv().await {
-                                log::debug!("发送键盘报告: {:02X?}", report);
+                                report::log_sent("BLE", "键盘", &report);
                                 if let Err(e) = notifier.notify(report).await {
                                     log::error!("通知发送失败: {}", e);
                                     break;
@@ -414,6 +948,58 @@ async fn build_gatt_application(state: Arc<BleHidState>) -> Result<Application>
                 ],
                 ..Default::default()
             },
+            // Report Characteristic - 键盘 LED 输出报告 (Report ID 1)
+            Characteristic {
+                uuid: HID_REPORT_UUID,
+                read: Some(CharacteristicRead {
+                    read: true,
+                    encrypt_read: true,
+                    fun: Box::new(move |req| {
+                        let leds = read_leds
+                            .get(&req.device_address.to_string())
+                            .map(|pref| pref.leds)
+                            .unwrap_or_default();
+                        async move { Ok(vec![leds.to_byte()]) }.boxed()
+                    }),
+                    ..Default::default()
+                }),
+                write: Some(CharacteristicWrite {
+                    write: true,
+                    write_without_response: true,
+                    encrypt_write: true,
+                    method: CharacteristicWriteMethod::Fun(Box::new(move |new_value, req| {
+                        if let Some(&byte) = new_value.first() {
+                            write_leds.set_leds(
+                                &req.device_address.to_string(),
+                                LedState::from_byte(byte),
+                            );
+                        }
+                        async move {
+                            log::debug!(
+                                "LED 输出报告: {:02X?} (主机 {})",
+                                new_value,
+                                req.device_address
+                            );
+                            Ok(())
+                        }
+                        .boxed()
+                    })),
+                    ..Default::default()
+                }),
+                descriptors: vec![Descriptor {
+                    uuid: REPORT_REFERENCE_UUID,
+                    read: Some(DescriptorRead {
+                        read: true,
+                        fun: Box::new(|_req| {
+                            // [Report ID=1, Type=Output(0x02)]
+                            async move { Ok(vec![0x01, 0x02]) }.boxed()
+                        }),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
             // Report Characteristic - 鼠标输入报告 (Report ID 2)
             Characteristic {
                 uuid: HID_REPORT_UUID,
@@ -434,17 +1020,13 @@ async fn build_gatt_application(state: Arc<BleHidState>) -> Result<Application>
                 notify: Some(CharacteristicNotify {
                     notify: true,
                     method: CharacteristicNotifyMethod::Fun(Box::new(move |mut notifier| {
-                        let mouse_notifier = Arc::clone(&mouse_notifier);
+                        let mouse_notifier = mouse_notifier.clone();
                         async move {
-                            let (tx, mut rx) = mpsc::channel::<Vec<u8>>(16);
-                            {
-                                let mut guard = mouse_notifier.lock().await;
-                                *guard = Some(tx);
-                            }
+                            let mut rx = mouse_notifier.subscribe().await;
                             log::info!("鼠标 Report 通知已启用");
 
                             while let Some(report) = rx.recv().await {
-                                log::trace!("发送鼠标报告: {:02X?}", report);
+                                report::log_sent("BLE", "鼠标", &report);
                                 if let Err(e) = notifier.notify(report).await {
                                     log::error!("通知发送失败: {}", e);
                                     break;
@@ -474,6 +1056,116 @@ async fn build_gatt_application(state: Arc<BleHidState>) -> Result<Application>
                 }],
                 ..Default::default()
             },
+            // Report Characteristic - 消费类控制输入报告 (Report ID 3)
+            Characteristic {
+                uuid: HID_REPORT_UUID,
+                read: Some(CharacteristicRead {
+                    read: true,
+                    encrypt_read: true,
+                    fun: Box::new(|_req| {
+                        async move {
+                            log::debug!("读取 Consumer Report");
+                            // 不包含 Report ID: [usage 低字节, usage 高字节]
+                            Ok(vec![0x00, 0x00])
+                        }
+                        .boxed()
+                    }),
+                    ..Default::default()
+                }),
+                notify: Some(CharacteristicNotify {
+                    notify: true,
+                    method: CharacteristicNotifyMethod::Fun(Box::new(move |mut notifier| {
+                        let consumer_notifier = consumer_notifier.clone();
+                        async move {
+                            let mut rx = consumer_notifier.subscribe().await;
+                            log::info!("消费类控制 Report 通知已启用");
+
+                            while let Some(report) = rx.recv().await {
+                                report::log_sent("BLE", "消费类控制", &report);
+                                if let Err(e) = notifier.notify(report).await {
+                                    log::error!("通知发送失败: {}", e);
+                                    break;
+                                }
+                            }
+                            log::info!("消费类控制 Report 通知已停止");
+                        }
+                        .boxed()
+                    })),
+                    ..Default::default()
+                }),
+                descriptors: vec![Descriptor {
+                    uuid: REPORT_REFERENCE_UUID,
+                    read: Some(DescriptorRead {
+                        read: true,
+                        fun: Box::new(|_req| {
+                            async move {
+                                log::debug!("读取 Consumer Report Reference");
+                                // [Report ID=3, Type=Input(0x01)]
+                                Ok(vec![0x03, 0x01])
+                            }
+                            .boxed()
+                        }),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            // Report Characteristic - 系统控制输入报告 (Report ID 4)
+            Characteristic {
+                uuid: HID_REPORT_UUID,
+                read: Some(CharacteristicRead {
+                    read: true,
+                    encrypt_read: true,
+                    fun: Box::new(|_req| {
+                        async move {
+                            log::debug!("读取 System Report");
+                            // 不包含 Report ID: [usage]
+                            Ok(vec![0x00])
+                        }
+                        .boxed()
+                    }),
+                    ..Default::default()
+                }),
+                notify: Some(CharacteristicNotify {
+                    notify: true,
+                    method: CharacteristicNotifyMethod::Fun(Box::new(move |mut notifier| {
+                        let system_notifier = system_notifier.clone();
+                        async move {
+                            let mut rx = system_notifier.subscribe().await;
+                            log::info!("系统控制 Report 通知已启用");
+
+                            while let Some(report) = rx.recv().await {
+                                report::log_sent("BLE", "系统控制", &report);
+                                if let Err(e) = notifier.notify(report).await {
+                                    log::error!("通知发送失败: {}", e);
+                                    break;
+                                }
+                            }
+                            log::info!("系统控制 Report 通知已停止");
+                        }
+                        .boxed()
+                    })),
+                    ..Default::default()
+                }),
+                descriptors: vec![Descriptor {
+                    uuid: REPORT_REFERENCE_UUID,
+                    read: Some(DescriptorRead {
+                        read: true,
+                        fun: Box::new(|_req| {
+                            async move {
+                                log::debug!("读取 System Report Reference");
+                                // [Report ID=4, Type=Input(0x01)]
+                                Ok(vec![0x04, 0x01])
+                            }
+                            .boxed()
+                        }),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
         ],
         ..Default::default()
     };
@@ -560,65 +1252,77 @@ async fn build_gatt_application(state: Arc<BleHidState>) -> Result<Application>
 #[async_trait]
 impl HidReportSender for BluetoothBleKeyboardHidDevice {
     async fn send_report(&mut self, report: InputReport) -> Result<()> {
-        if let InputReport::Keyboard { modifiers, keys } = report {
-            let guard = self.keyboard_notifier.lock().await;
-            if let Some(ref tx) = *guard {
-                // BLE HID 通知时不包含 Report ID！
-                // Report ID 通过 Report Reference Descriptor 标识
-                // 只发送: [modifier, reserved, 6 keys] = 8 字节
-                let mut hid_report = Vec::with_capacity(8);
-                hid_report.push(modifiers);
-                hid_report.push(0x00); // reserved
-                for i in 0..6 {
-                    hid_report.push(*keys.get(i).unwrap_or(&0x00));
-                }
-
-                tx.send(hid_report)
-                    .await
-                    .map_err(|e| BleError(format!("发送报告失败: {}", e)))?;
-            } else {
-                return Err(BleError("通知器未就绪".to_string()).into());
-            }
+        if let InputReport::Consumer { usage } = report {
+            // 只发送: [usage 低字节, usage 高字节] = 2 字节
+            let hid_report = report::build_consumer(usage, Framing::RAW);
+            self.mtu.check(&hid_report)?;
+            self.consumer_notifier.send(hid_report).await?;
+        } else if let InputReport::System { usage } = report {
+            let hid_report = report::build_system(usage, Framing::RAW);
+            self.mtu.check(&hid_report)?;
+            self.system_notifier.send(hid_report).await?;
+        } else if let InputReport::Keyboard { modifiers, keys } = report {
+            // BLE HID 通知时不包含 Report ID！
+            // Report ID 通过 Report Reference Descriptor 标识
+            // 只发送: [modifier, reserved, 6 keys] = 8 字节
+            let hid_report = report::build_keyboard(modifiers, &keys, Framing::RAW);
+            self.mtu.check(&hid_report)?;
+            self.keyboard_notifier.send(hid_report).await?;
         }
         Ok(())
     }
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities::KEYBOARD | BackendCapabilities::CONSUMER | BackendCapabilities::SYSTEM
+    }
 }
 
 #[async_trait]
 impl HidReportSender for BluetoothBleMouseHidDevice {
     async fn send_report(&mut self, report: InputReport) -> Result<()> {
-        if let InputReport::Mouse {
+        let admitted = self.rate_cap.admit(report);
+        self.notify(admitted).await
+    }
+
+    /// 每个报告之前等到报告率上限允许再发送，连续的位移不会被合并掉
+    async fn send_reports(&mut self, reports: &[InputReport]) -> Result<()> {
+        for report in reports {
+            let wait = self.rate_cap.wait_time();
+            if !wait.is_zero() {
+                tokio::time::sleep(wait).await;
+            }
+            self.send_report(report.clone()).await?;
+        }
+        Ok(())
+    }
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities::MOUSE
+    }
+
+    fn flush_deadline(&self) -> Option<std::time::Instant> {
+        self.rate_cap.flush_deadline()
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        let flushed = self.rate_cap.flush();
+        self.notify(flushed).await
+    }
+}
+
+impl BluetoothBleMouseHidDevice {
+    /// 通知已通过报告率上限的鼠标报告
+    async fn notify(&mut self, report: Option<InputReport>) -> Result<()> {
+        if let Some(InputReport::Mouse {
             buttons,
             x,
             y,
             wheel,
-        } = report
+        }) = report
         {
-            let guard = self.mouse_notifier.lock().await;
-            if let Some(ref tx) = *guard {
-                let clamp_i8 = |v: i16| -> i8 {
-                    if v > 127 {
-                        127
-                    } else if v < -127 {
-                        -127
-                    } else {
-                        v as i8
-                    }
-                };
-                let x = clamp_i8(x) as u8;
-                let y = clamp_i8(y) as u8;
-                let wheel = (wheel as i8) as u8;
-
-                // BLE HID 通知时不包含 Report ID！
-                // 只发送: [buttons, x, y, wheel] = 4 字节
-                let hid_report = vec![buttons, x, y, wheel];
-                // log::info!("发送鼠标报告: {:02X?}", hid_report);
-                tx.send(hid_report)
-                    .await
-                    .map_err(|e| BleError(format!("发送报告失败: {}", e)))?;
-            } else {
-                return Err(BleError("通知器未就绪".to_string()).into());
-            }
+            // BLE HID 通知时不包含 Report ID！
+            // 只发送: [buttons, x, y, wheel] = 4 字节
+            let hid_report = report::build_mouse(buttons, x, y, wheel, Framing::RAW);
+            self.mtu.check(&hid_report)?;
+            self.mouse_notifier.send(hid_report).await?;
         }
         Ok(())
     }
@@ -627,15 +1331,139 @@ impl HidReportSender for BluetoothBleMouseHidDevice {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::output::pairing::AutoAccept;
     use std::time::Duration;
 
+    #[tokio::test]
+    async fn test_report_fans_out_to_all_subscribers() {
+        let subscribers = ReportSubscribers::default();
+        assert!(subscribers.send(vec![0x01]).await.is_err());
+
+        let mut first = subscribers.subscribe().await;
+        let mut second = subscribers.subscribe().await;
+        subscribers.send(vec![0x02, 0x04]).await.unwrap();
+        assert_eq!(first.recv().await, Some(vec![0x02, 0x04]));
+        assert_eq!(second.recv().await, Some(vec![0x02, 0x04]));
+
+        // 第一个主机停止通知后，第二个主机不受影响
+        drop(first);
+        subscribers.send(vec![0x00]).await.unwrap();
+        assert_eq!(second.recv().await, Some(vec![0x00]));
+        assert!(!subscribers.is_empty().await);
+
+        drop(second);
+        assert!(subscribers.is_empty().await);
+    }
+
+    #[test]
+    fn test_peer_is_connected_and_paired_device() {
+        let device = |address: &str, connected, paired| PeerCandidate {
+            address: address.to_string(),
+            connected,
+            paired,
+        };
+        let devices = [
+            device("11:11:11:11:11:11", true, false),
+            device("22:22:22:22:22:22", false, true),
+        ];
+        assert_eq!(select_peer(&devices), None);
+
+        let devices = [devices[0].clone(), device("33:33:33:33:33:33", true, true)];
+        assert_eq!(select_peer(&devices).as_deref(), Some("33:33:33:33:33:33"));
+    }
+
+    #[tokio::test]
+    async fn test_stalled_subscriber_does_not_block_others() {
+        let subscribers = ReportSubscribers::default();
+        let mut stalled = subscribers.subscribe().await;
+        let mut active = subscribers.subscribe().await;
+
+        for i in 0..(SUBSCRIBER_CAPACITY as u8 + 4) {
+            tokio::time::timeout(Duration::from_secs(1), subscribers.send(vec![i]))
+                .await
+                .expect("积压的订阅者阻塞了发送")
+                .unwrap();
+            assert_eq!(active.recv().await, Some(vec![i]));
+        }
+
+        // 积压满后的报告被丢弃，之后仍能收到新报告
+        for i in 0..SUBSCRIBER_CAPACITY as u8 {
+            assert_eq!(stalled.recv().await, Some(vec![i]));
+        }
+        subscribers.send(vec![0xFF]).await.unwrap();
+        assert_eq!(stalled.recv().await, Some(vec![0xFF]));
+    }
+
+    #[test]
+    fn test_protocol_mode_per_host() {
+        let modes = ProtocolModes::default();
+        let (a, b) = (
+            Address::new([0xAA, 0, 0, 0, 0, 0x01]),
+            Address::new([0xBB, 0, 0, 0, 0, 0x02]),
+        );
+        modes.set(a, 0x00);
+        assert_eq!(modes.get(a), 0x00);
+        assert_eq!(modes.get(b), ProtocolModes::REPORT);
+    }
+
+    #[test]
+    fn test_report_must_fit_att_mtu() {
+        let mtu = AttMtu::new(DEFAULT_ATT_MTU);
+        assert_eq!(mtu.max_payload(), 20);
+        assert!(mtu.check(&[0u8; 8]).is_ok());
+
+        mtu.update(10);
+        let err = mtu.check(&[0u8; 8]).unwrap_err().to_string();
+        assert!(err.contains("报告长度 8 字节"), "{err}");
+        assert!(err.contains("MTU 10"), "{err}");
+        assert!(mtu.check(&[0u8; 7]).is_ok());
+    }
+
+    #[test]
+    fn test_advertisement_follows_config() {
+        let adv = build_advertisement(&BleConfig::default());
+        assert_eq!(adv.local_name.as_deref(), Some("BLE Keyboard"));
+        assert_eq!(adv.appearance, Some(0x03C2));
+
+        let keyboard_only = BleConfig {
+            name: "Desk Bridge".to_string(),
+            mouse_enabled: false,
+            ..BleConfig::default()
+        };
+        let adv = build_advertisement(&keyboard_only);
+        assert_eq!(adv.local_name.as_deref(), Some("Desk Bridge"));
+        assert_eq!(adv.appearance, Some(0x03C1));
+
+        let custom = BleConfig {
+            appearance: Some(0x03C0),
+            ..BleConfig::default()
+        };
+        assert_eq!(build_advertisement(&custom).appearance, Some(0x03C0));
+    }
+
+    #[test]
+    fn test_select_named_adapter() {
+        let available = vec!["hci0".to_string(), "hci1".to_string()];
+        assert_eq!(select_adapter_name(None, &available).unwrap(), None);
+        assert_eq!(
+            select_adapter_name(Some("hci1"), &available).unwrap(),
+            Some("hci1".to_string())
+        );
+
+        let err = select_adapter_name(Some("hci2"), &available).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("hci2"));
+        assert!(msg.contains("hci0, hci1"));
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_ble_hid_connection() -> Result<()> {
         env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
-        let (mut keyboard, mouse, _session) = build_ble_hid_device().await?;
-        let (_app_handle, _adv_handle) = run_ble_server(&keyboard, &mouse).await?;
+        let (mut keyboard, mouse, _session) =
+            build_ble_hid_device(&BleConfig::default(), Arc::new(AutoAccept::default())).await?;
+        let _handles = run_ble_server(&keyboard, &mouse, &BleConfig::default()).await?;
 
         println!("--------------------------------------------------");
         println!("BLE HID 测试已启动！");
@@ -645,7 +1473,7 @@ mod tests {
         for i in 0..120 {
             tokio::time::sleep(Duration::from_secs(1)).await;
 
-            let is_ready = keyboard.keyboard_notifier.lock().await.is_some();
+            let is_ready = !keyboard.keyboard_notifier.is_empty().await;
 
             if is_ready {
                 println!("连接成功！等待 2 秒后发送测试按键...");
@@ -678,8 +1506,9 @@ mod tests {
     async fn test_ble_mouse_square_motion() -> Result<()> {
         env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
-        let (_keyboard, mut mouse, _session) = build_ble_hid_device().await?;
-        let (_app_handle, _adv_handle) = run_ble_server(&_keyboard, &mouse).await?;
+        let (_keyboard, mut mouse, _session) =
+            build_ble_hid_device(&BleConfig::default(), Arc::new(AutoAccept::default())).await?;
+        let _handles = run_ble_server(&_keyboard, &mouse, &BleConfig::default()).await?;
 
         println!("--------------------------------------------------");
         println!("BLE 鼠标测试已启动！");
@@ -690,7 +1519,7 @@ mod tests {
         for i in 0..120 {
             tokio::time::sleep(Duration::from_secs(1)).await;
 
-            let is_ready = mouse.mouse_notifier.lock().await.is_some();
+            let is_ready = !mouse.mouse_notifier.is_empty().await;
 
             if is_ready {
                 println!("鼠标连接成功！等待 2 秒后开始移动...");
@@ -0,0 +1,436 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::PathBuf;
+use tokio::io::unix::AsyncFd;
+
+/// 期望的 BLE 连接参数
+///
+/// 主机（尤其是 iOS/Android）默认的连接间隔较长，指针会有明显延迟。BlueZ 没有为外设角色
+/// 提供设置连接参数的 D-Bus 接口；内核在作为外设被连接后，若当前间隔不在
+/// `conn_min_interval..=conn_max_interval` 内，会向主机发送连接参数更新请求，
+/// 因此这里通过 debugfs 设置这几个值。主机可以拒绝请求，实际协商结果由 [`log_negotiated`]
+/// 从控制器上报的事件中读取。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnParams {
+    /// 最小连接间隔，单位 1.25 ms
+    pub min_interval: u16,
+    /// 最大连接间隔，单位 1.25 ms
+    pub max_interval: u16,
+    /// 外设延迟（可跳过的连接事件数）
+    pub latency: u16,
+    /// 监督超时，单位 10 ms
+    pub supervision_timeout: u16,
+}
+
+impl Default for ConnParams {
+    fn default() -> Self {
+        Self {
+            min_interval: 6,  // 7.5 ms
+            max_interval: 12, // 15 ms
+            latency: 0,
+            supervision_timeout: 200, // 2 s
+        }
+    }
+}
+
+impl ConnParams {
+    /// 按蓝牙核心规范检查取值范围
+    pub fn validate(&self) -> Result<()> {
+        if !(6..=3200).contains(&self.min_interval) || !(6..=3200).contains(&self.max_interval) {
+            bail!(
+                "连接间隔 {}~{} 超出范围（6~3200，单位 1.25 ms）",
+                self.min_interval,
+                self.max_interval
+            );
+        }
+        if self.min_interval > self.max_interval {
+            bail!(
+                "最小连接间隔 {} 大于最大连接间隔 {}",
+                self.min_interval,
+                self.max_interval
+            );
+        }
+        if self.latency > 499 {
+            bail!("外设延迟 {} 超出范围（0~499）", self.latency);
+        }
+        if !(10..=3200).contains(&self.supervision_timeout) {
+            bail!(
+                "监督超时 {} 超出范围（10~3200，单位 10 ms）",
+                self.supervision_timeout
+            );
+        }
+        // 超时必须大于 (1 + latency) * max_interval * 2
+        if self.supervision_timeout as u32 * 4
+            <= (1 + self.latency as u32) * self.max_interval as u32
+        {
+            bail!(
+                "监督超时 {} ms 不足以覆盖外设延迟 {} 与最大连接间隔 {} ms",
+                self.supervision_timeout as u32 * 10,
+                self.latency,
+                self.max_interval as f32 * 1.25
+            );
+        }
+        Ok(())
+    }
+}
+
+/// 读写适配器的连接参数默认值，测试中可替换为模拟实现
+pub trait ConnParamsApi {
+    fn read(&self, name: &str) -> Result<u16>;
+    fn write(&self, name: &str, value: u16) -> Result<()>;
+}
+
+/// 通过 `/sys/kernel/debug/bluetooth/<adapter>/` 读写，需要 root 且已挂载 debugfs
+pub struct DebugfsConnParams {
+    dir: PathBuf,
+}
+
+impl DebugfsConnParams {
+    pub fn for_adapter(adapter: &str) -> Self {
+        Self {
+            dir: PathBuf::from("/sys/kernel/debug/bluetooth").join(adapter),
+        }
+    }
+}
+
+impl ConnParamsApi for DebugfsConnParams {
+    fn read(&self, name: &str) -> Result<u16> {
+        let path = self.dir.join(name);
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("读取 {} 失败", path.display()))?;
+        text.trim()
+            .parse()
+            .with_context(|| format!("解析 {} 失败: {:?}", path.display(), text))
+    }
+
+    fn write(&self, name: &str, value: u16) -> Result<()> {
+        let path = self.dir.join(name);
+        std::fs::write(&path, value.to_string())
+            .with_context(|| format!("写入 {} 失败", path.display()))
+    }
+}
+
+const MIN_INTERVAL: &str = "conn_min_interval";
+const MAX_INTERVAL: &str = "conn_max_interval";
+const LATENCY: &str = "conn_latency";
+const SUPERVISION_TIMEOUT: &str = "supervision_timeout";
+
+/// 检查并写入连接参数，返回写入后读回的值
+pub fn apply(api: &dyn ConnParamsApi, params: ConnParams) -> Result<ConnParams> {
+    params.validate()?;
+    // 内核拒绝最小值大于当前最大值（或反之）的写入，按需调整先后顺序
+    if params.min_interval > api.read(MAX_INTERVAL)? {
+        api.write(MAX_INTERVAL, params.max_interval)?;
+        api.write(MIN_INTERVAL, params.min_interval)?;
+    } else {
+        api.write(MIN_INTERVAL, params.min_interval)?;
+        api.write(MAX_INTERVAL, params.max_interval)?;
+    }
+    api.write(LATENCY, params.latency)?;
+    api.write(SUPERVISION_TIMEOUT, params.supervision_timeout)?;
+    Ok(ConnParams {
+        min_interval: api.read(MIN_INTERVAL)?,
+        max_interval: api.read(MAX_INTERVAL)?,
+        latency: api.read(LATENCY)?,
+        supervision_timeout: api.read(SUPERVISION_TIMEOUT)?,
+    })
+}
+
+/// 控制器在连接建立或参数更新后上报的实际连接参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Negotiated {
+    /// 连接间隔，单位 1.25 ms
+    pub interval: u16,
+    /// 外设延迟
+    pub latency: u16,
+    /// 监督超时，单位 10 ms
+    pub supervision_timeout: u16,
+}
+
+const BTPROTO_HCI: libc::c_int = 1;
+const SOL_HCI: libc::c_int = 0;
+const HCI_FILTER: libc::c_int = 2;
+const HCI_CHANNEL_RAW: u16 = 0;
+const HCI_EVENT_PKT: u8 = 0x04;
+const EVT_LE_META: u8 = 0x3E;
+const LE_CONN_COMPLETE: u8 = 0x01;
+const LE_CONN_UPDATE_COMPLETE: u8 = 0x03;
+const LE_ENHANCED_CONN_COMPLETE: u8 = 0x0A;
+const LE_ENHANCED_CONN_COMPLETE_V2: u8 = 0x29;
+
+#[repr(C)]
+struct SockaddrHci {
+    hci_family: libc::sa_family_t,
+    hci_dev: u16,
+    hci_channel: u16,
+}
+
+#[repr(C)]
+struct HciFilter {
+    type_mask: u32,
+    event_mask: [u32; 2],
+    opcode: u16,
+}
+
+/// 从 HCI 事件包中取出连接参数，不是连接完成或参数更新完成事件、或状态非 0 时返回 `None`
+fn parse_le_event(packet: &[u8]) -> Option<Negotiated> {
+    // [包类型, 事件码, 参数长度, 子事件, 参数...]
+    let [HCI_EVENT_PKT, EVT_LE_META, _, subevent, params @ ..] = packet else {
+        return None;
+    };
+    // 各子事件中连接间隔的偏移，其后依次为延迟与监督超时
+    let offset = match *subevent {
+        LE_CONN_COMPLETE => 11,
+        LE_CONN_UPDATE_COMPLETE => 3,
+        LE_ENHANCED_CONN_COMPLETE | LE_ENHANCED_CONN_COMPLETE_V2 => 23,
+        _ => return None,
+    };
+    if params.first() != Some(&0) {
+        return None;
+    }
+    let field = |at: usize| {
+        params
+            .get(at..at + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    };
+    Some(Negotiated {
+        interval: field(offset)?,
+        latency: field(offset + 2)?,
+        supervision_timeout: field(offset + 4)?,
+    })
+}
+
+/// 打开只接收 LE Meta 事件的原始 HCI 套接字，需要 `CAP_NET_RAW`
+fn open_le_events(adapter: &str) -> std::io::Result<OwnedFd> {
+    let dev: u16 = adapter
+        .strip_prefix("hci")
+        .and_then(|index| index.parse().ok())
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("无法识别的适配器名称: {}", adapter),
+            )
+        })?;
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_BLUETOOTH,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC | libc::SOCK_NONBLOCK,
+            BTPROTO_HCI,
+        )
+    };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let filter = HciFilter {
+        type_mask: 1 << HCI_EVENT_PKT,
+        event_mask: [0, 1 << (EVT_LE_META - 32)],
+        opcode: 0,
+    };
+    let ret = unsafe {
+        libc::setsockopt(
+            fd.as_raw_fd(),
+            SOL_HCI,
+            HCI_FILTER,
+            (&filter as *const HciFilter).cast(),
+            size_of::<HciFilter>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let addr = SockaddrHci {
+        hci_family: libc::AF_BLUETOOTH as libc::sa_family_t,
+        hci_dev: dev,
+        hci_channel: HCI_CHANNEL_RAW,
+    };
+    let ret = unsafe {
+        libc::bind(
+            fd.as_raw_fd(),
+            (&addr as *const SockaddrHci).cast(),
+            size_of::<SockaddrHci>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(fd)
+}
+
+/// 监听适配器的 HCI 事件，记录每次连接建立或参数更新后控制器给出的实际连接参数
+pub async fn log_negotiated(adapter: String) {
+    let socket = match open_le_events(&adapter).and_then(AsyncFd::new) {
+        Ok(socket) => socket,
+        Err(e) => {
+            log::warn!(
+                "无法监听 {} 的 HCI 事件，不记录协商后的连接参数: {}",
+                adapter,
+                e
+            );
+            return;
+        }
+    };
+    let mut buf = [0u8; 260];
+    loop {
+        let mut guard = match socket.readable().await {
+            Ok(guard) => guard,
+            Err(e) => {
+                log::warn!("等待 HCI 事件失败: {}", e);
+                return;
+            }
+        };
+        let read = guard.try_io(|fd| {
+            let n = unsafe { libc::read(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+            if n < 0 {
+                Err(std::io::Error::last_os_error())
+            } else {
+                Ok(n as usize)
+            }
+        });
+        match read {
+            Ok(Ok(len)) => {
+                if let Some(params) = parse_le_event(&buf[..len]) {
+                    log::info!(
+                        "BLE 连接参数已协商: 间隔 {} ms，延迟 {}，超时 {} ms",
+                        params.interval as f32 * 1.25,
+                        params.latency,
+                        params.supervision_timeout as u32 * 10
+                    );
+                }
+            }
+            Ok(Err(e)) => {
+                log::warn!("读取 HCI 事件失败: {}", e);
+                return;
+            }
+            Err(_would_block) => continue,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    /// 模拟内核的 debugfs 属性，按内核的规则拒绝不一致的间隔
+    struct MockApi {
+        values: RefCell<HashMap<String, u16>>,
+        writes: RefCell<Vec<(String, u16)>>,
+    }
+
+    impl ConnParamsApi for MockApi {
+        fn read(&self, name: &str) -> Result<u16> {
+            Ok(self.values.borrow()[name])
+        }
+
+        fn write(&self, name: &str, value: u16) -> Result<()> {
+            let rejected = match name {
+                MIN_INTERVAL => value > self.read(MAX_INTERVAL)?,
+                MAX_INTERVAL => value < self.read(MIN_INTERVAL)?,
+                _ => false,
+            };
+            if rejected {
+                bail!("{} = {} 被拒绝", name, value);
+            }
+            self.values.borrow_mut().insert(name.to_string(), value);
+            self.writes.borrow_mut().push((name.to_string(), value));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_apply_passes_params_to_adapter() {
+        // 内核默认值：间隔 24~40，延迟 0，超时 42
+        let api = MockApi {
+            values: RefCell::new(HashMap::from([
+                (MIN_INTERVAL.to_string(), 24),
+                (MAX_INTERVAL.to_string(), 40),
+                (LATENCY.to_string(), 0),
+                (SUPERVISION_TIMEOUT.to_string(), 42),
+            ])),
+            writes: RefCell::default(),
+        };
+
+        let low = ConnParams::default();
+        assert_eq!(apply(&api, low).unwrap(), low);
+
+        // 调高到当前最大值以上时先写最大值
+        api.writes.borrow_mut().clear();
+        let high = ConnParams {
+            min_interval: 80,
+            max_interval: 100,
+            latency: 4,
+            supervision_timeout: 600,
+        };
+        assert_eq!(apply(&api, high).unwrap(), high);
+        assert_eq!(api.writes.borrow()[0], (MAX_INTERVAL.to_string(), 100));
+
+        // 超出范围的参数不写入
+        api.writes.borrow_mut().clear();
+        for invalid in [
+            ConnParams {
+                min_interval: 5,
+                ..low
+            },
+            ConnParams {
+                min_interval: 20,
+                max_interval: 10,
+                ..low
+            },
+            ConnParams {
+                latency: 500,
+                ..low
+            },
+            ConnParams {
+                max_interval: 400,
+                supervision_timeout: 100,
+                ..low
+            },
+        ] {
+            assert!(apply(&api, invalid).is_err(), "{:?}", invalid);
+        }
+        assert!(api.writes.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_parse_negotiated_params() {
+        // LE Connection Complete：间隔 12（15 ms），延迟 0，超时 200
+        let complete = [
+            0x04, 0x3E, 0x13, 0x01, 0x00, 0x40, 0x00, 0x01, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55,
+            0x66, 0x0C, 0x00, 0x00, 0x00, 0xC8, 0x00, 0x00,
+        ];
+        assert_eq!(
+            parse_le_event(&complete),
+            Some(Negotiated {
+                interval: 12,
+                latency: 0,
+                supervision_timeout: 200,
+            })
+        );
+
+        // LE Connection Update Complete：主机改为间隔 24、延迟 4、超时 600
+        let update = [
+            0x04, 0x3E, 0x0A, 0x03, 0x00, 0x40, 0x00, 0x18, 0x00, 0x04, 0x00, 0x58, 0x02,
+        ];
+        assert_eq!(
+            parse_le_event(&update),
+            Some(Negotiated {
+                interval: 24,
+                latency: 4,
+                supervision_timeout: 600,
+            })
+        );
+
+        // 失败的更新、其他子事件与截断的包都忽略
+        let mut failed = update;
+        failed[4] = 0x3B;
+        assert_eq!(parse_le_event(&failed), None);
+        assert_eq!(parse_le_event(&[0x04, 0x3E, 0x02, 0x02, 0x00]), None);
+        assert_eq!(parse_le_event(&update[..10]), None);
+    }
+}
@@ -0,0 +1,426 @@
+use super::{BackendCapabilities, HidReportSender};
+use crate::input::InputReport;
+use crate::metrics::{self, ReportKind, Stage};
+use anyhow::Result;
+use async_trait::async_trait;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+
+/// 后端连接状态
+///
+/// 通过 watch 通道广播“是否可以发送报告”，并记录最近一次成功发送的时间，
+/// 供健康检查和其他需要感知连接变化的模块订阅。
+#[derive(Clone)]
+pub struct ConnectionState {
+    connected: Arc<watch::Sender<bool>>,
+    /// 最近一次成功发送报告的 Unix 毫秒时间戳，0 表示尚未发送
+    last_report_ms: Arc<AtomicU64>,
+    /// 当前连接的主机地址，后端无法得知时为 `None`
+    peer: Arc<watch::Sender<Option<String>>>,
+}
+
+impl ConnectionState {
+    pub fn new(connected: bool) -> Self {
+        let (tx, _) = watch::channel(connected);
+        Self {
+            connected: Arc::new(tx),
+            last_report_ms: Arc::new(AtomicU64::new(0)),
+            peer: Arc::new(watch::channel(None).0),
+        }
+    }
+
+    /// 更新连接状态，只有状态变化时才通知订阅者
+    pub fn set_connected(&self, connected: bool) {
+        self.connected.send_if_modified(|current| {
+            let changed = *current != connected;
+            *current = connected;
+            changed
+        });
+    }
+
+    pub fn is_connected(&self) -> bool {
+        *self.connected.borrow()
+    }
+
+    /// 订阅连接状态变化
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.connected.subscribe()
+    }
+
+    /// 记录一次成功发送
+    pub fn mark_report_sent(&self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        self.last_report_ms.store(now, Ordering::Relaxed);
+    }
+
+    /// 最近一次成功发送的 Unix 毫秒时间戳
+    pub fn last_report_ms(&self) -> Option<u64> {
+        match self.last_report_ms.load(Ordering::Relaxed) {
+            0 => None,
+            ms => Some(ms),
+        }
+    }
+
+    /// 记录主机连接（`Some`）或断开（`None`），返回地址是否变化
+    pub fn set_peer(&self, peer: Option<String>) -> bool {
+        self.peer.send_if_modified(|current| {
+            let changed = *current != peer;
+            *current = peer;
+            changed
+        })
+    }
+
+    /// 当前连接的主机地址
+    pub fn peer(&self) -> Option<String> {
+        self.peer.borrow().clone()
+    }
+
+    /// 订阅主机地址变化
+    pub fn subscribe_peer(&self) -> watch::Receiver<Option<String>> {
+        self.peer.subscribe()
+    }
+}
+
+impl Default for ConnectionState {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+/// 带超时的发送端包装
+///
+/// 主机卡住时后端的 `send_report` 可能永远等待，而调用方持有发送端的锁，
+/// 会连带阻塞整个主循环。超时后记录日志、把后端标记为断开并丢弃该报告；
+/// 之后任何一次发送成功都会恢复连接状态。
+pub struct TimeoutSender {
+    inner: Box<dyn HidReportSender>,
+    /// 单个报告的发送超时，为零表示不限制
+    timeout: Duration,
+    connection: ConnectionState,
+    name: &'static str,
+}
+
+impl TimeoutSender {
+    /// - `name`: 日志中显示的后端名称
+    pub fn new(
+        inner: Box<dyn HidReportSender>,
+        timeout: Duration,
+        connection: ConnectionState,
+        name: &'static str,
+    ) -> Self {
+        Self {
+            inner,
+            timeout,
+            connection,
+            name,
+        }
+    }
+}
+
+#[async_trait]
+impl HidReportSender for TimeoutSender {
+    async fn send_report(&mut self, report: InputReport) -> Result<()> {
+        if self.timeout.is_zero() {
+            return self.inner.send_report(report).await;
+        }
+        let kind = ReportKind::of(&report);
+        match tokio::time::timeout(self.timeout, self.inner.send_report(report)).await {
+            Ok(result) => {
+                if result.is_ok() {
+                    self.connection.set_connected(true);
+                    self.connection.mark_report_sent();
+                }
+                result
+            }
+            Err(_) => {
+                warn!(
+                    "{} 发送报告超时（{:?}），已丢弃并标记为断开",
+                    self.name, self.timeout
+                );
+                metrics::global()
+                    .report_loss
+                    .record_dropped(Stage::BackendSend, kind);
+                self.connection.set_connected(false);
+                Ok(())
+            }
+        }
+    }
+
+    /// 整批共用一个超时，时长为单个报告超时乘以报告数
+    async fn send_reports(&mut self, reports: &[InputReport]) -> Result<()> {
+        if self.timeout.is_zero() || reports.is_empty() {
+            return self.inner.send_reports(reports).await;
+        }
+        let timeout = self.timeout * reports.len() as u32;
+        match tokio::time::timeout(timeout, self.inner.send_reports(reports)).await {
+            Ok(result) => {
+                if result.is_ok() {
+                    self.connection.set_connected(true);
+                    self.connection.mark_report_sent();
+                }
+                result
+            }
+            Err(_) => {
+                warn!(
+                    "{} 批量发送 {} 个报告超时（{:?}），已丢弃并标记为断开",
+                    self.name,
+                    reports.len(),
+                    timeout
+                );
+                for report in reports {
+                    metrics::global()
+                        .report_loss
+                        .record_dropped(Stage::BackendSend, ReportKind::of(report));
+                }
+                self.connection.set_connected(false);
+                Ok(())
+            }
+        }
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn flush_deadline(&self) -> Option<Instant> {
+        self.inner.flush_deadline()
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        if self.timeout.is_zero() {
+            return self.inner.flush().await;
+        }
+        match tokio::time::timeout(self.timeout, self.inner.flush()).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!(
+                    "{} 补发合并的鼠标报告超时（{:?}），已丢弃",
+                    self.name, self.timeout
+                );
+                metrics::global()
+                    .report_loss
+                    .record_dropped(Stage::BackendSend, ReportKind::Mouse);
+                self.connection.set_connected(false);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// 连接后的问候报告配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HelloConfig {
+    pub enabled: bool,
+    /// 问候报告之后、第一个真实报告之前的等待时间（毫秒）
+    pub delay_ms: u64,
+}
+
+impl Default for HelloConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            delay_ms: 50,
+        }
+    }
+}
+
+impl HelloConfig {
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_ms)
+    }
+}
+
+/// 连接后先发送空报告的发送端包装
+///
+/// 部分主机会忽略刚连接后的第一个报告。订阅连接状态，每次新连接后的第一个真实报告之前
+/// 先发送一个不产生任何输入的报告（空键盘报告或零位移鼠标报告），并等待一段时间让主机就绪。
+/// 创建时已连接（如 USB 启动时）按一次新连接处理。
+pub struct HelloSender {
+    inner: Box<dyn HidReportSender>,
+    connected: watch::Receiver<bool>,
+    delay: Duration,
+    /// 连接后尚未发送问候报告
+    pending: bool,
+}
+
+impl HelloSender {
+    pub fn new(
+        inner: Box<dyn HidReportSender>,
+        connection: &ConnectionState,
+        delay: Duration,
+    ) -> Self {
+        let mut connected = connection.subscribe();
+        let pending = *connected.borrow_and_update();
+        Self {
+            inner,
+            connected,
+            delay,
+            pending,
+        }
+    }
+
+    /// 后端能力对应的空报告
+    fn hello_report(&self) -> Option<InputReport> {
+        let caps = self.inner.capabilities();
+        if caps.contains(BackendCapabilities::KEYBOARD) {
+            Some(InputReport::Keyboard {
+                modifiers: 0,
+                keys: vec![],
+            })
+        } else if caps.contains(BackendCapabilities::MOUSE) {
+            Some(InputReport::Mouse {
+                buttons: 0,
+                x: 0,
+                y: 0,
+                wheel: 0,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+#[async_trait]
+impl HidReportSender for HelloSender {
+    async fn send_report(&mut self, report: InputReport) -> Result<()> {
+        // 状态只在变化时通知，读到的变化以已连接结束说明期间经历了断开再连接
+        if self.connected.has_changed().unwrap_or(false) {
+            self.pending = *self.connected.borrow_and_update();
+        }
+        if std::mem::take(&mut self.pending)
+            && let Some(hello) = self.hello_report()
+        {
+            info!("主机新连接，先发送空报告");
+            self.inner.send_report(hello).await?;
+            tokio::time::sleep(self.delay).await;
+        }
+        self.inner.send_report(report).await
+    }
+
+    async fn send_reports(&mut self, reports: &[InputReport]) -> Result<()> {
+        let Some((first, rest)) = reports.split_first() else {
+            return Ok(());
+        };
+        self.send_report(first.clone()).await?;
+        self.inner.send_reports(rest).await
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn flush_deadline(&self) -> Option<Instant> {
+        self.inner.flush_deadline()
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        self.inner.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::virtual_hid::VirtualHidDevice;
+
+    /// 永远不完成发送的后端
+    struct StalledSender;
+
+    #[async_trait]
+    impl HidReportSender for StalledSender {
+        async fn send_report(&mut self, _report: InputReport) -> Result<()> {
+            std::future::pending().await
+        }
+
+        fn capabilities(&self) -> BackendCapabilities {
+            BackendCapabilities::all()
+        }
+    }
+
+    fn release() -> InputReport {
+        InputReport::Keyboard {
+            modifiers: 0,
+            keys: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stalled_send_times_out_and_marks_disconnected() {
+        let connection = ConnectionState::new(true);
+        let changes = connection.subscribe();
+        let mut sender = TimeoutSender::new(
+            Box::new(StalledSender),
+            Duration::from_millis(20),
+            connection.clone(),
+            "test",
+        );
+
+        tokio::time::timeout(Duration::from_secs(1), sender.send_report(release()))
+            .await
+            .expect("发送没有在超时后返回")
+            .unwrap();
+        assert!(!connection.is_connected());
+        assert!(changes.has_changed().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_successful_send_restores_connection() {
+        let connection = ConnectionState::new(false);
+        let device = VirtualHidDevice::new();
+        let mut sender = TimeoutSender::new(
+            Box::new(device.clone()),
+            Duration::from_millis(20),
+            connection.clone(),
+            "test",
+        );
+
+        sender.send_report(release()).await.unwrap();
+        assert!(connection.is_connected());
+        assert!(connection.last_report_ms().is_some());
+        assert_eq!(device.reports().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_hello_precedes_first_report_after_connect() {
+        let connection = ConnectionState::new(false);
+        let device = VirtualHidDevice::new();
+        let mut sender = HelloSender::new(Box::new(device.clone()), &connection, Duration::ZERO);
+        let press = || InputReport::Keyboard {
+            modifiers: 0,
+            keys: vec![0x04],
+        };
+
+        connection.set_connected(true);
+        sender.send_report(press()).await.unwrap();
+        sender.send_report(release()).await.unwrap();
+        let reports = device.reports();
+        assert_eq!(reports.len(), 3);
+        assert!(matches!(&reports[0], InputReport::Keyboard { keys, .. } if keys.is_empty()));
+        assert!(matches!(&reports[1], InputReport::Keyboard { keys, .. } if keys == &vec![0x04]));
+
+        // 断开后重新连接，再次问候
+        connection.set_connected(false);
+        connection.set_connected(true);
+        sender.send_report(press()).await.unwrap();
+        assert_eq!(device.reports().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_hello_on_startup_when_already_connected() {
+        let connection = ConnectionState::new(true);
+        let device = VirtualHidDevice::new();
+        let mut sender = HelloSender::new(Box::new(device.clone()), &connection, Duration::ZERO);
+
+        sender.send_report(release()).await.unwrap();
+        sender.send_report(release()).await.unwrap();
+        assert_eq!(device.reports().len(), 3);
+    }
+}
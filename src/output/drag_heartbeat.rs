@@ -0,0 +1,139 @@
+use crate::input::InputReport;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// 拖拽心跳配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DragHeartbeatConfig {
+    pub enabled: bool,
+    /// 按住按键且没有移动时重发按键状态的间隔（毫秒）
+    pub interval_ms: u64,
+}
+
+impl Default for DragHeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: 50,
+        }
+    }
+}
+
+impl DragHeartbeatConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms.max(1))
+    }
+}
+
+/// 拖拽心跳
+///
+/// 部分主机协议栈在长时间收不到报告时会丢失按住状态，导致拖拽中断。
+/// 任一鼠标按键按住期间，距上一个鼠标报告超过间隔就重发一次当前按键状态（无位移），
+/// 全部松开后停止。心跳与其他报告一样经过后端的报告率上限。
+pub struct DragHeartbeat {
+    enabled: bool,
+    interval: Duration,
+    buttons: u8,
+    next: Option<Instant>,
+}
+
+impl DragHeartbeat {
+    pub fn new(config: &DragHeartbeatConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            interval: config.interval(),
+            buttons: 0,
+            next: None,
+        }
+    }
+
+    /// 记录一个已发送的报告
+    pub fn observe(&mut self, report: &InputReport, now: Instant) {
+        let InputReport::Mouse { buttons, .. } = *report else {
+            return;
+        };
+        self.buttons = buttons;
+        self.next = (self.enabled && buttons != 0).then(|| now + self.interval);
+    }
+
+    /// 所有按键已被释放，例如切换输出之后
+    pub fn reset(&mut self) {
+        self.buttons = 0;
+        self.next = None;
+    }
+
+    /// 下一次心跳的时间，没有按键按住时为 `None`
+    pub fn deadline(&self) -> Option<Instant> {
+        self.next
+    }
+
+    /// 到期时返回心跳报告
+    pub fn poll(&mut self, now: Instant) -> Option<InputReport> {
+        if now < self.next? {
+            return None;
+        }
+        self.next = Some(now + self.interval);
+        Some(InputReport::Mouse {
+            buttons: self.buttons,
+            x: 0,
+            y: 0,
+            wheel: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mouse(buttons: u8, x: i16) -> InputReport {
+        InputReport::Mouse {
+            buttons,
+            x,
+            y: 0,
+            wheel: 0,
+        }
+    }
+
+    #[test]
+    fn test_heartbeat_while_held_and_stops_on_release() {
+        let mut heartbeat = DragHeartbeat::new(&DragHeartbeatConfig {
+            enabled: true,
+            interval_ms: 50,
+        });
+        let t0 = Instant::now();
+        let ms = |ms| t0 + Duration::from_millis(ms);
+
+        heartbeat.observe(&mouse(0x01, 0), t0);
+        let beats: Vec<u64> = (0..=200)
+            .step_by(10)
+            .filter(|&t| {
+                matches!(
+                    heartbeat.poll(ms(t)),
+                    Some(InputReport::Mouse {
+                        buttons: 0x01,
+                        x: 0,
+                        ..
+                    })
+                )
+            })
+            .collect();
+        assert_eq!(beats, vec![50, 100, 150, 200]);
+
+        // 移动本身就是报告，重新计时
+        heartbeat.observe(&mouse(0x01, 3), ms(210));
+        assert_eq!(heartbeat.deadline(), Some(ms(260)));
+
+        heartbeat.observe(&mouse(0, 0), ms(220));
+        assert_eq!(heartbeat.deadline(), None);
+        assert!(heartbeat.poll(ms(300)).is_none());
+    }
+
+    #[test]
+    fn test_disabled_never_beats() {
+        let mut heartbeat = DragHeartbeat::new(&DragHeartbeatConfig::default());
+        heartbeat.observe(&mouse(0x01, 0), Instant::now());
+        assert_eq!(heartbeat.deadline(), None);
+    }
+}
@@ -0,0 +1,333 @@
+use super::{HidLedReader, LedState};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::watch;
+
+/// 某个已配对主机最近一次使用的设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HostPref {
+    /// Protocol Mode（0 = Boot，1 = Report）
+    pub protocol_mode: u8,
+    pub leds: LedState,
+}
+
+impl Default for HostPref {
+    fn default() -> Self {
+        Self {
+            protocol_mode: 0x01,
+            leds: LedState::default(),
+        }
+    }
+}
+
+/// 按主机地址记录的偏好
+///
+/// 主机重连后往往要过一会儿才重新写入 Protocol Mode 和 LED 状态，
+/// 记住上次的值可以在这之前就让物理键盘的指示灯正确。
+/// 指定文件时每次变化都由后台任务整体写回，未指定时只保存在内存中。
+#[derive(Clone)]
+pub struct HostPrefs {
+    path: Option<PathBuf>,
+    prefs: Arc<Mutex<HashMap<String, HostPref>>>,
+    /// 记录的修改次数，只在持有 `prefs` 锁时修改
+    generation: Arc<AtomicU64>,
+    changed: Arc<watch::Sender<()>>,
+    /// 后台写入任务已写入的修改次数，首次修改时启动任务
+    saved: Arc<OnceLock<watch::Receiver<u64>>>,
+}
+
+impl Default for HostPrefs {
+    fn default() -> Self {
+        Self::with_prefs(None, HashMap::new())
+    }
+}
+
+impl HostPrefs {
+    fn with_prefs(path: Option<PathBuf>, prefs: HashMap<String, HostPref>) -> Self {
+        let (changed, _) = watch::channel(());
+        Self {
+            path,
+            prefs: Arc::new(Mutex::new(prefs)),
+            generation: Arc::new(AtomicU64::new(0)),
+            changed: Arc::new(changed),
+            saved: Arc::new(OnceLock::new()),
+        }
+    }
+
+    /// 从文件加载，文件不存在或无法解析时从空记录开始
+    pub fn load(path: Option<&Path>) -> Self {
+        let prefs = path
+            .and_then(|path| match std::fs::read_to_string(path) {
+                Ok(text) => serde_json::from_str(&text)
+                    .inspect_err(|e| log::warn!("解析主机偏好 {} 失败: {}", path.display(), e))
+                    .ok(),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => {
+                    log::warn!("读取主机偏好 {} 失败: {}", path.display(), e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        Self::with_prefs(path.map(Path::to_path_buf), prefs)
+    }
+
+    pub fn get(&self, host: &str) -> Option<HostPref> {
+        self.prefs.lock().unwrap().get(host).copied()
+    }
+
+    pub fn set_protocol_mode(&self, host: &str, mode: u8) {
+        self.update(host, |pref| pref.protocol_mode = mode);
+    }
+
+    pub fn set_leds(&self, host: &str, leds: LedState) {
+        self.update(host, |pref| pref.leds = leds);
+    }
+
+    /// 所有主机的记录，按地址排序
+    pub fn snapshot(&self) -> BTreeMap<String, HostPref> {
+        self.prefs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(host, pref)| (host.clone(), *pref))
+            .collect()
+    }
+
+    /// 整体替换所有记录，先写入文件，写入失败时内存中的记录保持不变
+    pub fn replace_all(&self, prefs: BTreeMap<String, HostPref>) -> Result<()> {
+        let prefs: HashMap<String, HostPref> = prefs.into_iter().collect();
+        if let Some(path) = &self.path {
+            save(path, &prefs)?;
+        }
+        {
+            let mut current = self.prefs.lock().unwrap();
+            *current = prefs;
+            // 后台任务可能正在写入旧的记录，之后需要再写一次
+            self.generation.fetch_add(1, Ordering::Relaxed);
+        }
+        self.changed.send_replace(());
+        Ok(())
+    }
+
+    /// 等待后台任务写完此前的所有修改
+    pub async fn flush(&self) {
+        let target = self.generation.load(Ordering::Relaxed);
+        if let Some(saved) = self.saved.get() {
+            let _ = saved.clone().wait_for(|&saved| saved >= target).await;
+        }
+    }
+
+    /// 订阅记录变化
+    pub fn subscribe(&self) -> watch::Receiver<()> {
+        self.changed.subscribe()
+    }
+
+    /// 锁内只修改内存中的记录，文件由后台任务写入，GATT 回调中调用也不会阻塞运行时
+    fn update(&self, host: &str, f: impl FnOnce(&mut HostPref)) {
+        {
+            let mut prefs = self.prefs.lock().unwrap();
+            let pref = prefs.entry(host.to_string()).or_default();
+            let before = *pref;
+            f(pref);
+            if *pref == before {
+                return;
+            }
+            self.generation.fetch_add(1, Ordering::Relaxed);
+        }
+        self.changed.send_replace(());
+        self.save_in_background();
+    }
+
+    fn save_in_background(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            // 不在运行时中，直接写入
+            let prefs = self.prefs.lock().unwrap().clone();
+            if let Err(e) = save(path, &prefs) {
+                log::warn!("保存主机偏好 {} 失败: {:?}", path.display(), e);
+            }
+            return;
+        };
+        self.saved.get_or_init(|| {
+            let (saved_tx, saved_rx) = watch::channel(0);
+            // 启动任务的这次修改已经通知过，标记为未读让任务立即写入
+            let mut changed = self.changed.subscribe();
+            changed.mark_changed();
+            runtime.spawn(save_loop(
+                path.clone(),
+                Arc::clone(&self.prefs),
+                Arc::clone(&self.generation),
+                changed,
+                saved_tx,
+            ));
+            saved_rx
+        });
+    }
+}
+
+/// 记录变化时写入文件，写入期间的多次变化合并为一次写入
+///
+/// 所有 [`HostPrefs`] 都被释放后再检查一次，写完剩余的修改后退出。
+async fn save_loop(
+    path: PathBuf,
+    prefs: Arc<Mutex<HashMap<String, HostPref>>>,
+    generation: Arc<AtomicU64>,
+    mut changed: watch::Receiver<()>,
+    saved: watch::Sender<u64>,
+) {
+    loop {
+        let open = changed.changed().await.is_ok();
+        let (snapshot, current) = {
+            let prefs = prefs.lock().unwrap();
+            (prefs.clone(), generation.load(Ordering::Relaxed))
+        };
+        if current != *saved.borrow() {
+            let target = path.clone();
+            match tokio::task::spawn_blocking(move || save(&target, &snapshot)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => log::warn!("保存主机偏好 {} 失败: {:?}", path.display(), e),
+                Err(e) => log::warn!("保存主机偏好 {} 失败: {}", path.display(), e),
+            }
+            saved.send_replace(current);
+        }
+        if !open {
+            break;
+        }
+    }
+}
+
+fn save(path: &Path, prefs: &HashMap<String, HostPref>) -> Result<()> {
+    let text = serde_json::to_string_pretty(prefs)?;
+    // 先写临时文件再改名，避免写到一半断电留下损坏的文件
+    let tmp = crate::config::temp_path(path);
+    std::fs::write(&tmp, text)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// 按当前连接主机给出 LED 状态
+///
+/// 主机连接或切换时立即返回该主机记录的 LED 状态，之后在主机写入新状态时返回新值。
+pub struct HostLedReader {
+    peer_rx: watch::Receiver<Option<String>>,
+    prefs: HostPrefs,
+    prefs_rx: watch::Receiver<()>,
+    last: Option<LedState>,
+}
+
+impl HostLedReader {
+    pub fn new(peer_rx: watch::Receiver<Option<String>>, prefs: HostPrefs) -> Self {
+        let prefs_rx = prefs.subscribe();
+        Self {
+            peer_rx,
+            prefs,
+            prefs_rx,
+            last: None,
+        }
+    }
+
+    /// 当前主机记录的 LED 状态，没有连接的主机或主机尚未下发过时为 `None`
+    fn current(&mut self) -> Option<LedState> {
+        self.prefs_rx.mark_unchanged();
+        self.peer_rx
+            .borrow_and_update()
+            .as_deref()
+            .and_then(|host| self.prefs.get(host))
+            .map(|pref| pref.leds)
+    }
+}
+
+#[async_trait]
+impl HidLedReader for HostLedReader {
+    async fn get_led_state(&mut self) -> Result<Option<LedState>> {
+        loop {
+            // 状态未知时不上报，物理键盘保持原来的 LED
+            if let Some(leds) = self.current()
+                && self.last != Some(leds)
+            {
+                self.last = Some(leds);
+                return Ok(Some(leds));
+            }
+            tokio::select! {
+                changed = self.peer_rx.changed() => changed?,
+                changed = self.prefs_rx.changed() => changed?,
+            }
+        }
+    }
+
+    fn host_led_state(&self) -> Option<LedState> {
+        let host = self.peer_rx.borrow().clone()?;
+        self.prefs.get(&host).map(|pref| pref.leds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::connection::ConnectionState;
+
+    #[tokio::test]
+    async fn test_restores_leds_and_mode_on_reconnect() {
+        let path =
+            std::env::temp_dir().join(format!("bridge-hid-hosts-{}.json", std::process::id()));
+        let host = "AA:BB:CC:DD:EE:01";
+        let caps = LedState {
+            caps_lock: true,
+            ..Default::default()
+        };
+
+        let prefs = HostPrefs::load(Some(&path));
+        prefs.set_protocol_mode(host, 0x00);
+        prefs.set_leds(host, caps);
+        prefs.flush().await;
+        drop(prefs);
+
+        // 重启后重新加载，主机重连前 LED 状态未知，不上报
+        let prefs = HostPrefs::load(Some(&path));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(prefs.get(host).unwrap().protocol_mode, 0x00);
+        let connection = ConnectionState::default();
+        let mut reader = HostLedReader::new(connection.subscribe_peer(), prefs.clone());
+        let unknown =
+            tokio::time::timeout(std::time::Duration::from_millis(50), reader.get_led_state());
+        assert!(unknown.await.is_err());
+
+        connection.set_peer(Some(host.to_string()));
+        assert_eq!(reader.get_led_state().await.unwrap(), Some(caps));
+
+        // 主机写入新状态
+        prefs.set_leds(host, LedState::default());
+        assert_eq!(
+            reader.get_led_state().await.unwrap(),
+            Some(LedState::default())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_saves_in_background() {
+        let path =
+            std::env::temp_dir().join(format!("bridge-hid-hosts-bg-{}.json", std::process::id()));
+        let host = "AA:BB:CC:DD:EE:02";
+        let prefs = HostPrefs::load(Some(&path));
+
+        // 修改立即可见，文件在后台任务运行后才写入
+        prefs.set_protocol_mode(host, 0x00);
+        prefs.set_protocol_mode(host, 0x01);
+        prefs.set_protocol_mode(host, 0x00);
+        assert_eq!(prefs.get(host).unwrap().protocol_mode, 0x00);
+        assert!(!path.exists());
+
+        prefs.flush().await;
+        let saved = HostPrefs::load(Some(&path));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(saved.get(host).unwrap().protocol_mode, 0x00);
+    }
+}
@@ -0,0 +1,121 @@
+use crate::input::InputReport;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// 防休眠配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeepAwakeConfig {
+    pub enabled: bool,
+    /// 无真实输入多久（秒）后开始轻推鼠标
+    pub idle_secs: u64,
+    /// 空闲期间每次轻推的间隔（秒）
+    pub interval_secs: u64,
+}
+
+impl Default for KeepAwakeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            idle_secs: 60,
+            interval_secs: 30,
+        }
+    }
+}
+
+impl KeepAwakeConfig {
+    pub fn idle(&self) -> Duration {
+        Duration::from_secs(self.idle_secs)
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs.max(1))
+    }
+}
+
+/// 防休眠鼠标轻推
+///
+/// 空闲超过阈值后按固定间隔交替产生 +1 / -1 像素的鼠标移动，光标总体保持在原位；
+/// 一旦有真实输入立即暂停，重新等待空闲。
+pub struct KeepAwake {
+    idle: Duration,
+    interval: Duration,
+    next: Instant,
+    /// 下一次轻推的方向，跨越活动保持，保证位移总能抵消
+    forward: bool,
+}
+
+impl KeepAwake {
+    /// - `now`: 视为最后一次真实输入的时间
+    pub fn new(config: &KeepAwakeConfig, now: Instant) -> Self {
+        Self {
+            idle: config.idle(),
+            interval: config.interval(),
+            next: now + config.idle(),
+            forward: true,
+        }
+    }
+
+    /// 记录一次真实输入
+    pub fn activity(&mut self, now: Instant) {
+        self.next = now + self.idle;
+    }
+
+    /// 下一次轻推的时间
+    pub fn deadline(&self) -> Instant {
+        self.next
+    }
+
+    /// 到期时返回需要发送的轻推报告
+    pub fn poll(&mut self, now: Instant) -> Option<InputReport> {
+        if now < self.next {
+            return None;
+        }
+        self.next = now + self.interval;
+        let x = if self.forward { 1 } else { -1 };
+        self.forward = !self.forward;
+        Some(InputReport::Mouse {
+            buttons: 0,
+            x,
+            y: 0,
+            wheel: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dx(report: Option<InputReport>) -> Option<i16> {
+        match report? {
+            InputReport::Mouse { x, .. } => Some(x),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_wiggle_only_while_idle() {
+        let config = KeepAwakeConfig {
+            enabled: true,
+            idle_secs: 60,
+            interval_secs: 10,
+        };
+        let t0 = Instant::now();
+        let secs = |s| t0 + Duration::from_secs(s);
+        let mut keep_awake = KeepAwake::new(&config, t0);
+
+        assert_eq!(dx(keep_awake.poll(secs(59))), None);
+        assert_eq!(dx(keep_awake.poll(secs(60))), Some(1));
+        assert_eq!(dx(keep_awake.poll(secs(65))), None);
+        assert_eq!(dx(keep_awake.poll(secs(70))), Some(-1));
+        assert_eq!(dx(keep_awake.poll(secs(80))), Some(1));
+
+        // 真实输入后暂停，重新等待完整的空闲时长
+        keep_awake.activity(secs(85));
+        assert_eq!(keep_awake.deadline(), secs(145));
+        assert_eq!(dx(keep_awake.poll(secs(90))), None);
+        assert_eq!(dx(keep_awake.poll(secs(144))), None);
+        assert_eq!(dx(keep_awake.poll(secs(145))), Some(-1));
+    }
+}
@@ -0,0 +1,402 @@
+//! 按键名与 HID 键码的互相转换
+//!
+//! 名称为小写蛇形（如 `enter`、`f12`、`kp_enter`），解析时忽略大小写并接受常见别名。
+
+use super::keycodes::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// 规范名称表，覆盖 [`super::keycodes`] 中的全部键码
+const KEY_NAMES: &[(&str, u8)] = &[
+    ("a", KEY_A),
+    ("b", KEY_B),
+    ("c", KEY_C),
+    ("d", KEY_D),
+    ("e", KEY_E),
+    ("f", KEY_F),
+    ("g", KEY_G),
+    ("h", KEY_H),
+    ("i", KEY_I),
+    ("j", KEY_J),
+    ("k", KEY_K),
+    ("l", KEY_L),
+    ("m", KEY_M),
+    ("n", KEY_N),
+    ("o", KEY_O),
+    ("p", KEY_P),
+    ("q", KEY_Q),
+    ("r", KEY_R),
+    ("s", KEY_S),
+    ("t", KEY_T),
+    ("u", KEY_U),
+    ("v", KEY_V),
+    ("w", KEY_W),
+    ("x", KEY_X),
+    ("y", KEY_Y),
+    ("z", KEY_Z),
+    ("1", KEY_1),
+    ("2", KEY_2),
+    ("3", KEY_3),
+    ("4", KEY_4),
+    ("5", KEY_5),
+    ("6", KEY_6),
+    ("7", KEY_7),
+    ("8", KEY_8),
+    ("9", KEY_9),
+    ("0", KEY_0),
+    ("enter", KEY_ENTER),
+    ("esc", KEY_ESC),
+    ("backspace", KEY_BACKSPACE),
+    ("tab", KEY_TAB),
+    ("space", KEY_SPACE),
+    ("minus", KEY_MINUS),
+    ("equal", KEY_EQUAL),
+    ("left_bracket", KEY_LEFT_BRACKET),
+    ("right_bracket", KEY_RIGHT_BRACKET),
+    ("backslash", KEY_BACKSLASH),
+    ("semicolon", KEY_SEMICOLON),
+    ("apostrophe", KEY_APOSTROPHE),
+    ("grave", KEY_GRAVE),
+    ("comma", KEY_COMMA),
+    ("dot", KEY_DOT),
+    ("slash", KEY_SLASH),
+    ("caps_lock", KEY_CAPS_LOCK),
+    ("f1", KEY_F1),
+    ("f2", KEY_F2),
+    ("f3", KEY_F3),
+    ("f4", KEY_F4),
+    ("f5", KEY_F5),
+    ("f6", KEY_F6),
+    ("f7", KEY_F7),
+    ("f8", KEY_F8),
+    ("f9", KEY_F9),
+    ("f10", KEY_F10),
+    ("f11", KEY_F11),
+    ("f12", KEY_F12),
+    ("print_screen", KEY_PRINT_SCREEN),
+    ("scroll_lock", KEY_SCROLL_LOCK),
+    ("pause", KEY_PAUSE),
+    ("insert", KEY_INSERT),
+    ("home", KEY_HOME),
+    ("page_up", KEY_PAGE_UP),
+    ("delete", KEY_DELETE),
+    ("end", KEY_END),
+    ("page_down", KEY_PAGE_DOWN),
+    ("right", KEY_RIGHT_ARROW),
+    ("left", KEY_LEFT_ARROW),
+    ("down", KEY_DOWN_ARROW),
+    ("up", KEY_UP_ARROW),
+    ("num_lock", KEY_NUM_LOCK),
+    ("kp_slash", KEY_KP_SLASH),
+    ("kp_asterisk", KEY_KP_ASTERISK),
+    ("kp_minus", KEY_KP_MINUS),
+    ("kp_plus", KEY_KP_PLUS),
+    ("kp_enter", KEY_KP_ENTER),
+    ("kp_1", KEY_KP_1),
+    ("kp_2", KEY_KP_2),
+    ("kp_3", KEY_KP_3),
+    ("kp_4", KEY_KP_4),
+    ("kp_5", KEY_KP_5),
+    ("kp_6", KEY_KP_6),
+    ("kp_7", KEY_KP_7),
+    ("kp_8", KEY_KP_8),
+    ("kp_9", KEY_KP_9),
+    ("kp_0", KEY_KP_0),
+    ("kp_dot", KEY_KP_DOT),
+    ("non_us_backslash", KEY_NON_US_BACKSLASH),
+    ("international1", KEY_INTERNATIONAL1),
+    ("international2", KEY_INTERNATIONAL2),
+    ("international3", KEY_INTERNATIONAL3),
+    ("international4", KEY_INTERNATIONAL4),
+    ("international5", KEY_INTERNATIONAL5),
+    ("lang1", KEY_LANG1),
+    ("lang2", KEY_LANG2),
+    ("left_ctrl", KEY_LEFT_CTRL),
+    ("left_shift", KEY_LEFT_SHIFT),
+    ("left_alt", KEY_LEFT_ALT),
+    ("left_gui", KEY_LEFT_GUI),
+    ("right_ctrl", KEY_RIGHT_CTRL),
+    ("right_shift", KEY_RIGHT_SHIFT),
+    ("right_alt", KEY_RIGHT_ALT),
+    ("right_gui", KEY_RIGHT_GUI),
+];
+
+/// 解析时额外接受的别名
+const KEY_ALIASES: &[(&str, u8)] = &[
+    ("escape", KEY_ESC),
+    ("return", KEY_ENTER),
+    ("del", KEY_DELETE),
+    ("ins", KEY_INSERT),
+    ("pgup", KEY_PAGE_UP),
+    ("pgdn", KEY_PAGE_DOWN),
+    ("period", KEY_DOT),
+    ("ctrl", KEY_LEFT_CTRL),
+    ("shift", KEY_LEFT_SHIFT),
+    ("alt", KEY_LEFT_ALT),
+    ("gui", KEY_LEFT_GUI),
+    ("super", KEY_LEFT_GUI),
+    ("meta", KEY_LEFT_GUI),
+];
+
+/// 按名称查找 HID 键码，忽略大小写
+pub fn usage_from_name(name: &str) -> Option<u8> {
+    let name = name.trim().to_ascii_lowercase();
+    KEY_NAMES
+        .iter()
+        .chain(KEY_ALIASES)
+        .find(|(n, _)| *n == name)
+        .map(|(_, usage)| *usage)
+}
+
+/// 按 HID 键码查找规范名称
+pub fn name_from_usage(usage: u8) -> Option<&'static str> {
+    KEY_NAMES
+        .iter()
+        .find(|(_, u)| *u == usage)
+        .map(|(name, _)| *name)
+}
+
+/// 配置中以名称书写的单个按键，如 `kp_5`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyName(pub u8);
+
+impl TryFrom<String> for KeyName {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        usage_from_name(&text)
+            .map(Self)
+            .ok_or_else(|| format!("未知按键: {}", text.trim()))
+    }
+}
+
+impl From<KeyName> for String {
+    fn from(key: KeyName) -> Self {
+        match name_from_usage(key.0) {
+            Some(name) => name.to_string(),
+            None => format!("0x{:02x}", key.0),
+        }
+    }
+}
+
+/// 修饰键组合 + 一个普通键的热键，如 `ctrl+alt+backspace`
+///
+/// 修饰键不区分左右：`ctrl` 与 `right_ctrl` 都匹配任意一侧的 Ctrl。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Hotkey {
+    /// 左侧修饰键位（低 4 位）
+    pub modifiers: u8,
+    pub key: u8,
+}
+
+impl Hotkey {
+    /// 解析以 `+` 分隔的热键，必须且只能包含一个非修饰键
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut modifiers = 0u8;
+        let mut key = None;
+        for part in text.split('+') {
+            let usage =
+                usage_from_name(part).ok_or_else(|| format!("未知按键: {}", part.trim()))?;
+            if is_modifier(usage) {
+                modifiers |= modifier_side_bit(usage);
+            } else if key.replace(usage).is_some() {
+                return Err(format!("热键只能包含一个非修饰键: {}", text));
+            }
+        }
+        let key = key.ok_or_else(|| format!("热键缺少非修饰键: {}", text))?;
+        Ok(Self { modifiers, key })
+    }
+
+    /// 当前报告是否按下了该热键（允许同时按住其他键）
+    pub fn matches(&self, modifiers: u8, keys: &[u8]) -> bool {
+        let held = (modifiers | modifiers >> 4) & 0x0F;
+        held & self.modifiers == self.modifiers && keys.contains(&self.key)
+    }
+}
+
+impl TryFrom<String> for Hotkey {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        Self::parse(&text)
+    }
+}
+
+impl From<Hotkey> for String {
+    fn from(hotkey: Hotkey) -> Self {
+        hotkey.to_string()
+    }
+}
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // 修饰键位按左侧修饰键的规范名称输出，与 [`KeyCombo`] 一致
+        for (i, usage) in (KEY_LEFT_CTRL..=KEY_LEFT_GUI).enumerate() {
+            if self.modifiers & (1 << i) != 0 {
+                write!(f, "{}+", name_from_usage(usage).unwrap_or_default())?;
+            }
+        }
+        match name_from_usage(self.key) {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "0x{:02x}", self.key),
+        }
+    }
+}
+
+/// 切换输出等功能的组合键，如 `ctrl+alt+f12` 或 `caps_lock+q`
+///
+/// 与 [`Hotkey`] 不同，前面的键既可以是修饰键，也可以是普通键（如重映射后的 Caps Lock）：
+/// 修饰键检查修饰键字节且不区分左右，普通键检查按下的键数组。最后一个键为触发键。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyCombo {
+    /// 需要同时按住的键
+    pub held: Vec<u8>,
+    /// 触发键，不能是修饰键
+    pub key: u8,
+}
+
+impl KeyCombo {
+    /// 解析以 `+` 分隔的组合键
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut usages = text
+            .split('+')
+            .map(|part| usage_from_name(part).ok_or_else(|| format!("未知按键: {}", part.trim())))
+            .collect::<Result<Vec<u8>, String>>()?;
+        let key = usages.pop().filter(|key| !is_modifier(*key));
+        let key = key.ok_or_else(|| format!("组合键最后一个键不能是修饰键: {}", text))?;
+        Ok(Self { held: usages, key })
+    }
+
+    /// 当前报告是否按下了该组合键（允许同时按住其他键）
+    pub fn matches(&self, modifiers: u8, keys: &[u8]) -> bool {
+        let held_modifiers = (modifiers | modifiers >> 4) & 0x0F;
+        let held = |usage: u8| {
+            if is_modifier(usage) {
+                held_modifiers & modifier_side_bit(usage) != 0
+            } else {
+                keys.contains(&usage)
+            }
+        };
+        self.held.iter().all(|&usage| held(usage)) && keys.contains(&self.key)
+    }
+}
+
+/// 修饰键对应的左侧修饰键位
+fn modifier_side_bit(usage: u8) -> u8 {
+    1 << ((usage - KEY_LEFT_CTRL) % 4)
+}
+
+fn is_modifier(usage: u8) -> bool {
+    (KEY_LEFT_CTRL..=KEY_RIGHT_GUI).contains(&usage)
+}
+
+impl Default for KeyCombo {
+    /// Ctrl + Alt + F12
+    fn default() -> Self {
+        Self {
+            held: vec![KEY_LEFT_CTRL, KEY_LEFT_ALT],
+            key: KEY_F12,
+        }
+    }
+}
+
+impl TryFrom<String> for KeyCombo {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        Self::parse(&text)
+    }
+}
+
+impl From<KeyCombo> for String {
+    fn from(combo: KeyCombo) -> Self {
+        combo.to_string()
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, &usage) in self.held.iter().chain([&self.key]).enumerate() {
+            if i > 0 {
+                write!(f, "+")?;
+            }
+            match name_from_usage(usage) {
+                Some(name) => write!(f, "{}", name)?,
+                None => write!(f, "0x{:02x}", usage)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hotkey_parse_and_match() {
+        let hotkey = Hotkey::parse("Ctrl+Alt+Backspace").unwrap();
+        assert_eq!(hotkey.modifiers, 0x05);
+        assert_eq!(hotkey.key, KEY_BACKSPACE);
+        assert_eq!(hotkey.to_string(), "left_ctrl+left_alt+backspace");
+        assert_eq!(Hotkey::parse(&hotkey.to_string()), Ok(hotkey));
+
+        // 右侧修饰键同样匹配，多按的修饰键不影响
+        assert!(hotkey.matches(0x10 | 0x40, &[KEY_BACKSPACE]));
+        assert!(hotkey.matches(0x01 | 0x04 | 0x02, &[KEY_A, KEY_BACKSPACE]));
+        assert!(!hotkey.matches(0x01, &[KEY_BACKSPACE]));
+        assert!(!hotkey.matches(0x05, &[KEY_A]));
+
+        assert!(Hotkey::parse("ctrl+alt").is_err());
+        assert!(Hotkey::parse("ctrl+a+b").is_err());
+        assert!(Hotkey::parse("ctrl+nope").is_err());
+    }
+
+    #[test]
+    fn test_combo_over_plain_key() {
+        let combo = KeyCombo::parse("caps_lock+q").unwrap();
+        assert_eq!(combo.held, vec![KEY_CAPS_LOCK]);
+        assert_eq!(combo.to_string(), "caps_lock+q");
+        assert!(combo.matches(0, &[KEY_CAPS_LOCK, KEY_Q]));
+        assert!(combo.matches(0x02, &[KEY_Q, KEY_CAPS_LOCK]));
+        assert!(!combo.matches(0, &[KEY_Q]));
+        assert!(!combo.matches(0, &[KEY_CAPS_LOCK]));
+
+        // 默认组合键与修饰键混用
+        let default = KeyCombo::default();
+        assert_eq!(KeyCombo::parse(&default.to_string()), Ok(default.clone()));
+        assert!(default.matches(0x10 | 0x04, &[KEY_F12]));
+        assert!(!default.matches(0x01, &[KEY_F12]));
+        let mixed = KeyCombo::parse("ctrl+caps_lock+q").unwrap();
+        assert!(mixed.matches(0x10, &[KEY_CAPS_LOCK, KEY_Q]));
+        assert!(!mixed.matches(0, &[KEY_CAPS_LOCK, KEY_Q]));
+
+        assert!(KeyCombo::parse("ctrl+alt").is_err());
+        assert!(KeyCombo::parse("caps_lock+nope").is_err());
+    }
+
+    #[test]
+    fn test_round_trip_whole_table() {
+        for &(name, usage) in KEY_NAMES {
+            assert_eq!(usage_from_name(name), Some(usage), "{}", name);
+            assert_eq!(name_from_usage(usage), Some(name), "0x{:02X}", usage);
+        }
+        for &(alias, usage) in KEY_ALIASES {
+            assert_eq!(usage_from_name(alias), Some(usage), "{}", alias);
+            assert!(name_from_usage(usage).is_some());
+        }
+    }
+
+    #[test]
+    fn test_lookup_examples() {
+        assert_eq!(usage_from_name("Enter"), Some(KEY_ENTER));
+        assert_eq!(usage_from_name("f12"), Some(KEY_F12));
+        assert_eq!(usage_from_name("kp_enter"), Some(KEY_KP_ENTER));
+        assert_eq!(name_from_usage(KEY_UP_ARROW), Some("up"));
+        assert_eq!(usage_from_name("no_such_key"), None);
+        assert_eq!(name_from_usage(0x00), None);
+    }
+}
@@ -0,0 +1,402 @@
+use crate::input::InputReport;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::time::{Duration, sleep};
+
+use super::HidReportSender;
+use super::keycodes::*;
+
+const MOD_LEFT_CTRL: u8 = 0x01;
+const MOD_LEFT_SHIFT: u8 = 0x02;
+const MOD_LEFT_ALT: u8 = 0x04;
+
+/// 组合键按住时长
+pub const CHORD_HOLD: Duration = Duration::from_millis(10);
+
+/// 输入一个字符的报告序列，无法直接映射的字符按 `unicode` 指定的方式输入
+fn char_reports(c: char, unicode: UnicodeInput) -> Vec<InputReport> {
+    match char_to_keycode(c) {
+        Some((modifiers, key)) => chord_reports(modifiers, &[key]).to_vec(),
+        None => unicode_reports(c, unicode),
+    }
+}
+
+/// 输入文本的节奏
+///
+/// 按键太快时有的主机会丢字；自带缓冲的主机则可以把两项都设为 0，不做任何等待。
+/// 默认每个字符之后都等待 [`CHORD_HOLD`]，与之前固定的节奏一致。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TypingConfig {
+    /// 每个按键按住的时长（毫秒）
+    pub tap_ms: u64,
+    /// 相邻两个字符之间额外等待的时长（毫秒）
+    pub char_delay_ms: u64,
+}
+
+impl Default for TypingConfig {
+    fn default() -> Self {
+        Self {
+            tap_ms: CHORD_HOLD.as_millis() as u64,
+            char_delay_ms: CHORD_HOLD.as_millis() as u64,
+        }
+    }
+}
+
+impl TypingConfig {
+    /// 不做任何等待，依赖主机缓冲
+    pub const FAST: Self = Self {
+        tap_ms: 0,
+        char_delay_ms: 0,
+    };
+
+    pub fn tap(&self) -> Duration {
+        Duration::from_millis(self.tap_ms)
+    }
+
+    pub fn char_delay(&self) -> Duration {
+        Duration::from_millis(self.char_delay_ms)
+    }
+}
+
+/// 等待指定时长，为零时直接返回
+pub(crate) async fn pause(duration: Duration) {
+    if !duration.is_zero() {
+        sleep(duration).await;
+    }
+}
+
+/// 生成组合键的按下与释放报告
+///
+/// 普通键最多取前 6 个，与标准键盘报告一致。
+pub fn chord_reports(modifiers: u8, keys: &[u8]) -> [InputReport; 2] {
+    [
+        InputReport::Keyboard {
+            modifiers,
+            keys: keys.iter().copied().take(6).collect(),
+        },
+        InputReport::Keyboard {
+            modifiers: 0,
+            keys: vec![],
+        },
+    ]
+}
+
+/// 按美式键盘布局把 ASCII 字符转换为 `(修饰键, 键码)`
+pub fn char_to_keycode(c: char) -> Option<(u8, u8)> {
+    const DIGITS: [u8; 10] = [
+        KEY_0, KEY_1, KEY_2, KEY_3, KEY_4, KEY_5, KEY_6, KEY_7, KEY_8, KEY_9,
+    ];
+    let plain = |key| Some((0, key));
+    let shifted = |key| Some((MOD_LEFT_SHIFT, key));
+    match c {
+        'a'..='z' => plain(KEY_A + (c as u8 - b'a')),
+        'A'..='Z' => shifted(KEY_A + (c as u8 - b'A')),
+        '0'..='9' => plain(DIGITS[(c as u8 - b'0') as usize]),
+        '\n' => plain(KEY_ENTER),
+        '\t' => plain(KEY_TAB),
+        ' ' => plain(KEY_SPACE),
+        '-' => plain(KEY_MINUS),
+        '=' => plain(KEY_EQUAL),
+        '[' => plain(KEY_LEFT_BRACKET),
+        ']' => plain(KEY_RIGHT_BRACKET),
+        '\\' => plain(KEY_BACKSLASH),
+        ';' => plain(KEY_SEMICOLON),
+        '\'' => plain(KEY_APOSTROPHE),
+        '`' => plain(KEY_GRAVE),
+        ',' => plain(KEY_COMMA),
+        '.' => plain(KEY_DOT),
+        '/' => plain(KEY_SLASH),
+        '!' => shifted(KEY_1),
+        '@' => shifted(KEY_2),
+        '#' => shifted(KEY_3),
+        '$' => shifted(KEY_4),
+        '%' => shifted(KEY_5),
+        '^' => shifted(KEY_6),
+        '&' => shifted(KEY_7),
+        '*' => shifted(KEY_8),
+        '(' => shifted(KEY_9),
+        ')' => shifted(KEY_0),
+        '_' => shifted(KEY_MINUS),
+        '+' => shifted(KEY_EQUAL),
+        '{' => shifted(KEY_LEFT_BRACKET),
+        '}' => shifted(KEY_RIGHT_BRACKET),
+        '|' => shifted(KEY_BACKSLASH),
+        ':' => shifted(KEY_SEMICOLON),
+        '"' => shifted(KEY_APOSTROPHE),
+        '~' => shifted(KEY_GRAVE),
+        '<' => shifted(KEY_COMMA),
+        '>' => shifted(KEY_DOT),
+        '?' => shifted(KEY_SLASH),
+        _ => None,
+    }
+}
+
+/// 没有直接键位的字符如何输入，取决于目标主机的系统
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnicodeInput {
+    /// 跳过无法映射的字符（默认）
+    #[default]
+    Skip,
+    /// Linux（IBus/GTK）：Ctrl+Shift+U，十六进制码点，回车确认
+    Linux,
+    /// Windows：按住 Alt，小键盘 `+`，十六进制码点，松开 Alt
+    ///
+    /// 需要主机注册表开启 `HKCU\Control Panel\Input Method\EnableHexNumpad`。
+    Windows,
+}
+
+/// 十六进制数字对应的键码（小写字母）
+fn hex_digit_keys(c: char) -> Vec<u8> {
+    format!("{:x}", c as u32)
+        .chars()
+        .filter_map(char_to_keycode)
+        .map(|(_, key)| key)
+        .collect()
+}
+
+/// 生成按 Unicode 码点输入字符的报告序列，`Skip` 时为空
+pub fn unicode_reports(c: char, method: UnicodeInput) -> Vec<InputReport> {
+    let mut reports = Vec::new();
+    match method {
+        UnicodeInput::Skip => {}
+        UnicodeInput::Linux => {
+            reports.extend(chord_reports(MOD_LEFT_CTRL | MOD_LEFT_SHIFT, &[KEY_U]));
+            for key in hex_digit_keys(c) {
+                reports.extend(chord_reports(0, &[key]));
+            }
+            reports.extend(chord_reports(0, &[KEY_ENTER]));
+        }
+        UnicodeInput::Windows => {
+            // Alt 在整个序列中保持按下
+            let with_alt = |keys: Vec<u8>| InputReport::Keyboard {
+                modifiers: MOD_LEFT_ALT,
+                keys,
+            };
+            reports.push(with_alt(vec![KEY_KP_PLUS]));
+            reports.push(with_alt(vec![]));
+            for key in hex_digit_keys(c) {
+                reports.push(with_alt(vec![key]));
+                reports.push(with_alt(vec![]));
+            }
+            reports.push(InputReport::Keyboard {
+                modifiers: 0,
+                keys: vec![],
+            });
+        }
+    }
+    reports
+}
+
+/// 键盘动作：在任意报告发送端上发送按键序列
+#[async_trait]
+pub trait KeyboardActions: HidReportSender {
+    /// 同时按下修饰键与普通键（如 Ctrl+Shift+T），短暂保持后全部释放
+    async fn send_chord(&mut self, modifiers: u8, keys: &[u8]) -> Result<()> {
+        let [down, up] = chord_reports(modifiers, keys);
+        self.send_report(down).await?;
+        sleep(CHORD_HOLD).await;
+        self.send_report(up).await
+    }
+
+    /// 按美式布局逐字输入文本，无法映射的字符被跳过
+    async fn type_string(&mut self, text: &str) -> Result<()> {
+        self.type_string_with(text, UnicodeInput::Skip).await
+    }
+
+    /// 逐字输入文本，无法直接映射的字符按 `unicode` 指定的方式输入
+    async fn type_string_with(&mut self, text: &str, unicode: UnicodeInput) -> Result<()> {
+        self.type_text(text, unicode, &TypingConfig::default())
+            .await
+    }
+
+    /// 按 `typing` 指定的节奏逐字输入文本
+    ///
+    /// 同一字符的相邻报告之间等待 `tap`，字符之间等待 `char_delay`，跳过的字符不等待。
+    /// 两者都为零时整段文本一次批量发送。
+    async fn type_text(
+        &mut self,
+        text: &str,
+        unicode: UnicodeInput,
+        typing: &TypingConfig,
+    ) -> Result<()> {
+        if *typing == TypingConfig::FAST {
+            let reports: Vec<InputReport> = text
+                .chars()
+                .flat_map(|c| char_reports(c, unicode))
+                .collect();
+            return self.send_reports(&reports).await;
+        }
+        let mut typed = false;
+        for c in text.chars() {
+            let reports = char_reports(c, unicode);
+            if reports.is_empty() {
+                continue;
+            }
+            if typed {
+                pause(typing.char_delay()).await;
+            }
+            typed = true;
+            for (i, report) in reports.into_iter().enumerate() {
+                if i > 0 {
+                    pause(typing.tap()).await;
+                }
+                self.send_report(report).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: HidReportSender + ?Sized> KeyboardActions for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::keycodes;
+    use crate::output::virtual_hid::VirtualHidDevice;
+
+    #[tokio::test]
+    async fn test_send_chord_presses_then_releases() {
+        let mut device = VirtualHidDevice::new();
+        // Ctrl + Shift + T
+        device
+            .send_chord(0x01 | 0x02, &[keycodes::KEY_T])
+            .await
+            .unwrap();
+
+        let reports = device.reports();
+        assert_eq!(reports.len(), 2);
+        match &reports[0] {
+            InputReport::Keyboard { modifiers, keys } => {
+                assert_eq!(*modifiers, 0x03);
+                assert_eq!(keys, &vec![keycodes::KEY_T]);
+            }
+            other => panic!("unexpected report: {:?}", other),
+        }
+        match &reports[1] {
+            InputReport::Keyboard { modifiers, keys } => {
+                assert_eq!(*modifiers, 0);
+                assert!(keys.is_empty());
+            }
+            other => panic!("unexpected report: {:?}", other),
+        }
+    }
+
+    fn keyboard(reports: &[InputReport]) -> Vec<(u8, Vec<u8>)> {
+        reports
+            .iter()
+            .map(|report| match report {
+                InputReport::Keyboard { modifiers, keys } => (*modifiers, keys.clone()),
+                other => panic!("unexpected report: {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_linux_unicode_sequence() {
+        use keycodes::*;
+
+        // é = U+00E9
+        let reports = unicode_reports('é', UnicodeInput::Linux);
+        let release = (0, vec![]);
+        assert_eq!(
+            keyboard(&reports),
+            vec![
+                (0x03, vec![KEY_U]),
+                release.clone(),
+                (0, vec![KEY_E]),
+                release.clone(),
+                (0, vec![KEY_9]),
+                release.clone(),
+                (0, vec![KEY_ENTER]),
+                release,
+            ]
+        );
+        assert!(unicode_reports('é', UnicodeInput::Skip).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_type_string_skips_unmapped_by_default() {
+        let mut device = VirtualHidDevice::new();
+        device.type_string("Hé!").await.unwrap();
+
+        let reports = device.reports();
+        assert_eq!(
+            keyboard(&reports),
+            vec![
+                (0x02, vec![keycodes::KEY_H]),
+                (0, vec![]),
+                (0x02, vec![keycodes::KEY_1]),
+                (0, vec![]),
+            ]
+        );
+    }
+
+    /// 记录每个报告发送时（暂停的 tokio 时钟）相对开始的毫秒数
+    struct Recorder {
+        start: tokio::time::Instant,
+        sent: Vec<(u64, InputReport)>,
+    }
+
+    #[async_trait]
+    impl HidReportSender for Recorder {
+        async fn send_report(&mut self, report: InputReport) -> Result<()> {
+            let elapsed = self.start.elapsed().as_millis() as u64;
+            self.sent.push((elapsed, report));
+            Ok(())
+        }
+
+        fn capabilities(&self) -> crate::output::BackendCapabilities {
+            crate::output::BackendCapabilities::all()
+        }
+    }
+
+    async fn type_timed(text: &str, typing: TypingConfig) -> Vec<(u64, (u8, Vec<u8>))> {
+        let mut recorder = Recorder {
+            start: tokio::time::Instant::now(),
+            sent: Vec::new(),
+        };
+        recorder
+            .type_text(text, UnicodeInput::Skip, &typing)
+            .await
+            .unwrap();
+        let (times, reports): (Vec<u64>, Vec<InputReport>) = recorder.sent.into_iter().unzip();
+        times.into_iter().zip(keyboard(&reports)).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_type_text_follows_configured_delays() {
+        use keycodes::*;
+
+        let typing = TypingConfig {
+            tap_ms: 10,
+            char_delay_ms: 30,
+        };
+        assert_eq!(
+            type_timed("aé!", typing).await,
+            vec![
+                (0, (0, vec![KEY_A])),
+                (10, (0, vec![])),
+                // é 被跳过，不产生额外等待
+                (40, (0x02, vec![KEY_1])),
+                (50, (0, vec![])),
+            ]
+        );
+
+        let fast = type_timed("ab", TypingConfig::FAST).await;
+        assert_eq!(fast.len(), 4);
+        assert!(fast.iter().all(|(t, _)| *t == 0));
+    }
+
+    #[test]
+    fn test_chord_reports_truncate_to_six_keys() {
+        let [down, _] = chord_reports(0, &[4, 5, 6, 7, 8, 9, 10]);
+        match down {
+            InputReport::Keyboard { keys, .. } => assert_eq!(keys, vec![4, 5, 6, 7, 8, 9]),
+            other => panic!("unexpected report: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use super::LedState;
+use std::time::{Duration, Instant};
+
+/// 默认 LED 去抖窗口
+pub const DEFAULT_LED_DEBOUNCE: Duration = Duration::from_millis(20);
+
+/// LED 状态去抖器
+///
+/// 主机快速切换 Num/Caps，或输出报告读取不稳定时，逐个推送会让所有物理键盘的灯闪烁。
+/// 去抖器只在状态保持窗口时长不变后才放行；窗口内回到已推送状态则什么都不推送，
+/// 因此最终状态总会被推送，中间状态被丢弃。
+pub struct LedDebouncer {
+    /// 去抖窗口，为零表示不去抖
+    window: Duration,
+    pushed: LedState,
+    pending: Option<(LedState, Instant)>,
+}
+
+impl LedDebouncer {
+    /// 创建去抖器
+    /// - `window`: 状态需要保持不变的时长
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pushed: LedState::default(),
+            pending: None,
+        }
+    }
+
+    /// 记录读取到的新状态
+    pub fn observe(&mut self, state: LedState, now: Instant) {
+        if state == self.pushed {
+            self.pending = None;
+            return;
+        }
+        match self.pending {
+            Some((pending, _)) if pending == state => {}
+            _ => self.pending = Some((state, now)),
+        }
+    }
+
+    /// 待推送状态的到期时间
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.map(|(_, since)| since + self.window)
+    }
+
+    /// 到期时返回需要推送的状态，并视为已推送
+    pub fn poll(&mut self, now: Instant) -> Option<LedState> {
+        let deadline = self.deadline()?;
+        if now < deadline {
+            return None;
+        }
+        let (state, _) = self.pending.take()?;
+        self.pushed = state;
+        Some(state)
+    }
+
+    /// 丢弃待推送状态，保留已推送状态，例如切换输出之后：
+    /// 新后端给出状态之前物理键盘继续显示最后一次推送的状态，而不是回到默认值
+    pub fn discard_pending(&mut self) {
+        self.pending = None;
+    }
+
+    /// 记录绕过去抖直接推送的状态
+    pub fn mark_pushed(&mut self, state: LedState) {
+        self.pushed = state;
+        self.pending = None;
+    }
+}
+
+impl Default for LedDebouncer {
+    fn default() -> Self {
+        Self::new(DEFAULT_LED_DEBOUNCE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn caps(on: bool) -> LedState {
+        LedState {
+            caps_lock: on,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_flicker_within_window_pushes_only_settled_state() {
+        let window = Duration::from_millis(20);
+        let mut debouncer = LedDebouncer::new(window);
+        let t0 = Instant::now();
+
+        // A 稳定后推送
+        debouncer.observe(caps(true), t0);
+        assert_eq!(debouncer.poll(t0 + window), Some(caps(true)));
+
+        // A -> B -> A 都在窗口内：不推送任何东西
+        let t1 = t0 + Duration::from_millis(100);
+        debouncer.observe(caps(false), t1);
+        debouncer.observe(caps(true), t1 + Duration::from_millis(5));
+        assert_eq!(debouncer.deadline(), None);
+        assert_eq!(debouncer.poll(t1 + window * 2), None);
+    }
+
+    #[test]
+    fn test_final_state_wins() {
+        let window = Duration::from_millis(20);
+        let mut debouncer = LedDebouncer::new(window);
+        let t0 = Instant::now();
+
+        let num = LedState {
+            num_lock: true,
+            ..Default::default()
+        };
+        debouncer.observe(caps(true), t0);
+        debouncer.observe(num, t0 + Duration::from_millis(5));
+
+        // 窗口从最后一次变化开始计算
+        assert_eq!(debouncer.poll(t0 + window), None);
+        assert_eq!(
+            debouncer.poll(t0 + Duration::from_millis(5) + window),
+            Some(num)
+        );
+        assert_eq!(debouncer.poll(t0 + window * 10), None);
+    }
+}
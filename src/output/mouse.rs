@@ -0,0 +1,465 @@
+use crate::input::InputReport;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tokio::time::{Duration, sleep};
+
+use super::HidReportSender;
+
+/// 单个相对报告的默认最大位移
+pub const DEFAULT_MOVE_STEP: i16 = 127;
+
+/// 单次移动每个分量的最大位移，超出部分被截断
+pub const MAX_MOVE: i32 = 65535;
+
+/// 把任意大小的净位移拆成多个相对鼠标报告
+///
+/// 各报告沿直线均匀分布，每个分量都不超过 `step`（限制在 1..=127），
+/// 所有报告的位移之和精确等于 `(dx, dy)`。位移先截断到 ±[`MAX_MOVE`]，
+/// 报告按需逐个生成，来自网络的超大位移不会一次性占用大量内存。
+pub fn split_move(dx: i32, dy: i32, step: i16) -> impl Iterator<Item = InputReport> {
+    let step = step.clamp(1, 127) as i64;
+    let (dx, dy) = (
+        dx.clamp(-MAX_MOVE, MAX_MOVE) as i64,
+        dy.clamp(-MAX_MOVE, MAX_MOVE) as i64,
+    );
+    let steps = dx
+        .unsigned_abs()
+        .div_ceil(step as u64)
+        .max(dy.unsigned_abs().div_ceil(step as u64)) as i64;
+
+    (0..steps).map(move |i| InputReport::Mouse {
+        buttons: 0,
+        x: (dx * (i + 1) / steps - dx * i / steps) as i16,
+        y: (dy * (i + 1) / steps - dy * i / steps) as i16,
+        wheel: 0,
+    })
+}
+
+/// 按住修饰键滚动（如 Ctrl+滚轮缩放）
+///
+/// 依次为修饰键按下、`|ticks|` 个每格一个的滚轮报告、修饰键释放，
+/// 主机在整个滚动期间都看到修饰键处于按下状态。
+pub fn modified_wheel_reports(modifiers: u8, ticks: i32) -> Vec<InputReport> {
+    let wheel = ticks.signum() as i8;
+    let mut reports = Vec::with_capacity(ticks.unsigned_abs() as usize + 2);
+    reports.push(InputReport::Keyboard {
+        modifiers,
+        keys: vec![],
+    });
+    reports.extend((0..ticks.unsigned_abs()).map(|_| InputReport::Mouse {
+        buttons: 0,
+        x: 0,
+        y: 0,
+        wheel,
+    }));
+    reports.push(InputReport::Keyboard {
+        modifiers: 0,
+        keys: vec![],
+    });
+    reports
+}
+
+/// 坐标轴变换，用于侧装的轨迹球、横屏的手机等
+///
+/// 先交换 X/Y，再按交换后的轴取反。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AxisTransform {
+    pub invert_x: bool,
+    pub invert_y: bool,
+    pub swap_xy: bool,
+}
+
+impl AxisTransform {
+    pub fn apply(&self, x: i32, y: i32) -> (i32, i32) {
+        let (x, y) = if self.swap_xy { (y, x) } else { (x, y) };
+        (
+            if self.invert_x { x.saturating_neg() } else { x },
+            if self.invert_y { y.saturating_neg() } else { y },
+        )
+    }
+
+    /// 变换鼠标报告的位移，其他报告原样返回
+    pub fn apply_report(&self, report: InputReport) -> InputReport {
+        match report {
+            InputReport::Mouse {
+                buttons,
+                x,
+                y,
+                wheel,
+            } => {
+                let (x, y) = self.apply(x as i32, y as i32);
+                InputReport::Mouse {
+                    buttons,
+                    x: x.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+                    y: y.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+                    wheel,
+                }
+            }
+            other => other,
+        }
+    }
+}
+
+/// 滚轮分辨率：每格滚轮输出 `multiplier / divisor` 个单位
+///
+/// 有的主机把一个单位当作一格，有的当作若干像素，按主机调整一格对应的滚动量。
+/// 不足一个单位的余量留到下一次报告。与自然滚动（方向反转）无关。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WheelResolution {
+    pub multiplier: u32,
+    pub divisor: u32,
+}
+
+impl Default for WheelResolution {
+    fn default() -> Self {
+        Self {
+            multiplier: 1,
+            divisor: 1,
+        }
+    }
+}
+
+impl WheelResolution {
+    /// 缩放滚轮量，`remainder` 保存以 `1/divisor` 为单位的余量
+    pub fn scale(&self, delta: i32, remainder: &mut i32) -> i32 {
+        let divisor = self.divisor.max(1) as i64;
+        let total = delta as i64 * self.multiplier as i64 + *remainder as i64;
+        *remainder = (total % divisor) as i32;
+        (total / divisor).clamp(i32::MIN as i64, i32::MAX as i64) as i32
+    }
+}
+
+/// 滚轮加速曲线，与指针灵敏度相互独立
+///
+/// 相邻两次滚动间隔越短，近期滚动量（按 `window_ms` 线性衰减）越大；超过 `threshold`
+/// 的部分每单位把倍率提高 `gain` 个百分点，最高 `max_percent`。快速连续拨动滚轮或
+/// 甩动触屏时一次滚得更远，慢速滚动保持原样。`gain` 为 0 时不加速。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScrollAccel {
+    /// 超出阈值后每单位增加的倍率（百分点）
+    pub gain: u32,
+    /// 近期滚动量超过该值后开始加速
+    pub threshold: u32,
+    /// 近期滚动量的衰减窗口（毫秒）
+    pub window_ms: u64,
+    /// 最大倍率（百分比）
+    pub max_percent: u32,
+}
+
+impl Default for ScrollAccel {
+    fn default() -> Self {
+        Self {
+            gain: 0,
+            threshold: 2,
+            window_ms: 100,
+            max_percent: 400,
+        }
+    }
+}
+
+/// 按 [`ScrollAccel`] 放大滚动量，不足一个单位的余量留到下一次
+#[derive(Debug, Clone, Default)]
+pub struct ScrollAccelerator {
+    curve: ScrollAccel,
+    last: Option<Instant>,
+    /// 上一次滚动的方向
+    direction: i32,
+    /// 近期滚动量，单位 1/100
+    recent: u64,
+    /// 以 1/100 为单位的余量
+    remainder: i64,
+}
+
+impl ScrollAccelerator {
+    pub fn new(curve: ScrollAccel) -> Self {
+        Self {
+            curve,
+            ..Default::default()
+        }
+    }
+
+    /// 放大 `now` 时刻的一次滚动量
+    pub fn apply(&mut self, delta: i32, now: Instant) -> i32 {
+        if self.curve.gain == 0 || delta == 0 {
+            return delta;
+        }
+        let window = Duration::from_millis(self.curve.window_ms.max(1));
+        let elapsed = self
+            .last
+            .map_or(window, |last| now.saturating_duration_since(last))
+            .min(window);
+        self.last = Some(now);
+        // 反向滚动重新开始计算
+        if delta.signum() != self.direction {
+            self.direction = delta.signum();
+            self.recent = 0;
+            self.remainder = 0;
+        }
+        let kept = (window - elapsed).as_millis() as u64;
+        self.recent =
+            self.recent * kept / window.as_millis() as u64 + delta.unsigned_abs() as u64 * 100;
+
+        let excess = (self.recent / 100).saturating_sub(self.curve.threshold as u64);
+        let percent = (100 + excess.saturating_mul(self.curve.gain as u64))
+            .min(self.curve.max_percent.max(100) as u64) as i64;
+        let total = delta as i64 * percent + self.remainder;
+        self.remainder = total % 100;
+        (total / 100).clamp(i32::MIN as i64, i32::MAX as i64) as i32
+    }
+}
+
+/// 位移死区
+///
+/// 有的鼠标和触屏客户端静止时会不断产生 ±1 的抖动。累积位移在两个轴上都不超过阈值时
+/// 不发送，超过后一次发出全部累积值，不丢失位移。为 0 时关闭。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DeadZone(pub u32);
+
+impl DeadZone {
+    /// 累积位移是否超出死区
+    pub fn exceeded(&self, x: i32, y: i32) -> bool {
+        x.unsigned_abs() > self.0 || y.unsigned_abs() > self.0
+    }
+}
+
+/// 绝对指针描述符的逻辑坐标最大值（Logical Maximum 32767）
+pub const ABS_LOGICAL_MAX: u16 = 0x7FFF;
+
+/// 屏幕尺寸（像素）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScreenSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for ScreenSize {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+        }
+    }
+}
+
+/// 相对位移到绝对坐标的累加器
+///
+/// 与 [`split_move`] 相反：有的 KVM 和虚拟机只接受绝对指针，这里记录指针在屏幕上的位置，
+/// 把物理鼠标的相对位移累加上去并裁剪到屏幕边缘，在边缘继续推动不会累积越界量。
+#[derive(Debug, Clone)]
+pub struct AbsolutePointer {
+    screen: ScreenSize,
+    x: u32,
+    y: u32,
+}
+
+impl AbsolutePointer {
+    /// 指针从屏幕中央开始
+    pub fn new(screen: ScreenSize) -> Self {
+        let screen = ScreenSize {
+            width: screen.width.max(1),
+            height: screen.height.max(1),
+        };
+        Self {
+            screen,
+            x: screen.width / 2,
+            y: screen.height / 2,
+        }
+    }
+
+    /// 累加一次相对位移，返回新的像素坐标
+    pub fn push(&mut self, dx: i32, dy: i32) -> (u32, u32) {
+        let clamp = |pos: u32, delta: i32, size: u32| {
+            (pos as i64 + delta as i64).clamp(0, size as i64 - 1) as u32
+        };
+        self.x = clamp(self.x, dx, self.screen.width);
+        self.y = clamp(self.y, dy, self.screen.height);
+        self.position()
+    }
+
+    /// 当前像素坐标
+    pub fn position(&self) -> (u32, u32) {
+        (self.x, self.y)
+    }
+
+    /// 换算到绝对指针描述符的逻辑坐标 `0..=ABS_LOGICAL_MAX`
+    pub fn logical(&self) -> (u16, u16) {
+        let scale = |pos: u32, size: u32| {
+            if size <= 1 {
+                0
+            } else {
+                (pos as u64 * ABS_LOGICAL_MAX as u64 / (size as u64 - 1)) as u16
+            }
+        };
+        (
+            scale(self.x, self.screen.width),
+            scale(self.y, self.screen.height),
+        )
+    }
+}
+
+/// 鼠标动作：在任意报告发送端上发送位移序列
+#[async_trait]
+pub trait MouseActions: HidReportSender {
+    /// 移动 `(dx, dy)`，拆分为多个不超过 `step` 的报告，报告之间间隔 `delay`
+    async fn mouse_move(&mut self, dx: i32, dy: i32, step: i16, delay: Duration) -> Result<()> {
+        for (i, report) in split_move(dx, dy, step).enumerate() {
+            if i > 0 && !delay.is_zero() {
+                sleep(delay).await;
+            }
+            self.send_report(report).await?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: HidReportSender + ?Sized> MouseActions for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::virtual_hid::VirtualHidDevice;
+
+    fn sum(reports: &[InputReport]) -> (i32, i32) {
+        reports.iter().fold((0, 0), |(sx, sy), r| match r {
+            InputReport::Mouse { x, y, .. } => {
+                assert!((-127..=127).contains(x) && (-127..=127).contains(y));
+                (sx + *x as i32, sy + *y as i32)
+            }
+            other => panic!("unexpected report: {:?}", other),
+        })
+    }
+
+    #[test]
+    fn test_axis_transform() {
+        let axes = |invert_x, invert_y, swap_xy| AxisTransform {
+            invert_x,
+            invert_y,
+            swap_xy,
+        };
+        assert_eq!(AxisTransform::default().apply(3, -4), (3, -4));
+        assert_eq!(axes(true, false, false).apply(3, -4), (-3, -4));
+        assert_eq!(axes(false, true, false).apply(3, -4), (3, 4));
+        assert_eq!(axes(false, false, true).apply(3, -4), (-4, 3));
+        // 先交换再取反
+        assert_eq!(axes(true, false, true).apply(3, -4), (4, 3));
+        assert_eq!(axes(true, true, true).apply(3, -4), (4, -3));
+
+        let report = axes(false, true, true).apply_report(InputReport::Mouse {
+            buttons: 0x01,
+            x: 3,
+            y: i16::MIN,
+            wheel: 1,
+        });
+        assert!(matches!(
+            report,
+            InputReport::Mouse {
+                buttons: 0x01,
+                x: i16::MIN,
+                y: -3,
+                wheel: 1
+            }
+        ));
+    }
+
+    #[test]
+    fn test_absolute_pointer_tracks_and_clamps() {
+        let mut pointer = AbsolutePointer::new(ScreenSize {
+            width: 100,
+            height: 50,
+        });
+        assert_eq!(pointer.position(), (50, 25));
+        assert_eq!(pointer.push(10, -5), (60, 20));
+        // 超出右上角时停在边缘
+        assert_eq!(pointer.push(1000, -1000), (99, 0));
+        assert_eq!(pointer.logical(), (ABS_LOGICAL_MAX, 0));
+        // 边缘的越界量不累积，反向移动立即生效
+        assert_eq!(pointer.push(-9, 1), (90, 1));
+        assert_eq!(pointer.push(-1000, 1000), (0, 49));
+        assert_eq!(pointer.logical(), (0, ABS_LOGICAL_MAX));
+    }
+
+    #[test]
+    fn test_split_move_sums_to_target() {
+        let reports: Vec<_> = split_move(500, -300, DEFAULT_MOVE_STEP).collect();
+        assert_eq!(reports.len(), 4);
+        assert_eq!(sum(&reports), (500, -300));
+
+        let reports: Vec<_> = split_move(7, 0, 3).collect();
+        assert_eq!(reports.len(), 3);
+        assert_eq!(sum(&reports), (7, 0));
+
+        assert_eq!(split_move(0, 0, DEFAULT_MOVE_STEP).count(), 0);
+    }
+
+    #[test]
+    fn test_split_move_clamps_huge_input() {
+        let reports: Vec<_> = split_move(i32::MAX, i32::MIN, DEFAULT_MOVE_STEP).collect();
+        assert_eq!(reports.len(), (MAX_MOVE as usize).div_ceil(127));
+        assert_eq!(sum(&reports), (MAX_MOVE, -MAX_MOVE));
+    }
+
+    #[tokio::test]
+    async fn test_mouse_move_sends_all_steps() {
+        let mut device = VirtualHidDevice::new();
+        device
+            .mouse_move(500, -300, DEFAULT_MOVE_STEP, Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(sum(&device.reports()), (500, -300));
+    }
+
+    #[test]
+    fn test_modified_wheel_holds_modifier_across_ticks() {
+        let reports = modified_wheel_reports(0x01, -3);
+        assert_eq!(reports.len(), 5);
+        assert!(matches!(
+            &reports[0],
+            InputReport::Keyboard { modifiers: 0x01, keys } if keys.is_empty()
+        ));
+        for report in &reports[1..4] {
+            assert!(matches!(
+                report,
+                InputReport::Mouse {
+                    buttons: 0,
+                    x: 0,
+                    y: 0,
+                    wheel: -1
+                }
+            ));
+        }
+        assert!(matches!(
+            &reports[4],
+            InputReport::Keyboard { modifiers: 0, keys } if keys.is_empty()
+        ));
+    }
+
+    #[test]
+    fn test_fast_scroll_travels_farther() {
+        let total = |curve: ScrollAccel, gap_ms: u64| {
+            let mut accel = ScrollAccelerator::new(curve);
+            let start = Instant::now();
+            (0..10)
+                .map(|i| accel.apply(1, start + Duration::from_millis(i * gap_ms)))
+                .sum::<i32>()
+        };
+        let curve = ScrollAccel {
+            gain: 50,
+            ..Default::default()
+        };
+
+        let (fast, slow) = (total(curve, 10), total(curve, 500));
+        assert_eq!(slow, 10);
+        assert!(fast > slow, "fast={} slow={}", fast, slow);
+        assert!(fast <= 10 * curve.max_percent as i32 / 100);
+
+        // 默认不加速
+        assert_eq!(total(ScrollAccel::default(), 10), 10);
+    }
+}
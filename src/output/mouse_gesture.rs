@@ -0,0 +1,167 @@
+use crate::input::InputReport;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// 鼠标切换手势配置：在限定间隔内连续点击同一按键若干次即切换输出
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MouseGestureConfig {
+    pub enabled: bool,
+    /// 触发手势的按键位（0x01 左、0x02 右、0x04 中、0x08 侧键1、0x10 侧键2）
+    pub button: u8,
+    /// 需要的连续点击次数
+    pub clicks: u32,
+    /// 相邻两次按下的最大间隔（毫秒）
+    pub interval_ms: u64,
+}
+
+impl Default for MouseGestureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            button: 0x04,
+            clicks: 3,
+            interval_ms: 300,
+        }
+    }
+}
+
+/// 连击手势识别
+///
+/// 只统计目标按键的按下边沿；期间按下其他按键、相邻按下超过间隔都会让计数重新开始，
+/// 因此普通的单击、双击以及与其他按键组合的操作不会误触发。
+pub struct MouseGesture {
+    enabled: bool,
+    button: u8,
+    clicks: u32,
+    interval: Duration,
+    buttons: u8,
+    count: u32,
+    last_press: Option<Instant>,
+}
+
+impl MouseGesture {
+    pub fn new(config: &MouseGestureConfig) -> Self {
+        Self {
+            enabled: config.enabled && config.button != 0,
+            button: config.button,
+            clicks: config.clicks.max(2),
+            interval: Duration::from_millis(config.interval_ms),
+            buttons: 0,
+            count: 0,
+            last_press: None,
+        }
+    }
+
+    /// 记录一个报告，手势完成时返回 `true`
+    pub fn observe(&mut self, report: &InputReport, now: Instant) -> bool {
+        let InputReport::Mouse { buttons, .. } = *report else {
+            return false;
+        };
+        if !self.enabled {
+            return false;
+        }
+        let pressed = buttons & !self.buttons;
+        self.buttons = buttons;
+
+        if pressed & !self.button != 0 {
+            self.count = 0;
+            self.last_press = None;
+            return false;
+        }
+        if pressed & self.button == 0 {
+            return false;
+        }
+
+        let in_time = self
+            .last_press
+            .is_some_and(|last| now.duration_since(last) <= self.interval);
+        self.count = if in_time { self.count + 1 } else { 1 };
+        self.last_press = Some(now);
+
+        if self.count >= self.clicks {
+            self.count = 0;
+            self.last_press = None;
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buttons(buttons: u8) -> InputReport {
+        InputReport::Mouse {
+            buttons,
+            x: 0,
+            y: 0,
+            wheel: 0,
+        }
+    }
+
+    fn mouse_move() -> InputReport {
+        InputReport::Mouse {
+            buttons: 0,
+            x: 3,
+            y: -2,
+            wheel: 0,
+        }
+    }
+
+    /// 在给定时刻（毫秒）点击按键，返回按下时是否触发
+    fn click(gesture: &mut MouseGesture, button: u8, t0: Instant, ms: u64) -> bool {
+        let at = t0 + Duration::from_millis(ms);
+        let fired = gesture.observe(&buttons(button), at);
+        assert!(!gesture.observe(&buttons(0), at + Duration::from_millis(50)));
+        fired
+    }
+
+    fn enabled() -> MouseGesture {
+        MouseGesture::new(&MouseGestureConfig {
+            enabled: true,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_triple_middle_click_toggles() {
+        let mut gesture = enabled();
+        let t0 = Instant::now();
+        assert!(!click(&mut gesture, 0x04, t0, 0));
+        assert!(!click(&mut gesture, 0x04, t0, 250));
+        assert!(click(&mut gesture, 0x04, t0, 500));
+
+        // 移动不影响计数
+        let mut gesture = enabled();
+        assert!(!click(&mut gesture, 0x04, t0, 0));
+        assert!(!gesture.observe(&mouse_move(), t0 + Duration::from_millis(100)));
+        assert!(!click(&mut gesture, 0x04, t0, 200));
+        assert!(click(&mut gesture, 0x04, t0, 400));
+    }
+
+    #[test]
+    fn test_near_misses_do_not_toggle() {
+        let t0 = Instant::now();
+
+        // 第三次点击太慢
+        let mut gesture = enabled();
+        assert!(!click(&mut gesture, 0x04, t0, 0));
+        assert!(!click(&mut gesture, 0x04, t0, 250));
+        assert!(!click(&mut gesture, 0x04, t0, 600));
+
+        // 中间夹杂左键
+        let mut gesture = enabled();
+        assert!(!click(&mut gesture, 0x04, t0, 0));
+        assert!(!click(&mut gesture, 0x01, t0, 100));
+        assert!(!click(&mut gesture, 0x04, t0, 200));
+        assert!(!click(&mut gesture, 0x04, t0, 400));
+
+        // 默认关闭
+        let mut gesture = MouseGesture::new(&MouseGestureConfig::default());
+        for ms in [0, 100, 200] {
+            assert!(!click(&mut gesture, 0x04, t0, ms));
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use super::key_names::KeyName;
+use super::keycodes::*;
+use crate::input::{InputReport, Reports};
+use serde::{Deserialize, Serialize};
+use smallvec::smallvec;
+use std::time::{Duration, Instant};
+
+/// 键盘控制指针配置，默认沿用数字小键盘的鼠标键布局
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MouseKeysConfig {
+    pub enabled: bool,
+    /// 按住该键时下列按键才控制指针，未设置时始终生效；应为非修饰键
+    pub layer: Option<KeyName>,
+    pub up: KeyName,
+    pub down: KeyName,
+    pub left: KeyName,
+    pub right: KeyName,
+    pub left_click: KeyName,
+    pub right_click: KeyName,
+    /// 刚按下时每次移动的距离
+    pub start_speed: u8,
+    /// 加速后每次移动的最大距离
+    pub max_speed: u8,
+    /// 从初始速度加速到最大速度所需的时间（毫秒）
+    pub ramp_ms: u64,
+    /// 按住期间发送移动报告的间隔（毫秒）
+    pub interval_ms: u64,
+}
+
+impl Default for MouseKeysConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            layer: None,
+            up: KeyName(KEY_KP_8),
+            down: KeyName(KEY_KP_2),
+            left: KeyName(KEY_KP_4),
+            right: KeyName(KEY_KP_6),
+            left_click: KeyName(KEY_KP_5),
+            right_click: KeyName(KEY_KP_0),
+            start_speed: 2,
+            max_speed: 20,
+            ramp_ms: 1000,
+            interval_ms: 20,
+        }
+    }
+}
+
+/// 键盘控制指针
+///
+/// 作为指针键按下的键从键盘报告中移除，直到释放为止；方向键按住期间由
+/// [`MouseKeys::poll`] 按固定间隔产生移动报告，速度随按住时间线性增加。
+/// 层键按下后才按下的键才会被接管，松开层键时已按住的指针键保持到释放，避免卡键。
+pub struct MouseKeys {
+    enabled: bool,
+    layer: Option<u8>,
+    /// 方向键及其 X/Y 方向
+    directions: [(u8, i32, i32); 4],
+    /// 点击键及其按键位
+    clicks: [(u8, u8); 2],
+    start_speed: i32,
+    max_speed: i32,
+    ramp: Duration,
+    interval: Duration,
+    /// 当前被接管的键及按下时间
+    held: Vec<(u8, Instant)>,
+    buttons: u8,
+    next_move: Option<Instant>,
+    /// 上一次输出的键盘报告，接管的键变化不产生重复报告
+    last_keyboard: Option<(u8, Vec<u8>)>,
+}
+
+impl MouseKeys {
+    pub fn new(config: &MouseKeysConfig) -> Self {
+        let max_speed = config.max_speed.max(1) as i32;
+        Self {
+            enabled: config.enabled,
+            layer: config.layer.map(|k| k.0),
+            directions: [
+                (config.up.0, 0, -1),
+                (config.down.0, 0, 1),
+                (config.left.0, -1, 0),
+                (config.right.0, 1, 0),
+            ],
+            clicks: [(config.left_click.0, 0x01), (config.right_click.0, 0x02)],
+            start_speed: (config.start_speed as i32).clamp(1, max_speed),
+            max_speed,
+            ramp: Duration::from_millis(config.ramp_ms),
+            interval: Duration::from_millis(config.interval_ms.max(1)),
+            held: Vec::new(),
+            buttons: 0,
+            next_move: None,
+            last_keyboard: None,
+        }
+    }
+
+    fn is_pointer_key(&self, usage: u8) -> bool {
+        self.directions.iter().any(|(u, ..)| *u == usage)
+            || self.clicks.iter().any(|(u, _)| *u == usage)
+    }
+
+    fn is_held(&self, usage: u8) -> bool {
+        self.held.iter().any(|(u, _)| *u == usage)
+    }
+
+    /// 处理一个报告：键盘报告中的指针键转换为鼠标按键报告，其余报告原样返回
+    pub fn filter(&mut self, report: InputReport, now: Instant) -> Reports {
+        if !self.enabled {
+            return smallvec![report];
+        }
+        let InputReport::Keyboard { modifiers, keys } = report else {
+            return smallvec![report];
+        };
+
+        self.held.retain(|(usage, _)| keys.contains(usage));
+        let layer_held = self.layer.is_none_or(|layer| keys.contains(&layer));
+        for &usage in &keys {
+            if layer_held && self.is_pointer_key(usage) && !self.is_held(usage) {
+                self.held.push((usage, now));
+            }
+        }
+
+        let mut reports = Reports::new();
+        let keys: Vec<u8> = keys
+            .into_iter()
+            .filter(|&k| Some(k) != self.layer && !self.is_held(k))
+            .collect();
+        if self.last_keyboard.as_ref() != Some(&(modifiers, keys.clone())) {
+            self.last_keyboard = Some((modifiers, keys.clone()));
+            reports.push(InputReport::Keyboard { modifiers, keys });
+        }
+
+        let buttons = self
+            .clicks
+            .iter()
+            .filter(|(usage, _)| self.is_held(*usage))
+            .fold(0, |acc, (_, bit)| acc | bit);
+        if buttons != self.buttons {
+            self.buttons = buttons;
+            reports.push(InputReport::Mouse {
+                buttons,
+                x: 0,
+                y: 0,
+                wheel: 0,
+            });
+        }
+
+        let moving = self
+            .directions
+            .iter()
+            .any(|(usage, ..)| self.is_held(*usage));
+        self.next_move = match (moving, self.next_move) {
+            (false, _) => None,
+            // 刚按下时立即移动一次
+            (true, None) => Some(now),
+            (true, next) => next,
+        };
+        reports
+    }
+
+    /// 所有按键已被释放，例如暂停或切换输出之后；仍按住的指针键要重新按下才会生效
+    pub fn reset(&mut self) {
+        self.held.clear();
+        self.buttons = 0;
+        self.next_move = None;
+        self.last_keyboard = None;
+    }
+
+    /// 下一次移动的时间，没有方向键按住时为 `None`
+    pub fn deadline(&self) -> Option<Instant> {
+        self.next_move
+    }
+
+    /// 到期时返回移动报告
+    pub fn poll(&mut self, now: Instant) -> Option<InputReport> {
+        if now < self.next_move? {
+            return None;
+        }
+        self.next_move = Some(now + self.interval);
+        let (mut x, mut y) = (0, 0);
+        for (usage, since) in &self.held {
+            if let Some((_, dx, dy)) = self.directions.iter().find(|(u, ..)| u == usage) {
+                let speed = self.speed(now.duration_since(*since));
+                x += dx * speed;
+                y += dy * speed;
+            }
+        }
+        Some(InputReport::Mouse {
+            buttons: self.buttons,
+            x: x as i16,
+            y: y as i16,
+            wheel: 0,
+        })
+    }
+
+    /// 按住 `held` 后的速度
+    fn speed(&self, held: Duration) -> i32 {
+        if held >= self.ramp {
+            return self.max_speed;
+        }
+        let gain =
+            (self.max_speed - self.start_speed) as u128 * held.as_millis() / self.ramp.as_millis();
+        self.start_speed + gain as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(keys: &[u8]) -> InputReport {
+        InputReport::Keyboard {
+            modifiers: 0,
+            keys: keys.to_vec(),
+        }
+    }
+
+    fn enabled() -> MouseKeys {
+        MouseKeys::new(&MouseKeysConfig {
+            enabled: true,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_held_direction_accelerates() {
+        let mut mouse_keys = enabled();
+        let t0 = Instant::now();
+        let at = |ms| t0 + Duration::from_millis(ms);
+
+        // 方向键不会出现在键盘报告中
+        let reports = mouse_keys.filter(keys(&[KEY_KP_6]), t0);
+        assert!(matches!(
+            reports.as_slice(),
+            [InputReport::Keyboard { keys, .. }] if keys.is_empty()
+        ));
+
+        let mut last = 0;
+        for ms in [0, 200, 500, 1000] {
+            assert_eq!(mouse_keys.deadline().map(|d| d <= at(ms)), Some(true));
+            match mouse_keys.poll(at(ms)) {
+                Some(InputReport::Mouse { x, y: 0, .. }) => {
+                    assert!(x as i32 > last, "{x} <= {last} at {ms}ms");
+                    last = x as i32;
+                }
+                other => panic!("unexpected report: {:?}", other),
+            }
+        }
+        assert_eq!(last, 20);
+
+        // 释放后停止移动
+        assert_eq!(mouse_keys.filter(keys(&[]), at(1010)).len(), 0);
+        assert_eq!(mouse_keys.deadline(), None);
+        assert!(mouse_keys.poll(at(1100)).is_none());
+    }
+
+    #[test]
+    fn test_reset_stops_movement() {
+        let mut mouse_keys = enabled();
+        let t0 = Instant::now();
+        mouse_keys.filter(keys(&[KEY_KP_6, KEY_KP_5]), t0);
+        assert!(mouse_keys.deadline().is_some());
+
+        mouse_keys.reset();
+        assert_eq!(mouse_keys.deadline(), None);
+        assert!(mouse_keys.poll(t0 + Duration::from_secs(1)).is_none());
+    }
+
+    #[test]
+    fn test_click_key_produces_button_report() {
+        let mut mouse_keys = enabled();
+        let t0 = Instant::now();
+
+        let reports = mouse_keys.filter(keys(&[KEY_A, KEY_KP_5]), t0);
+        match reports.as_slice() {
+            [
+                InputReport::Keyboard { keys, .. },
+                InputReport::Mouse { buttons: 0x01, .. },
+            ] => assert_eq!(keys, &vec![KEY_A]),
+            other => panic!("unexpected reports: {:?}", other),
+        }
+
+        let reports = mouse_keys.filter(keys(&[KEY_A]), t0);
+        assert!(matches!(
+            reports.as_slice(),
+            [InputReport::Mouse { buttons: 0, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_layer_gates_pointer_keys() {
+        let mut mouse_keys = MouseKeys::new(&MouseKeysConfig {
+            enabled: true,
+            layer: Some(KeyName(KEY_CAPS_LOCK)),
+            right: KeyName(KEY_L),
+            ..Default::default()
+        });
+        let t0 = Instant::now();
+
+        // 没有按住层键时正常输入
+        let reports = mouse_keys.filter(keys(&[KEY_L]), t0);
+        assert!(matches!(
+            reports.as_slice(),
+            [InputReport::Keyboard { keys, .. }] if keys == &vec![KEY_L]
+        ));
+        mouse_keys.filter(keys(&[]), t0);
+
+        mouse_keys.filter(keys(&[KEY_CAPS_LOCK]), t0);
+        mouse_keys.filter(keys(&[KEY_CAPS_LOCK, KEY_L]), t0);
+        assert!(mouse_keys.deadline().is_some());
+        // 松开层键后仍保持接管直到释放
+        assert!(mouse_keys.filter(keys(&[KEY_L]), t0).is_empty());
+        assert!(matches!(
+            mouse_keys.poll(t0),
+            Some(InputReport::Mouse { x: 2, .. })
+        ));
+    }
+}
@@ -0,0 +1,192 @@
+use async_trait::async_trait;
+use bluer::Address;
+use bluer::agent::{Agent, ReqError, ReqResult};
+use std::sync::Arc;
+
+/// 配对时的 passkey 与确认、授权请求由谁回答
+///
+/// 方法与 BlueZ Agent 的回调一一对应。默认的 [`AutoAccept`] 接受所有请求；
+/// 需要人工确认时可以实现为从终端读取 passkey，或通过网页弹窗询问。
+#[async_trait]
+pub trait PairingProvider: Send + Sync {
+    /// 主机要求输入 passkey
+    async fn request_passkey(&self, device: Address) -> ReqResult<u32>;
+    /// 确认双方显示的 passkey 一致
+    async fn request_confirmation(&self, device: Address, passkey: u32) -> ReqResult<()>;
+    /// 是否允许未经 passkey 的配对
+    async fn request_authorization(&self, device: Address) -> ReqResult<()>;
+}
+
+/// 默认 passkey
+pub const DEFAULT_PASSKEY: u32 = 123456;
+
+/// 接受所有请求，passkey 固定
+#[derive(Debug, Clone, Copy)]
+pub struct AutoAccept {
+    pub passkey: u32,
+}
+
+impl Default for AutoAccept {
+    fn default() -> Self {
+        Self {
+            passkey: DEFAULT_PASSKEY,
+        }
+    }
+}
+
+#[async_trait]
+impl PairingProvider for AutoAccept {
+    async fn request_passkey(&self, _device: Address) -> ReqResult<u32> {
+        Ok(self.passkey)
+    }
+
+    async fn request_confirmation(&self, _device: Address, _passkey: u32) -> ReqResult<()> {
+        Ok(())
+    }
+
+    async fn request_authorization(&self, _device: Address) -> ReqResult<()> {
+        Ok(())
+    }
+}
+
+/// 记录日志后把 Agent 回调转交给 [`PairingProvider`]
+#[derive(Clone)]
+struct Delegate(Arc<dyn PairingProvider>);
+
+impl Delegate {
+    async fn request_passkey(&self, device: Address) -> ReqResult<u32> {
+        log::info!("请求 Passkey，设备: {}", device);
+        let result = self.0.request_passkey(device).await;
+        log_rejected("Passkey 请求", device, &result);
+        result
+    }
+
+    async fn request_confirmation(&self, device: Address, passkey: u32) -> ReqResult<()> {
+        log::info!("确认配对请求，设备: {}，passkey: {}", device, passkey);
+        let result = self.0.request_confirmation(device, passkey).await;
+        log_rejected("配对确认", device, &result);
+        result
+    }
+
+    async fn request_authorization(&self, device: Address) -> ReqResult<()> {
+        log::info!("授权请求: {}", device);
+        let result = self.0.request_authorization(device).await;
+        log_rejected("授权请求", device, &result);
+        result
+    }
+}
+
+fn log_rejected<T>(what: &str, device: Address, result: &ReqResult<T>) {
+    match result {
+        Err(ReqError::Rejected) => log::warn!("{}被拒绝，设备: {}", what, device),
+        Err(e) => log::warn!("{}未完成，设备: {}: {:?}", what, device, e),
+        Ok(_) => {}
+    }
+}
+
+/// 构造配对 Agent，passkey、确认与授权请求交给 `provider`
+pub fn pairing_agent(provider: Arc<dyn PairingProvider>) -> Agent {
+    let delegate = Delegate(provider);
+    let (passkey, confirmation, authorization) = (delegate.clone(), delegate.clone(), delegate);
+    Agent {
+        request_default: true,
+        request_passkey: Some(Box::new(move |req| {
+            let delegate = passkey.clone();
+            Box::pin(async move { delegate.request_passkey(req.device).await })
+        })),
+        display_passkey: Some(Box::new(|req| {
+            Box::pin(async move {
+                log::info!("显示 Passkey: {} (已输入: {})", req.passkey, req.entered);
+                Ok(())
+            })
+        })),
+        request_confirmation: Some(Box::new(move |req| {
+            let delegate = confirmation.clone();
+            Box::pin(async move { delegate.request_confirmation(req.device, req.passkey).await })
+        })),
+        authorize_service: Some(Box::new(|req| {
+            Box::pin(async move {
+                log::info!("授权服务: 设备 {} 访问 {}", req.device, req.service);
+                Ok(())
+            })
+        })),
+        request_authorization: Some(Box::new(move |req| {
+            let delegate = authorization.clone();
+            Box::pin(async move { delegate.request_authorization(req.device).await })
+        })),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// 记录收到的请求，拒绝授权
+    #[derive(Default)]
+    struct MockProvider {
+        calls: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl PairingProvider for MockProvider {
+        async fn request_passkey(&self, device: Address) -> ReqResult<u32> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("passkey {}", device));
+            Ok(654321)
+        }
+
+        async fn request_confirmation(&self, device: Address, passkey: u32) -> ReqResult<()> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("confirm {} {}", device, passkey));
+            Ok(())
+        }
+
+        async fn request_authorization(&self, device: Address) -> ReqResult<()> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("authorize {}", device));
+            Err(ReqError::Rejected)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_agent_callbacks_delegate_to_provider() {
+        let provider = Arc::new(MockProvider::default());
+        let delegate = Delegate(provider.clone());
+        let device = Address::new([0x11, 0x22, 0x33, 0x44, 0x55, 0x66]);
+
+        assert_eq!(delegate.request_passkey(device).await, Ok(654321));
+        assert_eq!(delegate.request_confirmation(device, 42).await, Ok(()));
+        assert_eq!(
+            delegate.request_authorization(device).await,
+            Err(ReqError::Rejected)
+        );
+        assert_eq!(
+            *provider.calls.lock().unwrap(),
+            [
+                "passkey 11:22:33:44:55:66",
+                "confirm 11:22:33:44:55:66 42",
+                "authorize 11:22:33:44:55:66",
+            ]
+        );
+
+        // 默认行为与之前一致：固定 passkey，接受所有请求
+        let auto = Delegate(Arc::new(AutoAccept::default()));
+        assert_eq!(auto.request_passkey(device).await, Ok(DEFAULT_PASSKEY));
+        assert_eq!(auto.request_confirmation(device, 1).await, Ok(()));
+        assert_eq!(auto.request_authorization(device).await, Ok(()));
+
+        // Agent 的三个回调都已设置
+        let agent = pairing_agent(provider);
+        assert!(agent.request_passkey.is_some());
+        assert!(agent.request_confirmation.is_some());
+        assert!(agent.request_authorization.is_some());
+    }
+}
@@ -0,0 +1,155 @@
+//! HID 报告字节布局
+//!
+//! 各后端只负责传输，报告内容统一在这里生成，避免不同后端的格式与裁剪规则不一致。
+
+/// 传输层的报告封装约定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Framing {
+    /// 报告前的传输头，例如经典蓝牙 HIDP 的 `0xA1`（DATA | Input）
+    pub header: Option<u8>,
+    /// 报告 ID，描述符中没有 Report ID 时为 `None`
+    pub report_id: Option<u8>,
+}
+
+impl Framing {
+    /// 不带任何前缀：USB gadget 的独立 HID 功能，以及 BLE 通知（Report ID 由 Report Reference 描述）
+    pub const RAW: Self = Self {
+        header: None,
+        report_id: None,
+    };
+
+    /// 经典蓝牙 HIDP 中断通道：`0xA1` 头 + Report ID
+    pub const fn classic(report_id: u8) -> Self {
+        Self {
+            header: Some(0xA1),
+            report_id: Some(report_id),
+        }
+    }
+
+    fn frame(&self, payload: &[u8]) -> Vec<u8> {
+        let mut data = Vec::with_capacity(payload.len() + 2);
+        data.extend(self.header);
+        data.extend(self.report_id);
+        data.extend_from_slice(payload);
+        data
+    }
+}
+
+/// 键盘报告：`[modifiers, reserved, key1..key6]`，多于 6 个的普通键被丢弃
+pub fn build_keyboard(modifiers: u8, keys: &[u8], framing: Framing) -> Vec<u8> {
+    let mut payload = [0u8; 8];
+    payload[0] = modifiers;
+    for (slot, &key) in payload[2..].iter_mut().zip(keys) {
+        *slot = key;
+    }
+    framing.frame(&payload)
+}
+
+/// 鼠标报告：`[buttons, x, y, wheel]`，位移裁剪到描述符的 -127..=127
+pub fn build_mouse(buttons: u8, x: i16, y: i16, wheel: i8, framing: Framing) -> Vec<u8> {
+    let clamp = |v: i16| v.clamp(-127, 127) as i8 as u8;
+    framing.frame(&[buttons, clamp(x), clamp(y), wheel.max(-127) as u8])
+}
+
+/// 消费类控制报告：2 字节小端用法值
+pub fn build_consumer(usage: u16, framing: Framing) -> Vec<u8> {
+    framing.frame(&usage.to_le_bytes())
+}
+
+/// 系统控制报告：1 字节用法值
+pub fn build_system(usage: u8, framing: Framing) -> Vec<u8> {
+    framing.frame(&[usage])
+}
+
+/// 报告字节日志的 target，`RUST_LOG=bridge_hid::report=trace` 打印所有后端发出的每个报告
+pub const LOG_TARGET: &str = "bridge_hid::report";
+
+/// 以空格分隔的十六进制格式化报告，例如 `02 00 04 00 00 00 00 00`
+pub fn hex(data: &[u8]) -> String {
+    data.iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 记录一个发出的报告，日志级别未启用时不做格式化
+/// - `backend`: 后端名称，如 `USB`、`BLE`
+/// - `kind`: 报告类型，如 `键盘`、`鼠标`
+pub fn log_sent(backend: &str, kind: &str, data: &[u8]) {
+    if log::log_enabled!(target: LOG_TARGET, log::Level::Trace) {
+        log::trace!(target: LOG_TARGET, "{} {}: {}", backend, kind, hex(data));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::system;
+
+    #[test]
+    fn test_hex_dump() {
+        let report = build_keyboard(0x02, &[0x04], Framing::classic(1));
+        assert_eq!(hex(&report), "A1 01 02 00 04 00 00 00 00 00");
+        assert_eq!(hex(&[]), "");
+    }
+
+    #[test]
+    fn test_keyboard_framing() {
+        let keys = [0x04, 0x05];
+        let payload = [0x02, 0x00, 0x04, 0x05, 0, 0, 0, 0];
+        assert_eq!(build_keyboard(0x02, &keys, Framing::RAW), payload);
+
+        let classic = build_keyboard(0x02, &keys, Framing::classic(1));
+        assert_eq!(classic[..2], [0xA1, 0x01]);
+        assert_eq!(classic[2..], payload);
+
+        let id_only = Framing {
+            header: None,
+            report_id: Some(1),
+        };
+        assert_eq!(build_keyboard(0x02, &keys, id_only)[..2], [0x01, 0x02]);
+    }
+
+    #[test]
+    fn test_keyboard_truncates_to_six_keys() {
+        let report = build_keyboard(0, &[1, 2, 3, 4, 5, 6, 7, 8], Framing::RAW);
+        assert_eq!(report, [0, 0, 1, 2, 3, 4, 5, 6]);
+        assert_eq!(build_keyboard(0, &[], Framing::RAW), [0u8; 8]);
+    }
+
+    #[test]
+    fn test_mouse_clamps_and_frames() {
+        assert_eq!(
+            build_mouse(0x01, 5, -5, 1, Framing::RAW),
+            [0x01, 5, 0xFB, 1]
+        );
+        // 超出范围的位移裁剪而不是截断
+        assert_eq!(
+            build_mouse(0, 300, -300, i8::MIN, Framing::RAW),
+            [0, 127, 0x81, 0x81]
+        );
+        assert_eq!(
+            build_mouse(0x02, 1, 2, 0, Framing::classic(2)),
+            [0xA1, 0x02, 0x02, 1, 2, 0]
+        );
+    }
+
+    #[test]
+    fn test_consumer_framing() {
+        assert_eq!(build_consumer(0x00E9, Framing::RAW), [0xE9, 0x00]);
+        assert_eq!(
+            build_consumer(0x00CD, Framing::classic(3)),
+            [0xA1, 0x03, 0xCD, 0x00]
+        );
+    }
+
+    #[test]
+    fn test_system_sleep_report() {
+        assert_eq!(build_system(system::SLEEP, Framing::RAW), [0x82]);
+        assert_eq!(build_system(0, Framing::RAW), [0x00]);
+        assert_eq!(
+            build_system(system::SLEEP, Framing::classic(4)),
+            [0xA1, 0x04, 0x82]
+        );
+    }
+}
@@ -0,0 +1,408 @@
+use crate::input::InputReport;
+use crate::metrics::{self, ReportKind, Stage};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// 键盘报告节流器
+///
+/// 与 `MouseRateController` 类似，但作用于发送路径上的键盘报告：相邻两个报告至少间隔
+/// `interval`。间隔内到达、与最新状态相同的报告被合并丢弃；按键或修饰键的变化不会丢失，
+/// 按到达顺序排队，调用方在 [`ReportThrottle::flush_deadline`] 到期后用
+/// [`ReportThrottle::flush`] 依次发出，因此主机看到的最终状态始终与输入一致。
+pub struct ReportThrottle {
+    /// 最小发送间隔，为零表示不限流
+    interval: Duration,
+    last_sent: Option<SentKeyboard>,
+    /// 间隔内到达、尚未发出的按键变化
+    pending: VecDeque<KeyState>,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+struct KeyState {
+    modifiers: u8,
+    keys: Vec<u8>,
+}
+
+struct SentKeyboard {
+    state: KeyState,
+    at: Instant,
+}
+
+impl ReportThrottle {
+    /// 创建节流器
+    /// - `interval`: 最小报告间隔，设为 0 表示不限制
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_sent: None,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// 是否启用限流
+    pub fn is_enabled(&self) -> bool {
+        !self.interval.is_zero()
+    }
+
+    /// 返回应当立即发送的报告，被合并或排队时返回 `None`；非键盘报告原样放行
+    pub fn admit(&mut self, report: InputReport) -> Option<InputReport> {
+        self.admit_at(report, Instant::now())
+    }
+
+    /// 清除已发送状态与排队的变化，例如切换输出或释放全部按键之后
+    pub fn reset(&mut self) {
+        self.last_sent = None;
+        self.pending.clear();
+    }
+
+    /// 有排队的按键变化时，下一个可以发出的时间
+    pub fn flush_deadline(&self) -> Option<Instant> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        self.last_sent.as_ref().map(|last| last.at + self.interval)
+    }
+
+    /// 到期时返回下一个排队的按键变化
+    pub fn flush(&mut self) -> Option<InputReport> {
+        self.flush_at(Instant::now())
+    }
+
+    fn flush_at(&mut self, now: Instant) -> Option<InputReport> {
+        if self.flush_deadline()? > now {
+            return None;
+        }
+        let state = self.pending.pop_front()?;
+        Some(self.emit(state, now))
+    }
+
+    fn admit_at(&mut self, report: InputReport, now: Instant) -> Option<InputReport> {
+        let InputReport::Keyboard { modifiers, keys } = report else {
+            return Some(report);
+        };
+        let state = KeyState { modifiers, keys };
+        if !self.is_enabled() {
+            return Some(self.emit(state, now));
+        }
+
+        let ready = self
+            .last_sent
+            .as_ref()
+            .is_none_or(|last| now.duration_since(last.at) >= self.interval);
+        if ready && self.pending.is_empty() {
+            return Some(self.emit(state, now));
+        }
+
+        let latest = self
+            .pending
+            .back()
+            .or(self.last_sent.as_ref().map(|last| &last.state));
+        if latest == Some(&state) {
+            metrics::global()
+                .report_loss
+                .record_coalesced(Stage::RateController, ReportKind::Keyboard);
+        } else {
+            self.pending.push_back(state);
+        }
+        None
+    }
+
+    fn emit(&mut self, state: KeyState, now: Instant) -> InputReport {
+        let report = InputReport::Keyboard {
+            modifiers: state.modifiers,
+            keys: state.keys.clone(),
+        };
+        self.last_sent = Some(SentKeyboard { state, at: now });
+        report
+    }
+}
+
+impl Default for ReportThrottle {
+    fn default() -> Self {
+        Self::new(Duration::ZERO) // 默认不限制
+    }
+}
+
+/// 后端鼠标报告率上限
+///
+/// 与只作用于 evdev 路径的 `MouseRateController` 无关，在后端的 `send_report` 中执行，
+/// 网页、宏等任何来源的鼠标报告都受其约束。间隔内到达的报告被合并，位移与滚轮
+/// 累加到下一次放行的报告中；按键变化总是立即放行。之后没有新报告时，
+/// 调用方在 [`MouseRateCap::flush_deadline`] 到期后用 [`MouseRateCap::flush`] 补发合并的位移。
+pub struct MouseRateCap {
+    /// 最小发送间隔，为零表示不限制
+    interval: Duration,
+    last_sent: Option<Instant>,
+    buttons: u8,
+    /// 尚未发出的位移与滚轮
+    pending: (i32, i32, i32),
+}
+
+impl MouseRateCap {
+    /// 创建上限
+    /// - `max_hz`: 每秒最多发送的鼠标报告数，设为 0 表示不限制
+    pub fn new(max_hz: u32) -> Self {
+        let interval = if max_hz == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs(1) / max_hz
+        };
+        Self {
+            interval,
+            last_sent: None,
+            buttons: 0,
+            pending: (0, 0, 0),
+        }
+    }
+
+    /// 返回应当发送的报告，被合并时返回 `None`；非鼠标报告原样放行
+    pub fn admit(&mut self, report: InputReport) -> Option<InputReport> {
+        self.admit_at(report, Instant::now())
+    }
+
+    /// 距离下一个报告可以放行还需等待的时间
+    pub fn wait_time(&self) -> Duration {
+        self.wait_time_at(Instant::now())
+    }
+
+    fn wait_time_at(&self, now: Instant) -> Duration {
+        self.last_sent
+            .map(|last| self.interval.saturating_sub(now.duration_since(last)))
+            .unwrap_or_default()
+    }
+
+    fn admit_at(&mut self, report: InputReport, now: Instant) -> Option<InputReport> {
+        let InputReport::Mouse {
+            buttons,
+            x,
+            y,
+            wheel,
+        } = report
+        else {
+            return Some(report);
+        };
+
+        self.pending.0 += x as i32;
+        self.pending.1 += y as i32;
+        self.pending.2 += wheel as i32;
+        if !self.wait_time_at(now).is_zero() && buttons == self.buttons {
+            metrics::global()
+                .report_loss
+                .record_coalesced(Stage::RateController, ReportKind::Mouse);
+            return None;
+        }
+        Some(self.emit(buttons, now))
+    }
+
+    /// 有被合并而尚未发出的位移或滚轮时，应当补发的时间
+    pub fn flush_deadline(&self) -> Option<Instant> {
+        if self.pending == (0, 0, 0) {
+            return None;
+        }
+        self.last_sent.map(|last| last + self.interval)
+    }
+
+    /// 到期时返回补发合并位移的报告，按键保持上一次发送的状态
+    pub fn flush(&mut self) -> Option<InputReport> {
+        self.flush_at(Instant::now())
+    }
+
+    fn flush_at(&mut self, now: Instant) -> Option<InputReport> {
+        if self.flush_deadline()? > now {
+            return None;
+        }
+        Some(self.emit(self.buttons, now))
+    }
+
+    /// 发出累积的位移与滚轮，超出报告范围的部分留到下一次
+    fn emit(&mut self, buttons: u8, now: Instant) -> InputReport {
+        let (px, py, pw) = self.pending;
+        let (x, y, wheel) = (
+            px.clamp(i16::MIN as i32, i16::MAX as i32),
+            py.clamp(i16::MIN as i32, i16::MAX as i32),
+            pw.clamp(i8::MIN as i32, i8::MAX as i32),
+        );
+        self.pending = (px - x, py - y, pw - wheel);
+        self.last_sent = Some(now);
+        self.buttons = buttons;
+        InputReport::Mouse {
+            buttons,
+            x: x as i16,
+            y: y as i16,
+            wheel: wheel as i8,
+        }
+    }
+}
+
+impl Default for MouseRateCap {
+    fn default() -> Self {
+        Self::new(0) // 默认不限制
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kb(modifiers: u8, keys: &[u8]) -> InputReport {
+        InputReport::Keyboard {
+            modifiers,
+            keys: keys.to_vec(),
+        }
+    }
+
+    fn keys_of(report: &InputReport) -> (u8, Vec<u8>) {
+        match report {
+            InputReport::Keyboard { modifiers, keys } => (*modifiers, keys.clone()),
+            other => panic!("unexpected report: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_burst_is_coalesced_and_final_state_kept() {
+        let mut throttle = ReportThrottle::new(Duration::from_millis(20));
+        let start = Instant::now();
+        let burst = [
+            kb(0x02, &[0x04]),
+            kb(0x02, &[0x04]),
+            kb(0x02, &[0x04]),
+            kb(0x02, &[0x04, 0x05]),
+            kb(0x02, &[0x04, 0x05]),
+            kb(0x00, &[]),
+            kb(0x00, &[]),
+        ];
+
+        // 1 ms 一个报告，只有第一个立即发出
+        let mut sent: Vec<_> = burst
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, r)| throttle.admit_at(r, start + Duration::from_millis(i as u64)))
+            .map(|r| (start, r))
+            .collect();
+        assert_eq!(sent.len(), 1);
+
+        // 排队的变化按间隔依次补发
+        while let Some(deadline) = throttle.flush_deadline() {
+            assert!(
+                throttle
+                    .flush_at(deadline - Duration::from_millis(1))
+                    .is_none()
+            );
+            sent.push((deadline, throttle.flush_at(deadline).unwrap()));
+        }
+        let states: Vec<_> = sent.iter().map(|(_, r)| keys_of(r)).collect();
+        assert_eq!(
+            states,
+            [(0x02, vec![0x04]), (0x02, vec![0x04, 0x05]), (0, vec![])]
+        );
+        for pair in sent.windows(2) {
+            assert_eq!(pair[1].0 - pair[0].0, Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn test_repeat_after_interval_is_sent() {
+        let mut throttle = ReportThrottle::new(Duration::from_millis(20));
+        let start = Instant::now();
+        assert!(throttle.admit_at(kb(0, &[0x04]), start).is_some());
+        assert!(
+            throttle
+                .admit_at(kb(0, &[0x04]), start + Duration::from_millis(5))
+                .is_none()
+        );
+        assert_eq!(throttle.flush_deadline(), None);
+        assert!(
+            throttle
+                .admit_at(kb(0, &[0x04]), start + Duration::from_millis(25))
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_disabled_sends_everything() {
+        let mut throttle = ReportThrottle::default();
+        let now = Instant::now();
+        for keys in [[0x04], [0x04], [0x05]] {
+            assert!(throttle.admit_at(kb(0, &keys), now).is_some());
+        }
+        assert_eq!(throttle.flush_deadline(), None);
+    }
+
+    fn mouse(buttons: u8, x: i16) -> InputReport {
+        InputReport::Mouse {
+            buttons,
+            x,
+            y: 0,
+            wheel: 0,
+        }
+    }
+
+    #[test]
+    fn test_mouse_cap_limits_rate_and_keeps_motion() {
+        let mut cap = MouseRateCap::new(125);
+        let start = Instant::now();
+        // 1 kHz 的注入持续 100ms
+        let sent: Vec<_> = (0..100)
+            .filter_map(|i| cap.admit_at(mouse(0, 1), start + Duration::from_millis(i)))
+            .collect();
+
+        // 8ms 间隔下 100ms 内至多 13 个报告
+        assert!(sent.len() <= 13, "{} reports", sent.len());
+        let moved: i32 = sent
+            .iter()
+            .map(|r| match r {
+                InputReport::Mouse { x, .. } => *x as i32,
+                _ => 0,
+            })
+            .sum();
+        // 合并的位移随后续报告发出，最多只剩最后一个间隔内的
+        assert!(moved > 90, "moved {moved}");
+    }
+
+    #[test]
+    fn test_mouse_cap_flushes_coalesced_motion() {
+        let mut cap = MouseRateCap::new(125);
+        let start = Instant::now();
+        assert!(cap.admit_at(mouse(0, 1), start).is_some());
+        assert_eq!(cap.flush_deadline(), None);
+        assert!(
+            cap.admit_at(mouse(0, 2), start + Duration::from_millis(1))
+                .is_none()
+        );
+        assert!(
+            cap.admit_at(mouse(0, 3), start + Duration::from_millis(2))
+                .is_none()
+        );
+
+        let deadline = cap.flush_deadline().unwrap();
+        assert_eq!(deadline, start + Duration::from_millis(8));
+        assert!(cap.flush_at(deadline - Duration::from_millis(1)).is_none());
+        assert!(matches!(
+            cap.flush_at(deadline),
+            Some(InputReport::Mouse {
+                buttons: 0,
+                x: 5,
+                ..
+            })
+        ));
+        assert_eq!(cap.flush_deadline(), None);
+    }
+
+    #[test]
+    fn test_mouse_cap_passes_button_edges_and_other_reports() {
+        let mut cap = MouseRateCap::new(125);
+        let now = Instant::now();
+        assert!(cap.admit_at(mouse(0, 1), now).is_some());
+        assert!(cap.admit_at(mouse(0, 1), now).is_none());
+        assert!(matches!(
+            cap.admit_at(mouse(0x01, 0), now),
+            Some(InputReport::Mouse {
+                buttons: 0x01,
+                x: 1,
+                ..
+            })
+        ));
+        assert!(cap.admit_at(mouse(0, 0), now).is_some());
+        assert!(cap.admit_at(kb(0, &[0x04]), now).is_some());
+    }
+}
@@ -1,21 +1,28 @@
 use anyhow::{Context, Ok, Result, anyhow};
 use async_trait::async_trait;
 use glob;
-use log::{debug, error, info, warn};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
 use std::error::Error as StdError;
 use std::fmt;
 use std::fs::OpenOptions;
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
 use tokio::fs::File as TokioFile;
 use tokio::io::AsyncWriteExt;
 use tokio::time::{Duration, sleep, timeout};
-use usb_gadget::{Class, Config, Gadget, Id, Strings, default_udc, function::hid::Hid};
+use usb_gadget::{
+    Class, Config, Gadget, Id, Strings, default_udc,
+    function::hid::{Hid, HidBuilder},
+};
 
 use crate::output::InputReport;
-use crate::output::{HidLedReader, HidReportSender};
+use crate::output::{BackendCapabilities, HidLedReader, HidReportSender};
 
 use super::LedState;
+use super::report::{self, Framing};
+use super::throttle::MouseRateCap;
 
 /// 键盘 HID 报告描述符
 const KEYBOARD_REPORT_DESC: &[u8] = &[
@@ -49,10 +56,10 @@ const KEYBOARD_REPORT_DESC: &[u8] = &[
     0x95, 0x06, //   Report Count (6)
     0x75, 0x08, //   Report Size (8)
     0x15, 0x00, //   Logical Minimum (0)
-    0x25, 0x65, //   Logical Maximum (101)
+    0x26, 0xFF, 0x00, // Logical Maximum (255)，覆盖 International/Lang 等日韩键
     0x05, 0x07, //   Usage Page (Key Codes)
     0x19, 0x00, //   Usage Minimum (0)
-    0x29, 0x65, //   Usage Maximum (101)
+    0x2A, 0xFF, 0x00, // Usage Maximum (255)
     0x81, 0x00, //   Input (Data, Array) - Key arrays (6 keys)
     0xC0, // End Collection
 ];
@@ -88,6 +95,257 @@ const MOUSE_REPORT_DESC: &[u8] = &[
     0xC0, // End Collection
 ];
 
+/// 消费类控制（媒体键）HID 报告描述符，报告为 2 字节小端用法值
+const CONSUMER_REPORT_DESC: &[u8] = &[
+    0x05, 0x0C, // Usage Page (Consumer)
+    0x09, 0x01, // Usage (Consumer Control)
+    0xA1, 0x01, // Collection (Application)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x26, 0xFF, 0x03, //   Logical Maximum (0x3FF)
+    0x19, 0x00, //   Usage Minimum (0)
+    0x2A, 0xFF, 0x03, //   Usage Maximum (0x3FF)
+    0x75, 0x10, //   Report Size (16)
+    0x95, 0x01, //   Report Count (1)
+    0x81, 0x00, //   Input (Data, Array, Absolute)
+    0xC0, // End Collection
+];
+
+/// 系统控制（关机、睡眠、唤醒）HID 报告描述符，报告为 1 字节用法值
+const SYSTEM_REPORT_DESC: &[u8] = &[
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x80, // Usage (System Control)
+    0xA1, 0x01, // Collection (Application)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x26, 0x83, 0x00, //   Logical Maximum (0x83)
+    0x19, 0x00, //   Usage Minimum (0)
+    0x29, 0x83, //   Usage Maximum (System Wake Up)
+    0x75, 0x08, //   Report Size (8)
+    0x95, 0x01, //   Report Count (1)
+    0x81, 0x00, //   Input (Data, Array, Absolute)
+    0xC0, // End Collection
+];
+
+/// 厂商自定义 HID 报告描述符，报告为 3 字节：evdev 键码（小端）+ 按下标志
+const VENDOR_REPORT_DESC: &[u8] = &[
+    0x06, 0x00, 0xFF, // Usage Page (Vendor Defined 0xFF00)
+    0x09, 0x01, // Usage (Vendor Usage 1)
+    0xA1, 0x01, // Collection (Application)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x26, 0xFF, 0x00, //   Logical Maximum (255)
+    0x75, 0x08, //   Report Size (8)
+    0x95, 0x03, //   Report Count (3)
+    0x09, 0x01, //   Usage (Vendor Usage 1)
+    0x81, 0x02, //   Input (Data, Variable, Absolute)
+    0xC0, // End Collection
+];
+
+/// 默认序列号
+pub const DEFAULT_SERIAL: &str = "001";
+/// 表示根据本机 `/etc/machine-id` 自动生成序列号
+pub const AUTO_SERIAL: &str = "auto";
+
+const MACHINE_ID_PATH: &str = "/etc/machine-id";
+
+/// USB 鼠标报告率上限：全速设备中断端点每 1ms 轮询一次
+pub const DEFAULT_USB_MAX_MOUSE_RATE_HZ: u32 = 1000;
+
+/// USB gadget 配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsbConfig {
+    /// 额外创建厂商自定义 HID 功能，用于透传没有标准映射的原始键码（跟随输入配置）
+    #[serde(skip)]
+    pub raw_passthrough: bool,
+    /// 额外创建消费类控制（媒体键）HID 功能；默认关闭，保持原有的 gadget 布局，
+    /// 旋钮映射为音量时自动启用
+    pub consumer_control: bool,
+    /// 额外创建系统控制（电源、睡眠、唤醒）HID 功能；默认关闭，保持原有的 gadget 布局
+    pub system_control: bool,
+    /// 设备序列号，设为 `"auto"` 时根据本机 machine-id 生成稳定的序列号
+    pub serial: String,
+    /// 鼠标报告率硬上限（Hz），超出的报告被合并，为 0 表示不限制
+    pub max_mouse_rate_hz: u32,
+    /// 在键盘与鼠标描述符中声明 Report ID 并在报告前附加，供要求 Report ID 的主机使用；
+    /// 默认关闭，保持引导协议布局
+    pub report_ids: bool,
+    /// sysfs 中找不到设备节点时，按设备号逐个检查的 `/dev/hidgN` 数量
+    pub hidg_scan_count: u32,
+    /// 以 `O_NONBLOCK` 单独打开读取 LED 的键盘节点：没有输出报告时读取立即返回，
+    /// LED 任务改为每隔 [`LED_POLL_INTERVAL`] 轮询一次；发送报告的句柄不受影响
+    pub nonblocking_leds: bool,
+}
+
+impl Default for UsbConfig {
+    fn default() -> Self {
+        Self {
+            raw_passthrough: false,
+            consumer_control: false,
+            system_control: false,
+            serial: DEFAULT_SERIAL.to_string(),
+            max_mouse_rate_hz: DEFAULT_USB_MAX_MOUSE_RATE_HZ,
+            report_ids: false,
+            hidg_scan_count: DEFAULT_HIDG_SCAN_COUNT,
+            nonblocking_leds: false,
+        }
+    }
+}
+
+impl UsbConfig {
+    /// 键盘报告的 Report ID，未启用 `report_ids` 时为 `None`
+    fn keyboard_report_id(&self) -> Option<u8> {
+        self.report_ids.then_some(KEYBOARD_REPORT_ID)
+    }
+
+    /// 鼠标报告的 Report ID，未启用 `report_ids` 时为 `None`
+    fn mouse_report_id(&self) -> Option<u8> {
+        self.report_ids.then_some(MOUSE_REPORT_ID)
+    }
+
+    /// 实际使用的序列号
+    pub fn resolve_serial(&self) -> String {
+        if self.serial == AUTO_SERIAL {
+            machine_serial()
+        } else {
+            self.serial.clone()
+        }
+    }
+}
+
+/// 本机稳定的序列号，同一次运行中只计算一次
+fn machine_serial() -> String {
+    static SERIAL: OnceLock<String> = OnceLock::new();
+    SERIAL
+        .get_or_init(|| match std::fs::read_to_string(MACHINE_ID_PATH) {
+            std::result::Result::Ok(id) if !id.trim().is_empty() => serial_from_machine_id(&id),
+            _ => {
+                warn!("无法读取 {}，使用默认序列号", MACHINE_ID_PATH);
+                DEFAULT_SERIAL.to_string()
+            }
+        })
+        .clone()
+}
+
+/// 对 machine-id 做 FNV-1a 哈希，避免直接暴露原始 ID
+fn serial_from_machine_id(id: &str) -> String {
+    let hash = id.trim().bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("{:016X}", hash)
+}
+
+/// 构造 gadget 字符串描述符
+fn gadget_strings(usb_config: &UsbConfig) -> Strings {
+    Strings::new(
+        GADGET_MANUFACTURER,
+        "Virtual Keyboard Mouse",
+        usb_config.resolve_serial(),
+    )
+}
+
+/// 本程序创建的 gadget 使用的制造商字符串，用于识别需要清理的残留 gadget
+const GADGET_MANUFACTURER: &str = "Bridge HID";
+/// 移除残留 gadget 的尝试次数
+const REMOVE_ATTEMPTS: u32 = 3;
+/// 两次移除尝试之间的等待时间
+const REMOVE_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// configfs 中已注册的 gadget，测试中可替换为模拟实现
+pub trait GadgetRegistry {
+    /// 列出所有已注册 gadget 的名称
+    fn list(&self) -> std::io::Result<Vec<String>>;
+    /// 读取 gadget 的制造商字符串，读取失败时返回 `None`
+    fn manufacturer(&self, name: &str) -> Option<String>;
+    /// 解绑并移除指定 gadget
+    fn remove(&self, name: &str) -> std::io::Result<()>;
+}
+
+/// 通过 usb-gadget 访问系统 configfs
+pub struct ConfigfsGadgets;
+
+impl ConfigfsGadgets {
+    fn find(&self, name: &str) -> std::io::Result<usb_gadget::RegGadget> {
+        usb_gadget::registered()?
+            .into_iter()
+            .find(|gadget| gadget.name() == name)
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))
+    }
+}
+
+impl GadgetRegistry for ConfigfsGadgets {
+    fn list(&self) -> std::io::Result<Vec<String>> {
+        std::result::Result::Ok(
+            usb_gadget::registered()?
+                .iter()
+                .map(|gadget| gadget.name().to_string_lossy().into_owned())
+                .collect(),
+        )
+    }
+
+    fn manufacturer(&self, name: &str) -> Option<String> {
+        let gadget = self.find(name).ok()?;
+        // 字符串按语言分目录存放，取第一个语言即可
+        let lang = std::fs::read_dir(gadget.path().join("strings"))
+            .ok()?
+            .flatten()
+            .next()?
+            .path();
+        std::fs::read_to_string(lang.join("manufacturer"))
+            .ok()
+            .map(|text| text.trim_end().to_string())
+    }
+
+    fn remove(&self, name: &str) -> std::io::Result<()> {
+        self.find(name)?.remove()
+    }
+}
+
+/// 移除本程序此前创建、尚未清理的 gadget（按制造商匹配），其他 gadget 不受影响
+///
+/// 不比较序列号：序列号或 machine-id 变化后，上次运行的 gadget 仍占用着 UDC。
+/// 部分移除失败时会重试；仍未移除的 gadget 名称通过错误返回。
+pub async fn remove_own_gadgets(registry: &impl GadgetRegistry) -> Result<Vec<String>> {
+    let mut pending: Vec<String> = match registry.list() {
+        std::result::Result::Ok(names) => names
+            .into_iter()
+            .filter(|name| {
+                registry
+                    .manufacturer(name)
+                    .is_some_and(|manufacturer| manufacturer == GADGET_MANUFACTURER)
+            })
+            .collect(),
+        // configfs 中尚无 usb_gadget 目录，说明没有任何 gadget
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context("无法列出现有 gadgets"),
+    };
+
+    let mut removed = Vec::new();
+    for attempt in 1..=REMOVE_ATTEMPTS {
+        let mut failed = Vec::new();
+        for name in pending {
+            match registry.remove(&name) {
+                std::result::Result::Ok(()) => removed.push(name),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => removed.push(name),
+                Err(e) => {
+                    debug!("第 {} 次移除 gadget {} 失败: {}", attempt, name, e);
+                    failed.push(name);
+                }
+            }
+        }
+        pending = failed;
+        if pending.is_empty() {
+            return Ok(removed);
+        }
+        if attempt < REMOVE_ATTEMPTS {
+            sleep(REMOVE_RETRY_DELAY).await;
+        }
+    }
+    Err(anyhow!(UsbError(format!(
+        "重试 {} 次后仍无法移除 gadget: {}",
+        REMOVE_ATTEMPTS,
+        pending.join(", ")
+    ))))
+}
+
 #[derive(Debug, Clone)]
 pub struct UsbError(String);
 
@@ -99,14 +357,199 @@ impl fmt::Display for UsbError {
 
 impl StdError for UsbError {}
 
+/// 启用 `report_ids` 时键盘报告（以及 LED 输出报告）的 Report ID
+///
+/// 默认描述符没有 Report ID，输出报告只有一个 LED 字节；声明 Report ID 后
+/// 输出报告首字节为 Report ID，LED 位紧随其后。
+const KEYBOARD_REPORT_ID: u8 = 1;
+/// 启用 `report_ids` 时鼠标报告的 Report ID
+const MOUSE_REPORT_ID: u8 = 2;
+
+/// 在描述符的第一个 Collection (Application) 之后声明 Report ID
+fn with_report_id(desc: &[u8], report_id: Option<u8>) -> Vec<u8> {
+    let mut desc = desc.to_vec();
+    if let Some(id) = report_id {
+        let at = desc
+            .windows(2)
+            .position(|item| item == [0xA1, 0x01])
+            .map_or(0, |pos| pos + 2);
+        desc.splice(at..at, [0x85, id]); // Report ID (id)
+    }
+    desc
+}
+
+/// 键盘 HID 功能，报告长度随 Report ID 变化，与描述符保持一致
+fn keyboard_function(report_id: Option<u8>) -> HidBuilder {
+    let mut builder = Hid::builder();
+    builder.sub_class = 1; // Boot Interface Subclass
+    builder.protocol = 1; // Keyboard
+    builder.report_desc = with_report_id(KEYBOARD_REPORT_DESC, report_id);
+    builder.report_len = 8 + report_id.is_some() as u8;
+    builder
+}
+
+/// 鼠标 HID 功能，报告长度随 Report ID 变化，与描述符保持一致
+fn mouse_function(report_id: Option<u8>) -> HidBuilder {
+    let mut builder = Hid::builder();
+    builder.sub_class = 1; // Boot Interface Subclass
+    builder.protocol = 2; // Mouse
+    builder.report_desc = with_report_id(MOUSE_REPORT_DESC, report_id);
+    builder.report_len = 4 + report_id.is_some() as u8;
+    builder
+}
+
+/// USB gadget 报告只可能带 Report ID，没有传输头
+fn usb_framing(report_id: Option<u8>) -> Framing {
+    Framing {
+        header: None,
+        report_id,
+    }
+}
+
+/// 单次读取的最大输出报告长度
+const OUTPUT_REPORT_MAX: usize = 64;
+/// 非阻塞模式下两次读取 LED 输出报告的间隔
+pub const LED_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// 读取一条输出报告并解析 LED 状态
+///
+/// 阻塞打开的节点会挂起直到主机下发报告；以 `O_NONBLOCK` 打开且没有数据时立即返回 `Ok(None)`。
+async fn read_led_report(file: &mut TokioFile, report_id: Option<u8>) -> Result<Option<LedState>> {
+    use tokio::io::AsyncReadExt;
+
+    // hidg 每次 read 返回一条完整的输出报告，缓冲区需能容纳最长的报告
+    let mut buf = [0u8; OUTPUT_REPORT_MAX];
+    match file.read(&mut buf).await {
+        std::result::Result::Ok(0) => Ok(None), // EOF，通常表示设备关闭
+        std::result::Result::Ok(n) => {
+            let state = parse_led_report(&buf[..n], report_id);
+            if state.is_none() {
+                debug!("忽略非 LED 输出报告: {:02x?}", &buf[..n]);
+            }
+            Ok(state)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(None),
+        Err(e) => Err(anyhow!("读取 LED 状态失败: {}", e)),
+    }
+}
+
+/// 从一条输出报告中解析 LED 状态，与 LED 无关的输出报告返回 `None`
+/// - `report_id`: LED 报告的 Report ID，`None` 表示报告不带 Report ID
+fn parse_led_report(data: &[u8], report_id: Option<u8>) -> Option<LedState> {
+    match report_id {
+        None => data.first().map(|&byte| LedState::from_byte(byte)),
+        Some(id) => match data {
+            [first, leds, ..] if *first == id => Some(LedState::from_byte(*leds)),
+            _ => None,
+        },
+    }
+}
+
 /// USB HID 键盘鼠标模拟器
 pub struct UsbKeyboardHidDevice {
-    keyboard_file: Option<tokio::fs::File>,
+    nodes: KeyboardNodes,
+    keyboard_report_id: Option<u8>,
+    /// 主机最近一次下发的 LED 状态
+    host_leds: Option<LedState>,
+    /// 键盘节点以 `O_NONBLOCK` 打开，LED 需要轮询
+    nonblocking_leds: bool,
     _registration: Arc<usb_gadget::RegGadget>,
 }
 
+/// 键盘侧报告写入的 hidg 节点
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyboardNode {
+    Keyboard,
+    Consumer,
+    System,
+    Vendor,
+}
+
+impl KeyboardNode {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Keyboard => "键盘",
+            Self::Consumer => "消费类控制",
+            Self::System => "系统控制",
+            Self::Vendor => "厂商自定义",
+        }
+    }
+}
+
+/// 键盘侧的 hidg 节点，未打开的节点为 `None`，发往它的报告被忽略
+#[derive(Default)]
+struct KeyboardNodes {
+    keyboard: Option<TokioFile>,
+    consumer: Option<TokioFile>,
+    system: Option<TokioFile>,
+    vendor: Option<TokioFile>,
+}
+
+impl KeyboardNodes {
+    fn file(&mut self, node: KeyboardNode) -> Option<&mut TokioFile> {
+        match node {
+            KeyboardNode::Keyboard => self.keyboard.as_mut(),
+            KeyboardNode::Consumer => self.consumer.as_mut(),
+            KeyboardNode::System => self.system.as_mut(),
+            KeyboardNode::Vendor => self.vendor.as_mut(),
+        }
+    }
+
+    /// 按顺序写入多个报告
+    ///
+    /// hidg 每次 write 只接受一个报告（多出的部分被截断），所以不能把多个报告拼成一次写入。
+    /// 切换到另一个节点前先 flush，保证不同节点之间的先后顺序。
+    async fn write_frames(&mut self, frames: &[(KeyboardNode, Vec<u8>)]) -> Result<()> {
+        for (i, (node, data)) in frames.iter().enumerate() {
+            let Some(file) = self.file(*node) else {
+                continue;
+            };
+            report::log_sent("USB", node.label(), data);
+            file.write_all(data)
+                .await
+                .map_err(|e| UsbError(format!("异步发送{}报告失败: {}", node.label(), e)))?;
+            if frames.get(i + 1).is_some_and(|(next, _)| next != node) {
+                file.flush()
+                    .await
+                    .map_err(|e| UsbError(format!("异步发送{}报告失败: {}", node.label(), e)))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 键盘侧报告对应的节点与报告数据
+fn frame_keyboard_report(
+    report: &InputReport,
+    keyboard_report_id: Option<u8>,
+) -> Result<(KeyboardNode, Vec<u8>)> {
+    Ok(match report {
+        InputReport::Keyboard { modifiers, keys } => (
+            KeyboardNode::Keyboard,
+            report::build_keyboard(*modifiers, keys, usb_framing(keyboard_report_id)),
+        ),
+        InputReport::Consumer { usage } => (
+            KeyboardNode::Consumer,
+            report::build_consumer(*usage, Framing::RAW),
+        ),
+        InputReport::System { usage } => (
+            KeyboardNode::System,
+            report::build_system(*usage, Framing::RAW),
+        ),
+        InputReport::Vendor { code, pressed } => {
+            let [lo, hi] = code.to_le_bytes();
+            (KeyboardNode::Vendor, vec![lo, hi, *pressed as u8])
+        }
+        InputReport::Mouse { .. } => {
+            return Err(anyhow!("收到鼠标报告,但当前后端仅支持键盘"));
+        }
+    })
+}
+
 pub struct UsbMouseHidDevice {
     mouse_file: Option<tokio::fs::File>,
+    mouse_report_id: Option<u8>,
+    rate_cap: MouseRateCap,
     _registration: Arc<usb_gadget::RegGadget>,
 }
 
@@ -116,29 +559,76 @@ pub async fn build_usb_hid_device() -> Result<(
     UsbKeyboardHidDevice,
     UsbMouseHidDevice,
 )> {
-    if let Err(e) = usb_gadget::remove_all() {
-        let err_str = e.to_string();
-        if !err_str.contains("No such file or directory") && !err_str.contains("os error 2") {
-            return Err(e).context("无法移除现有 gadgets");
+    build_usb_hid_device_with_config(&UsbConfig::default()).await
+}
+
+/// 自检：确认存在 UDC 且 gadget 能够绑定，随后立即移除，返回 UDC 名称
+pub fn probe_gadget(usb_config: &UsbConfig) -> Result<String> {
+    let udc = default_udc().context("获取 UDC 失败")?;
+    let (_keyboard_hid, keyboard_handle) =
+        keyboard_function(usb_config.keyboard_report_id()).build();
+
+    let mut gadget = Gadget::new(
+        Class::new(0x00, 0x00, 0x00),
+        Id::new(0x1d6b, 0x0104),
+        gadget_strings(usb_config),
+    );
+    let mut config = Config::new("config");
+    config.add_function(keyboard_handle);
+    gadget.add_config(config);
+
+    let reg = gadget.bind(&udc).context("注册并绑定 Gadget 失败")?;
+    reg.remove().context("移除自检 Gadget 失败")?;
+    Ok(udc.name().to_string_lossy().into_owned())
+}
+
+/// 按配置创建并初始化 USB HID 设备
+pub async fn build_usb_hid_device_with_config(
+    usb_config: &UsbConfig,
+) -> Result<(
+    UsbKeyboardHidDevice,
+    UsbKeyboardHidDevice,
+    UsbMouseHidDevice,
+)> {
+    // 只清理本程序上次运行残留的 gadget，不触碰系统上其他服务的 gadget
+    match remove_own_gadgets(&ConfigfsGadgets).await {
+        std::result::Result::Ok(removed) if !removed.is_empty() => {
+            debug!("已移除残留 gadgets: {}", removed.join(", "))
         }
-        warn!("没有现有 gadgets 需要移除");
+        std::result::Result::Ok(_) => debug!("没有残留 gadgets 需要移除"),
+        Err(e) => warn!("{:#}，继续创建新的 gadget", e),
     }
 
     // 创建键盘 HID 功能
-    let mut keyboard_builder = Hid::builder();
-    keyboard_builder.sub_class = 1; // Boot Interface Subclass
-    keyboard_builder.protocol = 1; // Keyboard
-    keyboard_builder.report_desc = KEYBOARD_REPORT_DESC.to_vec();
-    keyboard_builder.report_len = 8;
-    let (keyboard_hid, keyboard_handle) = keyboard_builder.build();
+    let (keyboard_hid, keyboard_handle) =
+        keyboard_function(usb_config.keyboard_report_id()).build();
 
     // 创建鼠标 HID 功能
-    let mut mouse_builder = Hid::builder();
-    mouse_builder.sub_class = 1; // Boot Interface Subclass
-    mouse_builder.protocol = 2; // Mouse
-    mouse_builder.report_desc = MOUSE_REPORT_DESC.to_vec();
-    mouse_builder.report_len = 4;
-    let (mouse_hid, mouse_handle) = mouse_builder.build();
+    let (mouse_hid, mouse_handle) = mouse_function(usb_config.mouse_report_id()).build();
+
+    // 创建消费类控制 HID 功能（仅在启用时）
+    let consumer = usb_config.consumer_control.then(|| {
+        let mut consumer_builder = Hid::builder();
+        consumer_builder.report_desc = CONSUMER_REPORT_DESC.to_vec();
+        consumer_builder.report_len = 2;
+        consumer_builder.build()
+    });
+
+    // 创建系统控制 HID 功能（仅在启用时）
+    let system = usb_config.system_control.then(|| {
+        let mut system_builder = Hid::builder();
+        system_builder.report_desc = SYSTEM_REPORT_DESC.to_vec();
+        system_builder.report_len = 1;
+        system_builder.build()
+    });
+
+    // 创建厂商自定义 HID 功能（仅在启用透传时）
+    let vendor = usb_config.raw_passthrough.then(|| {
+        let mut vendor_builder = Hid::builder();
+        vendor_builder.report_desc = VENDOR_REPORT_DESC.to_vec();
+        vendor_builder.report_len = 3;
+        vendor_builder.build()
+    });
 
     // 获取 UDC
     let udc = default_udc().context("获取 UDC 失败")?;
@@ -147,12 +637,24 @@ pub async fn build_usb_hid_device() -> Result<(
     let mut gadget = Gadget::new(
         Class::new(0x00, 0x00, 0x00),
         Id::new(0x1d6b, 0x0104),
-        Strings::new("Bridge HID", "Virtual Keyboard Mouse", "001"),
+        gadget_strings(usb_config),
     );
 
     let mut config = Config::new("config");
     config.add_function(keyboard_handle);
     config.add_function(mouse_handle);
+    let consumer_hid = consumer.map(|(consumer_hid, consumer_handle)| {
+        config.add_function(consumer_handle);
+        consumer_hid
+    });
+    let system_hid = system.map(|(system_hid, system_handle)| {
+        config.add_function(system_handle);
+        system_hid
+    });
+    let vendor_hid = vendor.map(|(vendor_hid, vendor_handle)| {
+        config.add_function(vendor_handle);
+        vendor_hid
+    });
     gadget.add_config(config);
 
     // 注册并绑定
@@ -161,14 +663,15 @@ pub async fn build_usb_hid_device() -> Result<(
     let shared_reg = Arc::new(reg);
 
     // 等待设备节点创建
-    std::thread::sleep(std::time::Duration::from_millis(100));
+    sleep(Duration::from_millis(100)).await;
 
     // 获取设备文件路径
     let keyboard_dev = keyboard_hid.device().context("获取键盘设备号失败")?;
     let mouse_dev = mouse_hid.device().context("获取鼠标设备号失败")?;
 
-    let keyboard_path = find_hidg_device(keyboard_dev.0, keyboard_dev.1)?;
-    let mouse_path = find_hidg_device(mouse_dev.0, mouse_dev.1)?;
+    let locator = HidgLocator::new(usb_config.hidg_scan_count);
+    let keyboard_path = locator.find(keyboard_dev.0, keyboard_dev.1)?;
+    let mouse_path = locator.find(mouse_dev.0, mouse_dev.1)?;
 
     let keyboard_file = OpenOptions::new()
         .write(true)
@@ -178,10 +681,20 @@ pub async fn build_usb_hid_device() -> Result<(
         .with_context(|| format!("打开键盘设备 {} 失败", keyboard_path.display()))?;
 
     let keyboard_file_tokio = TokioFile::from_std(keyboard_file);
-    let keyboard_file_tokio_clone = keyboard_file_tokio
-        .try_clone()
-        .await
-        .context("克隆键盘文件句柄失败")?;
+    // O_NONBLOCK 属于打开的文件描述，克隆的句柄会共享它，因此非阻塞模式单独打开节点
+    let led_file = if usb_config.nonblocking_leds {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&keyboard_path)
+            .with_context(|| format!("打开键盘设备 {} 失败", keyboard_path.display()))?;
+        TokioFile::from_std(file)
+    } else {
+        keyboard_file_tokio
+            .try_clone()
+            .await
+            .context("克隆键盘文件句柄失败")?
+    };
 
     let mouse_file = OpenOptions::new()
         .write(true)
@@ -192,19 +705,75 @@ pub async fn build_usb_hid_device() -> Result<(
 
     let mouse_file_tokio = TokioFile::from_std(mouse_file);
 
-    let _ = wait_for_enumeration(10).await?;
+    let consumer_file = match consumer_hid {
+        Some(consumer_hid) => {
+            let consumer_dev = consumer_hid.device().context("获取消费类控制设备号失败")?;
+            let consumer_path = locator.find(consumer_dev.0, consumer_dev.1)?;
+            let file = OpenOptions::new()
+                .write(true)
+                .open(&consumer_path)
+                .with_context(|| format!("打开消费类控制设备 {} 失败", consumer_path.display()))?;
+            Some(TokioFile::from_std(file))
+        }
+        None => None,
+    };
+
+    let system_file = match system_hid {
+        Some(system_hid) => {
+            let system_dev = system_hid.device().context("获取系统控制设备号失败")?;
+            let system_path = locator.find(system_dev.0, system_dev.1)?;
+            let file = OpenOptions::new()
+                .write(true)
+                .open(&system_path)
+                .with_context(|| format!("打开系统控制设备 {} 失败", system_path.display()))?;
+            Some(TokioFile::from_std(file))
+        }
+        None => None,
+    };
+
+    let vendor_file = match vendor_hid {
+        Some(vendor_hid) => {
+            let vendor_dev = vendor_hid.device().context("获取厂商自定义设备号失败")?;
+            let vendor_path = locator.find(vendor_dev.0, vendor_dev.1)?;
+            let file = OpenOptions::new()
+                .write(true)
+                .open(&vendor_path)
+                .with_context(|| format!("打开厂商自定义设备 {} 失败", vendor_path.display()))?;
+            Some(TokioFile::from_std(file))
+        }
+        None => None,
+    };
+
+    wait_for_enumeration(10).await?;
 
     Ok((
         UsbKeyboardHidDevice {
-            keyboard_file: Some(keyboard_file_tokio),
+            nodes: KeyboardNodes {
+                keyboard: Some(keyboard_file_tokio),
+                consumer: consumer_file,
+                system: system_file,
+                vendor: vendor_file,
+            },
+            keyboard_report_id: usb_config.keyboard_report_id(),
+            host_leds: None,
+            nonblocking_leds: false,
             _registration: Arc::clone(&shared_reg),
         },
+        // 仅用于读取 LED 状态
         UsbKeyboardHidDevice {
-            keyboard_file: Some(keyboard_file_tokio_clone),
+            nodes: KeyboardNodes {
+                keyboard: Some(led_file),
+                ..Default::default()
+            },
+            keyboard_report_id: usb_config.keyboard_report_id(),
+            host_leds: None,
+            nonblocking_leds: usb_config.nonblocking_leds,
             _registration: Arc::clone(&shared_reg),
         },
         UsbMouseHidDevice {
             mouse_file: Some(mouse_file_tokio),
+            mouse_report_id: usb_config.mouse_report_id(),
+            rate_cap: MouseRateCap::new(usb_config.max_mouse_rate_hz),
             _registration: Arc::clone(&shared_reg),
         },
     ))
@@ -236,65 +805,117 @@ pub async fn wait_for_enumeration(timeout_secs: u64) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// 根据 UDC `state` 判断主机是否在线
+///
+/// `configured` 表示已被主机枚举；`suspended` 通常是主机休眠而线缆仍然连接，
+/// 无法判断时返回 `None`。
+pub fn host_present_from_state(state: &str) -> Option<bool> {
+    match state.trim() {
+        "configured" => Some(true),
+        "suspended" => None,
+        _ => Some(false),
+    }
+}
+
+/// 读取 UDC 状态判断 USB 主机是否在线，没有 UDC 时返回 `None`
+pub async fn read_host_present() -> Option<bool> {
+    let entries = glob::glob("/sys/class/udc/*/state").ok()?;
+    let mut present = None;
+    for entry in entries.flatten() {
+        if let std::result::Result::Ok(state) = tokio::fs::read_to_string(&entry).await {
+            match host_present_from_state(&state) {
+                Some(true) => return Some(true),
+                Some(false) => present = Some(false),
+                None => {}
+            }
+        }
+    }
+    present
+}
+
 #[async_trait]
 impl HidReportSender for UsbKeyboardHidDevice {
     async fn send_report(&mut self, report: InputReport) -> Result<()> {
-        match report {
-            InputReport::Keyboard { modifiers, keys } => {
-                // 1. 构造标准的 8 字节键盘报告
-                let mut data = [0u8; 8];
-                data[0] = modifiers; // 修饰键字节
-                data[1] = 0x00; // 保留字节
-
-                // 2. 填充按键 (最多支持 6 个同时按下的普通键)
-                for (i, &key) in keys.iter().take(6).enumerate() {
-                    data[i + 2] = key;
-                }
+        let frame = frame_keyboard_report(&report, self.keyboard_report_id)?;
+        self.nodes.write_frames(&[frame]).await
+    }
 
-                // 3. 异步写入到键盘设备文件
-                if let Some(ref mut file) = self.keyboard_file {
-                    file.write_all(&data)
-                        .await
-                        .map_err(|e| UsbError(format!("异步发送键盘报告失败: {}", e)))?;
-                    // file.flush().await?;
-                }
-            }
-            InputReport::Mouse { .. } => {
-                Err(anyhow!("收到鼠标报告,但当前后端仅支持键盘"))?;
-            }
+    /// 先生成全部报告数据再依次写入，含鼠标报告时一个也不写
+    async fn send_reports(&mut self, reports: &[InputReport]) -> Result<()> {
+        let frames = reports
+            .iter()
+            .map(|report| frame_keyboard_report(report, self.keyboard_report_id))
+            .collect::<Result<Vec<_>>>()?;
+        self.nodes.write_frames(&frames).await
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        let mut caps = BackendCapabilities::KEYBOARD | BackendCapabilities::LED_READ;
+        if self.nodes.consumer.is_some() {
+            caps |= BackendCapabilities::CONSUMER;
         }
-        Ok(())
+        if self.nodes.system.is_some() {
+            caps |= BackendCapabilities::SYSTEM;
+        }
+        if self.nodes.vendor.is_some() {
+            caps |= BackendCapabilities::VENDOR;
+        }
+        caps
     }
 }
 
 #[async_trait]
 impl HidLedReader for UsbKeyboardHidDevice {
     async fn get_led_state(&mut self) -> Result<Option<LedState>> {
-        use tokio::io::AsyncReadExt;
-
-        if let Some(ref mut file) = self.keyboard_file {
-            let mut buf = [0u8; 1];
-
-            // 使用 .await 挂起任务，直到内核缓冲区有数据或返回错误
-            match file.read(&mut buf).await {
-                std::result::Result::Ok(1) => Ok(Some(LedState::from_byte(buf[0]))),
-                std::result::Result::Ok(0) => Ok(None), // EOF，通常表示设备关闭
-                // Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                //     // 如果是 O_NONBLOCK 模式且没数据，Tokio 有时会直接返回这个错误
-                //     Ok(None)
-                // }
-                Err(e) => Err(anyhow!("读取 LED 状态失败: {}", e)),
-                _ => Err(anyhow!("读取了意外的字节数")),
-            }
-        } else {
-            Ok(None)
-        }
+        let Some(ref mut file) = self.nodes.keyboard else {
+            return Ok(None);
+        };
+        let state = read_led_report(file, self.keyboard_report_id).await?;
+        self.host_leds = state.or(self.host_leds);
+        Ok(state)
+    }
+
+    fn poll_interval(&self) -> Option<Duration> {
+        self.nonblocking_leds.then_some(LED_POLL_INTERVAL)
+    }
+
+    fn host_led_state(&self) -> Option<LedState> {
+        self.host_leds
+    }
+
+    fn clear_host_led_state(&mut self) {
+        self.host_leds = None;
     }
 }
 
 #[async_trait]
 impl HidReportSender for UsbMouseHidDevice {
     async fn send_report(&mut self, report: InputReport) -> Result<()> {
+        match self.rate_cap.admit(report) {
+            Some(report) => self.write_report(report).await,
+            None => Ok(()),
+        }
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities::MOUSE
+    }
+
+    fn flush_deadline(&self) -> Option<std::time::Instant> {
+        self.rate_cap.flush_deadline()
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        match self.rate_cap.flush() {
+            Some(report) => self.write_report(report).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl UsbMouseHidDevice {
+    /// 写入已通过报告率上限的报告
+    async fn write_report(&mut self, report: InputReport) -> Result<()> {
         match report {
             InputReport::Mouse {
                 buttons,
@@ -302,14 +923,10 @@ impl HidReportSender for UsbMouseHidDevice {
                 y,
                 wheel,
             } => {
-                // 1. 构造标准的 4 字节鼠标报告
-                let data = [
-                    buttons,     // 按钮状态字节
-                    x as u8,     // X 轴移动
-                    y as u8,     // Y 轴移动
-                    wheel as u8, // 滚轮移动
-                ];
-                // 2. 异步写入到鼠标设备文件
+                let framing = usb_framing(self.mouse_report_id);
+                let data = report::build_mouse(buttons, x, y, wheel, framing);
+                report::log_sent("USB", "鼠标", &data);
+                // 异步写入到鼠标设备文件
                 if let Some(ref mut file) = self.mouse_file {
                     file.write_all(&data)
                         .await
@@ -318,7 +935,10 @@ impl HidReportSender for UsbMouseHidDevice {
                     // file.flush().await?;
                 }
             }
-            InputReport::Keyboard { .. } => {
+            InputReport::Keyboard { .. }
+            | InputReport::Consumer { .. }
+            | InputReport::System { .. }
+            | InputReport::Vendor { .. } => {
                 Err(anyhow!("收到键盘报告,但当前后端仅支持鼠标"))?;
             }
         }
@@ -326,29 +946,249 @@ impl HidReportSender for UsbMouseHidDevice {
     }
 }
 
+/// 默认逐个检查的 `/dev/hidgN` 数量
+pub const DEFAULT_HIDG_SCAN_COUNT: u32 = 10;
+
 /// 根据主次设备号查找 HID gadget 设备文件
-fn find_hidg_device(major: u32, minor: u32) -> Result<PathBuf> {
-    for i in 0..10 {
-        let path = PathBuf::from(format!("/dev/hidg{}", i));
-        if path.exists() {
+///
+/// 优先读取 `/sys/dev/char/<major>:<minor>/uevent` 中的 `DEVNAME` 直接得到节点名，
+/// sysfs 不可用时再按设备号逐个检查 `/dev/hidg0..N`。节点是符号链接时返回链接指向的路径。
+struct HidgLocator {
+    sys_root: PathBuf,
+    dev_root: PathBuf,
+    scan_count: u32,
+}
+
+impl HidgLocator {
+    fn new(scan_count: u32) -> Self {
+        Self::with_roots("/sys", "/dev", scan_count)
+    }
+
+    fn with_roots(
+        sys_root: impl Into<PathBuf>,
+        dev_root: impl Into<PathBuf>,
+        scan_count: u32,
+    ) -> Self {
+        Self {
+            sys_root: sys_root.into(),
+            dev_root: dev_root.into(),
+            scan_count,
+        }
+    }
+
+    fn find(&self, major: u32, minor: u32) -> Result<PathBuf> {
+        let uevent = self
+            .sys_root
+            .join(format!("dev/char/{}:{}/uevent", major, minor));
+        let sysfs = match self.read_uevent(&uevent) {
+            std::result::Result::Ok(path) => return Ok(path),
+            Err(reason) => reason,
+        };
+
+        for i in 0..self.scan_count {
+            let path = self.dev_root.join(format!("hidg{}", i));
             if let std::result::Result::Ok(metadata) = std::fs::metadata(&path) {
                 use std::os::unix::fs::MetadataExt;
-                let dev = metadata.rdev();
-                let dev_major = ((dev >> 8) & 0xfff) as u32;
-                let dev_minor = (dev & 0xff) as u32;
-                if dev_major == major && dev_minor == minor {
-                    return Ok(path);
+                if dev_numbers(metadata.rdev()) == (major, minor) {
+                    return Ok(resolve_link(path));
                 }
             }
         }
+        Err(anyhow!(
+            "未找到设备 {}:{}，已检查 {}（{}）以及 {}/hidg0..hidg{}",
+            major,
+            minor,
+            uevent.display(),
+            sysfs,
+            self.dev_root.display(),
+            self.scan_count.saturating_sub(1)
+        ))
+    }
+
+    /// 从 uevent 的 `DEVNAME` 得到节点路径，失败时返回原因
+    fn read_uevent(&self, uevent: &Path) -> std::result::Result<PathBuf, String> {
+        let text = std::fs::read_to_string(uevent).map_err(|e| e.to_string())?;
+        let name = text
+            .lines()
+            .find_map(|line| line.strip_prefix("DEVNAME="))
+            .ok_or("没有 DEVNAME")?;
+        let path = self.dev_root.join(name);
+        if !path.exists() {
+            return Err(format!("{} 不存在", path.display()));
+        }
+        std::result::Result::Ok(resolve_link(path))
     }
-    Err(anyhow!("未找到设备 {}:{}", major, minor))
+}
+
+/// 符号链接解析为实际路径，无法解析时原样返回
+fn resolve_link(path: PathBuf) -> PathBuf {
+    std::fs::canonicalize(&path).unwrap_or(path)
+}
+
+/// 拆分 `st_rdev` 为主次设备号（与 glibc `major()`/`minor()` 一致）
+fn dev_numbers(rdev: u64) -> (u32, u32) {
+    let major = ((rdev >> 8) & 0xfff) | ((rdev >> 32) & !0xfff);
+    let minor = (rdev & 0xff) | ((rdev >> 12) & !0xff);
+    (major as u32, minor as u32)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::output::keycodes;
+    use log::{debug, error, info};
+
+    #[test]
+    fn test_parse_standalone_led_report() {
+        let state = parse_led_report(&[0x03], None).unwrap();
+        assert!(state.num_lock && state.caps_lock && !state.scroll_lock);
+        assert_eq!(parse_led_report(&[], None), None);
+    }
+
+    #[test]
+    fn test_locate_hidg_through_sysfs() {
+        let root = std::env::temp_dir().join(format!("bridge-hid-hidg-{}", std::process::id()));
+        let (sys, dev) = (root.join("sys"), root.join("dev"));
+        std::fs::create_dir_all(sys.join("dev/char/236:12")).unwrap();
+        std::fs::create_dir_all(&dev).unwrap();
+        std::fs::write(
+            sys.join("dev/char/236:12/uevent"),
+            "MAJOR=236\nMINOR=12\nDEVNAME=hidg12\n",
+        )
+        .unwrap();
+        // udev 以其他名称创建节点，hidg12 只是指向它的符号链接
+        std::fs::write(dev.join("gadget-keyboard"), "").unwrap();
+        std::os::unix::fs::symlink("gadget-keyboard", dev.join("hidg12")).unwrap();
+
+        let locator = HidgLocator::with_roots(&sys, &dev, DEFAULT_HIDG_SCAN_COUNT);
+        let found = locator.find(236, 12).unwrap();
+        assert_eq!(
+            found,
+            std::fs::canonicalize(dev.join("gadget-keyboard")).unwrap()
+        );
+
+        let err = locator.find(236, 3).unwrap_err().to_string();
+        assert!(err.contains("dev/char/236:3/uevent"), "{err}");
+        assert!(err.contains("hidg0..hidg9"), "{err}");
+
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(dev_numbers((236 << 8) | 12), (236, 12));
+    }
+
+    #[test]
+    fn test_parse_report_id_prefixed_led_report() {
+        let state = parse_led_report(&[0x01, 0x02], Some(0x01)).unwrap();
+        assert!(state.caps_lock && !state.num_lock);
+        // 其他 Report ID 的输出报告被忽略
+        assert_eq!(parse_led_report(&[0x05, 0xff, 0xff], Some(0x01)), None);
+        // 只有 Report ID 没有数据
+        assert_eq!(parse_led_report(&[0x01], Some(0x01)), None);
+    }
+
+    #[tokio::test]
+    async fn test_nonblocking_led_read_returns_none_without_data() {
+        use std::io::Write;
+        use std::os::fd::FromRawFd;
+
+        // 用非阻塞管道代替以 O_NONBLOCK 打开的 hidg 节点
+        let mut fds = [0; 2];
+        assert_eq!(
+            unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK) },
+            0
+        );
+        let (reader, mut writer) = unsafe {
+            (
+                std::fs::File::from_raw_fd(fds[0]),
+                std::fs::File::from_raw_fd(fds[1]),
+            )
+        };
+        let mut reader = TokioFile::from_std(reader);
+
+        let read = timeout(Duration::from_secs(1), read_led_report(&mut reader, None));
+        assert_eq!(read.await.unwrap().unwrap(), None);
+
+        // 主机下发报告后能读到
+        writer.write_all(&[0x02]).unwrap();
+        let state = read_led_report(&mut reader, None).await.unwrap().unwrap();
+        assert!(state.caps_lock);
+    }
+
+    #[test]
+    fn test_boot_layout_has_no_report_id() {
+        let config = UsbConfig::default();
+        let keyboard = keyboard_function(config.keyboard_report_id());
+        assert_eq!(keyboard.report_desc, KEYBOARD_REPORT_DESC);
+        assert_eq!(keyboard.report_len, 8);
+        let mouse = mouse_function(config.mouse_report_id());
+        assert_eq!(mouse.report_desc, MOUSE_REPORT_DESC);
+        assert_eq!(mouse.report_len, 4);
+
+        let framing = usb_framing(config.keyboard_report_id());
+        assert_eq!(
+            report::build_keyboard(0x02, &[0x04], framing),
+            [0x02, 0x00, 0x04, 0, 0, 0, 0, 0]
+        );
+        let framing = usb_framing(config.mouse_report_id());
+        assert_eq!(
+            report::build_mouse(0x01, 1, -1, 0, framing),
+            [0x01, 1, 0xFF, 0]
+        );
+    }
+
+    #[test]
+    fn test_report_id_variant_keeps_descriptor_and_length_in_sync() {
+        let config = UsbConfig {
+            report_ids: true,
+            ..UsbConfig::default()
+        };
+        let keyboard = keyboard_function(config.keyboard_report_id());
+        assert_eq!(keyboard.report_desc[6..8], [0x85, KEYBOARD_REPORT_ID]);
+        assert_eq!(keyboard.report_desc.len(), KEYBOARD_REPORT_DESC.len() + 2);
+        let bytes = report::build_keyboard(0x02, &[0x04], usb_framing(config.keyboard_report_id()));
+        assert_eq!(bytes, [KEYBOARD_REPORT_ID, 0x02, 0x00, 0x04, 0, 0, 0, 0, 0]);
+        assert_eq!(bytes.len(), keyboard.report_len as usize);
+
+        let mouse = mouse_function(config.mouse_report_id());
+        assert_eq!(mouse.report_desc[6..8], [0x85, MOUSE_REPORT_ID]);
+        let bytes = report::build_mouse(0x01, 1, -1, 0, usb_framing(config.mouse_report_id()));
+        assert_eq!(bytes, [MOUSE_REPORT_ID, 0x01, 1, 0xFF, 0]);
+        assert_eq!(bytes.len(), mouse.report_len as usize);
+    }
+
+    #[test]
+    fn test_serial_threaded_into_strings() {
+        let config = UsbConfig {
+            serial: "bridge-42".to_string(),
+            ..UsbConfig::default()
+        };
+        assert_eq!(gadget_strings(&config).serial_number, "bridge-42");
+        assert_eq!(
+            gadget_strings(&UsbConfig::default()).serial_number,
+            DEFAULT_SERIAL
+        );
+
+        let auto = UsbConfig {
+            serial: AUTO_SERIAL.to_string(),
+            ..UsbConfig::default()
+        };
+        let first = gadget_strings(&auto).serial_number;
+        assert_eq!(first, gadget_strings(&auto).serial_number);
+        assert_ne!(first, AUTO_SERIAL);
+    }
+
+    #[test]
+    fn test_serial_from_machine_id_is_stable() {
+        let a = serial_from_machine_id("0123456789abcdef0123456789abcdef\n");
+        assert_eq!(
+            a,
+            serial_from_machine_id("0123456789abcdef0123456789abcdef")
+        );
+        assert_eq!(a.len(), 16);
+        assert_ne!(
+            a,
+            serial_from_machine_id("fedcba9876543210fedcba9876543210")
+        );
+    }
 
     #[tokio::test]
     #[ignore]
@@ -462,4 +1302,129 @@ mod tests {
             }
         }
     }
+
+    #[tokio::test]
+    async fn test_send_reports_writes_all_in_order() {
+        use tokio::io::AsyncWriteExt;
+
+        let root = std::env::temp_dir().join(format!("bridge-hid-batch-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let (keyboard, consumer) = (root.join("keyboard"), root.join("consumer"));
+        let mut nodes = KeyboardNodes {
+            keyboard: Some(TokioFile::create(&keyboard).await.unwrap()),
+            consumer: Some(TokioFile::create(&consumer).await.unwrap()),
+            ..Default::default()
+        };
+
+        let reports = [
+            InputReport::Keyboard {
+                modifiers: 0x02,
+                keys: vec![keycodes::KEY_A],
+            },
+            InputReport::Consumer { usage: 0x00E9 },
+            InputReport::Keyboard {
+                modifiers: 0,
+                keys: vec![],
+            },
+            InputReport::Consumer { usage: 0 },
+            // 没有打开的节点被忽略
+            InputReport::System { usage: 0x82 },
+        ];
+        let frames = reports
+            .iter()
+            .map(|report| frame_keyboard_report(report, None))
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        nodes.write_frames(&frames).await.unwrap();
+        for file in [&mut nodes.keyboard, &mut nodes.consumer] {
+            file.as_mut().unwrap().flush().await.unwrap();
+        }
+
+        assert_eq!(
+            std::fs::read(&keyboard).unwrap(),
+            [
+                report::build_keyboard(0x02, &[keycodes::KEY_A], Framing::RAW),
+                report::build_keyboard(0, &[], Framing::RAW),
+            ]
+            .concat()
+        );
+        assert_eq!(std::fs::read(&consumer).unwrap(), [0xE9, 0x00, 0x00, 0x00]);
+
+        let mouse = InputReport::Mouse {
+            buttons: 0,
+            x: 1,
+            y: 0,
+            wheel: 0,
+        };
+        assert!(frame_keyboard_report(&mouse, None).is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    /// 模拟 configfs：记录移除请求，可让指定 gadget 的前几次移除失败
+    struct MockGadgets {
+        gadgets: std::cell::RefCell<Vec<(String, String)>>,
+        failures: std::cell::RefCell<std::collections::HashMap<String, u32>>,
+        removals: std::cell::RefCell<Vec<String>>,
+    }
+
+    impl GadgetRegistry for MockGadgets {
+        fn list(&self) -> std::io::Result<Vec<String>> {
+            std::result::Result::Ok(self.gadgets.borrow().iter().map(|g| g.0.clone()).collect())
+        }
+
+        fn manufacturer(&self, name: &str) -> Option<String> {
+            self.gadgets
+                .borrow()
+                .iter()
+                .find(|g| g.0 == name)
+                .map(|g| g.1.clone())
+        }
+
+        fn remove(&self, name: &str) -> std::io::Result<()> {
+            self.removals.borrow_mut().push(name.to_string());
+            if let Some(left) = self.failures.borrow_mut().get_mut(name).filter(|n| **n > 0) {
+                *left -= 1;
+                return Err(std::io::Error::other("Device or resource busy"));
+            }
+            self.gadgets.borrow_mut().retain(|g| g.0 != name);
+            std::result::Result::Ok(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_remove_only_own_gadgets() {
+        let gadget = |name: &str, manufacturer: &str| (name.to_string(), manufacturer.to_string());
+        let registry = MockGadgets {
+            gadgets: std::cell::RefCell::new(vec![
+                gadget("g1", "Other Vendor"),
+                gadget("usb-gadget0", GADGET_MANUFACTURER),
+                // 序列号变化前创建的 gadget 同样属于本程序
+                gadget("usb-gadget1", GADGET_MANUFACTURER),
+            ]),
+            failures: std::cell::RefCell::new([("usb-gadget0".to_string(), 1)].into()),
+            removals: std::cell::RefCell::default(),
+        };
+
+        // 第一次移除失败后重试成功，其他服务的 gadget 保持不动
+        let removed = remove_own_gadgets(&registry).await.unwrap();
+        assert_eq!(removed, ["usb-gadget1", "usb-gadget0"]);
+        assert_eq!(
+            *registry.removals.borrow(),
+            ["usb-gadget0", "usb-gadget1", "usb-gadget0"]
+        );
+        assert_eq!(registry.list().unwrap(), ["g1"]);
+
+        // 始终失败时返回错误而不是无限重试
+        registry
+            .gadgets
+            .borrow_mut()
+            .push(gadget("usb-gadget2", GADGET_MANUFACTURER));
+        registry
+            .failures
+            .borrow_mut()
+            .insert("usb-gadget2".to_string(), u32::MAX);
+        registry.removals.borrow_mut().clear();
+        assert!(remove_own_gadgets(&registry).await.is_err());
+        assert_eq!(registry.removals.borrow().len(), REMOVE_ATTEMPTS as usize);
+    }
 }
@@ -0,0 +1,61 @@
+use crate::input::InputReport;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+
+use super::{BackendCapabilities, HidReportSender};
+
+/// 虚拟 HID 设备
+///
+/// 不连接任何硬件，只把收到的报告按顺序记录在内存中，
+/// 用于测试以及在没有 USB/蓝牙的环境下运行管线。
+#[derive(Clone)]
+pub struct VirtualHidDevice {
+    reports: Arc<Mutex<Vec<InputReport>>>,
+    capabilities: BackendCapabilities,
+}
+
+impl Default for VirtualHidDevice {
+    fn default() -> Self {
+        Self::with_capabilities(BackendCapabilities::all())
+    }
+}
+
+impl VirtualHidDevice {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 模拟只具备部分能力的后端，例如仅键盘
+    pub fn with_capabilities(capabilities: BackendCapabilities) -> Self {
+        Self {
+            reports: Arc::default(),
+            capabilities,
+        }
+    }
+
+    /// 已记录报告的快照
+    pub fn reports(&self) -> Vec<InputReport> {
+        self.reports.lock().unwrap().clone()
+    }
+
+    /// 取出并清空已记录的报告
+    pub fn take_reports(&self) -> Vec<InputReport> {
+        std::mem::take(&mut *self.reports.lock().unwrap())
+    }
+}
+
+#[async_trait]
+impl HidReportSender for VirtualHidDevice {
+    async fn send_report(&mut self, report: InputReport) -> Result<()> {
+        if !self.capabilities.supports(&report) {
+            return Err(anyhow!("虚拟后端不支持该报告: {:?}", report));
+        }
+        self.reports.lock().unwrap().push(report);
+        Ok(())
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        self.capabilities
+    }
+}
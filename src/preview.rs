@@ -0,0 +1,156 @@
+use crate::audit::{content, kind_index};
+use crate::input::InputReport;
+use crate::metrics::AUDIT_KINDS;
+use serde_json::json;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// 鼠标预览的最短间隔，约 30Hz，足够网页显示指示
+pub const PREVIEW_MOUSE_INTERVAL: Duration = Duration::from_millis(33);
+/// 广播缓冲的消息数，订阅者落后更多时跳过旧消息
+const PREVIEW_CAPACITY: usize = 64;
+
+/// 已转发报告的实时预览，供网页显示按键与鼠标指示
+///
+/// 发布只做非阻塞的广播，没有订阅者时连序列化都跳过，不影响转发延迟。
+/// 键盘等报告逐条发布；鼠标报告按 [`PREVIEW_MOUSE_INTERVAL`] 节流，按键变化总是发布。
+/// 与审计记录一致，未开启 `log_content` 时键盘报告只包含修饰键，不包含键码。
+pub struct Preview {
+    tx: broadcast::Sender<String>,
+    mouse_interval: Duration,
+    log_content: bool,
+    last_mouse: Mutex<Option<(Instant, u8)>>,
+}
+
+impl Preview {
+    pub fn new(mouse_interval: Duration) -> Self {
+        Self {
+            tx: broadcast::channel(PREVIEW_CAPACITY).0,
+            mouse_interval,
+            log_content: false,
+            last_mouse: Mutex::new(None),
+        }
+    }
+
+    /// 是否在预览中包含键码
+    pub fn with_content(mut self, log_content: bool) -> Self {
+        self.log_content = log_content;
+        self
+    }
+
+    /// 订阅预览消息，每条为一个 JSON 对象
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.tx.subscribe()
+    }
+
+    /// 发布一个已发往 `target` 的报告
+    pub fn publish(&self, target: &str, report: &InputReport) {
+        self.publish_at(target, report, Instant::now());
+    }
+
+    fn publish_at(&self, target: &str, report: &InputReport, now: Instant) {
+        if self.tx.receiver_count() == 0 || self.throttled(report, now) {
+            return;
+        }
+        let body = match report {
+            InputReport::Keyboard { modifiers, .. } if !self.log_content => {
+                json!({ "modifiers": modifiers })
+            }
+            _ => content(report),
+        };
+        let message = json!({
+            "target": target,
+            "kind": AUDIT_KINDS[kind_index(report)],
+            "report": body,
+        });
+        let _ = self.tx.send(message.to_string());
+    }
+
+    /// 鼠标报告在间隔内且按键未变化时跳过
+    fn throttled(&self, report: &InputReport, now: Instant) -> bool {
+        let InputReport::Mouse { buttons, .. } = report else {
+            return false;
+        };
+        let mut last = self.last_mouse.lock().unwrap();
+        if let Some((at, last_buttons)) = *last
+            && last_buttons == *buttons
+            && now.duration_since(at) < self.mouse_interval
+        {
+            return true;
+        }
+        *last = Some((now, *buttons));
+        false
+    }
+}
+
+impl Default for Preview {
+    fn default() -> Self {
+        Self::new(PREVIEW_MOUSE_INTERVAL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mouse(buttons: u8) -> InputReport {
+        InputReport::Mouse {
+            buttons,
+            x: 3,
+            y: -1,
+            wheel: 0,
+        }
+    }
+
+    #[test]
+    fn test_mouse_preview_is_throttled() {
+        let preview = Preview::default().with_content(true);
+        let mut rx = preview.subscribe();
+        let start = Instant::now();
+
+        preview.publish_at("usb", &mouse(0), start);
+        // 间隔内的移动被跳过，按键变化照常发布
+        preview.publish_at("usb", &mouse(0), start + Duration::from_millis(5));
+        preview.publish_at("usb", &mouse(1), start + Duration::from_millis(10));
+        preview.publish_at("usb", &mouse(1), start + PREVIEW_MOUSE_INTERVAL * 2);
+        preview.publish_at(
+            "ble",
+            &InputReport::Keyboard {
+                modifiers: 2,
+                keys: vec![0x04],
+            },
+            start + Duration::from_millis(70),
+        );
+
+        let messages: Vec<serde_json::Value> = std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|m| serde_json::from_str(&m).unwrap())
+            .collect();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0]["report"]["x"], 3);
+        assert_eq!(messages[1]["report"]["buttons"], 1);
+        assert_eq!(
+            messages[3],
+            json!({
+                "target": "ble",
+                "kind": "keyboard",
+                "report": { "modifiers": 2, "keys": [4] },
+            })
+        );
+    }
+
+    #[test]
+    fn test_key_codes_require_log_content() {
+        let preview = Preview::default();
+        let mut rx = preview.subscribe();
+        preview.publish(
+            "usb",
+            &InputReport::Keyboard {
+                modifiers: 2,
+                keys: vec![0x04],
+            },
+        );
+        let message: serde_json::Value = serde_json::from_str(&rx.try_recv().unwrap()).unwrap();
+        assert_eq!(message["report"], json!({ "modifiers": 2 }));
+    }
+}
@@ -0,0 +1,167 @@
+use crate::config::Config;
+use crate::input::{self, ScanStatus};
+use crate::output::bluetooth_ble::{self, BleConfig};
+use crate::output::usb::{self, UsbConfig};
+use async_trait::async_trait;
+use std::fmt;
+
+/// 自检所需的各项硬件探测，测试中可替换为虚拟实现
+#[async_trait]
+pub trait SelftestProbe: Send + Sync {
+    /// 绑定并移除一个 USB gadget，成功时返回 UDC 名称
+    async fn usb_gadget(&self) -> Result<String, String>;
+    /// 开始并停止 BLE 广播，成功时返回适配器名称
+    async fn bluetooth(&self) -> Result<String, String>;
+    /// 扫描输入设备
+    async fn input_devices(&self) -> ScanStatus;
+}
+
+/// 单项检查结果，`Ok` 与 `Err` 中均为给用户看的说明
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub result: Result<String, String>,
+}
+
+/// 自检报告
+#[derive(Debug, Clone)]
+pub struct SelftestReport {
+    pub checks: Vec<Check>,
+}
+
+impl SelftestReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.result.is_ok())
+    }
+}
+
+impl fmt::Display for SelftestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            match &check.result {
+                Ok(detail) => writeln!(f, "[PASS] {}: {}", check.name, detail)?,
+                Err(reason) => writeln!(f, "[FAIL] {}: {}", check.name, reason)?,
+            }
+        }
+        let verdict = if self.passed() { "通过" } else { "未通过" };
+        write!(f, "自检{}", verdict)
+    }
+}
+
+/// 依次执行所有检查；某项失败不影响其余检查
+pub async fn run(probe: &dyn SelftestProbe) -> SelftestReport {
+    let usb = probe
+        .usb_gadget()
+        .await
+        .map(|udc| format!("UDC {} 可绑定 gadget", udc));
+    let bluetooth = probe
+        .bluetooth()
+        .await
+        .map(|adapter| format!("适配器 {} 可开始广播", adapter));
+    let input = match probe.input_devices().await {
+        ScanStatus::Ok { event_nodes } => Ok(format!("找到 {} 个事件节点", event_nodes)),
+        status => Err(status
+            .problem()
+            .unwrap_or_else(|| "尚未扫描输入设备".to_string())),
+    };
+    SelftestReport {
+        checks: vec![
+            Check {
+                name: "usb",
+                result: usb,
+            },
+            Check {
+                name: "bluetooth",
+                result: bluetooth,
+            },
+            Check {
+                name: "input",
+                result: input,
+            },
+        ],
+    }
+}
+
+/// 探测真实硬件
+pub struct SystemProbe {
+    usb: UsbConfig,
+    ble: BleConfig,
+}
+
+impl SystemProbe {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            usb: config.usb.clone(),
+            ble: config.ble.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl SelftestProbe for SystemProbe {
+    async fn usb_gadget(&self) -> Result<String, String> {
+        let usb_config = self.usb.clone();
+        tokio::task::spawn_blocking(move || usb::probe_gadget(&usb_config))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("{:#}", e))
+    }
+
+    async fn bluetooth(&self) -> Result<String, String> {
+        bluetooth_ble::probe_advertising(&self.ble)
+            .await
+            .map_err(|e| format!("{:#}", e))
+    }
+
+    async fn input_devices(&self) -> ScanStatus {
+        input::probe_input_devices()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct VirtualProbe {
+        input: ScanStatus,
+    }
+
+    #[async_trait]
+    impl SelftestProbe for VirtualProbe {
+        async fn usb_gadget(&self) -> Result<String, String> {
+            Ok("dummy_udc.0".to_string())
+        }
+
+        async fn bluetooth(&self) -> Result<String, String> {
+            Ok("hci0".to_string())
+        }
+
+        async fn input_devices(&self) -> ScanStatus {
+            self.input.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_virtual_backend_passes() {
+        let report = run(&VirtualProbe {
+            input: ScanStatus::Ok { event_nodes: 2 },
+        })
+        .await;
+        assert!(report.passed(), "{report}");
+        let text = report.to_string();
+        assert!(text.contains("[PASS] usb: UDC dummy_udc.0 可绑定 gadget"));
+        assert!(text.contains("[PASS] bluetooth: 适配器 hci0 可开始广播"));
+        assert!(text.contains("[PASS] input: 找到 2 个事件节点"));
+        assert!(text.ends_with("自检通过"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_input_fails_with_reason() {
+        let report = run(&VirtualProbe {
+            input: ScanStatus::PermissionDenied,
+        })
+        .await;
+        assert!(!report.passed());
+        assert!(report.to_string().contains("[FAIL] input: 没有权限读取"));
+    }
+}
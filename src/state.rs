@@ -0,0 +1,236 @@
+//! 运行状态的导出与导入
+//!
+//! 把配置（含各方案的重映射与当前方案）、最后使用的输出以及各 BLE 主机的偏好
+//! 打包成一个 JSON 文件，迁移到新硬件时整体带走。
+
+use crate::config::{Config, temp_path};
+use crate::core::OutputMode;
+use crate::output::host_prefs::{HostPref, HostPrefs};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// 当前的状态文件格式版本
+pub const STATE_VERSION: u32 = 1;
+
+/// 可迁移的完整运行状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateBundle {
+    pub version: u32,
+    /// 完整配置，`active_profile` 为导出时正在使用的方案
+    pub config: Config,
+    /// 导出时正在使用的输出；离线导出时没有运行中的输出，为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<OutputMode>,
+    /// 各 BLE 主机的 Protocol Mode 与 LED 状态
+    #[serde(default)]
+    pub host_prefs: BTreeMap<String, HostPref>,
+}
+
+impl StateBundle {
+    pub fn capture(config: &Config, output: Option<OutputMode>, host_prefs: &HostPrefs) -> Self {
+        Self {
+            version: STATE_VERSION,
+            config: config.clone(),
+            output,
+            host_prefs: host_prefs.snapshot(),
+        }
+    }
+
+    /// 导入前的检查，任何一项不通过都不应用
+    pub fn validate(&self) -> Result<()> {
+        if self.version != STATE_VERSION {
+            bail!(
+                "不支持的状态文件版本 {}（当前版本 {}）",
+                self.version,
+                STATE_VERSION
+            );
+        }
+        if !self
+            .config
+            .all_profiles()
+            .contains_key(&self.config.active_profile)
+        {
+            bail!("配置方案 {} 不存在", self.config.active_profile);
+        }
+        if let Some((host, pref)) = self
+            .host_prefs
+            .iter()
+            .find(|(_, pref)| pref.protocol_mode > 1)
+        {
+            bail!("主机 {} 的 Protocol Mode {} 无效", host, pref.protocol_mode);
+        }
+        Ok(())
+    }
+
+    /// 读取并检查状态文件
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("读取状态文件 {} 失败", path.display()))?;
+        let bundle: Self = serde_json::from_str(&text)
+            .with_context(|| format!("解析状态文件 {} 失败", path.display()))?;
+        bundle.validate()?;
+        Ok(bundle)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let tmp = stage(path, self)?;
+        commit(&[(tmp, path.to_path_buf())])
+    }
+
+    /// 把配置写入 `config_path`，主机偏好写入配置中指定的文件
+    ///
+    /// 所有文件先写入临时文件，全部成功后才依次改名，任何一个写入失败都不改动现有文件。
+    /// 改名逐个进行，多个文件之间不是原子的：某次改名失败时，之前已改名的文件保持新内容，
+    /// 其余文件保持原样，剩下的临时文件会被删除。
+    pub fn persist(&self, config_path: &Path) -> Result<()> {
+        self.validate()?;
+        let mut staged = vec![(stage(config_path, &self.config)?, config_path.to_path_buf())];
+        if let Some(path) = &self.config.ble.host_prefs_path {
+            match stage(path, &self.host_prefs) {
+                Ok(tmp) => staged.push((tmp, path.clone())),
+                Err(e) => {
+                    for (tmp, _) in &staged {
+                        let _ = std::fs::remove_file(tmp);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        commit(&staged)
+    }
+}
+
+/// 把 `value` 写入 `path` 旁的临时文件，返回临时文件路径
+fn stage(path: &Path, value: &impl Serialize) -> Result<PathBuf> {
+    let tmp = temp_path(path);
+    std::fs::write(&tmp, serde_json::to_string_pretty(value)?)
+        .with_context(|| format!("写入 {} 失败", tmp.display()))?;
+    Ok(tmp)
+}
+
+/// 把临时文件依次改名为目标文件，失败时删除尚未改名的临时文件
+fn commit(staged: &[(PathBuf, PathBuf)]) -> Result<()> {
+    for (i, (tmp, path)) in staged.iter().enumerate() {
+        if let Err(e) = std::fs::rename(tmp, path) {
+            for (tmp, _) in &staged[i..] {
+                let _ = std::fs::remove_file(tmp);
+            }
+            return Err(e).with_context(|| format!("写入 {} 失败", path.display()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Profile;
+    use crate::core::{Core, StartupOptions};
+    use crate::output::LedState;
+
+    #[tokio::test]
+    async fn test_exported_state_round_trips() {
+        let mut config = Config::without_devices();
+        config.profiles.insert(
+            "typing".to_string(),
+            Profile {
+                caps_to_ctrl: true,
+                ..Default::default()
+            },
+        );
+        config.active_profile = "typing".to_string();
+        let source = Core::with_startup(
+            &config,
+            &StartupOptions {
+                mode: OutputMode::Ble,
+                mouse_rate_hz: None,
+            },
+        );
+        source.ble_host_prefs().set_leds(
+            "AA:BB:CC:DD:EE:01",
+            LedState {
+                num_lock: true,
+                ..Default::default()
+            },
+        );
+
+        let exported = source.export_state().await;
+        let text = serde_json::to_string(&exported).unwrap();
+        let bundle: StateBundle = serde_json::from_str(&text).unwrap();
+
+        let target = Core::new(&Config::without_devices());
+        target.import_state(bundle).await.unwrap();
+        assert_eq!(target.output_name(), "ble");
+        assert!(target.key_remap().caps_to_ctrl());
+        assert_eq!(
+            serde_json::to_value(target.export_state().await).unwrap(),
+            serde_json::to_value(&exported).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_bundle_changes_nothing() {
+        let core = Core::new(&Config::without_devices());
+        let mut bundle = core.export_state().await;
+        bundle.output = Some(OutputMode::Ble);
+        bundle.host_prefs.insert(
+            "AA:BB:CC:DD:EE:01".to_string(),
+            HostPref {
+                protocol_mode: 7,
+                ..Default::default()
+            },
+        );
+        assert!(core.import_state(bundle).await.is_err());
+        assert_eq!(core.output_name(), "usb");
+        assert!(core.ble_host_prefs().snapshot().is_empty());
+    }
+
+    #[test]
+    fn test_failed_persist_leaves_files_untouched() {
+        let dir = std::env::temp_dir().join(format!("bridge-hid-state-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("config.json");
+        std::fs::write(&config_path, "{}").unwrap();
+
+        let mut config = Config::without_devices();
+        // 主机偏好所在目录不存在，写入失败
+        config.ble.host_prefs_path = Some(dir.join("missing").join("hosts.json"));
+        let bundle = StateBundle::capture(&config, None, &HostPrefs::load(None));
+        assert!(bundle.persist(&config_path).is_err());
+        assert_eq!(std::fs::read_to_string(&config_path).unwrap(), "{}");
+        let leftover = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .any(|entry| entry.file_name().to_string_lossy().ends_with(".tmp"));
+        assert!(!leftover);
+
+        config.ble.host_prefs_path = Some(dir.join("hosts.json"));
+        let bundle = StateBundle::capture(&config, None, &HostPrefs::load(None));
+        bundle.persist(&config_path).unwrap();
+        assert!(dir.join("hosts.json").exists());
+        assert_ne!(std::fs::read_to_string(&config_path).unwrap(), "{}");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_failed_rename_removes_remaining_temp_files() {
+        let dir = std::env::temp_dir().join(format!("bridge-hid-commit-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("busy").join("child")).unwrap();
+        let config_path = dir.join("config.json");
+        let first = stage(&config_path, &1).unwrap();
+        // 目标是非空目录，改名失败
+        let second = stage(&dir.join("busy"), &2).unwrap();
+        assert_ne!(first, second);
+
+        let staged = [
+            (first, config_path.clone()),
+            (second.clone(), dir.join("busy")),
+        ];
+        assert!(commit(&staged).is_err());
+        assert_eq!(std::fs::read_to_string(&config_path).unwrap(), "1");
+        assert!(!second.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,2 +1,5 @@
+pub mod api;
+pub mod health;
+pub mod protocol;
 pub mod router;
 pub mod ws;
@@ -0,0 +1,318 @@
+use crate::core::CoreStatus;
+use crate::input::KeyRemap;
+use crate::output::key_names::usage_from_name;
+use crate::state::StateBundle;
+use crate::web::ws::WsState;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use futures::Stream;
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+/// 组合键请求，例如 `{"modifiers": 3, "keys": [23]}` 或 `{"modifiers": 3, "keys": ["t"]}`
+/// 表示 Ctrl+Shift+T
+#[derive(Debug, Deserialize)]
+pub struct ChordRequest {
+    #[serde(default)]
+    pub modifiers: u8,
+    #[serde(default)]
+    pub keys: Vec<KeySpec>,
+}
+
+/// 按键：HID 键码或按键名
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum KeySpec {
+    Usage(u8),
+    Name(String),
+}
+
+impl KeySpec {
+    pub fn usage(&self) -> Option<u8> {
+        match self {
+            KeySpec::Usage(usage) => Some(*usage),
+            KeySpec::Name(name) => usage_from_name(name),
+        }
+    }
+}
+
+/// `POST /api/chord`：按下并释放一组组合键
+pub async fn chord_handler(
+    State(state): State<Arc<WsState>>,
+    Json(req): Json<ChordRequest>,
+) -> StatusCode {
+    let Some(keys) = req
+        .keys
+        .iter()
+        .map(KeySpec::usage)
+        .collect::<Option<Vec<u8>>>()
+    else {
+        return StatusCode::BAD_REQUEST;
+    };
+    match state.send_chord(req.modifiers, &keys).await {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(e) => {
+            error!("发送组合键失败: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// `POST /api/pause`：暂停转发输入，单独运行网页触控板时没有切换器，返回 404
+pub async fn pause_handler(State(state): State<Arc<WsState>>) -> StatusCode {
+    match state.core() {
+        Some(core) => {
+            core.pause();
+            StatusCode::NO_CONTENT
+        }
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+/// `POST /api/resume`：恢复转发输入
+pub async fn resume_handler(State(state): State<Arc<WsState>>) -> StatusCode {
+    match state.core() {
+        Some(core) => {
+            core.resume();
+            StatusCode::NO_CONTENT
+        }
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+/// `GET /api/status`：输出、连接、主机地址、暂停与输入设备扫描等运行状态
+pub async fn status_handler(
+    State(state): State<Arc<WsState>>,
+) -> Result<Json<CoreStatus>, StatusCode> {
+    let core = state.core().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(core.status().await))
+}
+
+/// 配置方案列表与当前方案
+#[derive(Debug, Serialize)]
+pub struct ProfilesResponse {
+    pub active: String,
+    pub profiles: Vec<String>,
+}
+
+/// `GET /api/profiles`：已定义的配置方案
+pub async fn profiles_handler(
+    State(state): State<Arc<WsState>>,
+) -> Result<Json<ProfilesResponse>, StatusCode> {
+    let core = state.core().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(ProfilesResponse {
+        active: core.status().await.active_profile,
+        profiles: core.profile_names(),
+    }))
+}
+
+/// `POST /api/profiles/{name}`：切换配置方案，未知方案返回 404
+pub async fn switch_profile_handler(
+    State(state): State<Arc<WsState>>,
+    Path(name): Path<String>,
+) -> StatusCode {
+    let Some(core) = state.core() else {
+        return StatusCode::NOT_FOUND;
+    };
+    match core.switch_profile(&name).await {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(e) => {
+            debug!("切换配置方案失败: {}", e);
+            StatusCode::NOT_FOUND
+        }
+    }
+}
+
+/// `GET /api/state`：导出完整运行状态，用于迁移
+pub async fn export_state_handler(
+    State(state): State<Arc<WsState>>,
+) -> Result<Json<StateBundle>, StatusCode> {
+    let core = state.core().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(core.export_state().await))
+}
+
+/// `POST /api/state`：导入运行状态，检查不通过时不做任何修改并返回 422
+pub async fn import_state_handler(
+    State(state): State<Arc<WsState>>,
+    Json(bundle): Json<StateBundle>,
+) -> StatusCode {
+    let Some(core) = state.core() else {
+        return StatusCode::NOT_FOUND;
+    };
+    match core.import_state(bundle).await {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(e) => {
+            error!("导入运行状态失败: {:#}", e);
+            StatusCode::UNPROCESSABLE_ENTITY
+        }
+    }
+}
+
+/// 运行时按键重映射开关；请求中省略的开关保持不变
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RemapSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caps_to_ctrl: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub swap_alt_meta: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disable_super: Option<bool>,
+}
+
+impl RemapSettings {
+    fn of(remap: &KeyRemap) -> Self {
+        Self {
+            caps_to_ctrl: Some(remap.caps_to_ctrl()),
+            swap_alt_meta: Some(remap.swap_alt_meta()),
+            disable_super: Some(remap.disable_super()),
+        }
+    }
+}
+
+/// `GET /api/remap`：当前的按键重映射开关
+pub async fn remap_handler(
+    State(state): State<Arc<WsState>>,
+) -> Result<Json<RemapSettings>, StatusCode> {
+    let core = state.core().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(RemapSettings::of(core.key_remap())))
+}
+
+/// `POST /api/remap`：修改按键重映射开关，对新按下的键生效，返回修改后的开关
+pub async fn set_remap_handler(
+    State(state): State<Arc<WsState>>,
+    Json(req): Json<RemapSettings>,
+) -> Result<Json<RemapSettings>, StatusCode> {
+    let core = state.core().ok_or(StatusCode::NOT_FOUND)?;
+    let remap = core.key_remap();
+    if let Some(enabled) = req.caps_to_ctrl {
+        remap.set_caps_to_ctrl(enabled);
+    }
+    if let Some(enabled) = req.swap_alt_meta {
+        remap.set_swap_alt_meta(enabled);
+    }
+    if let Some(enabled) = req.disable_super {
+        remap.set_disable_super(enabled);
+    }
+    Ok(Json(RemapSettings::of(remap)))
+}
+
+/// `GET /api/preview`：以 Server-Sent Events 推送已转发报告的实时预览，
+/// 单独运行网页触控板时没有切换器，返回 404
+pub async fn preview_handler(
+    State(state): State<Arc<WsState>>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    let core = state.core().ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Sse::new(preview_events(core.subscribe_preview())).keep_alive(KeepAlive::default()))
+}
+
+/// 把预览订阅转换为事件流，落后时跳过旧消息
+fn preview_events(
+    rx: broadcast::Receiver<String>,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    futures::stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(message) => return Some((Ok(Event::default().data(message)), rx)),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    debug!("预览订阅落后，跳过 {} 条消息", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, WebConfig};
+    use crate::core::Core;
+
+    #[tokio::test]
+    async fn test_status_endpoint_reports_input_scan() {
+        let core = Arc::new(Core::new(&Config::without_devices()));
+        let state = Arc::new(WsState::with_core(&WebConfig::default(), core));
+
+        let Json(status) = status_handler(State(state)).await.unwrap();
+        let status = serde_json::to_value(status).unwrap();
+        assert_eq!(status["output"], "usb");
+        assert_eq!(status["paused"], false);
+        assert_eq!(status["input"], "pending");
+    }
+
+    #[tokio::test]
+    async fn test_profile_endpoint_switches_profile() {
+        let mut config = Config::without_devices();
+        config
+            .profiles
+            .insert("gaming".to_string(), Default::default());
+        let core = Arc::new(Core::new(&config));
+        let state = Arc::new(WsState::with_core(&WebConfig::default(), Arc::clone(&core)));
+
+        let status =
+            switch_profile_handler(State(Arc::clone(&state)), Path("gaming".to_string())).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let status =
+            switch_profile_handler(State(Arc::clone(&state)), Path("missing".to_string())).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let Json(profiles) = profiles_handler(State(state)).await.unwrap();
+        assert_eq!(profiles.active, "gaming");
+        assert!(profiles.profiles.contains(&"gaming".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_state_endpoints_round_trip() {
+        let core = Arc::new(Core::new(&Config::without_devices()));
+        let state = Arc::new(WsState::with_core(&WebConfig::default(), Arc::clone(&core)));
+
+        let Json(mut bundle) = export_state_handler(State(Arc::clone(&state)))
+            .await
+            .unwrap();
+        bundle.config.profiles.insert(
+            "typing".to_string(),
+            crate::config::Profile {
+                caps_to_ctrl: true,
+                ..Default::default()
+            },
+        );
+        bundle.config.active_profile = "typing".to_string();
+        let status = import_state_handler(State(Arc::clone(&state)), Json(bundle.clone())).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert!(core.key_remap().caps_to_ctrl());
+
+        bundle.config.active_profile = "missing".to_string();
+        let status = import_state_handler(State(state), Json(bundle)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(core.status().await.active_profile, "typing");
+    }
+
+    #[tokio::test]
+    async fn test_remap_endpoint_updates_given_switches() {
+        let core = Arc::new(Core::new(&Config::without_devices()));
+        let state = Arc::new(WsState::with_core(&WebConfig::default(), Arc::clone(&core)));
+
+        let Json(settings) = set_remap_handler(
+            State(Arc::clone(&state)),
+            Json(RemapSettings {
+                caps_to_ctrl: Some(true),
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(settings.caps_to_ctrl, Some(true));
+        assert_eq!(settings.swap_alt_meta, Some(false));
+        assert!(core.key_remap().caps_to_ctrl());
+
+        let Json(settings) = remap_handler(State(state)).await.unwrap();
+        assert_eq!(settings.caps_to_ctrl, Some(true));
+    }
+}
@@ -0,0 +1,71 @@
+use crate::metrics;
+use crate::output::connection::ConnectionState;
+use crate::web::ws::WsState;
+use axum::{
+    Json,
+    extract::State,
+    http::{StatusCode, header},
+    response::IntoResponse,
+};
+use serde_json::{Value, json};
+use std::sync::Arc;
+
+/// `GET /healthz`：后端可发送时返回 200，否则返回 503
+pub async fn healthz_handler(State(state): State<Arc<WsState>>) -> impl IntoResponse {
+    health_response(&state.connection(), state.output_mode())
+}
+
+/// `GET /metrics`：Prometheus 文本格式的运行指标
+pub async fn metrics_handler() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::global().render(),
+    )
+}
+
+/// 根据连接状态构造健康检查响应
+pub fn health_response(connection: &ConnectionState, mode: &str) -> (StatusCode, Json<Value>) {
+    let ready = connection.is_connected();
+    let code = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    let body = json!({
+        "ready": ready,
+        "mode": mode,
+        "last_report_ms": connection.last_report_ms(),
+    });
+    (code, Json(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_follows_readiness() {
+        let connection = ConnectionState::new(false);
+        let mut rx = connection.subscribe();
+
+        let (code, Json(body)) = health_response(&connection, "usb");
+        assert_eq!(code, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["ready"], false);
+        assert!(body["last_report_ms"].is_null());
+
+        connection.set_connected(true);
+        connection.mark_report_sent();
+        assert!(rx.has_changed().unwrap());
+        assert!(*rx.borrow_and_update());
+
+        let (code, Json(body)) = health_response(&connection, "usb");
+        assert_eq!(code, StatusCode::OK);
+        assert_eq!(body["mode"], "usb");
+        assert!(body["last_report_ms"].as_u64().is_some());
+
+        connection.set_connected(false);
+        let (code, _) = health_response(&connection, "usb");
+        assert_eq!(code, StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -0,0 +1,278 @@
+//! 网页触控板 WebSocket 二进制协议
+//!
+//! 每条消息首字节为类型，其余字段按固定偏移排列，多字节整数一律小端。
+//! 解码器与 `GET /protocol` 都以这里的表为准，二者不会不一致。
+
+use axum::Json;
+use serde::Serialize;
+
+/// 字段类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+}
+
+impl FieldType {
+    pub const fn size(self) -> usize {
+        match self {
+            Self::U8 | Self::I8 => 1,
+            Self::U16 | Self::I16 => 2,
+            Self::U32 | Self::I32 => 4,
+        }
+    }
+}
+
+/// 消息中的一个字段
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Field {
+    pub name: &'static str,
+    /// 相对消息起始（含类型字节）的偏移
+    pub offset: usize,
+    #[serde(rename = "type")]
+    pub ty: FieldType,
+    pub size: usize,
+    pub endianness: &'static str,
+}
+
+impl Field {
+    const fn new(name: &'static str, offset: usize, ty: FieldType) -> Self {
+        Self {
+            name,
+            offset,
+            ty,
+            size: ty.size(),
+            endianness: "little",
+        }
+    }
+
+    fn bytes<const N: usize>(&self, data: &[u8]) -> [u8; N] {
+        data[self.offset..self.offset + N].try_into().unwrap()
+    }
+
+    /// 以下读取函数要求调用方已用 [`MessageSpec::fits`] 检查长度
+    pub fn u8(&self, data: &[u8]) -> u8 {
+        data[self.offset]
+    }
+
+    pub fn i8(&self, data: &[u8]) -> i8 {
+        data[self.offset] as i8
+    }
+
+    pub fn u16(&self, data: &[u8]) -> u16 {
+        u16::from_le_bytes(self.bytes(data))
+    }
+
+    pub fn i16(&self, data: &[u8]) -> i16 {
+        i16::from_le_bytes(self.bytes(data))
+    }
+
+    pub fn u32(&self, data: &[u8]) -> u32 {
+        u32::from_le_bytes(self.bytes(data))
+    }
+
+    pub fn i32(&self, data: &[u8]) -> i32 {
+        i32::from_le_bytes(self.bytes(data))
+    }
+}
+
+/// 一种消息的布局
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct MessageSpec {
+    #[serde(rename = "type")]
+    pub id: u8,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub fields: &'static [Field],
+}
+
+impl MessageSpec {
+    /// 消息最小长度（含类型字节）
+    pub fn min_len(&self) -> usize {
+        self.fields
+            .iter()
+            .map(|f| f.offset + f.size)
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// 数据是否为该类型且长度足够
+    pub fn fits(&self, data: &[u8]) -> bool {
+        data.first() == Some(&self.id) && data.len() >= self.min_len()
+    }
+}
+
+pub const MOUSE_MOVE_X: Field = Field::new("x", 1, FieldType::I16);
+pub const MOUSE_MOVE_Y: Field = Field::new("y", 3, FieldType::I16);
+pub const MOUSE_MOVE: MessageSpec = MessageSpec {
+    id: 0x01,
+    name: "mouse_move",
+    description: "鼠标相对移动",
+    fields: &[MOUSE_MOVE_X, MOUSE_MOVE_Y],
+};
+
+pub const MOUSE_BUTTON_BUTTONS: Field = Field::new("buttons", 1, FieldType::U8);
+pub const MOUSE_BUTTON_STATE: Field = Field::new("state", 2, FieldType::U8);
+pub const MOUSE_BUTTON: MessageSpec = MessageSpec {
+    id: 0x02,
+    name: "mouse_button",
+    description: "鼠标按键位图（0x01 左、0x02 右、0x04 中）",
+    fields: &[MOUSE_BUTTON_BUTTONS, MOUSE_BUTTON_STATE],
+};
+
+pub const SCROLL_X: Field = Field::new("x", 1, FieldType::I16);
+pub const SCROLL_Y: Field = Field::new("y", 3, FieldType::I16);
+pub const SCROLL: MessageSpec = MessageSpec {
+    id: 0x03,
+    name: "scroll",
+    description: "滚动量，按阈值累积为滚轮格数",
+    fields: &[SCROLL_X, SCROLL_Y],
+};
+
+pub const KEY_CHAR_CODEPOINT: Field = Field::new("codepoint", 1, FieldType::U32);
+pub const KEY_CHAR: MessageSpec = MessageSpec {
+    id: 0x04,
+    name: "key_char",
+    description: "输入一个 Unicode 字符",
+    fields: &[KEY_CHAR_CODEPOINT],
+};
+
+pub const MOUSE_MOVE_LONG_DX: Field = Field::new("dx", 1, FieldType::I32);
+pub const MOUSE_MOVE_LONG_DY: Field = Field::new("dy", 5, FieldType::I32);
+pub const MOUSE_MOVE_LONG: MessageSpec = MessageSpec {
+    id: 0x05,
+    name: "mouse_move_long",
+    description: "长距离移动，拆分为多个相对报告",
+    fields: &[MOUSE_MOVE_LONG_DX, MOUSE_MOVE_LONG_DY],
+};
+
+pub const CONSUMER_USAGE: Field = Field::new("usage", 1, FieldType::U16);
+pub const CONSUMER: MessageSpec = MessageSpec {
+    id: 0x06,
+    name: "consumer",
+    description: "媒体键（Consumer Page 用法），按下后立即释放",
+    fields: &[CONSUMER_USAGE],
+};
+
+pub const MOUSE_REPORT_BUTTONS: Field = Field::new("buttons", 1, FieldType::U8);
+pub const MOUSE_REPORT_DX: Field = Field::new("dx", 2, FieldType::I16);
+pub const MOUSE_REPORT_DY: Field = Field::new("dy", 4, FieldType::I16);
+pub const MOUSE_REPORT_WHEEL: Field = Field::new("wheel", 6, FieldType::I8);
+pub const MOUSE_REPORT: MessageSpec = MessageSpec {
+    id: 0x07,
+    name: "mouse_report",
+    description: "完整鼠标报告：按键位图、位移与滚轮在同一帧，用于拖拽",
+    fields: &[
+        MOUSE_REPORT_BUTTONS,
+        MOUSE_REPORT_DX,
+        MOUSE_REPORT_DY,
+        MOUSE_REPORT_WHEEL,
+    ],
+};
+
+pub const MODIFIED_SCROLL_MODIFIERS: Field = Field::new("modifiers", 1, FieldType::U8);
+pub const MODIFIED_SCROLL_TICKS: Field = Field::new("ticks", 2, FieldType::I8);
+pub const MODIFIED_SCROLL: MessageSpec = MessageSpec {
+    id: 0x08,
+    name: "modified_scroll",
+    description: "按住修饰键滚动若干格，如 Ctrl+滚轮缩放",
+    fields: &[MODIFIED_SCROLL_MODIFIERS, MODIFIED_SCROLL_TICKS],
+};
+
+pub const VIEWPORT_WIDTH: Field = Field::new("width", 1, FieldType::U16);
+pub const VIEWPORT_HEIGHT: Field = Field::new("height", 3, FieldType::U16);
+pub const VIEWPORT: MessageSpec = MessageSpec {
+    id: 0x09,
+    name: "viewport",
+    description: "握手：上报触控区域尺寸（像素），尺寸变化时重新发送，服务端以 viewport_reply 回复指针模式（目前总是相对模式）",
+    fields: &[VIEWPORT_WIDTH, VIEWPORT_HEIGHT],
+};
+
+/// 握手回复中的指针模式：只接受相对位移
+///
+/// 输出后端只有相对鼠标，握手目前只支持该模式：服务端总是回复它，
+/// 上报的尺寸不参与坐标换算，客户端应继续发送相对位移。
+pub const POINTER_RELATIVE: u8 = 0;
+
+pub const VIEWPORT_REPLY_MODE: Field = Field::new("mode", 1, FieldType::U8);
+/// 服务端发往客户端的握手回复，与 `viewport` 同一类型字节
+pub const VIEWPORT_REPLY: MessageSpec = MessageSpec {
+    id: VIEWPORT.id,
+    name: "viewport_reply",
+    description: "握手回复：mode 0 为相对模式",
+    fields: &[VIEWPORT_REPLY_MODE],
+};
+
+/// 编码握手回复
+pub fn viewport_reply() -> Vec<u8> {
+    vec![VIEWPORT_REPLY.id, POINTER_RELATIVE]
+}
+
+/// 所有消息类型
+pub const MESSAGES: &[MessageSpec] = &[
+    MOUSE_MOVE,
+    MOUSE_BUTTON,
+    SCROLL,
+    KEY_CHAR,
+    MOUSE_MOVE_LONG,
+    CONSUMER,
+    MOUSE_REPORT,
+    MODIFIED_SCROLL,
+    VIEWPORT,
+];
+
+/// `GET /protocol`：二进制消息布局的机器可读描述
+pub async fn protocol_handler() -> Json<&'static [MessageSpec]> {
+    Json(MESSAGES)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_protocol_lists_mouse_move() {
+        let Json(messages) = protocol_handler().await;
+        let value = serde_json::to_value(messages).unwrap();
+        let mouse_move = value
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|m| m["type"] == 0x01)
+            .unwrap();
+
+        assert_eq!(mouse_move["name"], "mouse_move");
+        let fields = mouse_move["fields"].as_array().unwrap();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0]["name"], "x");
+        assert_eq!(fields[0]["offset"], 1);
+        assert_eq!(fields[0]["type"], "i16");
+        assert_eq!(fields[0]["size"], 2);
+        assert_eq!(fields[0]["endianness"], "little");
+        assert_eq!(fields[1]["name"], "y");
+        assert_eq!(fields[1]["offset"], 3);
+        assert_eq!(MOUSE_MOVE.min_len(), 5);
+    }
+
+    #[test]
+    fn test_message_ids_unique_and_fields_in_bounds() {
+        for (i, spec) in MESSAGES.iter().enumerate() {
+            assert!(MESSAGES[i + 1..].iter().all(|other| other.id != spec.id));
+            let frame = vec![spec.id; spec.min_len()];
+            assert!(spec.fits(&frame));
+            assert!(!spec.fits(&frame[..frame.len() - 1]));
+        }
+    }
+
+    #[test]
+    fn test_viewport_reply_layout() {
+        let reply = viewport_reply();
+        assert!(VIEWPORT_REPLY.fits(&reply));
+        assert_eq!(reply, [VIEWPORT.id, POINTER_RELATIVE]);
+    }
+}
@@ -1,13 +1,42 @@
-use crate::web::ws;
-use axum::{Router, routing::get};
+use crate::config::WebConfig;
+use crate::web::{api, health, protocol, ws};
+use axum::{
+    Router,
+    routing::{get, post},
+};
 use std::sync::Arc;
 use tower_http::services::ServeDir;
 
-pub async fn build_router() -> Router {
-    let ws_state = Arc::new(ws::WsState::new().await);
+pub async fn build_router(config: &WebConfig) -> Router {
+    router_with_state(Arc::new(ws::WsState::with_config(config).await))
+}
 
-    Router::new()
+/// 使用已有的连接状态构建路由，例如与切换器共用输出后端时
+pub fn router_with_state(ws_state: Arc<ws::WsState>) -> Router {
+    let mut router = Router::new()
         .route("/ws", get(ws::ws_handler))
+        .route("/healthz", get(health::healthz_handler))
+        .route("/metrics", get(health::metrics_handler))
+        .route("/protocol", get(protocol::protocol_handler))
+        .route("/api/chord", post(api::chord_handler))
+        .route("/api/pause", post(api::pause_handler))
+        .route("/api/resume", post(api::resume_handler))
+        .route("/api/status", get(api::status_handler))
+        .route("/api/profiles", get(api::profiles_handler))
+        .route("/api/profiles/{name}", post(api::switch_profile_handler))
+        .route(
+            "/api/state",
+            get(api::export_state_handler).post(api::import_state_handler),
+        )
+        .route(
+            "/api/remap",
+            get(api::remap_handler).post(api::set_remap_handler),
+        );
+    // 预览会暴露转发的输入，只在配置开启时提供
+    if ws_state.preview_enabled() {
+        router = router.route("/api/preview", get(api::preview_handler));
+    }
+    router
         .with_state(ws_state)
         .fallback_service(ServeDir::new("static"))
 }
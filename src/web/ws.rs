@@ -6,37 +6,260 @@ use axum::{
     response::IntoResponse,
 };
 
+use async_trait::async_trait;
 use futures::SinkExt;
-use log::{error, info};
-use usb_gadget::function::hid;
+use log::{error, info, warn};
 
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 
 use crate::output::{
     HidReportSender, UsbKeyboardHidDevice, UsbMouseHidDevice,
+    connection::ConnectionState,
+    keyboard::{TypingConfig, char_to_keycode, chord_reports, pause},
+    mouse::{
+        AxisTransform, DEFAULT_MOVE_STEP, DeadZone, ScrollAccel, ScrollAccelerator,
+        modified_wheel_reports, split_move,
+    },
     usb::{UsbError, build_usb_hid_device},
 };
 
+use crate::config::WebConfig;
+use crate::core::Core;
 use crate::input::{DeviceType, InputReport};
+use crate::web::protocol;
 use std::sync::atomic::{AtomicBool, Ordering};
 
-use anyhow::Result;
+use anyhow::{Result, anyhow, bail};
+
+/// 默认滚动阈值：0 表示不累加，每条滚动消息直接转发为一个滚轮报告
+pub const DEFAULT_SCROLL_THRESHOLD: i32 = 0;
 
 // WebSocket 连接状态
 pub struct WsState {
     active_socket: Mutex<Option<Arc<Mutex<WebSocket>>>>,
-    hid_guard: Arc<ReconnectGuard>,
+    sink: ReportSink,
+    scroll_threshold: i32,
+    scroll_accel: ScrollAccel,
+    axes: AxisTransform,
+    dead_zone: DeadZone,
+    typing: TypingConfig,
+    preview: bool,
+    ping_interval: Duration,
+    pong_timeout: Duration,
 }
 
 impl WsState {
     pub async fn new() -> Self {
-        let hid_guard = Arc::new(ReconnectGuard::new().await);
+        Self::with_config(&WebConfig::default()).await
+    }
+
+    pub async fn with_config(config: &WebConfig) -> Self {
+        Self::with_sink(config, ReportSink::Usb(ReconnectGuard::new().await))
+    }
+
+    /// 与切换器同时运行：报告注入 `core` 的输入管线，不再单独创建 USB gadget
+    pub fn with_core(config: &WebConfig, core: Arc<Core>) -> Self {
+        Self::with_sink(config, ReportSink::Core(core))
+    }
+
+    fn with_sink(config: &WebConfig, sink: ReportSink) -> Self {
         Self {
             active_socket: Mutex::new(None),
-            hid_guard,
+            sink,
+            scroll_threshold: config.scroll_threshold,
+            scroll_accel: config.scroll_accel,
+            axes: config.mouse_axes,
+            dead_zone: config.mouse_dead_zone,
+            typing: config.typing,
+            preview: config.preview,
+            ping_interval: config.ping_interval(),
+            pong_timeout: config.pong_timeout(),
+        }
+    }
+
+    /// 当前输出后端的连接状态
+    pub fn connection(&self) -> ConnectionState {
+        match &self.sink {
+            ReportSink::Usb(guard) => guard.connected.clone(),
+            ReportSink::Core(core) => core.output_connection(),
         }
     }
+
+    /// 发送组合键：按下后全部释放
+    pub async fn send_chord(&self, modifiers: u8, keys: &[u8]) -> Result<()> {
+        let [down, up] = chord_reports(modifiers, keys);
+        self.sink.send_report(DeviceType::Keyboard, down).await?;
+        pause(self.typing.tap()).await;
+        self.sink.send_report(DeviceType::Keyboard, up).await
+    }
+
+    /// 移动任意距离，拆分为多个相对报告依次发送
+    pub async fn mouse_move(&self, dx: i32, dy: i32) -> Result<()> {
+        move_by(&self.sink, self.axes, dx, dy).await
+    }
+
+    /// 按住修饰键滚动 `ticks` 格，正数向上
+    pub async fn modified_scroll(&self, modifiers: u8, ticks: i32) -> Result<()> {
+        send_modified_wheel(&self.sink, modifiers, ticks, self.typing.tap()).await
+    }
+
+    /// 与切换器同时运行时的切换器
+    pub fn core(&self) -> Option<&Arc<Core>> {
+        match &self.sink {
+            ReportSink::Usb(_) => None,
+            ReportSink::Core(core) => Some(core),
+        }
+    }
+
+    /// 是否开放实时预览
+    pub fn preview_enabled(&self) -> bool {
+        self.preview
+    }
+
+    /// 单独运行时固定输出到 USB，与切换器同时运行时跟随当前输出
+    pub fn output_mode(&self) -> &'static str {
+        match &self.sink {
+            ReportSink::Usb(_) => "usb",
+            ReportSink::Core(core) => core.output_name(),
+        }
+    }
+}
+
+/// 滚轮累加器
+///
+/// 触屏双指滚动会产生大量细小的 `0x03` 消息，逐条映射为滚轮值会造成抖动。
+/// 累加器将滚动量求和，只在跨过阈值时输出整数格数，余量留到下一次。
+/// 滚动量在累加前先经过加速曲线。阈值为 0 时不累加，滚动量截断到单个报告的范围后直接输出。
+pub struct ScrollAccumulator {
+    threshold: i32,
+    remainder: i32,
+    accel: ScrollAccelerator,
+}
+
+impl ScrollAccumulator {
+    /// - `threshold`: 每格滚轮对应的滚动量，小于 1 时直接转发
+    pub fn new(threshold: i32) -> Self {
+        Self {
+            threshold: threshold.max(0),
+            remainder: 0,
+            accel: ScrollAccelerator::default(),
+        }
+    }
+
+    /// 累加前按 `curve` 加速滚动量
+    pub fn with_accel(mut self, curve: ScrollAccel) -> Self {
+        self.accel = ScrollAccelerator::new(curve);
+        self
+    }
+
+    /// 累加一次滚动量，返回本次应输出的滚轮格数
+    pub fn push(&mut self, delta: i16) -> i8 {
+        self.push_at(delta, Instant::now())
+    }
+
+    /// 不累加，每条消息都输出一个滚轮报告（包括 0 格）
+    pub fn passes_through(&self) -> bool {
+        self.threshold == 0
+    }
+
+    /// 累加 `now` 时刻的一次滚动量
+    pub fn push_at(&mut self, delta: i16, now: Instant) -> i8 {
+        let delta = self.accel.apply(delta as i32, now);
+        if self.passes_through() {
+            return delta.clamp(i8::MIN as i32, i8::MAX as i32) as i8;
+        }
+        self.remainder = self.remainder.saturating_add(delta);
+        // 单个报告最多 ±127 格，超出部分留在余量中
+        let detents = (self.remainder / self.threshold).clamp(-127, 127);
+        self.remainder -= detents * self.threshold;
+        detents as i8
+    }
+}
+
+/// 触控板位移累加器
+///
+/// 位移未超出死区时累加而不发送，超出后返回全部累积值；完整鼠标报告到来时把累积值并入其中。
+pub struct MoveAccumulator {
+    dead_zone: DeadZone,
+    pending: (i32, i32),
+}
+
+impl MoveAccumulator {
+    pub fn new(dead_zone: DeadZone) -> Self {
+        Self {
+            dead_zone,
+            pending: (0, 0),
+        }
+    }
+
+    /// 累加一次位移，超出死区时返回应发送的位移
+    pub fn push(&mut self, x: i16, y: i16) -> Option<(i32, i32)> {
+        self.pending.0 += x as i32;
+        self.pending.1 += y as i32;
+        self.dead_zone
+            .exceeded(self.pending.0, self.pending.1)
+            .then(|| self.take())
+    }
+
+    /// 取出尚未发送的位移
+    pub fn take(&mut self) -> (i32, i32) {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+/// 心跳检测
+///
+/// 手机断开 Wi-Fi 时往往不会正常关闭连接，占用唯一的连接槽直到 TCP 超时。
+/// 定期发送 Ping，最早一个未得到回应的 Ping 超过期限后即判定连接失效；
+/// 收到任何消息都视为连接存活。
+pub struct Heartbeat {
+    timeout: Duration,
+    /// 最早一个未得到回应的 Ping 的发送时间
+    unanswered_since: Option<Instant>,
+}
+
+impl Heartbeat {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            unanswered_since: None,
+        }
+    }
+
+    /// 记录已发送 Ping
+    pub fn ping_sent(&mut self, now: Instant) {
+        self.unanswered_since.get_or_insert(now);
+    }
+
+    /// 记录收到 Pong 或其他消息
+    pub fn alive(&mut self) {
+        self.unanswered_since = None;
+    }
+
+    /// 判定连接失效的时间
+    pub fn deadline(&self) -> Option<Instant> {
+        self.unanswered_since.map(|since| since + self.timeout)
+    }
+}
+
+/// WebSocket 连接的最小接口，测试中可替换为模拟连接
+#[async_trait]
+trait WsConnection: Send {
+    async fn recv(&mut self) -> Option<Result<Message, axum::Error>>;
+    async fn send(&mut self, msg: Message) -> Result<(), axum::Error>;
+}
+
+#[async_trait]
+impl WsConnection for WebSocket {
+    async fn recv(&mut self) -> Option<Result<Message, axum::Error>> {
+        WebSocket::recv(self).await
+    }
+
+    async fn send(&mut self, msg: Message) -> Result<(), axum::Error> {
+        WebSocket::send(self, msg).await
+    }
 }
 
 pub async fn ws_handler(
@@ -65,130 +288,433 @@ async fn handle_socket(socket: WebSocket, state: Arc<WsState>) {
     drop(active); // 释放锁
 
     info!("新 WebSocket 连接已建立");
+    let mut handler = SinkHandler {
+        sink: &state.sink,
+        decoder: WsDecoder::new(
+            state.axes,
+            ScrollAccumulator::new(state.scroll_threshold).with_accel(state.scroll_accel),
+            MoveAccumulator::new(state.dead_zone),
+        ),
+        typing: state.typing,
+    };
+
+    serve_socket(
+        &socket_arc,
+        state.ping_interval,
+        state.pong_timeout,
+        &mut handler,
+    )
+    .await;
+
+    // 释放可能仍按住的按键和鼠标键
+    let _ = state
+        .sink
+        .send_report(
+            DeviceType::Keyboard,
+            InputReport::Keyboard {
+                modifiers: 0,
+                keys: vec![],
+            },
+        )
+        .await;
+    let _ = state
+        .sink
+        .send_report(
+            DeviceType::Mouse,
+            InputReport::Mouse {
+                buttons: 0,
+                x: 0,
+                y: 0,
+                wheel: 0,
+            },
+        )
+        .await;
+
+    // 清理连接，只清除属于自己的槽位，被新连接替换时不影响新连接
+    let mut active = state.active_socket.lock().await;
+    if active
+        .as_ref()
+        .is_some_and(|current| Arc::ptr_eq(current, &socket_arc))
+    {
+        *active = None;
+    }
+    info!("WebSocket 连接已清理");
+}
+
+/// 二进制消息的处理方，测试中可替换为计数等实现
+#[async_trait]
+trait BinaryHandler: Send {
+    /// 处理一条消息，返回需要回复客户端的消息
+    async fn on_binary(&mut self, data: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// 解码消息并把报告发往 `sink`
+struct SinkHandler<'a> {
+    sink: &'a ReportSink,
+    decoder: WsDecoder,
+    typing: TypingConfig,
+}
+
+#[async_trait]
+impl BinaryHandler for SinkHandler<'_> {
+    async fn on_binary(&mut self, data: &[u8]) -> Option<Vec<u8>> {
+        handle_binary_message(data, self.sink, &mut self.decoder, &self.typing).await;
+        self.decoder.take_reply()
+    }
+}
+
+/// 处理消息直到连接关闭、出错或心跳超时
+///
+/// 收到二进制消息后先释放连接锁再处理，发送报告期间新连接可以立即替换本连接。
+async fn serve_socket<C: WsConnection>(
+    socket: &Mutex<C>,
+    ping_interval: Duration,
+    pong_timeout: Duration,
+    handler: &mut impl BinaryHandler,
+) {
+    let mut heartbeat = Heartbeat::new(pong_timeout);
+    let mut ping_timer = tokio::time::interval(ping_interval);
+    ping_timer.tick().await;
 
-    // 处理消息
     loop {
-        let mut sock = socket_arc.lock().await;
-        match sock.recv().await {
-            Some(Ok(msg)) => match msg {
-                Message::Binary(data) => {
-                    info!("收到二进制消息: {} bytes", data.len());
-                    if data.len() > 0 {
-                        handle_binary_message(&data, &state.hid_guard);
+        let mut sock = socket.lock().await;
+        let deadline = heartbeat.deadline();
+        let reap = async {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                None => std::future::pending::<()>().await,
+            }
+        };
+
+        let data = tokio::select! {
+            msg = sock.recv() => match msg {
+                Some(Ok(msg)) => {
+                    heartbeat.alive();
+                    match msg {
+                        Message::Binary(data) => {
+                            info!("收到二进制消息: {} bytes", data.len());
+                            data
+                        }
+                        Message::Close(_) => {
+                            info!("客户端关闭连接");
+                            break;
+                        }
+                        _ => continue,
                     }
                 }
-                Message::Close(_) => {
-                    info!("客户端关闭连接");
+                Some(Err(e)) => {
+                    error!("WebSocket 错误: {}", e);
+                    break;
+                }
+                None => {
+                    info!("连接已关闭");
                     break;
                 }
-                _ => {}
             },
-            Some(Err(e)) => {
-                error!("WebSocket 错误: {}", e);
-                break;
+            _ = ping_timer.tick() => {
+                if sock.send(Message::Ping(Default::default())).await.is_err() {
+                    info!("发送 Ping 失败，连接已断开");
+                    break;
+                }
+                heartbeat.ping_sent(Instant::now());
+                continue;
             }
-            None => {
-                info!("连接已关闭");
+            _ = reap => {
+                warn!("超过 {:?} 未收到 Pong，断开失效连接", pong_timeout);
+                let _ = sock.send(Message::Close(None)).await;
                 break;
             }
+        };
+        drop(sock); // 释放锁后再处理消息
+
+        if data.is_empty() {
+            continue;
+        }
+        if let Some(reply) = handler.on_binary(&data).await
+            && socket
+                .lock()
+                .await
+                .send(Message::Binary(reply.into()))
+                .await
+                .is_err()
+        {
+            info!("发送回复失败，连接已断开");
+            break;
         }
-        drop(sock); // 释放锁
     }
+}
 
-    // 清理连接
-    let mut active = state.active_socket.lock().await;
-    *active = None;
-    info!("WebSocket 连接已清理");
+/// WebSocket 二进制消息解码器
+///
+/// 滚动与触控板位移需要跨消息累积，解码器保存这部分状态；解码本身不涉及设备与运行时。
+/// 每个连接一个解码器。
+pub struct WsDecoder {
+    axes: AxisTransform,
+    scroll: ScrollAccumulator,
+    moves: MoveAccumulator,
+    /// 待发给客户端的回复
+    reply: Option<Vec<u8>>,
 }
 
-fn handle_binary_message(data: &[u8], hid_guard: &ReconnectGuard) {
-    if data.is_empty() {
-        return;
-    }
-
-    let msg_type = data[0];
-    match msg_type {
-        0x01 => {
-            // 鼠标移动
-            if data.len() >= 5 {
-                let x = i16::from_le_bytes([data[1], data[2]]);
-                let y = i16::from_le_bytes([data[3], data[4]]);
-                let _ = tokio::task::block_in_place(|| {
-                    tokio::runtime::Handle::current().block_on(async {
-                        hid_guard
-                            .send_report(
-                                DeviceType::Mouse,
-                                InputReport::Mouse {
-                                    buttons: 0, // 默认无按钮按下
-                                    x,
-                                    y,
-                                    wheel: 0, // 默认无滚轮
-                                },
-                            )
-                            .await
-                    })
-                });
-                info!("鼠标移动: x={}, y={}", x, y);
-            }
+impl Default for WsDecoder {
+    fn default() -> Self {
+        Self::new(
+            AxisTransform::default(),
+            ScrollAccumulator::new(DEFAULT_SCROLL_THRESHOLD),
+            MoveAccumulator::new(DeadZone::default()),
+        )
+    }
+}
+
+impl WsDecoder {
+    pub fn new(axes: AxisTransform, scroll: ScrollAccumulator, moves: MoveAccumulator) -> Self {
+        Self {
+            axes,
+            scroll,
+            moves,
+            reply: None,
         }
-        0x02 => {
-            // 鼠标点击
-            if data.len() >= 3 {
-                let button = data[1];
-                let state = data[2];
-                let _ = tokio::task::block_in_place(|| {
-                    tokio::runtime::Handle::current().block_on(async {
-                        hid_guard
-                            .send_report(
-                                DeviceType::Mouse,
-                                InputReport::Mouse {
-                                    buttons: button,
-                                    x: 0,
-                                    y: 0,
-                                    wheel: 0,
-                                },
-                            )
-                            .await
-                    })
-                });
+    }
+
+    /// 取出待发给客户端的回复
+    pub fn take_reply(&mut self) -> Option<Vec<u8>> {
+        self.reply.take()
+    }
+
+    /// 解码一条消息，返回应依次发送的报告
+    ///
+    /// 滚动或位移尚未累积够时返回空列表；空消息、未知类型、长度不足等返回错误。
+    pub fn decode(&mut self, data: &[u8]) -> Result<Vec<InputReport>> {
+        let Some(&msg_type) = data.first() else {
+            bail!("空消息");
+        };
+        let Some(spec) = protocol::MESSAGES.iter().find(|spec| spec.id == msg_type) else {
+            bail!("未知消息类型: 0x{:02X}", msg_type);
+        };
+        if !spec.fits(data) {
+            bail!(
+                "{} 消息长度 {} 不足 {} 字节",
+                spec.name,
+                data.len(),
+                spec.min_len()
+            );
+        }
+
+        let reports = match msg_type {
+            id if id == protocol::MOUSE_MOVE.id => {
+                // 鼠标移动
+                let x = protocol::MOUSE_MOVE_X.i16(data);
+                let y = protocol::MOUSE_MOVE_Y.i16(data);
+                let Some((dx, dy)) = self.moves.push(x, y) else {
+                    return Ok(vec![]);
+                };
+                info!("鼠标移动: x={}, y={}", dx, dy);
+                vec![self.axes.apply_report(InputReport::Mouse {
+                    buttons: 0, // 默认无按钮按下
+                    x: clamp_i16(dx),
+                    y: clamp_i16(dy),
+                    wheel: 0, // 默认无滚轮
+                })]
+            }
+            id if id == protocol::MOUSE_BUTTON.id => {
+                // 鼠标点击
+                let button = protocol::MOUSE_BUTTON_BUTTONS.u8(data);
+                let state = protocol::MOUSE_BUTTON_STATE.u8(data);
                 info!("鼠标点击: button={}, state={}", button, state);
+                vec![InputReport::Mouse {
+                    buttons: button,
+                    x: 0,
+                    y: 0,
+                    wheel: 0,
+                }]
             }
-        }
-        0x03 => {
-            // 滚轮
-            if data.len() >= 5 {
-                let x = i16::from_le_bytes([data[1], data[2]]);
-                let y = i16::from_le_bytes([data[3], data[4]]);
-                let wheel = y.clamp(i8::MIN as i16, i8::MAX as i16) as i8;
-                let _ = tokio::task::block_in_place(|| {
-                    tokio::runtime::Handle::current().block_on(async {
-                        hid_guard
-                            .send_report(
-                                DeviceType::Mouse,
-                                InputReport::Mouse {
-                                    buttons: 0,
-                                    x: 0,
-                                    y: 0,
-                                    wheel,
-                                },
-                            )
-                            .await
-                    })
-                });
+            id if id == protocol::SCROLL.id => {
+                // 滚轮
+                let x = protocol::SCROLL_X.i16(data);
+                let y = protocol::SCROLL_Y.i16(data);
+                let wheel = self.scroll.push(y);
+                if wheel == 0 && !self.scroll.passes_through() {
+                    return Ok(vec![]);
+                }
                 info!("滚轮: x={}, y={}", x, y);
+                vec![InputReport::Mouse {
+                    buttons: 0,
+                    x: 0,
+                    y: 0,
+                    wheel,
+                }]
             }
-        }
-        0x04 => {
-            // 键盘
-            if data.len() >= 5 {
-                let key_code = u32::from_le_bytes([data[1], data[2], data[3], data[4]]);
-                if let Some(ch) = char::from_u32(key_code) {
-                    info!("键盘输入: '{}'", ch);
-                }
+            id if id == protocol::KEY_CHAR.id => {
+                // 输入一个字符：按下后释放
+                let code = protocol::KEY_CHAR_CODEPOINT.u32(data);
+                let ch = char::from_u32(code).ok_or_else(|| anyhow!("无效码点: 0x{:X}", code))?;
+                let (modifiers, key) =
+                    char_to_keycode(ch).ok_or_else(|| anyhow!("字符 {:?} 没有对应键位", ch))?;
+                info!("键盘输入: '{}'", ch);
+                chord_reports(modifiers, &[key]).to_vec()
+            }
+            id if id == protocol::MOUSE_MOVE_LONG.id => {
+                // 长距离移动：dx、dy 为 i32，拆分为多个相对报告
+                let dx = protocol::MOUSE_MOVE_LONG_DX.i32(data);
+                let dy = protocol::MOUSE_MOVE_LONG_DY.i32(data);
+                info!("鼠标长距离移动: dx={}, dy={}", dx, dy);
+                let (dx, dy) = self.axes.apply(dx, dy);
+                split_move(dx, dy, DEFAULT_MOVE_STEP).collect()
+            }
+            id if id == protocol::CONSUMER.id => {
+                // 媒体键：按下后立即释放
+                info!("媒体键: usage=0x{:04X}", protocol::CONSUMER_USAGE.u16(data));
+                decode_consumer(data).map(Vec::from).unwrap_or_default()
             }
+            id if id == protocol::MOUSE_REPORT.id => {
+                // 完整鼠标报告：拖拽时按键与位移必须在同一报告中
+                let Some(InputReport::Mouse {
+                    buttons,
+                    x,
+                    y,
+                    wheel,
+                }) = decode_mouse_report(data)
+                else {
+                    return Ok(vec![]);
+                };
+                let (px, py) = self.moves.take();
+                vec![self.axes.apply_report(InputReport::Mouse {
+                    buttons,
+                    x: clamp_i16(x as i32 + px),
+                    y: clamp_i16(y as i32 + py),
+                    wheel,
+                })]
+            }
+            id if id == protocol::MODIFIED_SCROLL.id => {
+                // 按住修饰键滚动：触屏上无法同时按住 Ctrl
+                let modifiers = protocol::MODIFIED_SCROLL_MODIFIERS.u8(data);
+                let ticks = protocol::MODIFIED_SCROLL_TICKS.i8(data);
+                info!("修饰键滚动: modifiers=0x{:02X}, ticks={}", modifiers, ticks);
+                modified_wheel_reports(modifiers, ticks as i32)
+            }
+            id if id == protocol::VIEWPORT.id => {
+                // 握手：输出后端只有相对鼠标，尺寸只记录到日志，总是回复相对模式
+                info!(
+                    "触控区域: {}x{}",
+                    protocol::VIEWPORT_WIDTH.u16(data),
+                    protocol::VIEWPORT_HEIGHT.u16(data)
+                );
+                self.reply = Some(protocol::viewport_reply());
+                vec![]
+            }
+            // MESSAGES 中登记了、这里却没有处理的类型
+            _ => bail!("{} 消息未实现", spec.name),
+        };
+        Ok(reports)
+    }
+}
+
+/// 无状态地解码单条消息，等价于用默认配置的新解码器解码
+pub fn decode_ws_message(data: &[u8]) -> Result<Vec<InputReport>> {
+    WsDecoder::default().decode(data)
+}
+
+/// 解码并发送一条消息的报告，按键按 `typing` 的节奏发送，输入字符后再等待字符间隔
+async fn handle_binary_message(
+    data: &[u8],
+    sink: &ReportSink,
+    decoder: &mut WsDecoder,
+    typing: &TypingConfig,
+) {
+    match decoder.decode(data) {
+        Ok(reports) if reports.is_empty() => {}
+        Ok(reports) => {
+            if let Err(e) = send_paced(sink, reports, typing.tap()).await {
+                warn!("发送 WebSocket 报告失败: {:#}", e);
+            } else if data.first() == Some(&protocol::KEY_CHAR.id) {
+                pause(typing.char_delay()).await;
+            }
+        }
+        Err(e) => info!("忽略 WebSocket 消息: {:#}", e),
+    }
+}
+
+/// 解析媒体键消息 `[0x06, usage(2, 小端)]`，返回按下与释放报告
+///
+/// 常用 usage 见 [`crate::output::consumer`]：音量加 0x00E9、音量减 0x00EA、
+/// 静音 0x00E2、播放/暂停 0x00CD，浏览器主页 0x0223、后退 0x0224、搜索 0x0221。
+fn decode_consumer(data: &[u8]) -> Option<[InputReport; 2]> {
+    if !protocol::CONSUMER.fits(data) {
+        return None;
+    }
+    let usage = protocol::CONSUMER_USAGE.u16(data);
+    Some([
+        InputReport::Consumer { usage },
+        InputReport::Consumer { usage: 0 },
+    ])
+}
+
+/// 裁剪到报告中 i16 位移的范围
+fn clamp_i16(v: i32) -> i16 {
+    v.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
+/// 解析完整鼠标报告 `[0x07, buttons(1), dx(2), dy(2), wheel(1)]`
+fn decode_mouse_report(data: &[u8]) -> Option<InputReport> {
+    if !protocol::MOUSE_REPORT.fits(data) {
+        return None;
+    }
+    Some(InputReport::Mouse {
+        buttons: protocol::MOUSE_REPORT_BUTTONS.u8(data),
+        x: protocol::MOUSE_REPORT_DX.i16(data),
+        y: protocol::MOUSE_REPORT_DY.i16(data),
+        wheel: protocol::MOUSE_REPORT_WHEEL.i8(data),
+    })
+}
+
+/// 依次发送修饰键按下、滚轮与释放报告，修饰键按下后等待 `hold` 再滚动
+async fn send_modified_wheel(
+    sink: &ReportSink,
+    modifiers: u8,
+    ticks: i32,
+    hold: Duration,
+) -> Result<()> {
+    send_paced(sink, modified_wheel_reports(modifiers, ticks), hold).await
+}
+
+/// 依次发送报告；键盘类报告（含媒体键）后还有报告时等待 `hold`，主机才能识别按下
+async fn send_paced(sink: &ReportSink, reports: Vec<InputReport>, hold: Duration) -> Result<()> {
+    let mut reports = reports.into_iter().peekable();
+    while let Some(report) = reports.next() {
+        let device_type = match report {
+            InputReport::Mouse { .. } => DeviceType::Mouse,
+            _ => DeviceType::Keyboard,
+        };
+        let is_edge = device_type == DeviceType::Keyboard;
+        sink.send_report(device_type, report).await?;
+        if is_edge && reports.peek().is_some() {
+            pause(hold).await;
         }
-        _ => {
-            info!("未知消息类型: 0x{:02X}", msg_type);
+    }
+    Ok(())
+}
+
+async fn move_by(sink: &ReportSink, axes: AxisTransform, dx: i32, dy: i32) -> Result<()> {
+    let (dx, dy) = axes.apply(dx, dy);
+    for report in split_move(dx, dy, DEFAULT_MOVE_STEP) {
+        sink.send_report(DeviceType::Mouse, report).await?;
+    }
+    Ok(())
+}
+
+/// 网页触控板报告的去向
+enum ReportSink {
+    /// 单独运行，自行管理 USB 设备
+    Usb(ReconnectGuard),
+    /// 注入切换器的输入管线，与物理键鼠共用同一组后端，报告由主循环依次发送
+    Core(Arc<Core>),
+}
+
+impl ReportSink {
+    async fn send_report(&self, device_type: DeviceType, report: InputReport) -> Result<()> {
+        match self {
+            Self::Usb(guard) => guard.send_report(device_type, report).await,
+            Self::Core(core) => core.injector().inject(report).await,
         }
     }
 }
@@ -196,7 +722,7 @@ fn handle_binary_message(data: &[u8], hid_guard: &ReconnectGuard) {
 struct ReconnectGuard {
     keyboard: Arc<Mutex<Option<UsbKeyboardHidDevice>>>,
     mouse: Arc<Mutex<Option<UsbMouseHidDevice>>>,
-    connected: Arc<AtomicBool>,
+    connected: ConnectionState,
     reconnecting: Arc<AtomicBool>,
 }
 
@@ -209,13 +735,13 @@ impl ReconnectGuard {
         Self {
             keyboard: Arc::new(Mutex::new(Some(keyboard))),
             mouse: Arc::new(Mutex::new(Some(mouse))),
-            connected: Arc::new(AtomicBool::new(true)),
+            connected: ConnectionState::new(true),
             reconnecting: Arc::new(AtomicBool::new(false)),
         }
     }
 
     async fn send_report(&self, device_type: DeviceType, report: InputReport) -> Result<()> {
-        if !self.connected.load(Ordering::SeqCst) {
+        if !self.connected.is_connected() {
             return Ok(()); // 断连中，静默丢弃
         }
 
@@ -239,16 +765,19 @@ impl ReconnectGuard {
         };
 
         match res {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                self.connected.mark_report_sent();
+                Ok(())
+            }
             Err(e) => {
                 if e.downcast_ref::<UsbError>().is_some() {
                     error!("USB 连接错误，尝试重连");
-                    self.connected.store(false, Ordering::SeqCst);
+                    self.connected.set_connected(false);
 
                     if !self.reconnecting.swap(true, Ordering::SeqCst) {
                         let keyboard_clone = Arc::clone(&self.keyboard);
                         let mouse_clone = Arc::clone(&self.mouse);
-                        let connected_clone = Arc::clone(&self.connected);
+                        let connected_clone = self.connected.clone();
                         let reconnecting_clone = Arc::clone(&self.reconnecting);
 
                         tokio::spawn(async move {
@@ -256,7 +785,7 @@ impl ReconnectGuard {
                             match Self::reconnect_devices(keyboard_clone, mouse_clone).await {
                                 Ok(_) => {
                                     info!("USB 设备重连成功");
-                                    connected_clone.store(true, Ordering::SeqCst);
+                                    connected_clone.set_connected(true);
                                 }
                                 Err(e) => {
                                     error!("USB 设备重连失败: {}", e);
@@ -306,3 +835,610 @@ impl ReconnectGuard {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::output::mouse::MAX_MOVE;
+    use crate::output::{consumer, keycodes};
+
+    /// 永远不回应的连接
+    #[derive(Default)]
+    struct SilentSocket {
+        pings: usize,
+        closed: bool,
+    }
+
+    #[async_trait]
+    impl WsConnection for SilentSocket {
+        async fn recv(&mut self) -> Option<Result<Message, axum::Error>> {
+            std::future::pending().await
+        }
+
+        async fn send(&mut self, msg: Message) -> Result<(), axum::Error> {
+            match msg {
+                Message::Ping(_) => self.pings += 1,
+                Message::Close(_) => self.closed = true,
+                _ => {}
+            }
+            Ok(())
+        }
+    }
+
+    /// 只统计收到的消息
+    #[derive(Default)]
+    struct CountingHandler {
+        frames: usize,
+    }
+
+    #[async_trait]
+    impl BinaryHandler for CountingHandler {
+        async fn on_binary(&mut self, _data: &[u8]) -> Option<Vec<u8>> {
+            self.frames += 1;
+            None
+        }
+    }
+
+    /// 依次交付预先准备的消息，随后关闭
+    #[derive(Default)]
+    struct ScriptedSocket {
+        frames: std::collections::VecDeque<Vec<u8>>,
+        replies: Vec<Vec<u8>>,
+    }
+
+    #[async_trait]
+    impl WsConnection for ScriptedSocket {
+        async fn recv(&mut self) -> Option<Result<Message, axum::Error>> {
+            let frame = self.frames.pop_front()?;
+            Some(Ok(Message::Binary(frame.into())))
+        }
+
+        async fn send(&mut self, msg: Message) -> Result<(), axum::Error> {
+            if let Message::Binary(data) = msg {
+                self.replies.push(data.to_vec());
+            }
+            Ok(())
+        }
+    }
+
+    /// 只解码并收集报告
+    struct DecodingHandler {
+        decoder: WsDecoder,
+        reports: Vec<InputReport>,
+    }
+
+    #[async_trait]
+    impl BinaryHandler for DecodingHandler {
+        async fn on_binary(&mut self, data: &[u8]) -> Option<Vec<u8>> {
+            if let Ok(reports) = self.decoder.decode(data) {
+                self.reports.extend(reports);
+            }
+            self.decoder.take_reply()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_many_frames_processed_without_blocking() {
+        use crate::core::{BleBackend, BleBackends, OutputBackends};
+        use crate::output::NoLedDevice;
+        use crate::output::virtual_hid::VirtualHidDevice;
+
+        const FRAMES: usize = 200;
+        let moves = || {
+            (0..FRAMES)
+                .map(|_| vec![0x01, 0x01, 0x00, 0x00, 0x00])
+                .collect()
+        };
+
+        // 单线程运行时中 block_in_place 会直接 panic，能走完说明全程都是 await
+        let socket = Mutex::new(ScriptedSocket {
+            frames: moves(),
+            ..Default::default()
+        });
+        let mut counter = CountingHandler::default();
+        tokio::time::timeout(
+            Duration::from_secs(2),
+            serve_socket(
+                &socket,
+                Duration::from_secs(60),
+                Duration::from_secs(60),
+                &mut counter,
+            ),
+        )
+        .await
+        .expect("处理消息时发生死锁");
+        assert_eq!(counter.frames, FRAMES);
+
+        // 经输入管线送达后端，位移一个不少
+        let core = Arc::new(Core::new(&Config::without_devices()));
+        let mouse = VirtualHidDevice::new();
+        let runner = Arc::clone(&core);
+        let backends = OutputBackends {
+            usb_keyboard: Box::new(VirtualHidDevice::new()),
+            usb_mouse: Box::new(mouse.clone()),
+            usb_led: Box::new(NoLedDevice),
+            ble: BleBackends::Ready(BleBackend::new(
+                Box::new(VirtualHidDevice::new()),
+                Box::new(VirtualHidDevice::new()),
+            )),
+            extra: Vec::new(),
+        };
+        let pipeline = tokio::spawn(async move { runner.run_with(backends).await });
+
+        let sink = ReportSink::Core(Arc::clone(&core));
+        let mut handler = SinkHandler {
+            sink: &sink,
+            decoder: WsDecoder::default(),
+            typing: TypingConfig::default(),
+        };
+        let socket = Mutex::new(ScriptedSocket {
+            frames: moves(),
+            ..Default::default()
+        });
+        tokio::time::timeout(
+            Duration::from_secs(2),
+            serve_socket(
+                &socket,
+                Duration::from_secs(60),
+                Duration::from_secs(60),
+                &mut handler,
+            ),
+        )
+        .await
+        .expect("处理消息时发生死锁");
+
+        let moved = || {
+            mouse
+                .reports()
+                .iter()
+                .map(|report| match report {
+                    InputReport::Mouse { x, .. } => *x as usize,
+                    _ => 0,
+                })
+                .sum::<usize>()
+        };
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while moved() < FRAMES {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("报告没有全部到达后端");
+        assert_eq!(moved(), FRAMES);
+
+        core.shutdown().await;
+        pipeline.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_viewport_handshake_replies_relative() {
+        // 旋转屏幕后重新握手，每次握手都回复相对模式
+        let socket = Mutex::new(ScriptedSocket {
+            frames: [
+                vec![0x09, 0xC0, 0x03, 0x1C, 0x02],
+                vec![0x09, 0x1C, 0x02, 0xC0, 0x03],
+            ]
+            .into(),
+            ..Default::default()
+        });
+        let mut handler = DecodingHandler {
+            decoder: WsDecoder::default(),
+            reports: Vec::new(),
+        };
+        serve_socket(
+            &socket,
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+            &mut handler,
+        )
+        .await;
+        assert_eq!(
+            socket.lock().await.replies,
+            [protocol::viewport_reply(), protocol::viewport_reply()]
+        );
+        assert!(handler.reports.is_empty());
+        // 0x0A 不是已知消息类型
+        assert!(decode_ws_message(&[0x0A, 0, 0, 0, 0]).is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_key_char_follows_typing_config() {
+        let core = Arc::new(Core::new(&Config::without_devices()));
+        let sink = ReportSink::Core(Arc::clone(&core));
+        let typing = TypingConfig {
+            tap_ms: 20,
+            char_delay_ms: 30,
+        };
+        let mut decoder = WsDecoder::default();
+
+        // 'a'：按下后等待 tap 再释放，之后再等待字符间隔
+        let started = tokio::time::Instant::now();
+        handle_binary_message(&[0x04, 0x61, 0, 0, 0], &sink, &mut decoder, &typing).await;
+        assert_eq!(started.elapsed(), Duration::from_millis(50));
+
+        let started = tokio::time::Instant::now();
+        handle_binary_message(
+            &[0x04, 0x61, 0, 0, 0],
+            &sink,
+            &mut decoder,
+            &TypingConfig::FAST,
+        )
+        .await;
+        assert_eq!(started.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_silent_connection_is_reaped() {
+        let socket = Mutex::new(SilentSocket::default());
+        let started = Instant::now();
+
+        tokio::time::timeout(
+            Duration::from_secs(2),
+            serve_socket(
+                &socket,
+                Duration::from_millis(10),
+                Duration::from_millis(50),
+                &mut CountingHandler::default(),
+            ),
+        )
+        .await
+        .expect("失效连接没有被断开");
+
+        // 第一个 Ping 在一个间隔后发出，再经过超时才断开
+        assert!(started.elapsed() >= Duration::from_millis(60));
+        let socket = socket.lock().await;
+        assert!(socket.pings >= 1);
+        assert!(socket.closed);
+    }
+
+    #[test]
+    fn test_heartbeat_deadline_tracks_oldest_ping() {
+        let mut heartbeat = Heartbeat::new(Duration::from_secs(5));
+        let t0 = Instant::now();
+        assert_eq!(heartbeat.deadline(), None);
+
+        heartbeat.ping_sent(t0);
+        heartbeat.ping_sent(t0 + Duration::from_secs(2));
+        assert_eq!(heartbeat.deadline(), Some(t0 + Duration::from_secs(5)));
+
+        heartbeat.alive();
+        assert_eq!(heartbeat.deadline(), None);
+    }
+
+    #[tokio::test]
+    async fn test_web_and_switcher_share_backends() {
+        use crate::core::{BleBackend, BleBackends, OutputBackends};
+        use crate::output::NoLedDevice;
+        use crate::output::virtual_hid::VirtualHidDevice;
+
+        let core = Arc::new(Core::new(&Config::without_devices()));
+        let (keyboard, mouse) = (VirtualHidDevice::new(), VirtualHidDevice::new());
+        let runner = Arc::clone(&core);
+        let backends = OutputBackends {
+            usb_keyboard: Box::new(keyboard.clone()),
+            usb_mouse: Box::new(mouse.clone()),
+            usb_led: Box::new(NoLedDevice),
+            ble: BleBackends::Ready(BleBackend::new(
+                Box::new(VirtualHidDevice::new()),
+                Box::new(VirtualHidDevice::new()),
+            )),
+            extra: Vec::new(),
+        };
+        let pipeline = tokio::spawn(async move { runner.run_with(backends).await });
+
+        let state = WsState::with_core(&WebConfig::default(), Arc::clone(&core));
+        assert_eq!(state.output_mode(), "usb");
+        // 物理键盘经输入管线送达
+        core.injector()
+            .inject(InputReport::Keyboard {
+                modifiers: 0,
+                keys: vec![0x05],
+            })
+            .await
+            .unwrap();
+        // 网页触控板的组合键与鼠标移动
+        state.send_chord(0x02, &[0x04]).await.unwrap();
+        state.mouse_move(3, -2).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while keyboard.reports().len() < 3 || mouse.reports().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("报告没有到达共用的后端");
+
+        let keys: Vec<(u8, Vec<u8>)> = keyboard
+            .reports()
+            .into_iter()
+            .filter_map(|report| match report {
+                InputReport::Keyboard { modifiers, keys } => Some((modifiers, keys)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(keys[0], (0, vec![0x05]));
+        assert_eq!(keys[1], (0x02, vec![0x04]));
+        assert_eq!(keys[2], (0, vec![]));
+        assert!(matches!(
+            mouse.reports()[0],
+            InputReport::Mouse { x: 3, y: -2, .. }
+        ));
+
+        core.shutdown().await;
+        pipeline.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_decode_consumer_frames() {
+        let [press, release] = decode_consumer(&[0x06, 0xE9, 0x00]).unwrap();
+        assert!(matches!(
+            press,
+            InputReport::Consumer {
+                usage: consumer::VOLUME_UP
+            }
+        ));
+        assert!(matches!(release, InputReport::Consumer { usage: 0 }));
+
+        let [press, _] = decode_consumer(&[0x06, 0xE2, 0x00]).unwrap();
+        assert!(matches!(
+            press,
+            InputReport::Consumer {
+                usage: consumer::MUTE
+            }
+        ));
+
+        // 浏览器后退
+        let [press, _] = decode_consumer(&[0x06, 0x24, 0x02]).unwrap();
+        assert!(matches!(
+            press,
+            InputReport::Consumer {
+                usage: consumer::AC_BACK
+            }
+        ));
+
+        assert!(decode_consumer(&[0x06, 0xE2]).is_none());
+    }
+
+    /// 把鼠标报告展开为 (buttons, x, y, wheel)
+    fn mouse_fields(reports: &[InputReport]) -> Vec<(u8, i16, i16, i8)> {
+        reports
+            .iter()
+            .map(|report| match report {
+                InputReport::Mouse {
+                    buttons,
+                    x,
+                    y,
+                    wheel,
+                } => (*buttons, *x, *y, *wheel),
+                other => panic!("unexpected report: {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_decode_every_message_type() {
+        // 0x01 移动 x=-2 y=5
+        let reports = decode_ws_message(&[0x01, 0xFE, 0xFF, 0x05, 0x00]).unwrap();
+        assert_eq!(mouse_fields(&reports), [(0, -2, 5, 0)]);
+
+        // 0x02 按键
+        let reports = decode_ws_message(&[0x02, 0x01, 0x01]).unwrap();
+        assert_eq!(mouse_fields(&reports), [(0x01, 0, 0, 0)]);
+
+        // 0x03 滚动 y=-3
+        let reports = decode_ws_message(&[0x03, 0x00, 0x00, 0xFD, 0xFF]).unwrap();
+        assert_eq!(mouse_fields(&reports), [(0, 0, 0, -3)]);
+        let reports = decode_ws_message(&[0x03, 0x07, 0x00, 0x00, 0x00]).unwrap();
+        assert_eq!(mouse_fields(&reports), [(0, 0, 0, 0)]);
+
+        // 0x04 字符 'A'：Shift+A 按下后释放
+        let reports = decode_ws_message(&[0x04, b'A', 0, 0, 0]).unwrap();
+        assert!(matches!(
+            &reports[..],
+            [
+                InputReport::Keyboard { modifiers: 0x02, keys },
+                InputReport::Keyboard { modifiers: 0, keys: up },
+            ] if keys == &[keycodes::KEY_A] && up.is_empty()
+        ));
+
+        // 0x05 长距离移动 dx=300：拆成多个报告，总和不变
+        let reports = decode_ws_message(&[0x05, 0x2C, 0x01, 0, 0, 0, 0, 0, 0]).unwrap();
+        let fields = mouse_fields(&reports);
+        assert!(fields.len() > 1);
+        assert_eq!(fields.iter().map(|f| f.1 as i32).sum::<i32>(), 300);
+
+        // 超大位移被截断，报告数量有上限
+        let mut frame = vec![0x05];
+        frame.extend(i32::MAX.to_le_bytes());
+        frame.extend(i32::MIN.to_le_bytes());
+        let fields = mouse_fields(&decode_ws_message(&frame).unwrap());
+        assert_eq!(fields.len(), (MAX_MOVE as usize).div_ceil(127));
+        assert_eq!(fields.iter().map(|f| f.1 as i32).sum::<i32>(), MAX_MOVE);
+        assert_eq!(fields.iter().map(|f| f.2 as i32).sum::<i32>(), -MAX_MOVE);
+
+        // 0x06 媒体键
+        let reports = decode_ws_message(&[0x06, 0xE2, 0x00]).unwrap();
+        assert!(matches!(
+            reports[..],
+            [
+                InputReport::Consumer {
+                    usage: consumer::MUTE
+                },
+                InputReport::Consumer { usage: 0 }
+            ]
+        ));
+
+        // 0x07 完整鼠标报告
+        let reports = decode_ws_message(&[0x07, 0x01, 0xFD, 0xFF, 0x2C, 0x01, 0xFF]).unwrap();
+        assert_eq!(mouse_fields(&reports), [(0x01, -3, 300, -1)]);
+
+        // 0x08 Ctrl+滚轮两格
+        let reports = decode_ws_message(&[0x08, 0x01, 0x02]).unwrap();
+        assert_eq!(reports.len(), 4);
+        assert!(matches!(
+            reports[0],
+            InputReport::Keyboard {
+                modifiers: 0x01,
+                ..
+            }
+        ));
+        assert_eq!(mouse_fields(&reports[1..3]), [(0, 0, 0, 1); 2]);
+        assert!(matches!(
+            reports[3],
+            InputReport::Keyboard { modifiers: 0, .. }
+        ));
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed_frames() {
+        assert!(decode_ws_message(&[]).is_err());
+        assert!(decode_ws_message(&[0x7F, 0, 0, 0, 0]).is_err());
+        // 每种类型少一个字节都返回错误
+        for spec in protocol::MESSAGES {
+            let frame = vec![spec.id; spec.min_len() - 1];
+            let err = decode_ws_message(&frame).unwrap_err();
+            assert!(err.to_string().contains(spec.name), "{}", err);
+        }
+        // 无效码点与没有键位的字符
+        assert!(decode_ws_message(&[0x04, 0x00, 0xD8, 0, 0]).is_err());
+        assert!(decode_ws_message(&[0x04, 0xE9, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_decoder_keeps_state_across_messages() {
+        let mut decoder = WsDecoder::new(
+            AxisTransform {
+                invert_x: true,
+                ..Default::default()
+            },
+            ScrollAccumulator::new(4),
+            MoveAccumulator::new(DeadZone(2)),
+        );
+        // 位移在死区内累积，随后并入完整鼠标报告
+        assert!(
+            decoder
+                .decode(&[0x01, 0x01, 0x00, 0x00, 0x00])
+                .unwrap()
+                .is_empty()
+        );
+        let reports = decoder
+            .decode(&[0x07, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00])
+            .unwrap();
+        assert_eq!(mouse_fields(&reports), [(0x01, -2, 0, 0)]);
+
+        // 滚动量跨消息累积到阈值
+        assert!(
+            decoder
+                .decode(&[0x03, 0, 0, 0x02, 0x00])
+                .unwrap()
+                .is_empty()
+        );
+        let reports = decoder.decode(&[0x03, 0, 0, 0x02, 0x00]).unwrap();
+        assert_eq!(mouse_fields(&reports), [(0, 0, 0, 1)]);
+    }
+
+    #[test]
+    fn test_decode_mouse_report_frame() {
+        // 左键按住，dx=-3，dy=300，滚轮 -1
+        let frame = [0x07, 0x01, 0xFD, 0xFF, 0x2C, 0x01, 0xFF];
+        match decode_mouse_report(&frame) {
+            Some(InputReport::Mouse {
+                buttons,
+                x,
+                y,
+                wheel,
+            }) => assert_eq!((buttons, x, y, wheel), (0x01, -3, 300, -1)),
+            other => panic!("unexpected report: {:?}", other),
+        }
+        assert!(decode_mouse_report(&frame[..6]).is_none());
+        assert!(decode_mouse_report(&[0x01, 0, 0, 0, 0, 0, 0]).is_none());
+    }
+
+    #[test]
+    fn test_scroll_accumulator_smooths_small_deltas() {
+        let mut scroll = ScrollAccumulator::new(4);
+        let input = [1i16, 1, 1, 2, 1, 3, 1, 1, 2, 1];
+        let output: Vec<i8> = input.iter().map(|&d| scroll.push(d)).collect();
+
+        assert!(output.iter().all(|&w| w == 0 || w == 1));
+        let total_in: i32 = input.iter().map(|&d| d as i32).sum();
+        let total_out: i32 = output.iter().map(|&w| w as i32).sum();
+        assert_eq!(total_out, total_in / 4);
+        assert_eq!(total_out * 4 + scroll.remainder, total_in);
+    }
+
+    #[test]
+    fn test_move_accumulator_dead_zone() {
+        let mut moves = MoveAccumulator::new(DeadZone(1));
+        assert_eq!(moves.push(1, 0), None);
+        assert_eq!(moves.push(-1, 1), None);
+        assert_eq!(moves.push(0, 1), Some((0, 2)));
+        assert_eq!(moves.take(), (0, 0));
+
+        // 关闭时每次位移立即发送
+        let mut moves = MoveAccumulator::new(DeadZone::default());
+        assert_eq!(moves.push(1, 0), Some((1, 0)));
+        assert_eq!(moves.push(0, 0), None);
+    }
+
+    #[test]
+    fn test_fast_flick_scrolls_farther() {
+        let wheel_total = |gap_ms: u64| {
+            let curve = ScrollAccel {
+                gain: 20,
+                threshold: 8,
+                ..Default::default()
+            };
+            let mut scroll = ScrollAccumulator::new(4).with_accel(curve);
+            let start = Instant::now();
+            (0..12)
+                .map(|i| scroll.push_at(6, start + Duration::from_millis(i * gap_ms)) as i32)
+                .sum::<i32>()
+        };
+        // 慢速滚动不加速：72 / 4 = 18 格
+        assert_eq!(wheel_total(400), 18);
+        assert!(wheel_total(16) > wheel_total(400));
+    }
+
+    #[test]
+    fn test_default_scroll_matches_pass_through() {
+        // 与累加器加入前一致：每条消息一个报告，y 截断到 i8，不保留余量
+        let web = Config::default().web;
+        let mut decoder = WsDecoder::new(
+            web.mouse_axes,
+            ScrollAccumulator::new(web.scroll_threshold).with_accel(web.scroll_accel),
+            MoveAccumulator::new(web.mouse_dead_zone),
+        );
+        for y in [
+            0i16,
+            1,
+            -1,
+            3,
+            127,
+            128,
+            -128,
+            -129,
+            300,
+            0,
+            i16::MAX,
+            i16::MIN,
+            0,
+        ] {
+            let [lo, hi] = y.to_le_bytes();
+            let reports = decoder.decode(&[0x03, 0x05, 0x00, lo, hi]).unwrap();
+            let wheel = y.clamp(i8::MIN as i16, i8::MAX as i16) as i8;
+            assert_eq!(mouse_fields(&reports), [(0, 0, 0, wheel)]);
+        }
+    }
+
+    #[test]
+    fn test_scroll_accumulator_negative_and_large() {
+        let mut scroll = ScrollAccumulator::new(1);
+        assert_eq!(scroll.push(-3), -3);
+        assert_eq!(scroll.push(300), 127);
+        assert_eq!(scroll.push(0), 127);
+        assert_eq!(scroll.push(0), 46);
+        assert_eq!(scroll.push(0), 0);
+    }
+}
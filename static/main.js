@@ -1,6 +1,7 @@
 // --- 配置与状态 ---
 const SENSITIVITY = 1.5; // 鼠标灵敏度
 const SCROLL_SENSITIVITY = 0.5; // 滚轮灵敏度
+const PINCH_STEP = 40; // 双指捏合距离每变化多少像素缩放一格
 const WS_URL = `ws://${window.location.host}/ws`;
 
 // 消息类型定义
@@ -9,6 +10,32 @@ const MSG_TYPE = {
   MOUSE_CLICK: 0x02, // 鼠标点击
   SCROLL: 0x03, // 滚轮
   KEYBOARD: 0x04, // 键盘
+  MOUSE_MOVE_LONG: 0x05, // 长距离移动（服务端拆分）
+  CONSUMER: 0x06, // 媒体键
+  MOUSE_REPORT: 0x07, // 完整鼠标报告（按键 + 位移 + 滚轮）
+  MODIFIED_SCROLL: 0x08, // 按住修饰键滚动（如 Ctrl+滚轮缩放）
+  VIEWPORT: 0x09, // 握手：上报触控区域尺寸，服务端以同类型消息回复
+};
+
+// 握手回复中的指针模式，服务端目前只有相对模式
+const POINTER_MODE = {
+  RELATIVE: 0x00,
+};
+
+// 媒体键 usage（HID Consumer Page）
+const CONSUMER_USAGE = {
+  PLAY_PAUSE: 0x00cd,
+  MUTE: 0x00e2,
+  VOLUME_UP: 0x00e9,
+  VOLUME_DOWN: 0x00ea,
+  AC_SEARCH: 0x0221,
+  AC_HOME: 0x0223,
+  AC_BACK: 0x0224,
+};
+
+// 修饰键位（与 HID 键盘报告的修饰键字节一致）
+const MODIFIER = {
+  CTRL: 0x01,
 };
 
 const MOUSE_BUTTON = {
@@ -28,7 +55,10 @@ let lastY = 0;
 let lastDistance = 0; // 双指距离
 let isScrollMode = false; // 是否为滚动模式
 let isKeyboardActive = false;
+let heldButtons = 0; // 当前按住的鼠标按键位图
 let retryCount = 0;
+// 握手协商结果，每次连接重新协商
+let pointerMode = POINTER_MODE.RELATIVE;
 
 // 获取 DOM 元素
 const statusEl = document.getElementById("status-bar");
@@ -48,6 +78,17 @@ function connect() {
     statusEl.textContent = "🟢 已连接";
     statusEl.className = "connected";
     retryCount = 0;
+    pointerMode = POINTER_MODE.RELATIVE;
+    sendViewport();
+  };
+
+  ws.onmessage = (event) => {
+    if (!(event.data instanceof ArrayBuffer) || event.data.byteLength < 2) return;
+    const view = new DataView(event.data);
+    if (view.getUint8(0) !== MSG_TYPE.VIEWPORT) return;
+    // 握手回复: [type(1), mode(1)]
+    pointerMode = view.getUint8(1);
+    console.log("指针模式:", pointerMode);
   };
 
   ws.onclose = () => {
@@ -77,6 +118,66 @@ function createMouseMoveMsg(x, y) {
   return buffer;
 }
 
+// 长距离移动: [type(1), dx(4), dy(4)] = 9 bytes，服务端拆分为多个相对报告
+function createMouseMoveLongMsg(dx, dy) {
+  const buffer = new ArrayBuffer(9);
+  const view = new DataView(buffer);
+  view.setUint8(0, MSG_TYPE.MOUSE_MOVE_LONG);
+  view.setInt32(1, dx, true);
+  view.setInt32(5, dy, true);
+  return buffer;
+}
+
+// 媒体键: [type(1), usage(2)] = 3 bytes，服务端发送按下并释放
+function createConsumerMsg(usage) {
+  const buffer = new ArrayBuffer(3);
+  const view = new DataView(buffer);
+  view.setUint8(0, MSG_TYPE.CONSUMER);
+  view.setUint16(1, usage, true);
+  return buffer;
+}
+
+// 完整鼠标报告: [type(1), buttons(1), dx(2), dy(2), wheel(1)] = 7 bytes
+// 拖拽时使用，按键与位移在同一报告中，主机不会在错误的位置看到按下或松开
+function createMouseReportMsg(buttons, dx, dy, wheel) {
+  const buffer = new ArrayBuffer(7);
+  const view = new DataView(buffer);
+  view.setUint8(0, MSG_TYPE.MOUSE_REPORT);
+  view.setUint8(1, buttons);
+  view.setInt16(2, dx, true);
+  view.setInt16(4, dy, true);
+  view.setInt8(6, wheel);
+  return buffer;
+}
+
+// 修饰键滚动: [type(1), modifiers(1), ticks(1)] = 3 bytes
+// 服务端按下修饰键、滚动 ticks 格后释放，例如 modifiers=0x01 (Ctrl) 用于缩放
+function createModifiedScrollMsg(modifiers, ticks) {
+  const buffer = new ArrayBuffer(3);
+  const view = new DataView(buffer);
+  view.setUint8(0, MSG_TYPE.MODIFIED_SCROLL);
+  view.setUint8(1, modifiers);
+  view.setInt8(2, ticks);
+  return buffer;
+}
+
+// 握手: [type(1), width(2), height(2)] = 5 bytes，上报触控区域尺寸
+function createViewportMsg(width, height) {
+  const buffer = new ArrayBuffer(5);
+  const view = new DataView(buffer);
+  view.setUint8(0, MSG_TYPE.VIEWPORT);
+  view.setUint16(1, Math.round(width), true);
+  view.setUint16(3, Math.round(height), true);
+  return buffer;
+}
+
+// 上报当前触控区域尺寸，连接建立及尺寸变化时发送
+function sendViewport() {
+  if (!ws || ws.readyState !== WebSocket.OPEN) return;
+  const rect = touchZone.getBoundingClientRect();
+  ws.send(createViewportMsg(rect.width, rect.height));
+}
+
 // 鼠标点击: [type(1), button(1), state(1)] = 3 bytes
 function createMouseClickMsg(button, state) {
   const buffer = new ArrayBuffer(3);
@@ -174,7 +275,14 @@ touchZone.addEventListener(
       const deltaY = (currentY - lastY) * SENSITIVITY;
 
       if (Math.abs(deltaX) > 0.5 || Math.abs(deltaY) > 0.5) {
-        send(createMouseMoveMsg(Math.round(deltaX), Math.round(deltaY)));
+        const dx = Math.round(deltaX);
+        const dy = Math.round(deltaY);
+        // 按住按键时为拖拽，移动报告需携带按键状态
+        send(
+          heldButtons
+            ? createMouseReportMsg(heldButtons, dx, dy, 0)
+            : createMouseMoveMsg(dx, dy),
+        );
       }
 
       lastX = currentX;
@@ -190,7 +298,17 @@ touchZone.addEventListener(
       // 水平滚动（可选，使用 X 轴变化）
       const deltaX = (center.x - lastX) * SCROLL_SENSITIVITY;
 
-      if (Math.abs(deltaY) > 0.5 || Math.abs(deltaX) > 0.5) {
+      // 双指捏合：Ctrl+滚轮缩放，张开放大、捏合缩小，不足一格的距离留到下次
+      const ticks = Math.trunc((currentDistance - lastDistance) / PINCH_STEP);
+      if (ticks !== 0) {
+        send(
+          createModifiedScrollMsg(
+            MODIFIER.CTRL,
+            Math.max(-127, Math.min(127, ticks)),
+          ),
+        );
+        lastDistance += ticks * PINCH_STEP;
+      } else if (Math.abs(deltaY) > 0.5 || Math.abs(deltaX) > 0.5) {
         send(
           createScrollMsg(
             Math.round(deltaX),
@@ -201,7 +319,6 @@ touchZone.addEventListener(
 
       lastX = center.x;
       lastY = center.y;
-      lastDistance = currentDistance;
     }
   },
   { passive: false },
@@ -226,18 +343,26 @@ touchZone.addEventListener(
 function bindMouseBtn(el, button) {
   el.addEventListener("touchstart", (e) => {
     e.preventDefault();
-    send(createMouseClickMsg(button, MOUSE_STATE.DOWN));
+    heldButtons |= button;
+    send(createMouseReportMsg(heldButtons, 0, 0, 0));
   });
 
   el.addEventListener("touchend", (e) => {
     e.preventDefault();
-    send(createMouseClickMsg(button, MOUSE_STATE.UP));
+    heldButtons &= ~button;
+    send(createMouseReportMsg(heldButtons, 0, 0, 0));
   });
 }
 
 bindMouseBtn(btnLeft, MOUSE_BUTTON.LEFT);
 bindMouseBtn(btnRight, MOUSE_BUTTON.RIGHT);
 
+// --- 媒体键 ---
+document.querySelectorAll(".media-btn").forEach((el) => {
+  const usage = CONSUMER_USAGE[el.dataset.usage.toUpperCase()];
+  el.addEventListener("click", () => send(createConsumerMsg(usage)));
+});
+
 // --- 键盘唤起逻辑 ---
 btnKeyboard.addEventListener("click", () => {
   if (!isKeyboardActive) {
@@ -277,5 +402,9 @@ hiddenInput.addEventListener("keydown", (e) => {
   }
 });
 
+// 触控区域尺寸变化（窗口缩放、屏幕旋转）时重新握手
+window.addEventListener("resize", sendViewport);
+window.addEventListener("orientationchange", sendViewport);
+
 // 初始化
 connect();
@@ -1,7 +1,9 @@
 use bridge_hid::input::{self, InputManager};
 use bridge_hid::logging::init;
 use bridge_hid::output::HidReportSender;
-use bridge_hid::output::bluetooth_ble::{build_ble_hid_device, run_ble_server};
+use bridge_hid::output::bluetooth_ble::{BleConfig, build_ble_hid_device, run_ble_server};
+use bridge_hid::output::pairing::AutoAccept;
+use std::sync::Arc;
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
 #[ignore]
@@ -10,14 +12,22 @@ async fn test_blue_input_output() {
     println!("Starting blue input/output test...");
     let mut manager = InputManager::new(125);
 
-    let (mut keyboard, mut mouse, _session) = build_ble_hid_device().await.unwrap();
-    let (_app_handle, _adv_handle) = run_ble_server(&keyboard, &mouse).await.unwrap();
+    let (mut keyboard, mut mouse, _session) =
+        build_ble_hid_device(&BleConfig::default(), Arc::new(AutoAccept::default()))
+            .await
+            .unwrap();
+    let _handles = run_ble_server(&keyboard, &mouse, &BleConfig::default())
+        .await
+        .unwrap();
 
     tokio::spawn(async move {
         loop {
             if let Some(event) = manager.next_event().await {
                 match event {
-                    input::InputReport::Keyboard { .. } => {
+                    input::InputReport::Keyboard { .. }
+                    | input::InputReport::Consumer { .. }
+                    | input::InputReport::System { .. }
+                    | input::InputReport::Vendor { .. } => {
                         keyboard.send_report(event).await.expect("发送键盘事件失败");
                     }
                     input::InputReport::Mouse { .. } => {
@@ -17,7 +17,7 @@ async fn test_usb_input_output() {
         let (mut kb_hid_device, mut kb_hid_device_clone, mut mouse_hid_device) =
             build_usb_hid_device().await.expect("创建 USB HID 设备失败");
 
-        let mouse_rate_controller = manager.mouse_rate_controller.clone();
+        let _mouse_rate_controller = manager.mouse_rate_controller.clone();
 
         // std::thread::sleep(std::time::Duration::from_secs(2));
         let (manager_tx, manager_rx) = oneshot::channel();
@@ -38,7 +38,10 @@ async fn test_usb_input_output() {
                     event = manager.next_event() => {
                         if let Some(event) = event {
                             let result = match event {
-                                input::InputReport::Keyboard { .. } => {
+                                input::InputReport::Keyboard { .. }
+                                | input::InputReport::Consumer { .. }
+                                | input::InputReport::System { .. }
+                                | input::InputReport::Vendor { .. } => {
                                     kb_hid_device.send_report(event).await
                                 }
                                 input::InputReport::Mouse { .. } => {
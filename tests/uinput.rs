@@ -0,0 +1,185 @@
+//! 通过 uinput 虚拟设备驱动 `InputManager` 的集成测试
+//!
+//! 需要 /dev/uinput 与 /dev/input 的读写权限，因此默认不编译：
+//!
+//! ```text
+//! sudo -E cargo test --features uinput-tests --test uinput
+//! ```
+//!
+//! 没有权限时测试打印原因后直接返回，不会失败。
+#![cfg(feature = "uinput-tests")]
+
+use bridge_hid::input::{DeviceFilter, InputConfig, InputManager, InputReport};
+use evdev::uinput::VirtualDevice;
+use evdev::{AttributeSet, EventType, InputEvent, KeyCode, RelativeAxisCode};
+use std::time::{Duration, Instant};
+
+/// 等待 `InputManager` 发现虚拟设备的最长时间（默认每秒扫描一次）
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 创建虚拟设备，没有 uinput 权限时返回 `None`
+fn virtual_device(
+    name: &str,
+    keys: &[KeyCode],
+    axes: &[RelativeAxisCode],
+) -> Option<VirtualDevice> {
+    let build = || {
+        let mut builder = VirtualDevice::builder()?
+            .name(name)
+            .with_keys(&keys.iter().copied().collect::<AttributeSet<_>>())?;
+        if !axes.is_empty() {
+            builder =
+                builder.with_relative_axes(&axes.iter().copied().collect::<AttributeSet<_>>())?;
+        }
+        builder.build()
+    };
+    match build() {
+        Ok(device) => Some(device),
+        Err(e) => {
+            eprintln!(
+                "跳过：无法创建 uinput 虚拟设备（需要加载 uinput 模块，并以 root 或有 /dev/uinput 写权限的用户运行）: {e}"
+            );
+            None
+        }
+    }
+}
+
+/// 只监听指定名称设备的管理器，避免独占真实键盘鼠标
+fn manager_for(name: &str) -> InputManager {
+    InputManager::with_config(
+        0,
+        InputConfig {
+            device_filter: DeviceFilter {
+                exclude: vec![],
+                allow: vec![name.to_string()],
+            },
+            ..Default::default()
+        },
+    )
+}
+
+/// 反复发送 `events` 直到管理器产生报告，返回第一个报告
+///
+/// 管理器打开设备之前发出的事件会丢失，因此需要重试。
+async fn first_report(
+    device: &mut VirtualDevice,
+    manager: &mut InputManager,
+    events: &[InputEvent],
+) -> InputReport {
+    let started = Instant::now();
+    while started.elapsed() < DISCOVERY_TIMEOUT {
+        device.emit(events).unwrap();
+        if let Ok(Some(report)) =
+            tokio::time::timeout(Duration::from_millis(200), manager.next_event()).await
+        {
+            return report;
+        }
+    }
+    panic!("{:?} 内没有收到虚拟设备的报告", DISCOVERY_TIMEOUT);
+}
+
+async fn next_report(manager: &mut InputManager) -> InputReport {
+    tokio::time::timeout(Duration::from_secs(1), manager.next_event())
+        .await
+        .expect("等待报告超时")
+        .expect("输入通道已关闭")
+}
+
+fn key(code: KeyCode, value: i32) -> InputEvent {
+    InputEvent::new(EventType::KEY.0, code.0, value)
+}
+
+fn rel(code: RelativeAxisCode, value: i32) -> InputEvent {
+    InputEvent::new(EventType::RELATIVE.0, code.0, value)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_virtual_keyboard_press_and_release() {
+    const NAME: &str = "bridge-hid uinput keyboard";
+    let keys = [KeyCode::KEY_A, KeyCode::KEY_Z, KeyCode::KEY_LEFTSHIFT];
+    let Some(mut device) = virtual_device(NAME, &keys, &[]) else {
+        return;
+    };
+    let mut manager = manager_for(NAME);
+
+    // 按下并松开 A，直到管理器开始监听
+    let report = loop {
+        let report = first_report(&mut device, &mut manager, &[key(KeyCode::KEY_A, 1)]).await;
+        device.emit(&[key(KeyCode::KEY_A, 0)]).unwrap();
+        if matches!(&report, InputReport::Keyboard { keys, .. } if !keys.is_empty()) {
+            break report;
+        }
+    };
+    assert!(matches!(
+        report,
+        InputReport::Keyboard { modifiers: 0, keys } if keys == vec![0x04]
+    ));
+    assert!(matches!(
+        next_report(&mut manager).await,
+        InputReport::Keyboard { keys, .. } if keys.is_empty()
+    ));
+
+    // Shift+Z
+    device
+        .emit(&[key(KeyCode::KEY_LEFTSHIFT, 1), key(KeyCode::KEY_Z, 1)])
+        .unwrap();
+    let mut last = next_report(&mut manager).await;
+    while !matches!(&last, InputReport::Keyboard { keys, .. } if keys.contains(&0x1D)) {
+        last = next_report(&mut manager).await;
+    }
+    assert!(matches!(
+        last,
+        InputReport::Keyboard { modifiers: 0x02, keys } if keys == vec![0x1D]
+    ));
+    device
+        .emit(&[key(KeyCode::KEY_Z, 0), key(KeyCode::KEY_LEFTSHIFT, 0)])
+        .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_virtual_mouse_motion_and_button() {
+    const NAME: &str = "bridge-hid uinput mouse";
+    let keys = [KeyCode::BTN_LEFT, KeyCode::BTN_RIGHT];
+    let axes = [
+        RelativeAxisCode::REL_X,
+        RelativeAxisCode::REL_Y,
+        RelativeAxisCode::REL_WHEEL,
+    ];
+    let Some(mut device) = virtual_device(NAME, &keys, &axes) else {
+        return;
+    };
+    let mut manager = manager_for(NAME);
+
+    let motion = [
+        rel(RelativeAxisCode::REL_X, 5),
+        rel(RelativeAxisCode::REL_Y, -3),
+    ];
+    match first_report(&mut device, &mut manager, &motion).await {
+        InputReport::Mouse {
+            buttons: 0,
+            x,
+            y,
+            wheel: 0,
+        } => {
+            // 多次重试的位移可能被合并，但方向与比例不变
+            assert!(
+                x > 0 && y < 0 && x as i32 * 3 == -(y as i32) * 5,
+                "{x}, {y}"
+            );
+        }
+        other => panic!("unexpected report: {:?}", other),
+    }
+
+    device.emit(&[key(KeyCode::BTN_LEFT, 1)]).unwrap();
+    let mut report = next_report(&mut manager).await;
+    while matches!(report, InputReport::Mouse { buttons: 0, .. }) {
+        report = next_report(&mut manager).await;
+    }
+    assert!(matches!(report, InputReport::Mouse { buttons: 0x01, .. }));
+
+    device.emit(&[key(KeyCode::BTN_LEFT, 0)]).unwrap();
+    assert!(matches!(
+        next_report(&mut manager).await,
+        InputReport::Mouse { buttons: 0, .. }
+    ));
+}